@@ -0,0 +1,26 @@
+extern crate neli;
+
+use std::error::Error;
+
+use neli::consts::*;
+use neli::tools::monitor::Monitor;
+
+/// Print link, address and route changes along with generic netlink family changes
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut monitor = Monitor::new();
+    monitor
+        .subscribe_rt(&[
+            RtnlGroup::Link,
+            RtnlGroup::Ipv4Ifaddr,
+            RtnlGroup::Ipv6Ifaddr,
+            RtnlGroup::Ipv4Route,
+            RtnlGroup::Ipv6Route,
+        ])?
+        .subscribe_genl("nlctrl", "notify")?;
+    for event in monitor {
+        let event = event?;
+        println!("{}", event);
+        println!("    {:?}", event.msg);
+    }
+    Ok(())
+}
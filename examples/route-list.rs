@@ -1,7 +1,6 @@
 extern crate neli;
 
 use std::error::Error;
-use std::net::IpAddr;
 
 use neli::consts::*;
 use neli::err::NlError;
@@ -17,21 +16,11 @@ fn parse_route_table(rtm: Nlmsghdr<Rtm, Rtmsg>) {
         let mut gateway = None;
 
         for attr in rtm.nl_payload.rtattrs.iter() {
-            fn to_addr(b: &[u8]) -> Option<IpAddr> {
-                use std::convert::TryFrom;
-                if let Ok(tup) = <&[u8; 4]>::try_from(b) {
-                    Some(IpAddr::from(*tup))
-                } else if let Ok(tup) = <&[u8; 16]>::try_from(b) {
-                    Some(IpAddr::from(*tup))
-                } else {
-                    None
-                }
-            }
-
+            let addr = attr.get_payload_as_addr(&rtm.nl_payload.rtm_family).ok();
             match attr.rta_type {
-                Rta::Dst => dst = to_addr(&attr.rta_payload),
-                Rta::Prefsrc => src = to_addr(&attr.rta_payload),
-                Rta::Gateway => gateway = to_addr(&attr.rta_payload),
+                Rta::Dst => dst = addr,
+                Rta::Prefsrc => src = addr,
+                Rta::Gateway => gateway = addr,
                 _ => (),
             }
         }
@@ -0,0 +1,38 @@
+//! Feeds arbitrary bytes to the deserializers of headers, attributes and the family headers of
+//! each protocol. Malformed input may fail to parse but must never panic or allocate without
+//! bound.
+//!
+//! Run from the root of the repository with `cargo fuzz run deserialize`. The golden vectors in
+//! `vectors/` make a good starting corpus once converted from hex to binary.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use neli::consts::{CtrlAttr, CtrlCmd, GenlId, Ifa, Ifla, Nlmsg, Rtm};
+use neli::err::Nlmsgerr;
+use neli::genl::Genlmsghdr;
+use neli::nl::Nlmsghdr;
+use neli::nlattr::Nlattr;
+use neli::rtnl::{Ifaddrmsg, Ifinfomsg, Rtattr, Rtmsg};
+use neli::socket::NlStreamParser;
+use neli::{Nl, StreamReadBuffer};
+
+fn parse<T: Nl>(data: &[u8]) {
+    let _ = T::deserialize(&mut StreamReadBuffer::new(data));
+}
+
+fuzz_target!(|data: &[u8]| {
+    parse::<Nlmsghdr<Nlmsg, Nlmsgerr<Nlmsg>>>(data);
+    parse::<Nlmsghdr<GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>>>(data);
+    parse::<Nlmsghdr<Rtm, Ifinfomsg>>(data);
+    parse::<Nlmsghdr<Rtm, Ifaddrmsg>>(data);
+    parse::<Nlmsghdr<Rtm, Rtmsg>>(data);
+    parse::<Vec<Nlmsghdr<u16, Vec<u8>>>>(data);
+    parse::<Vec<Nlattr<u16, Vec<u8>>>>(data);
+    parse::<Rtattr<Ifla, Vec<u8>>>(data);
+    parse::<Rtattr<Ifa, Vec<u8>>>(data);
+
+    let mut parser = NlStreamParser::<u16, Vec<u8>>::new();
+    parser.push(data);
+    while let Ok(Some(_)) = parser.next_msg() {}
+});
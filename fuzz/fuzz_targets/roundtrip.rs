@@ -0,0 +1,42 @@
+//! Generates well-formed messages through the `arbitrary` feature of neli and checks that
+//! serializing, deserializing and serializing them again gives the same bytes.
+//!
+//! Run from the root of the repository with `cargo fuzz run roundtrip`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use neli::consts::{Ifla, Rtm};
+use neli::err::DeError;
+use neli::nl::Nlmsghdr;
+use neli::nlattr::Nlattr;
+use neli::rtnl::Rtattr;
+use neli::{Nl, StreamReadBuffer, StreamWriteBuffer};
+
+fn roundtrip<T: Nl>(msg: &T) {
+    let mut mem = StreamWriteBuffer::new_growable(None);
+    if msg.serialize(&mut mem).is_err() {
+        return;
+    }
+    let parsed = match T::deserialize(&mut StreamReadBuffer::new(mem.as_ref())) {
+        Ok(parsed) => parsed,
+        // Generated messages may be larger or deeper than the default parse limits allow
+        Err(ref e) if matches!(e.root(), DeError::LimitExceeded { .. }) => return,
+        Err(e) => panic!("Serialized message failed to parse: {}", e),
+    };
+    let mut again = StreamWriteBuffer::new_growable(None);
+    parsed
+        .serialize(&mut again)
+        .expect("Parsed message failed to serialize");
+    assert_eq!(mem.as_ref(), again.as_ref());
+}
+
+fuzz_target!(|input: (
+    Nlmsghdr<Rtm, Vec<u8>>,
+    Nlmsghdr<u16, Nlattr<u16, Vec<u8>>>,
+    Rtattr<Ifla, Vec<u8>>
+)| {
+    roundtrip(&input.0);
+    roundtrip(&input.1);
+    roundtrip(&input.2);
+});
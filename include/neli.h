@@ -0,0 +1,43 @@
+/*
+ * C interface to the neli netlink codec. Build the library with
+ * `cargo rustc --release --features ffi --crate-type cdylib`.
+ *
+ * All functions return a negative errno value on failure and the number of
+ * bytes consumed or written, including padding, on success. A panic inside
+ * neli is reported as -EIO.
+ */
+
+#ifndef NELI_H
+#define NELI_H
+
+#include <stddef.h>
+#include <stdint.h>
+#include <sys/types.h>
+
+struct neli_nlmsghdr {
+	uint32_t nlmsg_len;
+	uint16_t nlmsg_type;
+	uint16_t nlmsg_flags;
+	uint32_t nlmsg_seq;
+	uint32_t nlmsg_pid;
+};
+
+struct neli_nlattr {
+	uint16_t nla_len;
+	uint16_t nla_type;
+};
+
+ssize_t neli_nlmsg_parse(const uint8_t *buf, size_t len,
+			 struct neli_nlmsghdr *hdr,
+			 const uint8_t **payload, size_t *payload_len);
+
+ssize_t neli_nlmsg_serialize(const struct neli_nlmsghdr *hdr,
+			     const uint8_t *payload, size_t payload_len,
+			     uint8_t *buf, size_t len);
+
+/* Returns 0 once len reaches 0, so it can drive a loop over attributes. */
+ssize_t neli_attr_parse(const uint8_t *buf, size_t len,
+			struct neli_nlattr *attr,
+			const uint8_t **payload, size_t *payload_len);
+
+#endif /* NELI_H */
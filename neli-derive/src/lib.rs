@@ -0,0 +1,328 @@
+//! # Derive macros for neli
+//!
+//! `#[derive(Nl)]` implements `neli::Nl` for a struct by serializing and deserializing its fields
+//! in declaration order, which is what most hand written implementations in `neli` do. It is
+//! reexported by `neli` with the `derive` feature and should be used through that reexport.
+//!
+//! Fields can be annotated with `#[neli(...)]` attributes:
+//!
+//! * `pad = N` - Write `N` zero bytes after the field when serializing and skip them when
+//!   deserializing, for reserved or padding members of C structs.
+//! * `len = "expr"` - Deserialize the field with a size hint of `expr` bytes. `expr` is evaluated
+//!   after all previous fields were deserialized and can refer to them by name, such as
+//!   `len = "data_len"` or `len = "hdr_len - 4"`.
+//! * `remaining` - Deserialize the last field with a size hint of whatever is left of the size
+//!   hint given for the whole struct, as done for attributes trailing a fixed header. Structs
+//!   with such a field require a size hint to deserialize.
+//!
+//! Errors while deserializing a field are wrapped in `DeError::Context` with the struct and field
+//! name, such as `Mymsg.name`.
+//!
+//! ```ignore
+//! #[derive(Nl)]
+//! struct Mymsg {
+//!     #[neli(pad = 3)]
+//!     family: u8,
+//!     name_len: u16,
+//!     #[neli(len = "name_len")]
+//!     name: String,
+//!     #[neli(remaining)]
+//!     attrs: Vec<Nlattr<u16, Vec<u8>>>,
+//! }
+//! ```
+//!
+//! `#[neli_enum(serialized_type = "u16")]` turns a plain enum with a value given for each variant
+//! into the same constant enum that `impl_var!` generates, with the conversions from and to the
+//! serialized type, the `UnrecognizedVariant` variant and the `Nl` implementation. Unlike with
+//! `impl_var!`, the enum and its variants can be documented like any other Rust item.
+//!
+//! ```ignore
+//! #[neli_enum(serialized_type = "u16")]
+//! /// Attributes of a custom generic netlink family
+//! pub enum MyAttr {
+//!     /// Unused
+//!     Unspec = 0,
+//!     /// Name of the object
+//!     Name = 1,
+//! }
+//! ```
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use syn::{
+    parse_macro_input, parse_quote, Data, DeriveInput, Error, Expr, Fields, Ident, ItemEnum,
+    LitInt, LitStr, Type,
+};
+
+/// Options given in `#[neli(...)]` attributes of one field
+#[derive(Default)]
+struct FieldOpts {
+    pad: usize,
+    len: Option<Expr>,
+    remaining: bool,
+}
+
+impl FieldOpts {
+    fn parse(field: &syn::Field) -> Result<Self, Error> {
+        let mut opts = FieldOpts::default();
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("neli"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("pad") {
+                    opts.pad = meta.value()?.parse::<LitInt>()?.base10_parse()?;
+                } else if meta.path.is_ident("len") {
+                    opts.len = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                } else if meta.path.is_ident("remaining") {
+                    opts.remaining = true;
+                } else {
+                    return Err(meta.error("expected `pad`, `len` or `remaining`"));
+                }
+                Ok(())
+            })?;
+        }
+        if opts.len.is_some() && opts.remaining {
+            return Err(Error::new_spanned(
+                field,
+                "`len` and `remaining` cannot be used together",
+            ));
+        }
+        Ok(opts)
+    }
+}
+
+/// Derive `neli::Nl` for a struct. See the crate documentation for supported attributes.
+#[proc_macro_derive(Nl, attributes(neli))]
+pub fn derive_nl(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_nl(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+fn expand_nl(mut input: DeriveInput) -> Result<TokenStream2, Error> {
+    let name = input.ident.clone();
+    let fields = match input.data {
+        Data::Struct(ref data) => data.fields.clone(),
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "derive(Nl) only supports structs, use impl_var! for enums",
+            ))
+        }
+    };
+
+    let count = fields.len();
+    let mut members = Vec::with_capacity(count);
+    let mut locals = Vec::with_capacity(count);
+    let mut opts = Vec::with_capacity(count);
+    for (i, field) in fields.iter().enumerate() {
+        let field_opts = FieldOpts::parse(field)?;
+        if field_opts.remaining && i + 1 != count {
+            return Err(Error::new_spanned(
+                field,
+                "`remaining` can only be used on the last field",
+            ));
+        }
+        match field.ident {
+            Some(ref ident) => {
+                members.push(quote!(#ident));
+                locals.push(ident.clone());
+            }
+            None => {
+                let index = syn::Index::from(i);
+                members.push(quote!(#index));
+                locals.push(Ident::new(&format!("__field{}", i), Span::call_site()));
+            }
+        }
+        opts.push(field_opts);
+    }
+
+    if !input.generics.params.is_empty() {
+        let where_clause = input.generics.make_where_clause();
+        for field in fields.iter() {
+            let ty = &field.ty;
+            where_clause.predicates.push(parse_quote!(#ty: ::neli::Nl));
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut serialize = Vec::with_capacity(count);
+    let mut deserialize = Vec::with_capacity(count);
+    let mut size = Vec::with_capacity(count);
+    let mut consumed = Vec::with_capacity(count);
+    for ((field, (member, local)), opts) in fields
+        .iter()
+        .zip(members.iter().zip(locals.iter()))
+        .zip(opts.iter())
+    {
+        let ty = &field.ty;
+        let pad = opts.pad;
+
+        serialize.push(quote! {
+            ::neli::Nl::serialize(&self.#member, __buf)?;
+        });
+        if pad > 0 {
+            serialize.push(quote! {
+                ::std::io::Write::write_all(__buf, &[0u8; #pad])?;
+            });
+        }
+
+        if let Some(ref len) = opts.len {
+            deserialize.push(quote! {
+                __buf.set_size_hint((#len) as usize);
+            });
+        } else if opts.remaining {
+            deserialize.push(quote! {
+                let __consumed = 0usize #(+ #consumed)*;
+                let __remaining = __size_hint.checked_sub(__consumed).ok_or(
+                    ::neli::err::DeError::InvalidLength {
+                        field: "size_hint",
+                        expected: __consumed,
+                        got: __size_hint,
+                    },
+                )?;
+                __buf.set_size_hint(__remaining);
+            });
+        }
+        let context = format!("{}.{}", name, member);
+        deserialize.push(quote! {
+            let #local = <#ty as ::neli::Nl>::deserialize(__buf)
+                .map_err(|e| ::neli::err::DeError::context(e, #context))?;
+        });
+        if pad > 0 {
+            deserialize.push(quote! {
+                ::std::io::Read::read_exact(__buf, &mut [0u8; #pad])?;
+            });
+        }
+
+        size.push(quote!(::neli::Nl::size(&self.#member) + #pad));
+        consumed.push(quote!(::neli::Nl::size(&#local) + #pad));
+    }
+
+    let take_size_hint = if opts.iter().any(|opts| opts.remaining) {
+        let msg = format!("{} requires a size hint to deserialize", name);
+        quote! {
+            let __size_hint = __buf
+                .take_size_hint()
+                .ok_or_else(|| ::neli::err::DeError::new(#msg))?;
+        }
+    } else {
+        quote!()
+    };
+    let construct = match fields {
+        Fields::Named(_) => quote!(#name { #(#locals),* }),
+        Fields::Unnamed(_) => quote!(#name ( #(#locals),* )),
+        Fields::Unit => quote!(#name),
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::neli::Nl for #name #ty_generics #where_clause {
+            fn serialize<__B>(
+                &self,
+                __buf: &mut __B,
+            ) -> ::std::result::Result<(), ::neli::err::SerError>
+            where
+                __B: ::neli::BufferMut,
+            {
+                #(#serialize)*
+                Ok(())
+            }
+
+            fn deserialize<__B>(
+                __buf: &mut __B,
+            ) -> ::std::result::Result<Self, ::neli::err::DeError>
+            where
+                __B: ::neli::Buffer,
+            {
+                #take_size_hint
+                #(#deserialize)*
+                Ok(#construct)
+            }
+
+            fn size(&self) -> usize {
+                0usize #(+ #size)*
+            }
+        }
+    })
+}
+
+/// Generate the conversions and `Nl` implementation of `impl_var!` for a plain enum. See the
+/// crate documentation for an example.
+#[proc_macro_attribute]
+pub fn neli_enum(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut serialized_type = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("serialized_type") {
+            serialized_type = Some(meta.value()?.parse::<LitStr>()?.parse::<Type>()?);
+            Ok(())
+        } else {
+            Err(meta.error("expected `serialized_type`"))
+        }
+    });
+    parse_macro_input!(attr with parser);
+    let item = parse_macro_input!(item as ItemEnum);
+    match serialized_type {
+        Some(ty) => expand_neli_enum(ty, item),
+        None => Err(Error::new(
+            Span::call_site(),
+            "`serialized_type` is required, such as #[neli_enum(serialized_type = \"u16\")]",
+        )),
+    }
+    .unwrap_or_else(|e| e.to_compile_error())
+    .into()
+}
+
+fn expand_neli_enum(ty: Type, mut item: ItemEnum) -> Result<TokenStream2, Error> {
+    let name = item.ident.clone();
+    let mut mappings = Vec::with_capacity(item.variants.len());
+    for variant in item.variants.iter_mut() {
+        if variant.ident == "UnrecognizedVariant" {
+            return Err(Error::new_spanned(
+                &variant.ident,
+                "UnrecognizedVariant is added by neli_enum",
+            ));
+        }
+        if !variant.fields.is_empty() {
+            return Err(Error::new_spanned(
+                &variant.fields,
+                "neli_enum variants cannot have fields",
+            ));
+        }
+        let value = match variant.discriminant.take() {
+            Some((_, value)) => value,
+            None => {
+                return Err(Error::new_spanned(
+                    &variant.ident,
+                    "neli_enum variants need a value, such as `Variant = 1`",
+                ))
+            }
+        };
+        let cfgs = variant
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("cfg"))
+            .collect::<Vec<_>>();
+        let ident = &variant.ident;
+        mappings.push(quote!(#(#cfgs)* #ident => #value));
+    }
+    item.variants.push(parse_quote! {
+        /// Variant that signifies an invalid value while deserializing
+        UnrecognizedVariant(#ty)
+    });
+
+    Ok(quote! {
+        #[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+        #item
+
+        ::neli::impl_var_base!(#name, #ty, #(#mappings),*);
+    })
+}
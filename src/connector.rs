@@ -0,0 +1,794 @@
+//! Kernel connector over `NETLINK_CONNECTOR` and the process events it carries
+//!
+//! Connector messages are netlink messages of type `Nlmsg::Done` whose payload is a `CnMsg`
+//! addressed to a kernel callback by its `CbId`. The process events connector reports forks,
+//! execs, exits and credential changes of all processes to sockets that joined its multicast
+//! group and sent it `ProcCnMcastOp::Listen`, which `NlSocket::subscribe_proc_events` does.
+//!
+//! ```no_run
+//! use neli::connector::ProcEvent;
+//! use neli::consts::NlFamily;
+//! use neli::socket::NlSocket;
+//!
+//! let mut socket = NlSocket::connect(NlFamily::Connector, None, None, false).unwrap();
+//! socket.subscribe_proc_events().unwrap();
+//! loop {
+//!     let msg = socket.recv_proc_event().unwrap();
+//!     if let ProcEvent::Exec { process_pid, .. } = msg.event {
+//!         println!("{} called exec", process_pid);
+//!     }
+//! }
+//! ```
+//!
+//! # Design decisions
+//!
+//! `ProcEventMsg` always serializes the event data padded to the size of the kernel union, as the
+//! kernel sends and expects `struct proc_event` at its full size whatever the kind of event.
+//! Events of unknown kinds are kept as raw bytes in `ProcEvent::Other`.
+
+use consts::connector::{ProcCnMcastOp, ProcEventWhat, CN_IDX_PROC, CN_VAL_PROC};
+use consts::Nlmsg;
+use err::{sub_len, DeError, NlError, SerError};
+use nl::Nlmsghdr;
+use socket::NlSocket;
+use value::{NlValue, ToNlValue};
+use {Buffer, BufferMut, Nl, StreamReadBuffer, StreamWriteBuffer};
+
+/// Size of the event data union of `struct proc_event`
+const PROC_EVENT_DATA_LEN: usize = 24;
+/// Size of `struct proc_event` before the event data
+const PROC_EVENT_HEADER_LEN: usize = 16;
+/// Size of `struct cn_msg` before its data
+const CN_MSG_HEADER_LEN: usize = 20;
+
+/// `struct cb_id`, identifying the kernel callback a connector message is addressed to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct CbId {
+    /// Index of the callback, also the multicast group of its notifications
+    pub idx: u32,
+    /// Value of the callback
+    pub val: u32,
+}
+
+impl CbId {
+    /// ID of the process events connector
+    pub fn proc() -> Self {
+        CbId {
+            idx: CN_IDX_PROC,
+            val: CN_VAL_PROC,
+        }
+    }
+}
+
+impl Nl for CbId {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.idx.serialize(mem)?;
+        self.val.serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        Ok(CbId {
+            idx: u32::deserialize(mem)?,
+            val: u32::deserialize(mem)?,
+        })
+    }
+
+    fn size(&self) -> usize {
+        8
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(8)
+    }
+}
+
+impl ToNlValue for CbId {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("idx", self.idx.to_nl_value()),
+            ("val", self.val.to_nl_value()),
+        ])
+    }
+}
+
+/// `struct cn_msg`, the payload of connector messages. The length of `data` is computed when
+/// serializing.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct CnMsg<P> {
+    /// Callback the message is addressed to or sent by
+    pub id: CbId,
+    /// Sequence number
+    pub seq: u32,
+    /// Acknowledgement number, echoed by the kernel in its reply
+    pub ack: u32,
+    /// Flags, unused by the kernel callbacks
+    pub flags: u16,
+    /// Data of the message
+    pub data: P,
+}
+
+impl<P> CnMsg<P> {
+    /// Message addressed to the callback `id`
+    pub fn new(id: CbId, data: P) -> Self {
+        CnMsg {
+            id,
+            seq: 0,
+            ack: 0,
+            flags: 0,
+            data,
+        }
+    }
+}
+
+impl<P> Nl for CnMsg<P>
+where
+    P: Nl,
+{
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.id.serialize(mem)?;
+        self.seq.serialize(mem)?;
+        self.ack.serialize(mem)?;
+        (self.data.size() as u16).serialize(mem)?;
+        self.flags.serialize(mem)?;
+        self.data.serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        let size_hint = mem.take_size_hint();
+        let id = CbId::deserialize(mem)?;
+        let seq = u32::deserialize(mem)?;
+        let ack = u32::deserialize(mem)?;
+        let len = u16::deserialize(mem)? as usize;
+        let flags = u16::deserialize(mem)?;
+        if let Some(size_hint) = size_hint {
+            let available = sub_len("size_hint", size_hint, CN_MSG_HEADER_LEN)?;
+            if len > available {
+                return Err(DeError::InvalidLength {
+                    field: "cn_msg.len",
+                    expected: available,
+                    got: len,
+                });
+            }
+        }
+        mem.set_size_hint(len);
+        let data = P::deserialize(mem).map_err(|e| e.context("CnMsg.data"))?;
+        Ok(CnMsg {
+            id,
+            seq,
+            ack,
+            flags,
+            data,
+        })
+    }
+
+    fn size(&self) -> usize {
+        CN_MSG_HEADER_LEN + self.data.size()
+    }
+}
+
+impl<P> ToNlValue for CnMsg<P>
+where
+    P: ToNlValue,
+{
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("id", self.id.to_nl_value()),
+            ("seq", self.seq.to_nl_value()),
+            ("ack", self.ack.to_nl_value()),
+            ("flags", self.flags.to_nl_value()),
+            ("data", self.data.to_nl_value()),
+        ])
+    }
+}
+
+/// Event data of `struct proc_event`, by kind of event
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum ProcEvent {
+    /// Reply to a `ProcCnMcastOp`, with 0 or an errno value
+    Ack {
+        /// Error of the operation
+        err: u32,
+    },
+    /// A process or thread was created
+    Fork {
+        /// PID of the thread calling fork
+        parent_pid: i32,
+        /// Process ID of the thread calling fork
+        parent_tgid: i32,
+        /// PID of the new thread
+        child_pid: i32,
+        /// Process ID of the new thread
+        child_tgid: i32,
+    },
+    /// A process called exec
+    Exec {
+        /// PID of the thread
+        process_pid: i32,
+        /// Process ID of the thread
+        process_tgid: i32,
+    },
+    /// The user IDs of a thread changed
+    Uid {
+        /// PID of the thread
+        process_pid: i32,
+        /// Process ID of the thread
+        process_tgid: i32,
+        /// Real user ID
+        ruid: u32,
+        /// Effective user ID
+        euid: u32,
+    },
+    /// The group IDs of a thread changed
+    Gid {
+        /// PID of the thread
+        process_pid: i32,
+        /// Process ID of the thread
+        process_tgid: i32,
+        /// Real group ID
+        rgid: u32,
+        /// Effective group ID
+        egid: u32,
+    },
+    /// A process started a new session
+    Sid {
+        /// PID of the thread
+        process_pid: i32,
+        /// Process ID of the thread
+        process_tgid: i32,
+    },
+    /// A thread was attached to or detached from a tracer
+    Ptrace {
+        /// PID of the traced thread
+        process_pid: i32,
+        /// Process ID of the traced thread
+        process_tgid: i32,
+        /// PID of the tracer, 0 when detaching
+        tracer_pid: i32,
+        /// Process ID of the tracer, 0 when detaching
+        tracer_tgid: i32,
+    },
+    /// The command name of a thread changed
+    Comm {
+        /// PID of the thread
+        process_pid: i32,
+        /// Process ID of the thread
+        process_tgid: i32,
+        /// New command name
+        comm: String,
+    },
+    /// A process dumped core
+    Coredump {
+        /// PID of the thread
+        process_pid: i32,
+        /// Process ID of the thread
+        process_tgid: i32,
+        /// PID of the parent
+        parent_pid: i32,
+        /// Process ID of the parent
+        parent_tgid: i32,
+    },
+    /// A thread exited
+    Exit {
+        /// PID of the thread
+        process_pid: i32,
+        /// Process ID of the thread
+        process_tgid: i32,
+        /// Exit status, as returned by `wait`
+        exit_code: u32,
+        /// Signal sent to the parent on exit
+        exit_signal: u32,
+        /// PID of the parent
+        parent_pid: i32,
+        /// Process ID of the parent
+        parent_tgid: i32,
+    },
+    /// Event of another kind, with its data left unparsed
+    Other(ProcEventWhat, Vec<u8>),
+}
+
+/// Length of the `comm` field of comm events
+const COMM_LEN: usize = 16;
+
+impl ProcEvent {
+    /// Kind of the event
+    pub fn what(&self) -> ProcEventWhat {
+        match *self {
+            ProcEvent::Ack { .. } => ProcEventWhat::None,
+            ProcEvent::Fork { .. } => ProcEventWhat::Fork,
+            ProcEvent::Exec { .. } => ProcEventWhat::Exec,
+            ProcEvent::Uid { .. } => ProcEventWhat::Uid,
+            ProcEvent::Gid { .. } => ProcEventWhat::Gid,
+            ProcEvent::Sid { .. } => ProcEventWhat::Sid,
+            ProcEvent::Ptrace { .. } => ProcEventWhat::Ptrace,
+            ProcEvent::Comm { .. } => ProcEventWhat::Comm,
+            ProcEvent::Coredump { .. } => ProcEventWhat::Coredump,
+            ProcEvent::Exit { .. } => ProcEventWhat::Exit,
+            ProcEvent::Other(ref what, _) => what.clone(),
+        }
+    }
+
+    /// Serialize the fields of the event, without padding
+    fn serialize_fields<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        match *self {
+            ProcEvent::Ack { err } => err.serialize(mem),
+            ProcEvent::Exec {
+                process_pid,
+                process_tgid,
+            }
+            | ProcEvent::Sid {
+                process_pid,
+                process_tgid,
+            } => {
+                process_pid.serialize(mem)?;
+                process_tgid.serialize(mem)
+            }
+            ProcEvent::Fork {
+                parent_pid: a,
+                parent_tgid: b,
+                child_pid: c,
+                child_tgid: d,
+            }
+            | ProcEvent::Ptrace {
+                process_pid: a,
+                process_tgid: b,
+                tracer_pid: c,
+                tracer_tgid: d,
+            }
+            | ProcEvent::Coredump {
+                process_pid: a,
+                process_tgid: b,
+                parent_pid: c,
+                parent_tgid: d,
+            } => {
+                a.serialize(mem)?;
+                b.serialize(mem)?;
+                c.serialize(mem)?;
+                d.serialize(mem)
+            }
+            ProcEvent::Uid {
+                process_pid,
+                process_tgid,
+                ruid: r,
+                euid: e,
+            }
+            | ProcEvent::Gid {
+                process_pid,
+                process_tgid,
+                rgid: r,
+                egid: e,
+            } => {
+                process_pid.serialize(mem)?;
+                process_tgid.serialize(mem)?;
+                r.serialize(mem)?;
+                e.serialize(mem)
+            }
+            ProcEvent::Comm {
+                process_pid,
+                process_tgid,
+                ref comm,
+            } => {
+                process_pid.serialize(mem)?;
+                process_tgid.serialize(mem)?;
+                if comm.len() >= COMM_LEN {
+                    return Err(SerError::new(format!(
+                        "Command name {:?} is longer than {} bytes",
+                        comm,
+                        COMM_LEN - 1
+                    )));
+                }
+                let mut bytes = [0; COMM_LEN];
+                bytes[..comm.len()].copy_from_slice(comm.as_bytes());
+                bytes.serialize(mem)
+            }
+            ProcEvent::Exit {
+                process_pid,
+                process_tgid,
+                exit_code,
+                exit_signal,
+                parent_pid,
+                parent_tgid,
+            } => {
+                process_pid.serialize(mem)?;
+                process_tgid.serialize(mem)?;
+                exit_code.serialize(mem)?;
+                exit_signal.serialize(mem)?;
+                parent_pid.serialize(mem)?;
+                parent_tgid.serialize(mem)
+            }
+            ProcEvent::Other(_, ref data) => data.serialize(mem),
+        }
+    }
+
+    /// Parse the event data of an event of kind `what`
+    fn parse(what: ProcEventWhat, data: Vec<u8>) -> Result<Self, DeError> {
+        let mut mem = StreamReadBuffer::new(&data);
+        let mem = &mut mem;
+        Ok(match what {
+            ProcEventWhat::None => ProcEvent::Ack {
+                err: u32::deserialize(mem)?,
+            },
+            ProcEventWhat::Fork => ProcEvent::Fork {
+                parent_pid: i32::deserialize(mem)?,
+                parent_tgid: i32::deserialize(mem)?,
+                child_pid: i32::deserialize(mem)?,
+                child_tgid: i32::deserialize(mem)?,
+            },
+            ProcEventWhat::Exec => ProcEvent::Exec {
+                process_pid: i32::deserialize(mem)?,
+                process_tgid: i32::deserialize(mem)?,
+            },
+            ProcEventWhat::Uid => ProcEvent::Uid {
+                process_pid: i32::deserialize(mem)?,
+                process_tgid: i32::deserialize(mem)?,
+                ruid: u32::deserialize(mem)?,
+                euid: u32::deserialize(mem)?,
+            },
+            ProcEventWhat::Gid => ProcEvent::Gid {
+                process_pid: i32::deserialize(mem)?,
+                process_tgid: i32::deserialize(mem)?,
+                rgid: u32::deserialize(mem)?,
+                egid: u32::deserialize(mem)?,
+            },
+            ProcEventWhat::Sid => ProcEvent::Sid {
+                process_pid: i32::deserialize(mem)?,
+                process_tgid: i32::deserialize(mem)?,
+            },
+            ProcEventWhat::Ptrace => ProcEvent::Ptrace {
+                process_pid: i32::deserialize(mem)?,
+                process_tgid: i32::deserialize(mem)?,
+                tracer_pid: i32::deserialize(mem)?,
+                tracer_tgid: i32::deserialize(mem)?,
+            },
+            ProcEventWhat::Comm => {
+                let process_pid = i32::deserialize(mem)?;
+                let process_tgid = i32::deserialize(mem)?;
+                let bytes = <[u8; COMM_LEN]>::deserialize(mem)?;
+                let len = bytes.iter().position(|b| *b == 0).unwrap_or(COMM_LEN);
+                ProcEvent::Comm {
+                    process_pid,
+                    process_tgid,
+                    comm: String::from_utf8_lossy(&bytes[..len]).into_owned(),
+                }
+            }
+            ProcEventWhat::Coredump => ProcEvent::Coredump {
+                process_pid: i32::deserialize(mem)?,
+                process_tgid: i32::deserialize(mem)?,
+                parent_pid: i32::deserialize(mem)?,
+                parent_tgid: i32::deserialize(mem)?,
+            },
+            ProcEventWhat::Exit => ProcEvent::Exit {
+                process_pid: i32::deserialize(mem)?,
+                process_tgid: i32::deserialize(mem)?,
+                exit_code: u32::deserialize(mem)?,
+                exit_signal: u32::deserialize(mem)?,
+                parent_pid: i32::deserialize(mem)?,
+                parent_tgid: i32::deserialize(mem)?,
+            },
+            what => ProcEvent::Other(what, data.clone()),
+        })
+    }
+}
+
+impl ToNlValue for ProcEvent {
+    fn to_nl_value(&self) -> NlValue {
+        match *self {
+            ProcEvent::Ack { err } => NlValue::map(vec![("err", err.to_nl_value())]),
+            ProcEvent::Fork {
+                parent_pid,
+                parent_tgid,
+                child_pid,
+                child_tgid,
+            } => NlValue::map(vec![
+                ("parent_pid", parent_pid.to_nl_value()),
+                ("parent_tgid", parent_tgid.to_nl_value()),
+                ("child_pid", child_pid.to_nl_value()),
+                ("child_tgid", child_tgid.to_nl_value()),
+            ]),
+            ProcEvent::Exec {
+                process_pid,
+                process_tgid,
+            }
+            | ProcEvent::Sid {
+                process_pid,
+                process_tgid,
+            } => NlValue::map(vec![
+                ("process_pid", process_pid.to_nl_value()),
+                ("process_tgid", process_tgid.to_nl_value()),
+            ]),
+            ProcEvent::Uid {
+                process_pid,
+                process_tgid,
+                ruid,
+                euid,
+            } => NlValue::map(vec![
+                ("process_pid", process_pid.to_nl_value()),
+                ("process_tgid", process_tgid.to_nl_value()),
+                ("ruid", ruid.to_nl_value()),
+                ("euid", euid.to_nl_value()),
+            ]),
+            ProcEvent::Gid {
+                process_pid,
+                process_tgid,
+                rgid,
+                egid,
+            } => NlValue::map(vec![
+                ("process_pid", process_pid.to_nl_value()),
+                ("process_tgid", process_tgid.to_nl_value()),
+                ("rgid", rgid.to_nl_value()),
+                ("egid", egid.to_nl_value()),
+            ]),
+            ProcEvent::Ptrace {
+                process_pid,
+                process_tgid,
+                tracer_pid,
+                tracer_tgid,
+            } => NlValue::map(vec![
+                ("process_pid", process_pid.to_nl_value()),
+                ("process_tgid", process_tgid.to_nl_value()),
+                ("tracer_pid", tracer_pid.to_nl_value()),
+                ("tracer_tgid", tracer_tgid.to_nl_value()),
+            ]),
+            ProcEvent::Comm {
+                process_pid,
+                process_tgid,
+                ref comm,
+            } => NlValue::map(vec![
+                ("process_pid", process_pid.to_nl_value()),
+                ("process_tgid", process_tgid.to_nl_value()),
+                ("comm", comm.to_nl_value()),
+            ]),
+            ProcEvent::Coredump {
+                process_pid,
+                process_tgid,
+                parent_pid,
+                parent_tgid,
+            } => NlValue::map(vec![
+                ("process_pid", process_pid.to_nl_value()),
+                ("process_tgid", process_tgid.to_nl_value()),
+                ("parent_pid", parent_pid.to_nl_value()),
+                ("parent_tgid", parent_tgid.to_nl_value()),
+            ]),
+            ProcEvent::Exit {
+                process_pid,
+                process_tgid,
+                exit_code,
+                exit_signal,
+                parent_pid,
+                parent_tgid,
+            } => NlValue::map(vec![
+                ("process_pid", process_pid.to_nl_value()),
+                ("process_tgid", process_tgid.to_nl_value()),
+                ("exit_code", exit_code.to_nl_value()),
+                ("exit_signal", exit_signal.to_nl_value()),
+                ("parent_pid", parent_pid.to_nl_value()),
+                ("parent_tgid", parent_tgid.to_nl_value()),
+            ]),
+            ProcEvent::Other(_, ref data) => data.to_nl_value(),
+        }
+    }
+}
+
+/// `struct proc_event`, a process event sent by the process events connector
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct ProcEventMsg {
+    /// CPU the event happened on
+    pub cpu: u32,
+    /// Time of the event, in nanoseconds since boot
+    pub timestamp_ns: u64,
+    /// Kind and data of the event
+    pub event: ProcEvent,
+}
+
+impl Nl for ProcEventMsg {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.event.what().serialize(mem)?;
+        self.cpu.serialize(mem)?;
+        self.timestamp_ns.serialize(mem)?;
+        let mut data = StreamWriteBuffer::new_growable(Some(PROC_EVENT_DATA_LEN));
+        self.event.serialize_fields(&mut data)?;
+        let data = data.as_ref();
+        mem.write_all(data)?;
+        if data.len() < PROC_EVENT_DATA_LEN {
+            mem.write_all(&[0; PROC_EVENT_DATA_LEN][data.len()..])?;
+        }
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        let size_hint = mem
+            .take_size_hint()
+            .ok_or_else(|| DeError::new("Must provide size hint to deserialize ProcEventMsg"))?;
+        let data_len = sub_len("size_hint", size_hint, PROC_EVENT_HEADER_LEN)?;
+        let what = ProcEventWhat::deserialize(mem)?;
+        let cpu = u32::deserialize(mem)?;
+        let timestamp_ns = u64::deserialize(mem)?;
+        let mut data = vec![0; data_len];
+        mem.read_exact(&mut data)?;
+        Ok(ProcEventMsg {
+            cpu,
+            timestamp_ns,
+            event: ProcEvent::parse(what, data).map_err(|e| e.context("ProcEventMsg.event"))?,
+        })
+    }
+
+    fn size(&self) -> usize {
+        let data_len = match self.event {
+            ProcEvent::Other(_, ref data) => data.len().max(PROC_EVENT_DATA_LEN),
+            _ => PROC_EVENT_DATA_LEN,
+        };
+        PROC_EVENT_HEADER_LEN + data_len
+    }
+}
+
+impl ToNlValue for ProcEventMsg {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("what", self.event.what().to_nl_value()),
+            ("cpu", self.cpu.to_nl_value()),
+            ("timestamp_ns", self.timestamp_ns.to_nl_value()),
+            ("event_data", self.event.to_nl_value()),
+        ])
+    }
+}
+
+impl NlSocket {
+    /// Join the multicast group of the process events connector and ask it to start sending
+    /// events. Events are received with `recv_proc_event`. This requires `CAP_NET_ADMIN`.
+    pub fn subscribe_proc_events(&mut self) -> Result<(), NlError> {
+        self.add_mcast_membership(&[CN_IDX_PROC])?;
+        self.send_proc_mcast_op(ProcCnMcastOp::Listen)
+    }
+
+    /// Ask the process events connector to stop sending events to this socket
+    pub fn unsubscribe_proc_events(&mut self) -> Result<(), NlError> {
+        self.send_proc_mcast_op(ProcCnMcastOp::Ignore)?;
+        self.drop_mcast_membership(&[CN_IDX_PROC])?;
+        Ok(())
+    }
+
+    fn send_proc_mcast_op(&mut self, op: ProcCnMcastOp) -> Result<(), NlError> {
+        let msg = Nlmsghdr::new(
+            None,
+            Nlmsg::Done,
+            vec![],
+            None,
+            None,
+            CnMsg::new(CbId::proc(), op),
+        );
+        self.send_nl(msg)
+    }
+
+    /// Receive the next process event, skipping connector messages of other callbacks
+    pub fn recv_proc_event(&mut self) -> Result<ProcEventMsg, NlError> {
+        loop {
+            let msg = self.recv_nl::<Nlmsg, CnMsg<Vec<u8>>>(None)?;
+            let cn_msg = msg.nl_payload;
+            if cn_msg.id != CbId::proc() {
+                continue;
+            }
+            let mut mem = StreamReadBuffer::new(&cn_msg.data);
+            mem.set_size_hint(cn_msg.data.len());
+            return Ok(ProcEventMsg::deserialize(&mut mem)?);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use test_utils::assert_roundtrip;
+
+    fn exit_msg() -> CnMsg<ProcEventMsg> {
+        CnMsg::new(
+            CbId::proc(),
+            ProcEventMsg {
+                cpu: 3,
+                timestamp_ns: 1_000_000,
+                event: ProcEvent::Exit {
+                    process_pid: 100,
+                    process_tgid: 100,
+                    exit_code: 256,
+                    exit_signal: 17,
+                    parent_pid: 1,
+                    parent_tgid: 1,
+                },
+            },
+        )
+    }
+
+    #[test]
+    fn test_cn_msg() {
+        let bytes = assert_roundtrip(&exit_msg());
+        assert_eq!(bytes.len(), 60);
+        assert_eq!(&bytes[16..18], &40u16.to_ne_bytes());
+        assert_eq!(&bytes[20..24], &0x8000_0000u32.to_ne_bytes());
+        assert_eq!(&bytes[44..48], &256u32.to_ne_bytes());
+    }
+
+    #[test]
+    fn test_cn_msg_bad_len() {
+        let mut bytes = assert_roundtrip(&exit_msg());
+        bytes[16..18].copy_from_slice(&41u16.to_ne_bytes());
+        let mut mem = StreamReadBuffer::new(&bytes);
+        mem.set_size_hint(bytes.len());
+        match CnMsg::<ProcEventMsg>::deserialize(&mut mem) {
+            Err(DeError::InvalidLength { got: 41, .. }) => (),
+            res => panic!("Unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_proc_event_comm() {
+        let mut comm = ProcEventMsg {
+            cpu: 0,
+            timestamp_ns: 0,
+            event: ProcEvent::Comm {
+                process_pid: 5,
+                process_tgid: 5,
+                comm: "kworker".to_string(),
+            },
+        };
+        assert_eq!(assert_roundtrip(&comm).len(), 40);
+
+        comm.event = ProcEvent::Comm {
+            process_pid: 5,
+            process_tgid: 5,
+            comm: "a".repeat(COMM_LEN),
+        };
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        assert!(comm.serialize(&mut mem).is_err());
+    }
+
+    #[test]
+    fn test_proc_event_unknown() {
+        let msg = ProcEventMsg {
+            cpu: 1,
+            timestamp_ns: 2,
+            event: ProcEvent::Other(
+                ProcEventWhat::UnrecognizedVariant(0x1234),
+                (0..PROC_EVENT_DATA_LEN as u8).collect(),
+            ),
+        };
+        let bytes = assert_roundtrip(&msg);
+        assert_eq!(&bytes[..4], &0x1234u32.to_ne_bytes());
+    }
+
+    #[test]
+    fn test_proc_event_truncated() {
+        let bytes = assert_roundtrip(&exit_msg().data);
+        // Exit events need 24 bytes of event data
+        let mut mem = StreamReadBuffer::new(&bytes[..PROC_EVENT_HEADER_LEN + 16]);
+        mem.set_size_hint(PROC_EVENT_HEADER_LEN + 16);
+        assert!(ProcEventMsg::deserialize(&mut mem).is_err());
+        // Without a size hint, the length of the event data is unknown
+        assert!(ProcEventMsg::deserialize(&mut StreamReadBuffer::new(&bytes)).is_err());
+    }
+}
@@ -0,0 +1,39 @@
+//! Constants of the ACPI event generic netlink family, from `drivers/acpi/event.c` as the kernel
+//! does not export them in a UAPI header
+
+use super::genl::Cmd;
+use super::nl::NlAttrType;
+use crate::genl::GenlFamily;
+
+/// The `acpi_event` generic netlink family
+pub struct AcpiEvent;
+
+impl GenlFamily for AcpiEvent {
+    const NAME: &'static str = "acpi_event";
+    const VERSION: u8 = 1;
+    type Cmd = AcpiGenlCmd;
+    type Attr = AcpiGenlAttr;
+}
+
+/// Multicast group of ACPI events
+pub const ACPI_GENL_MCAST_GROUP_NAME: &str = "acpi_mc_group";
+
+impl_var_trait!(
+    /// Commands of the `acpi_event` family
+    AcpiGenlCmd, u8, Cmd,
+    Unspec => 0,
+    Event => 1
+);
+
+impl_var_trait!(
+    /// Attributes of the `acpi_event` family
+    AcpiGenlAttr, u16, NlAttrType,
+    Unspec => 0,
+    Event => 1
+);
+
+/// Constants needed for ACPI event messages, for glob imports
+pub mod prelude {
+    pub use super::{AcpiEvent, AcpiGenlAttr, AcpiGenlCmd, ACPI_GENL_MCAST_GROUP_NAME};
+    pub use crate::consts::genl::prelude::*;
+}
@@ -0,0 +1,36 @@
+//! Constants of the `NETLINK_CONNECTOR` protocol and of the process events connector, from
+//! `linux/connector.h` and `linux/cn_proc.h`
+
+/// `cb_id.idx` of the process events connector, also its multicast group
+pub const CN_IDX_PROC: u32 = 1;
+/// `cb_id.val` of the process events connector
+pub const CN_VAL_PROC: u32 = 1;
+
+impl_var!(
+    /// Kinds of process events, from the `what` field of `struct proc_event`
+    ProcEventWhat, u32,
+    None => 0x0,
+    Fork => 0x1,
+    Exec => 0x2,
+    Uid => 0x4,
+    Gid => 0x40,
+    Sid => 0x80,
+    Ptrace => 0x100,
+    Comm => 0x200,
+    NonzeroExit => 0x2000_0000,
+    Coredump => 0x4000_0000,
+    Exit => 0x8000_0000
+);
+
+impl_var!(
+    /// Operations sent to the process events connector to start or stop receiving events
+    ProcCnMcastOp, u32,
+    Listen => 1,
+    Ignore => 2
+);
+
+/// Constants needed for connector messages, for glob imports
+pub mod prelude {
+    pub use super::*;
+    pub use crate::consts::nl::prelude::*;
+}
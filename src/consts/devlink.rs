@@ -0,0 +1,329 @@
+//! Constants of the devlink generic netlink family managing devices as a whole, from
+//! `linux/devlink.h`
+
+use super::genl::Cmd;
+use super::nl::NlAttrType;
+use crate::genl::GenlFamily;
+
+/// The `devlink` generic netlink family
+pub struct Devlink;
+
+impl GenlFamily for Devlink {
+    const NAME: &'static str = "devlink";
+    const VERSION: u8 = 1;
+    type Cmd = DevlinkCmd;
+    type Attr = DevlinkAttr;
+}
+
+/// Multicast group of notifications of changes to devices, ports and their objects
+pub const DEVLINK_GENL_MCGRP_CONFIG_NAME: &str = "config";
+
+impl_var_trait!(
+    /// Commands of the `devlink` family
+    DevlinkCmd, u8, Cmd,
+    Unspec => 0,
+    Get => 1,
+    Set => 2,
+    New => 3,
+    Del => 4,
+    PortGet => 5,
+    PortSet => 6,
+    PortNew => 7,
+    PortDel => 8,
+    PortSplit => 9,
+    PortUnsplit => 10,
+    SbGet => 11,
+    SbSet => 12,
+    SbNew => 13,
+    SbDel => 14,
+    SbPoolGet => 15,
+    SbPoolSet => 16,
+    SbPoolNew => 17,
+    SbPoolDel => 18,
+    SbPortPoolGet => 19,
+    SbPortPoolSet => 20,
+    SbPortPoolNew => 21,
+    SbPortPoolDel => 22,
+    SbTcPoolBindGet => 23,
+    SbTcPoolBindSet => 24,
+    SbTcPoolBindNew => 25,
+    SbTcPoolBindDel => 26,
+    SbOccSnapshot => 27,
+    SbOccMaxClear => 28,
+    EswitchGet => 29,
+    EswitchSet => 30,
+    DpipeTableGet => 31,
+    DpipeEntriesGet => 32,
+    DpipeHeadersGet => 33,
+    DpipeTableCountersSet => 34,
+    ResourceSet => 35,
+    ResourceDump => 36,
+    Reload => 37,
+    ParamGet => 38,
+    ParamSet => 39,
+    ParamNew => 40,
+    ParamDel => 41,
+    RegionGet => 42,
+    RegionSet => 43,
+    RegionNew => 44,
+    RegionDel => 45,
+    RegionRead => 46,
+    PortParamGet => 47,
+    PortParamSet => 48,
+    PortParamNew => 49,
+    PortParamDel => 50,
+    InfoGet => 51,
+    HealthReporterGet => 52,
+    HealthReporterSet => 53,
+    HealthReporterRecover => 54,
+    HealthReporterDiagnose => 55,
+    HealthReporterDumpGet => 56,
+    HealthReporterDumpClear => 57,
+    FlashUpdate => 58,
+    FlashUpdateEnd => 59,
+    FlashUpdateStatus => 60,
+    TrapGet => 61,
+    TrapSet => 62,
+    TrapNew => 63,
+    TrapDel => 64,
+    TrapGroupGet => 65,
+    TrapGroupSet => 66,
+    TrapGroupNew => 67,
+    TrapGroupDel => 68,
+    TrapPolicerGet => 69,
+    TrapPolicerSet => 70,
+    TrapPolicerNew => 71,
+    TrapPolicerDel => 72,
+    HealthReporterTest => 73,
+    RateGet => 74,
+    RateSet => 75,
+    RateNew => 76,
+    RateDel => 77,
+    LinecardGet => 78,
+    LinecardSet => 79,
+    LinecardNew => 80,
+    LinecardDel => 81,
+    SelftestsGet => 82,
+    SelftestsRun => 83
+);
+
+impl_var_trait!(
+    /// Attributes of the `devlink` family
+    DevlinkAttr, u16, NlAttrType,
+    Unspec => 0,
+    BusName => 1,
+    DevName => 2,
+    PortIndex => 3,
+    PortType => 4,
+    PortDesiredType => 5,
+    PortNetdevIfindex => 6,
+    PortNetdevName => 7,
+    PortIbdevName => 8,
+    PortSplitCount => 9,
+    PortSplitGroup => 10,
+    SbIndex => 11,
+    SbSize => 12,
+    SbIngressPoolCount => 13,
+    SbEgressPoolCount => 14,
+    SbIngressTcCount => 15,
+    SbEgressTcCount => 16,
+    SbPoolIndex => 17,
+    SbPoolType => 18,
+    SbPoolSize => 19,
+    SbPoolThresholdType => 20,
+    SbThreshold => 21,
+    SbTcIndex => 22,
+    SbOccCur => 23,
+    SbOccMax => 24,
+    EswitchMode => 25,
+    EswitchInlineMode => 26,
+    DpipeTables => 27,
+    DpipeTable => 28,
+    DpipeTableName => 29,
+    DpipeTableSize => 30,
+    DpipeTableMatches => 31,
+    DpipeTableActions => 32,
+    DpipeTableCountersEnabled => 33,
+    DpipeEntries => 34,
+    DpipeEntry => 35,
+    DpipeEntryIndex => 36,
+    DpipeEntryMatchValues => 37,
+    DpipeEntryActionValues => 38,
+    DpipeEntryCounter => 39,
+    DpipeMatch => 40,
+    DpipeMatchValue => 41,
+    DpipeMatchType => 42,
+    DpipeAction => 43,
+    DpipeActionValue => 44,
+    DpipeActionType => 45,
+    DpipeValue => 46,
+    DpipeValueMask => 47,
+    DpipeValueMapping => 48,
+    DpipeHeaders => 49,
+    DpipeHeader => 50,
+    DpipeHeaderName => 51,
+    DpipeHeaderId => 52,
+    DpipeHeaderFields => 53,
+    DpipeHeaderGlobal => 54,
+    DpipeHeaderIndex => 55,
+    DpipeField => 56,
+    DpipeFieldName => 57,
+    DpipeFieldId => 58,
+    DpipeFieldBitwidth => 59,
+    DpipeFieldMappingType => 60,
+    Pad => 61,
+    EswitchEncapMode => 62,
+    ResourceList => 63,
+    Resource => 64,
+    ResourceName => 65,
+    ResourceId => 66,
+    ResourceSize => 67,
+    ResourceSizeNew => 68,
+    ResourceSizeValid => 69,
+    ResourceSizeMin => 70,
+    ResourceSizeMax => 71,
+    ResourceSizeGran => 72,
+    ResourceUnit => 73,
+    ResourceOcc => 74,
+    DpipeTableResourceId => 75,
+    DpipeTableResourceUnits => 76,
+    PortFlavour => 77,
+    PortNumber => 78,
+    PortSplitSubportNumber => 79,
+    Param => 80,
+    ParamName => 81,
+    ParamGeneric => 82,
+    ParamType => 83,
+    ParamValuesList => 84,
+    ParamValue => 85,
+    ParamValueData => 86,
+    ParamValueCmode => 87,
+    RegionName => 88,
+    RegionSize => 89,
+    RegionSnapshots => 90,
+    RegionSnapshot => 91,
+    RegionSnapshotId => 92,
+    RegionChunks => 93,
+    RegionChunk => 94,
+    RegionChunkData => 95,
+    RegionChunkAddr => 96,
+    RegionChunkLen => 97,
+    InfoDriverName => 98,
+    InfoSerialNumber => 99,
+    InfoVersionFixed => 100,
+    InfoVersionRunning => 101,
+    InfoVersionStored => 102,
+    InfoVersionName => 103,
+    InfoVersionValue => 104,
+    SbPoolCellSize => 105,
+    Fmsg => 106,
+    FmsgObjNestStart => 107,
+    FmsgPairNestStart => 108,
+    FmsgArrNestStart => 109,
+    FmsgNestEnd => 110,
+    FmsgObjName => 111,
+    FmsgObjValueType => 112,
+    FmsgObjValueData => 113,
+    HealthReporter => 114,
+    HealthReporterName => 115,
+    HealthReporterState => 116,
+    HealthReporterErrCount => 117,
+    HealthReporterRecoverCount => 118,
+    HealthReporterDumpTs => 119,
+    HealthReporterGracefulPeriod => 120,
+    HealthReporterAutoRecover => 121,
+    FlashUpdateFileName => 122,
+    FlashUpdateComponent => 123,
+    FlashUpdateStatusMsg => 124,
+    FlashUpdateStatusDone => 125,
+    FlashUpdateStatusTotal => 126,
+    PortPciPfNumber => 127,
+    PortPciVfNumber => 128,
+    Stats => 129,
+    TrapName => 130,
+    TrapAction => 131,
+    TrapType => 132,
+    TrapGeneric => 133,
+    TrapMetadata => 134,
+    TrapGroupName => 135,
+    ReloadFailed => 136,
+    HealthReporterDumpTsNs => 137,
+    NetnsFd => 138,
+    NetnsPid => 139,
+    NetnsId => 140,
+    HealthReporterAutoDump => 141,
+    TrapPolicerId => 142,
+    TrapPolicerRate => 143,
+    TrapPolicerBurst => 144,
+    PortFunction => 145,
+    InfoBoardSerialNumber => 146,
+    PortLanes => 147,
+    PortSplittable => 148,
+    PortExternal => 149,
+    PortControllerNumber => 150,
+    FlashUpdateStatusTimeout => 151,
+    FlashUpdateOverwriteMask => 152,
+    ReloadAction => 153,
+    ReloadActionsPerformed => 154,
+    ReloadLimits => 155,
+    DevStats => 156,
+    ReloadStats => 157,
+    ReloadStatsEntry => 158,
+    ReloadStatsLimit => 159,
+    ReloadStatsValue => 160,
+    RemoteReloadStats => 161,
+    ReloadActionInfo => 162,
+    ReloadActionStats => 163,
+    PortPciSfNumber => 164,
+    RateType => 165,
+    RateTxShare => 166,
+    RateTxMax => 167,
+    RateNodeName => 168,
+    RateParentNodeName => 169,
+    RegionMaxSnapshots => 170,
+    LinecardIndex => 171,
+    LinecardState => 172,
+    LinecardType => 173,
+    LinecardSupportedTypes => 174,
+    NestedDevlink => 175,
+    Selftests => 176
+);
+
+impl_var!(
+    /// Port types in `DevlinkAttr::PortType`
+    DevlinkPortType, u16,
+    Notset => 0,
+    Auto => 1,
+    Eth => 2,
+    Ib => 3
+);
+
+impl_var!(
+    /// Port flavours in `DevlinkAttr::PortFlavour`
+    DevlinkPortFlavour, u16,
+    Physical => 0,
+    Cpu => 1,
+    Dsa => 2,
+    PciPf => 3,
+    PciVf => 4,
+    Virtual => 5,
+    Unused => 6,
+    PciSf => 7
+);
+
+impl_var!(
+    /// Configuration modes of parameter values in `DevlinkAttr::ParamValueCmode`
+    DevlinkParamCmode, u8,
+    Runtime => 0,
+    Driverinit => 1,
+    Permanent => 2
+);
+
+/// Constants needed for devlink messages, for glob imports
+pub mod prelude {
+    pub use super::{
+        Devlink, DevlinkAttr, DevlinkCmd, DevlinkParamCmode, DevlinkPortFlavour, DevlinkPortType,
+        DEVLINK_GENL_MCGRP_CONFIG_NAME,
+    };
+    pub use crate::consts::genl::prelude::*;
+}
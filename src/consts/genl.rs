@@ -1,3 +1,5 @@
+use super::nl::{NlAttrType, NlType};
+
 impl_trait!(
     /// Trait marking constants valid for use in `Genlmsghdr.cmd`
     Cmd,
@@ -16,5 +18,120 @@ impl_var_trait!(
     Getops => libc::CTRL_CMD_GETOPS as u8,
     NewmcastGrp => libc::CTRL_CMD_NEWMCAST_GRP as u8,
     DelmcastGrp => libc::CTRL_CMD_DELMCAST_GRP as u8,
-    GetmcastGrp => libc::CTRL_CMD_GETMCAST_GRP as u8
+    GetmcastGrp => libc::CTRL_CMD_GETMCAST_GRP as u8,
+    Getpolicy => 10
+);
+
+impl_var_trait!(
+    /// Values for `nl_type` in `Nlmsghdr`
+    GenlId, u16, NlType,
+    Ctrl => libc::GENL_ID_CTRL as u16,
+    #[cfg(target_env="gnu")]
+    VfsDquot => libc::GENL_ID_VFS_DQUOT as u16,
+    #[cfg(target_env="gnu")]
+    Pmcraid => libc::GENL_ID_PMCRAID as u16
+);
+
+impl_var_trait!(
+    /// Values for `nla_type` in `Nlattr`
+    CtrlAttr, u16, NlAttrType,
+    Unspec => libc::CTRL_ATTR_UNSPEC as u16,
+    FamilyId => libc::CTRL_ATTR_FAMILY_ID as u16,
+    FamilyName => libc::CTRL_ATTR_FAMILY_NAME as u16,
+    Version => libc::CTRL_ATTR_VERSION as u16,
+    Hdrsize => libc::CTRL_ATTR_HDRSIZE as u16,
+    Maxattr => libc::CTRL_ATTR_MAXATTR as u16,
+    Ops => libc::CTRL_ATTR_OPS as u16,
+    McastGroups => libc::CTRL_ATTR_MCAST_GROUPS as u16,
+    Policy => 8,
+    OpPolicy => 9,
+    Op => 10
+);
+
+impl_var_trait!(
+    /// Attributes of each command nested in `CtrlAttr::Ops`
+    CtrlAttrOp, u16, NlAttrType,
+    Unspec => libc::CTRL_ATTR_OP_UNSPEC as u16,
+    Id => libc::CTRL_ATTR_OP_ID as u16,
+    Flags => libc::CTRL_ATTR_OP_FLAGS as u16
+);
+
+impl_var!(
+    /// Flags of a command in the `CtrlAttrOp::Flags` attribute
+    GenlOpFlag, u32,
+    AdminPerm => libc::GENL_ADMIN_PERM as u32,
+    CmdCapDo => libc::GENL_CMD_CAP_DO as u32,
+    CmdCapDump => libc::GENL_CMD_CAP_DUMP as u32,
+    CmdCapHaspol => libc::GENL_CMD_CAP_HASPOL as u32,
+    UnsAdminPerm => 0x10
+);
+
+impl_flags!(
+    /// Set of `GenlOpFlag` flags
+    GenlOpFlags, GenlOpFlag, u32
+);
+
+impl_var_trait!(
+    /// Attributes nested in `CtrlAttr::OpPolicy` of `CtrlCmd::Getpolicy` replies, giving the
+    /// policy index of the do and dump variants of a command
+    CtrlAttrPolicy, u16, NlAttrType,
+    Unspec => 0,
+    Do => 1,
+    Dump => 2
+);
+
+impl_var_trait!(
+    /// Attributes describing one attribute of a policy nested in `CtrlAttr::Policy` of
+    /// `CtrlCmd::Getpolicy` replies
+    NlPolicyTypeAttr, u16, NlAttrType,
+    Unspec => 0,
+    Type => 1,
+    MinValueS => 2,
+    MaxValueS => 3,
+    MinValueU => 4,
+    MaxValueU => 5,
+    MinLength => 6,
+    MaxLength => 7,
+    PolicyIdx => 8,
+    PolicyMaxtype => 9,
+    Bitfield32Mask => 10,
+    Pad => 11,
+    Mask => 12
 );
+
+impl_var!(
+    /// Attribute types in the `NlPolicyTypeAttr::Type` attribute
+    NlAttrKind, u32,
+    Invalid => 0,
+    Flag => 1,
+    U8 => 2,
+    U16 => 3,
+    U32 => 4,
+    U64 => 5,
+    S8 => 6,
+    S16 => 7,
+    S32 => 8,
+    S64 => 9,
+    Binary => 10,
+    String => 11,
+    NulString => 12,
+    Nested => 13,
+    NestedArray => 14,
+    Bitfield32 => 15,
+    Sint => 16,
+    Uint => 17
+);
+
+impl_var_trait!(
+    /// Values for `nla_type` in `Nlattr`
+    CtrlAttrMcastGrp, u16, NlAttrType,
+    Unspec => libc::CTRL_ATTR_MCAST_GRP_UNSPEC as u16,
+    Name => libc::CTRL_ATTR_MCAST_GRP_NAME as u16,
+    Id => libc::CTRL_ATTR_MCAST_GRP_ID as u16
+);
+
+/// Constants needed for generic netlink messages, for glob imports
+pub mod prelude {
+    pub use super::*;
+    pub use crate::consts::nl::prelude::*;
+}
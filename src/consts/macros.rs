@@ -47,24 +47,242 @@ macro_rules! impl_var_base {
         }
 
         impl $crate::Nl for $name {
-            fn serialize(&self, mem: &mut $crate::StreamWriteBuffer) -> Result<(), $crate::err::SerError> {
+            fn serialize<B>(&self, mem: &mut B) -> Result<(), $crate::err::SerError>
+                    where B: $crate::BufferMut {
                 let v: $ty = self.clone().into();
-                v.serialize(mem)
+                $crate::Nl::serialize(&v, mem)
             }
 
-            fn deserialize<T>(mem: &mut $crate::StreamReadBuffer<T>) -> Result<Self, $crate::err::DeError>
-                    where T: AsRef<[u8]> {
-                let v = <$ty>::deserialize(mem)?;
-                Ok(v.into())
+            fn deserialize<T>(mem: &mut T) -> Result<Self, $crate::err::DeError>
+                    where T: $crate::Buffer {
+                let v = <$ty as $crate::Nl>::deserialize(mem)?;
+                let var = $name::from(v);
+                if let $name::UnrecognizedVariant(_) = var {
+                    $crate::consts::report_unrecognized(stringify!($name), v as i128);
+                }
+                Ok(var)
             }
 
             fn size(&self) -> usize {
                 std::mem::size_of::<$ty>()
             }
+
+            fn type_size() -> Option<usize> {
+                Some(std::mem::size_of::<$ty>())
+            }
+        }
+
+        $crate::impl_var_serde!($name, $ty, $( $( #[cfg($meta)] )* $var ),* );
+        $crate::impl_var_arbitrary!($name, $ty, $( $( #[cfg($meta)] )* $var ),* );
+
+        impl $crate::value::ToNlValue for $name {
+            fn to_nl_value(&self) -> $crate::value::NlValue {
+                let v: $ty = self.clone().into();
+                $crate::value::NlValue::Const {
+                    name: match *self {
+                        $name::UnrecognizedVariant(_) => None,
+                        _ => Some(format!("{:?}", self)),
+                    },
+                    value: v as i128,
+                }
+            }
+        }
+    };
+}
+
+// Serde support for `impl_var!` enums. It is selected by the `serde` feature of neli rather than
+// of the crate invoking `impl_var!`. Known variants are represented by their name and unknown
+// ones by their value; either is accepted when deserializing.
+#[cfg(feature = "serde")]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! impl_var_serde {
+    ($name:ident, $ty:ty, $( $( #[cfg($meta:meta)] )* $var:ident ),* ) => {
+        impl $crate::serde::Serialize for $name {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+                    where S: $crate::serde::Serializer {
+                match *self {
+                    $(
+                        $(
+                            #[cfg($meta)]
+                        )*
+                        $name::$var => serializer.serialize_str(stringify!($var)),
+                    )*
+                    $name::UnrecognizedVariant(ref v) => $crate::serde::Serialize::serialize(v, serializer),
+                }
+            }
+        }
+
+        impl<'de> $crate::serde::Deserialize<'de> for $name {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+                    where D: $crate::serde::Deserializer<'de> {
+                struct Visitor;
+
+                impl<'de> $crate::serde::de::Visitor<'de> for Visitor {
+                    type Value = $name;
+
+                    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        write!(f, "a variant name or value of {}", stringify!($name))
+                    }
+
+                    fn visit_str<E>(self, v: &str) -> Result<$name, E>
+                            where E: $crate::serde::de::Error {
+                        $(
+                            $(
+                                #[cfg($meta)]
+                            )*
+                            {
+                                if v == stringify!($var) {
+                                    return Ok($name::$var);
+                                }
+                            }
+                        )*
+                        Err(E::custom(format!("unknown variant `{}` of {}", v, stringify!($name))))
+                    }
+
+                    fn visit_u64<E>(self, v: u64) -> Result<$name, E>
+                            where E: $crate::serde::de::Error {
+                        <$ty as std::convert::TryFrom<u64>>::try_from(v).map($name::from).map_err(|_| {
+                            E::invalid_value($crate::serde::de::Unexpected::Unsigned(v), &self)
+                        })
+                    }
+
+                    fn visit_i64<E>(self, v: i64) -> Result<$name, E>
+                            where E: $crate::serde::de::Error {
+                        <$ty as std::convert::TryFrom<i64>>::try_from(v).map($name::from).map_err(|_| {
+                            E::invalid_value($crate::serde::de::Unexpected::Signed(v), &self)
+                        })
+                    }
+                }
+
+                deserializer.deserialize_any(Visitor)
+            }
         }
     };
 }
 
+#[cfg(not(feature = "serde"))]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! impl_var_serde {
+    ($($tt:tt)*) => {};
+}
+
+// Serde support for `impl_const!` types, represented like `impl_var!` enums by the name of known
+// constants and the value of unknown ones.
+#[cfg(feature = "serde")]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! impl_const_serde {
+    ($name:ident, $ty:ty) => {
+        impl $crate::serde::Serialize for $name {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: $crate::serde::Serializer,
+            {
+                match self.name() {
+                    Some(name) => serializer.serialize_str(name),
+                    None => $crate::serde::Serialize::serialize(&self.0, serializer),
+                }
+            }
+        }
+
+        impl<'de> $crate::serde::Deserialize<'de> for $name {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: $crate::serde::Deserializer<'de>,
+            {
+                struct Visitor;
+
+                impl<'de> $crate::serde::de::Visitor<'de> for Visitor {
+                    type Value = $name;
+
+                    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        write!(f, "a constant name or value of {}", stringify!($name))
+                    }
+
+                    fn visit_str<E>(self, v: &str) -> Result<$name, E>
+                    where
+                        E: $crate::serde::de::Error,
+                    {
+                        $name::from_name(v).ok_or_else(|| {
+                            E::custom(format!("unknown constant `{}` of {}", v, stringify!($name)))
+                        })
+                    }
+
+                    fn visit_u64<E>(self, v: u64) -> Result<$name, E>
+                    where
+                        E: $crate::serde::de::Error,
+                    {
+                        <$ty as std::convert::TryFrom<u64>>::try_from(v)
+                            .map($name)
+                            .map_err(|_| {
+                                E::invalid_value($crate::serde::de::Unexpected::Unsigned(v), &self)
+                            })
+                    }
+
+                    fn visit_i64<E>(self, v: i64) -> Result<$name, E>
+                    where
+                        E: $crate::serde::de::Error,
+                    {
+                        <$ty as std::convert::TryFrom<i64>>::try_from(v)
+                            .map($name)
+                            .map_err(|_| {
+                                E::invalid_value($crate::serde::de::Unexpected::Signed(v), &self)
+                            })
+                    }
+                }
+
+                deserializer.deserialize_any(Visitor)
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "serde"))]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! impl_const_serde {
+    ($($tt:tt)*) => {};
+}
+
+// Arbitrary support for `impl_var!` enums and `impl_const!` types, selected by the `arbitrary`
+// feature of neli like serde support. Half of the generated values are known variants so that
+// fuzzers reach the code handling them instead of mostly producing `UnrecognizedVariant`.
+#[cfg(feature = "arbitrary")]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! impl_var_arbitrary {
+    ($name:ident, $ty:ty, $( $( #[cfg($meta:meta)] )* $var:ident ),* ) => {
+        impl<'a> $crate::arbitrary::Arbitrary<'a> for $name {
+            fn arbitrary(
+                u: &mut $crate::arbitrary::Unstructured<'a>,
+            ) -> $crate::arbitrary::Result<Self> {
+                let known: &[$name] = &[
+                    $(
+                        $(
+                            #[cfg($meta)]
+                        )*
+                        $name::$var,
+                    )*
+                ];
+                if !known.is_empty() && u.arbitrary::<bool>()? {
+                    Ok(u.choose(known)?.clone())
+                } else {
+                    Ok($name::from(<$ty as $crate::arbitrary::Arbitrary>::arbitrary(u)?))
+                }
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "arbitrary"))]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! impl_var_arbitrary {
+    ($($tt:tt)*) => {};
+}
+
 #[macro_export]
 /// For naming a new enum, passing in what type it serializes to and deserializes
 /// from, and providing a mapping from variants to expressions (such as libc consts) that
@@ -145,7 +363,143 @@ macro_rules! impl_var {
 /// convention is to use `impl_trait` to create the trait with the name of the field that
 /// is the generic type and then use `impl_var_trait` to flag the new enum as usable in
 /// this field. See the examples below for more details.
+///
+/// Optionally, a wrapper enum can be generated along with the trait. It has one variant holding
+/// each of the listed enums implementing the trait and an `UnrecognizedVariant` for values none
+/// of them recognize. Converting a value into the wrapper picks the first listed enum that
+/// recognizes it. As the same value can be valid for several enums, `TryFrom` and the accessors
+/// named after each enum convert the value itself regardless of the variant holding it.
+///
+/// ```ignore
+/// impl_trait!(
+///     /// Marker trait for message types of my protocols
+///     MyType, u16,
+///     /// Message types of any of my protocols
+///     MyTypeWrapper,
+///     FooMsg(FooMsg) => as_foo_msg,
+///     BarMsg(bar::BarMsg) => as_bar_msg
+/// );
+///
+/// let msg_type = MyTypeWrapper::from(16);
+/// if let Some(foo) = msg_type.as_foo_msg() {
+///     // ...
+/// }
+/// ```
 macro_rules! impl_trait {
+    (
+        $(#[$outer:meta])* $trait_name:ident, $to_from_ty:ty,
+        $(#[$wrapper_outer:meta])* $wrapper:ident,
+        $( $var:ident($ty:ty) => $as_var:ident ),+
+    ) => { // with wrapper enum
+        impl_trait!($(#[$outer])* $trait_name, $to_from_ty);
+
+        $(#[$wrapper_outer])*
+        #[derive(Clone, Debug, Eq, Hash, PartialEq)]
+        pub enum $wrapper {
+            $(
+                #[allow(missing_docs)]
+                $var($ty),
+            )+
+            /// Variant that signifies a value that none of the wrapped types recognize
+            UnrecognizedVariant($to_from_ty),
+        }
+
+        impl $wrapper {
+            $(
+                #[doc = concat!(
+                    "The value as `", stringify!($var), "`, if it is a recognized value of it"
+                )]
+                pub fn $as_var(&self) -> Option<$ty> {
+                    std::convert::TryFrom::try_from(self.clone()).ok()
+                }
+            )+
+        }
+
+        impl From<$to_from_ty> for $wrapper {
+            fn from(v: $to_from_ty) -> Self {
+                $(
+                    let var = <$ty>::from(v);
+                    if var != <$ty>::UnrecognizedVariant(v) {
+                        return $wrapper::$var(var);
+                    }
+                )+
+                $wrapper::UnrecognizedVariant(v)
+            }
+        }
+
+        impl From<$wrapper> for $to_from_ty {
+            fn from(v: $wrapper) -> Self {
+                match v {
+                    $(
+                        $wrapper::$var(var) => var.into(),
+                    )+
+                    $wrapper::UnrecognizedVariant(v) => v,
+                }
+            }
+        }
+
+        $(
+            impl From<$ty> for $wrapper {
+                fn from(v: $ty) -> Self {
+                    $wrapper::$var(v)
+                }
+            }
+
+            impl std::convert::TryFrom<$wrapper> for $ty {
+                type Error = $crate::err::DeError;
+
+                fn try_from(v: $wrapper) -> Result<Self, $crate::err::DeError> {
+                    let v = <$to_from_ty>::from(v);
+                    let var = <$ty>::from(v);
+                    if var == <$ty>::UnrecognizedVariant(v) {
+                        return Err($crate::err::DeError::Msg(format!(
+                            "{} is not a valid {}",
+                            v,
+                            stringify!($var)
+                        )));
+                    }
+                    Ok(var)
+                }
+            }
+        )+
+
+        impl $crate::Nl for $wrapper {
+            fn serialize<B>(&self, mem: &mut B) -> Result<(), $crate::err::SerError>
+                    where B: $crate::BufferMut {
+                let v: $to_from_ty = self.clone().into();
+                $crate::Nl::serialize(&v, mem)
+            }
+
+            fn deserialize<T>(mem: &mut T) -> Result<Self, $crate::err::DeError>
+                    where T: $crate::Buffer {
+                Ok($wrapper::from(<$to_from_ty as $crate::Nl>::deserialize(mem)?))
+            }
+
+            fn size(&self) -> usize {
+                std::mem::size_of::<$to_from_ty>()
+            }
+
+            fn type_size() -> Option<usize> {
+                Some(std::mem::size_of::<$to_from_ty>())
+            }
+        }
+
+        impl $crate::value::ToNlValue for $wrapper {
+            fn to_nl_value(&self) -> $crate::value::NlValue {
+                match *self {
+                    $(
+                        $wrapper::$var(ref var) => var.to_nl_value(),
+                    )+
+                    $wrapper::UnrecognizedVariant(v) => $crate::value::NlValue::Const {
+                        name: None,
+                        value: v as i128,
+                    },
+                }
+            }
+        }
+
+        impl $trait_name for $wrapper {}
+    };
     ( $(#[$outer:meta])* $trait_name:ident, $to_from_ty:ty ) => { // with comments
         $(#[$outer])*
         pub trait $trait_name: $crate::Nl + PartialEq + From<$to_from_ty> + Into<$to_from_ty> {}
@@ -181,3 +535,496 @@ macro_rules! impl_var_trait {
         impl $impl_name for $name {}
     );
 }
+
+#[macro_export]
+/// For generating a set of the flags of an enum defined with `impl_var!`, stored as the bits of
+/// the serialized type. The set provides `contains`, `insert` and `remove`, the bitwise operators
+/// with other sets and with single flags, a `Debug` implementation listing the names of the set
+/// flags and an `Nl` implementation serializing the bits.
+///
+/// Flags are decoded one bit at a time, so a constant covering several bits is listed as the
+/// flags of its single bits. Bits without a name are kept and shown in hexadecimal.
+///
+/// ```ignore
+/// impl_flags!(
+///     /// Set of `MyFlag` flags
+///     MyFlags, MyFlag, u32
+/// );
+///
+/// let flags = MyFlags::empty() | MyFlag::Up | MyFlag::Running;
+/// assert!(flags.contains(&MyFlag::Up));
+/// ```
+macro_rules! impl_flags {
+    ( $(#[$outer:meta])* $name:ident, $flag:ident, $ty:ty ) => {
+        $(#[$outer])*
+        #[derive(Clone, Copy, Default, Eq, Hash, PartialEq)]
+        pub struct $name($ty);
+
+        impl $name {
+            /// Set containing no flags
+            pub fn empty() -> Self {
+                $name(0)
+            }
+
+            /// Set containing the given flags
+            pub fn new(flags: &[$flag]) -> Self {
+                flags.iter().fold($name::empty(), |set, flag| set | flag)
+            }
+
+            /// Set containing the flags whose bits are set in `bits`
+            pub fn from_bits(bits: $ty) -> Self {
+                $name(bits)
+            }
+
+            /// Bits of the flags in the set
+            pub fn bits(&self) -> $ty {
+                self.0
+            }
+
+            /// Returns true if no flag is in the set
+            pub fn is_empty(&self) -> bool {
+                self.0 == 0
+            }
+
+            /// Returns true if all bits of the flag are in the set
+            pub fn contains(&self, flag: &$flag) -> bool {
+                let bits: $ty = flag.into();
+                self.0 & bits == bits
+            }
+
+            /// Add a flag to the set
+            pub fn insert(&mut self, flag: $flag) {
+                self.0 |= <$ty>::from(flag);
+            }
+
+            /// Remove all bits of a flag from the set
+            pub fn remove(&mut self, flag: &$flag) {
+                self.0 &= !<$ty>::from(flag);
+            }
+
+            /// The flags in the set, one for each set bit in ascending order
+            pub fn into_vec(self) -> Vec<$flag> {
+                let mut flags = Vec::new();
+                for i in 0..std::mem::size_of::<$ty>() * 8 {
+                    let bit = (1 as $ty) << i;
+                    if self.0 & bit == bit {
+                        flags.push($flag::from(bit));
+                    }
+                }
+                flags
+            }
+        }
+
+        impl std::fmt::Debug for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "{}(", stringify!($name))?;
+                let flags = self.into_vec();
+                if flags.is_empty() {
+                    write!(f, "0x0")?;
+                }
+                for (i, flag) in flags.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " | ")?;
+                    }
+                    match *flag {
+                        $flag::UnrecognizedVariant(bit) => write!(f, "{:#x}", bit)?,
+                        ref flag => write!(f, "{:?}", flag)?,
+                    }
+                }
+                write!(f, ")")
+            }
+        }
+
+        impl std::ops::BitOr for $name {
+            type Output = $name;
+
+            fn bitor(self, rhs: $name) -> $name {
+                $name(self.0 | rhs.0)
+            }
+        }
+
+        impl std::ops::BitOr<$flag> for $name {
+            type Output = $name;
+
+            fn bitor(self, rhs: $flag) -> $name {
+                $name(self.0 | <$ty>::from(rhs))
+            }
+        }
+
+        impl<'a> std::ops::BitOr<&'a $flag> for $name {
+            type Output = $name;
+
+            fn bitor(self, rhs: &'a $flag) -> $name {
+                $name(self.0 | <$ty>::from(rhs))
+            }
+        }
+
+        impl std::ops::BitOrAssign for $name {
+            fn bitor_assign(&mut self, rhs: $name) {
+                self.0 |= rhs.0;
+            }
+        }
+
+        impl std::ops::BitOrAssign<$flag> for $name {
+            fn bitor_assign(&mut self, rhs: $flag) {
+                self.insert(rhs);
+            }
+        }
+
+        impl std::ops::BitAnd for $name {
+            type Output = $name;
+
+            fn bitand(self, rhs: $name) -> $name {
+                $name(self.0 & rhs.0)
+            }
+        }
+
+        impl std::ops::BitAndAssign for $name {
+            fn bitand_assign(&mut self, rhs: $name) {
+                self.0 &= rhs.0;
+            }
+        }
+
+        impl std::ops::BitXor for $name {
+            type Output = $name;
+
+            fn bitxor(self, rhs: $name) -> $name {
+                $name(self.0 ^ rhs.0)
+            }
+        }
+
+        impl std::ops::BitXorAssign for $name {
+            fn bitxor_assign(&mut self, rhs: $name) {
+                self.0 ^= rhs.0;
+            }
+        }
+
+        impl std::iter::FromIterator<$flag> for $name {
+            fn from_iter<I>(iter: I) -> Self
+            where
+                I: IntoIterator<Item = $flag>,
+            {
+                iter.into_iter().fold($name::empty(), |set, flag| set | flag)
+            }
+        }
+
+        impl From<$ty> for $name {
+            fn from(bits: $ty) -> Self {
+                $name(bits)
+            }
+        }
+
+        impl From<$name> for $ty {
+            fn from(flags: $name) -> Self {
+                flags.0
+            }
+        }
+
+        impl<'a> From<&'a $name> for $ty {
+            fn from(flags: &'a $name) -> Self {
+                flags.0
+            }
+        }
+
+        impl From<$name> for Vec<$flag> {
+            fn from(flags: $name) -> Self {
+                flags.into_vec()
+            }
+        }
+
+        impl $crate::Nl for $name {
+            fn serialize<B>(&self, mem: &mut B) -> Result<(), $crate::err::SerError>
+                    where B: $crate::BufferMut {
+                $crate::Nl::serialize(&self.0, mem)
+            }
+
+            fn deserialize<T>(mem: &mut T) -> Result<Self, $crate::err::DeError>
+                    where T: $crate::Buffer {
+                Ok($name(<$ty as $crate::Nl>::deserialize(mem)?))
+            }
+
+            fn size(&self) -> usize {
+                std::mem::size_of::<$ty>()
+            }
+
+            fn type_size() -> Option<usize> {
+                Some(std::mem::size_of::<$ty>())
+            }
+        }
+
+        impl $crate::value::ToNlValue for $name {
+            fn to_nl_value(&self) -> $crate::value::NlValue {
+                $crate::value::NlValue::list(&self.into_vec())
+            }
+        }
+    };
+}
+
+#[macro_export]
+/// For defining a constant type as a newtype over the serialized integer with an associated
+/// constant for each known value, as an alternative to the enums of `impl_var!`. Adding a value
+/// to an `impl_var!` enum adds a variant and breaks exhaustive matches in code using it, while
+/// adding one here is not a breaking change: any value of the integer is valid, so matches always
+/// need an arm for unknown values. Known values can still be used as patterns, as the type
+/// derives `PartialEq` and `Eq`.
+///
+/// The type implements `Nl`, the conversions from and to the integer, `Debug` showing the name of
+/// known values, `ToNlValue`, and `Serialize`, `Deserialize` and `Arbitrary` with the features of
+/// the same names. Marker traits such as `NlType` can be implemented for it with an empty `impl`.
+///
+/// ```ignore
+/// impl_const!(
+///     /// Commands of my protocol
+///     MyCmd, u8,
+///     Get => 1,
+///     Set => 2
+/// );
+///
+/// match cmd {
+///     MyCmd::Get => (),
+///     MyCmd::Set => (),
+///     MyCmd(other) => println!("unknown command {}", other),
+/// }
+/// ```
+macro_rules! impl_const {
+    (
+        $( #[$outer:meta] )*
+        $name:ident, $ty:ty, $( $( #[cfg($meta:meta)] )* $var:ident => $val:expr ),*
+    ) => {
+        $(#[$outer])*
+        #[derive(Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
+        pub struct $name(pub $ty);
+
+        #[allow(non_upper_case_globals)]
+        impl $name {
+            $(
+                $(
+                    #[cfg($meta)]
+                )*
+                #[allow(missing_docs)]
+                pub const $var: $name = $name($val);
+            )*
+
+            /// Name of the constant, if the value is a known one
+            pub fn name(&self) -> Option<&'static str> {
+                $(
+                    $(
+                        #[cfg($meta)]
+                    )*
+                    {
+                        if *self == $name::$var {
+                            return Some(stringify!($var));
+                        }
+                    }
+                )*
+                None
+            }
+
+            /// Known constant with the given name
+            pub fn from_name(name: &str) -> Option<Self> {
+                $(
+                    $(
+                        #[cfg($meta)]
+                    )*
+                    {
+                        if name == stringify!($var) {
+                            return Some($name::$var);
+                        }
+                    }
+                )*
+                None
+            }
+        }
+
+        impl std::fmt::Debug for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                match self.name() {
+                    Some(name) => write!(f, "{}", name),
+                    None => write!(f, "{}({})", stringify!($name), self.0),
+                }
+            }
+        }
+
+        impl From<$ty> for $name {
+            fn from(v: $ty) -> Self {
+                $name(v)
+            }
+        }
+
+        impl From<$name> for $ty {
+            fn from(v: $name) -> Self {
+                v.0
+            }
+        }
+
+        impl<'a> From<&'a $name> for $ty {
+            fn from(v: &'a $name) -> Self {
+                v.0
+            }
+        }
+
+        impl $crate::Nl for $name {
+            fn serialize<B>(&self, mem: &mut B) -> Result<(), $crate::err::SerError>
+                    where B: $crate::BufferMut {
+                $crate::Nl::serialize(&self.0, mem)
+            }
+
+            fn deserialize<T>(mem: &mut T) -> Result<Self, $crate::err::DeError>
+                    where T: $crate::Buffer {
+                let v = $name(<$ty as $crate::Nl>::deserialize(mem)?);
+                if v.name().is_none() {
+                    $crate::consts::report_unrecognized(stringify!($name), v.0 as i128);
+                }
+                Ok(v)
+            }
+
+            fn size(&self) -> usize {
+                std::mem::size_of::<$ty>()
+            }
+
+            fn type_size() -> Option<usize> {
+                Some(std::mem::size_of::<$ty>())
+            }
+        }
+
+        $crate::impl_const_serde!($name, $ty);
+        $crate::impl_var_arbitrary!($name, $ty, $( $( #[cfg($meta)] )* $var ),* );
+
+        impl $crate::value::ToNlValue for $name {
+            fn to_nl_value(&self) -> $crate::value::NlValue {
+                $crate::value::NlValue::Const {
+                    name: self.name().map(String::from),
+                    value: self.0 as i128,
+                }
+            }
+        }
+    };
+}
+
+#[macro_export]
+/// For extending a constant enum of `neli` with values defined outside of it, such as vendor
+/// generic netlink families or attributes of kernels newer than `neli`. The generated enum has a
+/// `Base` variant holding the enum being extended and one variant for each additional value,
+/// and implements the given marker trait, so it can be used in place of the extended enum in
+/// `Nlmsghdr`, `Nlattr` or `Rtattr`.
+///
+/// The additional values are checked first when converting from the serialized type, so code
+/// matching on them keeps working if `neli` later adds a variant for the same value. Values
+/// neither of them recognizes are held as `Base(UnrecognizedVariant(_))`.
+///
+/// ```ignore
+/// impl_var_ext!(
+///     /// Routing netlink attributes of interfaces, including ones of a newer kernel
+///     MyIfla, Ifla, libc::c_ushort, RtaType,
+///     NewAttr => 70
+/// );
+///
+/// match attr.rta_type {
+///     MyIfla::NewAttr => (),
+///     MyIfla::Base(Ifla::Mtu) => (),
+///     _ => (),
+/// }
+/// ```
+macro_rules! impl_var_ext {
+    (
+        $( #[$outer:meta] )*
+        $name:ident, $base:ty, $ty:ty, $trait_name:path,
+        $( $( #[cfg($meta:meta)] )* $var:ident => $val:expr ),+
+    ) => {
+        $(#[$outer])*
+        #[derive(Clone, Debug, Eq, Hash, PartialEq)]
+        pub enum $name {
+            /// Value of the extended enum, including values none of the enums recognize
+            Base($base),
+            $(
+                $(
+                    #[cfg($meta)]
+                )*
+                #[allow(missing_docs)]
+                $var,
+            )+
+        }
+
+        impl From<$ty> for $name {
+            fn from(v: $ty) -> Self {
+                $(
+                    $(
+                        #[cfg($meta)]
+                    )*
+                    {
+                        if v == $val {
+                            return $name::$var;
+                        }
+                    }
+                )+
+                $name::Base(<$base>::from(v))
+            }
+        }
+
+        impl From<$name> for $ty {
+            fn from(v: $name) -> Self {
+                <$ty>::from(&v)
+            }
+        }
+
+        impl<'a> From<&'a $name> for $ty {
+            fn from(v: &'a $name) -> Self {
+                match *v {
+                    $name::Base(ref base) => base.into(),
+                    $(
+                        $(
+                            #[cfg($meta)]
+                        )*
+                        $name::$var => $val,
+                    )+
+                }
+            }
+        }
+
+        impl From<$base> for $name {
+            fn from(v: $base) -> Self {
+                $name::from(<$ty>::from(v))
+            }
+        }
+
+        impl $crate::Nl for $name {
+            fn serialize<B>(&self, mem: &mut B) -> Result<(), $crate::err::SerError>
+                    where B: $crate::BufferMut {
+                $crate::Nl::serialize(&<$ty>::from(self), mem)
+            }
+
+            fn deserialize<T>(mem: &mut T) -> Result<Self, $crate::err::DeError>
+                    where T: $crate::Buffer {
+                type BaseEnum = $base;
+                let v = <$ty as $crate::Nl>::deserialize(mem)?;
+                let var = $name::from(v);
+                if let $name::Base(BaseEnum::UnrecognizedVariant(_)) = var {
+                    $crate::consts::report_unrecognized(stringify!($name), v as i128);
+                }
+                Ok(var)
+            }
+
+            fn size(&self) -> usize {
+                std::mem::size_of::<$ty>()
+            }
+
+            fn type_size() -> Option<usize> {
+                Some(std::mem::size_of::<$ty>())
+            }
+        }
+
+        impl $crate::value::ToNlValue for $name {
+            fn to_nl_value(&self) -> $crate::value::NlValue {
+                match *self {
+                    $name::Base(ref base) => $crate::value::ToNlValue::to_nl_value(base),
+                    _ => $crate::value::NlValue::Const {
+                        name: Some(format!("{:?}", self)),
+                        value: <$ty>::from(self) as i128,
+                    },
+                }
+            }
+        }
+
+        impl $trait_name for $name {}
+    };
+}
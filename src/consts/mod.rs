@@ -2,12 +2,33 @@
 //!
 //! The items in this module are created by macros, which give them the traits necessary to be
 //! serialized into Netlink compatible types. The macros are exported - you can use them too!
-//! See `impl_var`, `impl_trait`, and `impl_var_trait`.
+//! See `impl_var`, `impl_trait`, and `impl_var_trait`. `impl_flags` generates a set of the flags
+//! of an `impl_var` enum, such as `IffFlags`, stored as the bits of its field.
+//! `impl_const` generates a newtype over an integer with associated constants instead of an enum,
+//! for constants whose users should not break when new values are added. `impl_var_ext` extends
+//! an enum of `neli`, such as `GenlId` or `Ifla`, with values defined outside of `neli`.
 //!
 //! Note that most of these constants come from the Linux kernel headers, which can be found
 //! in `/usr/include/linux` on many distros. You can also see `man 3 netlink`, `man 7 netlink`,
 //! and `man 7 rtnetlink` for more information.
 //!
+//! # Layout
+//!
+//! Constants are grouped by protocol into `nl`, `genl`, `rtnl`, `netfilter`, `sockdiag`, `xfrm`,
+//! `connector` and `socket`, with `nl` holding what messages of every protocol share. Each of these
+//! modules has a `prelude` module that also re-exports the shared constants, so that
+//! `use neli::consts::rtnl::prelude::*;` imports everything needed for routing netlink messages.
+//! `consts::prelude` is the prelude of `nl`.
+//!
+//! The constants of `nl`, `genl`, `rtnl` and `socket` are also re-exported at the top of this
+//! module for existing code. New constant families are only added to their protocol module so
+//! that their names cannot clash with those of other protocols. Constants that moved to another
+//! module remain available under their old path with a deprecation warning.
+//!
+//! Generic netlink families other than the controller each have their own module, such as
+//! `wireguard`, which is only compiled with the cargo feature of the same name to keep binaries
+//! small for users of other families. These modules are not re-exported here.
+//!
 //! # Design decisions
 //!
 //! * Macros are exported so that these conventions are extensible and usable for data types
@@ -30,36 +51,123 @@
 //!   every constant for every protocol. This allows you to inspect the integer value returned
 //!   and if you are sure that it is correct, you can use it. If it is a garbage value, this can
 //!   also be useful for error reporting.
+//! * `set_unrecognized_hook` installs a callback reporting each `UnrecognizedVariant` parsed
+//!   from a message, so that deployments notice kernel features this library does not know about
+//!   yet instead of silently ignoring them. Constructing an `UnrecognizedVariant` by hand with
+//!   `From` is not reported.
+
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 
 #[macro_use]
 mod macros;
 
+/// Constants of the ACPI event generic netlink family
+#[cfg(feature = "acpi-event")]
+pub mod acpi_event;
+/// Constants of the kernel connector
+pub mod connector;
+/// Constants of the devlink generic netlink family
+#[cfg(feature = "devlink")]
+pub mod devlink;
 /// Constants related to generic netlink
 pub mod genl;
 pub use crate::consts::genl::*;
 pub mod netfilter;
-/// Constants related to generic netlink attributes
-pub mod nlattr;
-pub use crate::consts::nlattr::*;
+/// Constants of the NFC generic netlink family
+#[cfg(feature = "nfc")]
+pub mod nfc;
 /// Constants related to generic netlink top level headers
 pub mod nl;
+/// Constants of the nl80211 generic netlink family
+#[cfg(feature = "nl80211")]
+pub mod nl80211;
+/// Constants related to generic netlink attributes
+#[deprecated(since = "0.5.0", note = "Use consts::nl or consts::genl")]
+pub mod nlattr;
 pub use crate::consts::nl::*;
 /// Constants related to rtnetlink
 pub mod rtnl;
 pub use crate::consts::rtnl::*;
+/// Constants of socket diagnostics over `NETLINK_SOCK_DIAG`
+pub mod sockdiag;
 /// Constants related to netlink socket operations
 pub mod socket;
 pub use crate::consts::socket::*;
+/// Constants of the taskstats generic netlink family
+#[cfg(feature = "taskstats")]
+pub mod taskstats;
+/// Constants of the WireGuard generic netlink family
+#[cfg(feature = "wireguard")]
+pub mod wireguard;
+/// Constants of the IPsec transformation protocol
+pub mod xfrm;
+// Take precedence over the deprecated aliases left in `nl` and the preludes of each protocol
+pub use crate::consts::genl::GenlId;
+pub use crate::consts::nl::prelude;
+pub use crate::consts::rtnl::Rtm;
+
+/// Callback invoked with the enum name and integer value of unrecognized constants
+pub type UnrecognizedHook = fn(&'static str, i128);
+
+static HOOK_SET: AtomicBool = AtomicBool::new(false);
+/// Installed hook and the values it was already called with
+type HookState = Option<(UnrecognizedHook, BTreeSet<(&'static str, i128)>)>;
+
+static HOOK: Mutex<HookState> = Mutex::new(None);
 
-/// Reimplementation of alignto macro in C
+/// Install a hook called whenever an `UnrecognizedVariant` is parsed from a message, or remove
+/// it with `None`. The hook is called once per distinct enum name and value. Installing a hook
+/// forgets which values were already reported.
+pub fn set_unrecognized_hook(hook: Option<UnrecognizedHook>) {
+    let mut guard = HOOK.lock().unwrap_or_else(|e| e.into_inner());
+    *guard = hook.map(|hook| (hook, BTreeSet::new()));
+    HOOK_SET.store(guard.is_some(), Ordering::Release);
+}
+
+/// Report an unrecognized value of enum `name` to the hook set with `set_unrecognized_hook`.
+/// Used by `impl_var!` and not meant to be called directly.
+#[doc(hidden)]
+pub fn report_unrecognized(name: &'static str, value: i128) {
+    if !HOOK_SET.load(Ordering::Acquire) {
+        return;
+    }
+    let hook = {
+        let mut guard = HOOK.lock().unwrap_or_else(|e| e.into_inner());
+        match *guard {
+            Some((hook, ref mut seen)) => {
+                if !seen.insert((name, value)) {
+                    return;
+                }
+                hook
+            }
+            None => return,
+        }
+    };
+    // Called without the lock held so that the hook may parse messages itself
+    hook(name, value);
+}
+
+/// Reimplementation of alignto macro in C. Lengths too large to be aligned saturate at
+/// `usize::MAX` so that offsets computed from malformed length fields never wrap around.
 pub fn alignto(len: usize) -> usize {
-    (len + libc::NLA_ALIGNTO as usize - 1) & !(libc::NLA_ALIGNTO as usize - 1)
+    checked_alignto(len).unwrap_or(usize::MAX)
+}
+
+/// Like `alignto`, but return `None` if the aligned length does not fit in `usize`
+pub fn checked_alignto(len: usize) -> Option<usize> {
+    len.checked_add(libc::NLA_ALIGNTO as usize - 1)
+        .map(|len| len & !(libc::NLA_ALIGNTO as usize - 1))
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
+    use crate::value::{NlValue, ToNlValue};
+    use crate::{Nl, StreamReadBuffer, StreamWriteBuffer};
+
     #[test]
     fn test_documented_conversions() {
         let unspec: u8 = CtrlCmd::Unspec.into();
@@ -68,4 +176,224 @@ mod test {
         let unspec_variant = CtrlCmd::from(libc::CTRL_CMD_UNSPEC as u8);
         assert_eq!(unspec_variant, CtrlCmd::Unspec);
     }
+
+    #[test]
+    fn test_nl_type_wrapper() {
+        use std::convert::TryFrom;
+
+        assert_eq!(
+            NlTypeWrapper::from(libc::NLMSG_DONE as u16),
+            NlTypeWrapper::Nlmsg(Nlmsg::Done)
+        );
+        // The controller ID is also the value of `Rtm::Newlink`
+        let ctrl = NlTypeWrapper::from(GenlId::Ctrl);
+        assert_eq!(ctrl.as_genl_id(), Some(GenlId::Ctrl));
+        assert_eq!(ctrl.as_rtm(), Some(Rtm::Newlink));
+        assert_eq!(ctrl.as_nlmsg(), None);
+        assert!(Nlmsg::try_from(ctrl.clone()).is_err());
+        assert_eq!(
+            NlTypeWrapper::from(u16::from(ctrl)),
+            NlTypeWrapper::Rtm(Rtm::Newlink)
+        );
+        assert_eq!(
+            NlTypeWrapper::from(0xffff),
+            NlTypeWrapper::UnrecognizedVariant(0xffff)
+        );
+    }
+
+    #[test]
+    fn test_rtnl_group() {
+        assert_eq!(RtnlGroup::Link.group(), libc::RTNLGRP_LINK);
+        assert_eq!(RtnlGroup::Link.bitmask(), Some(libc::RTMGRP_LINK as u32));
+        assert_eq!(
+            RtnlGroup::Ipv4Ifaddr.bitmask(),
+            Some(libc::RTMGRP_IPV4_IFADDR as u32)
+        );
+        assert_eq!(RtnlGroup::Brvlan.bitmask(), None);
+        assert_eq!(RtnlGroup::UnrecognizedVariant(0).bitmask(), None);
+        assert_eq!(
+            RtnlGroup::from_bitmask((libc::RTMGRP_LINK | libc::RTMGRP_NEIGH) as u32),
+            vec![RtnlGroup::Link, RtnlGroup::Neigh]
+        );
+    }
+
+    #[test]
+    fn test_nlmf_presets() {
+        assert_eq!(u16::from(&NlmFFlags::request_ack()), 0x5);
+        assert_eq!(u16::from(&NlmFFlags::dump_request()), 0x301);
+        assert_eq!(u16::from(&NlmFFlags::create_excl()), 0x601);
+        assert_eq!(u16::from(&NlmFFlags::create_replace().ack()), 0x505);
+        assert_eq!(
+            NlmFFlags::new(&[NlmF::Request, NlmF::Request]).into_vec(),
+            vec![NlmF::Request]
+        );
+        let flags: Vec<NlmF> = NlmFFlags::request().ack().ack().into();
+        assert_eq!(flags, vec![NlmF::Request, NlmF::Ack]);
+        assert_eq!(
+            NlmFFlags::create_excl().into_vec(),
+            vec![NlmF::Request, NlmF::Create, NlmF::Excl]
+        );
+    }
+
+    #[test]
+    fn test_flags() {
+        let mut flags = IffFlags::empty() | Iff::Up | Iff::Running;
+        assert!(flags.contains(&Iff::Up));
+        assert!(!flags.contains(&Iff::Loopback));
+        flags |= IffFlags::from_bits(0x8000_0000);
+        flags.remove(&Iff::Running);
+        assert_eq!(format!("{:?}", flags), "IffFlags(Up | 0x80000000)");
+        assert_eq!(format!("{:?}", IffFlags::empty()), "IffFlags(0x0)");
+        assert_eq!(flags & IffFlags::new(&[Iff::Up]), IffFlags::new(&[Iff::Up]));
+
+        let mut buf = [0u8; 4];
+        flags
+            .serialize(&mut StreamWriteBuffer::new_sized(&mut buf[..]))
+            .unwrap();
+        let parsed = IffFlags::deserialize(&mut StreamReadBuffer::new(&buf)).unwrap();
+        assert_eq!(parsed, flags);
+        assert_eq!(
+            parsed.into_vec(),
+            vec![Iff::Up, Iff::UnrecognizedVariant(0x8000_0000)]
+        );
+        assert!(NlmFFlags::dump_request().contains(&NlmF::Dump));
+    }
+
+    impl_const!(
+        /// Constants for testing `impl_const!`
+        TestConst, u16,
+        First => 1,
+        Second => 2
+    );
+
+    impl NlType for TestConst {}
+
+    impl_const!(
+        /// Unsigned 64-bit constants for testing `impl_const!`
+        TestConstU64, u64,
+        Last => u64::MAX
+    );
+
+    #[test]
+    fn test_impl_const() {
+        let v = TestConst::deserialize(&mut StreamReadBuffer::new(&2u16.to_ne_bytes())).unwrap();
+        let name = match v {
+            TestConst::First => "first",
+            TestConst::Second => "second",
+            TestConst(_) => "unknown",
+        };
+        assert_eq!(name, "second");
+        assert_eq!(format!("{:?}", v), "Second");
+        assert_eq!(format!("{:?}", TestConst(7)), "TestConst(7)");
+        assert_eq!(TestConst::from_name("First"), Some(TestConst(1)));
+        assert_eq!(u16::from(TestConst::Second), 2);
+
+        // Values of unsigned 64-bit constants above `i64::MAX` are kept as they are
+        let big = TestConstU64::Last.to_nl_value();
+        assert_eq!(
+            big,
+            NlValue::Const {
+                name: Some("Last".to_string()),
+                value: i128::from(u64::MAX),
+            }
+        );
+    }
+
+    impl_var_ext!(
+        /// `CtrlAttr` extended with an attribute unknown to neli
+        ExtCtrlAttr, CtrlAttr, u16, NlAttrType,
+        Vendor => 0x40,
+        // Takes precedence over the same value in `CtrlAttr`
+        Name => 2
+    );
+
+    #[test]
+    fn test_impl_var_ext() {
+        assert_eq!(ExtCtrlAttr::from(0x40), ExtCtrlAttr::Vendor);
+        assert_eq!(ExtCtrlAttr::from(2), ExtCtrlAttr::Name);
+        assert_eq!(
+            ExtCtrlAttr::from(CtrlAttr::FamilyId),
+            ExtCtrlAttr::Base(CtrlAttr::FamilyId)
+        );
+        assert_eq!(
+            ExtCtrlAttr::from(0x41),
+            ExtCtrlAttr::Base(CtrlAttr::UnrecognizedVariant(0x41))
+        );
+
+        let attr = crate::nlattr::Nlattr::new(None, ExtCtrlAttr::Vendor, 5u32).unwrap();
+        let mut buf = [0u8; 8];
+        attr.serialize(&mut StreamWriteBuffer::new_sized(&mut buf[..]))
+            .unwrap();
+        assert_eq!(&buf[2..4], &0x40u16.to_ne_bytes());
+    }
+
+    #[test]
+    fn test_update_mode() {
+        assert_eq!(u16::from(&UpdateMode::Create.flags()), 0x601);
+        assert_eq!(u16::from(&UpdateMode::Replace.flags()), 0x101);
+        assert_eq!(u16::from(&UpdateMode::CreateOrReplace.flags()), 0x501);
+        assert_eq!(u16::from(&NlmFFlags::from(UpdateMode::Append).ack()), 0xc05);
+    }
+
+    static REPORTED: Mutex<Vec<(&'static str, i128)>> = Mutex::new(Vec::new());
+
+    fn record_unrecognized(name: &'static str, value: i128) {
+        REPORTED.lock().unwrap().push((name, value));
+    }
+
+    #[test]
+    fn test_unrecognized_hook() {
+        set_unrecognized_hook(Some(record_unrecognized));
+        for _ in 0..2 {
+            let cmd = CtrlCmd::deserialize(&mut StreamReadBuffer::new(&[0xeeu8])).unwrap();
+            assert_eq!(cmd, CtrlCmd::UnrecognizedVariant(0xee));
+        }
+        CtrlCmd::deserialize(&mut StreamReadBuffer::new(&[CtrlCmd::Newfamily.into()])).unwrap();
+        let _ = CtrlCmd::from(0xefu8);
+        for &attr in &[0x41u16, 0x40, 1] {
+            ExtCtrlAttr::deserialize(&mut StreamReadBuffer::new(&attr.to_ne_bytes()[..])).unwrap();
+        }
+        set_unrecognized_hook(None);
+        // Other tests run in parallel and may report their own values while the hook is set
+        let reported = REPORTED.lock().unwrap();
+        let ctrl_cmds = reported
+            .iter()
+            .filter(|&&(name, value)| name == "CtrlCmd" && (value == 0xee || value == 0xef))
+            .collect::<Vec<_>>();
+        assert_eq!(ctrl_cmds, vec![&("CtrlCmd", 0xee)]);
+        let ext_attrs = reported
+            .iter()
+            .filter(|&&(name, _)| name == "ExtCtrlAttr")
+            .collect::<Vec<_>>();
+        assert_eq!(ext_attrs, vec![&("ExtCtrlAttr", 0x41)]);
+    }
+
+    #[test]
+    fn test_preludes() {
+        {
+            use crate::consts::rtnl::prelude::*;
+            assert_eq!(u16::from(Rtm::Newlink), libc::RTM_NEWLINK);
+            assert_eq!(u16::from(&NlmFFlags::request()), 0x1);
+        }
+        {
+            use crate::consts::genl::prelude::*;
+            assert_eq!(u16::from(GenlId::Ctrl), libc::GENL_ID_CTRL as u16);
+            assert_eq!(u16::from(CtrlAttr::FamilyName), 2);
+            assert_eq!(NlFamily::from(libc::NETLINK_GENERIC), NlFamily::Generic);
+        }
+        #[allow(deprecated)]
+        {
+            assert_eq!(nl::GenlId::Ctrl, genl::GenlId::Ctrl);
+            assert_eq!(nl::Rtm::Getlink, rtnl::Rtm::Getlink);
+            assert_eq!(nlattr::CtrlAttr::Version, genl::CtrlAttr::Version);
+        }
+    }
+
+    #[test]
+    fn test_alignto() {
+        assert_eq!(alignto(5), 8);
+        assert_eq!(alignto(8), 8);
+        assert_eq!(checked_alignto(usize::MAX - 1), None);
+        assert_eq!(alignto(usize::MAX - 1), usize::MAX);
+    }
 }
@@ -3,7 +3,7 @@
 //! Note that this doesn't cover everything yet, both the list of types and variants in enums will
 //! be added over time.
 
-use super::{NlAttrType, NlType};
+use super::nl::{NlAttrType, NlType};
 
 impl_var_trait! {
     /// Attributes inside a netfilter log packet message.
@@ -80,3 +80,9 @@ impl_var! {
     Meta => libc::NFULNL_COPY_META as u8,
     Packet => libc::NFULNL_COPY_PACKET as u8
 }
+
+/// Constants needed for netfilter messages, for glob imports
+pub mod prelude {
+    pub use super::*;
+    pub use crate::consts::nl::prelude::*;
+}
@@ -0,0 +1,118 @@
+//! Constants of the NFC generic netlink family, from `linux/nfc.h`
+
+use super::genl::Cmd;
+use super::nl::NlAttrType;
+use crate::genl::GenlFamily;
+
+/// The `nfc` generic netlink family
+pub struct Nfc;
+
+impl GenlFamily for Nfc {
+    const NAME: &'static str = "nfc";
+    const VERSION: u8 = 1;
+    type Cmd = NfcCmd;
+    type Attr = NfcAttr;
+}
+
+/// Multicast group of the `NfcCmd::Event*` notifications
+pub const NFC_GENL_MCAST_EVENT_NAME: &str = "events";
+
+impl_var_trait!(
+    /// Commands and notifications of the `nfc` family
+    NfcCmd, u8, Cmd,
+    Unspec => 0,
+    GetDevice => 1,
+    DevUp => 2,
+    DevDown => 3,
+    DepLinkUp => 4,
+    DepLinkDown => 5,
+    StartPoll => 6,
+    StopPoll => 7,
+    GetTarget => 8,
+    EventTargetsFound => 9,
+    EventDeviceAdded => 10,
+    EventDeviceRemoved => 11,
+    EventTargetLost => 12,
+    EventTmActivated => 13,
+    EventTmDeactivated => 14,
+    LlcGetParams => 15,
+    LlcSetParams => 16,
+    EnableSe => 17,
+    DisableSe => 18,
+    LlcSdreq => 19,
+    EventLlcSdres => 20,
+    FwDownload => 21,
+    EventSeAdded => 22,
+    EventSeRemoved => 23,
+    EventSeConnectivity => 24,
+    EventSeTransaction => 25,
+    GetSe => 26,
+    SeIo => 27,
+    ActivateTarget => 28,
+    Vendor => 29,
+    DeactivateTarget => 30
+);
+
+impl_var_trait!(
+    /// Attributes of the `nfc` family
+    NfcAttr, u16, NlAttrType,
+    Unspec => 0,
+    DeviceIndex => 1,
+    DeviceName => 2,
+    Protocols => 3,
+    TargetIndex => 4,
+    TargetSensRes => 5,
+    TargetSelRes => 6,
+    TargetNfcid1 => 7,
+    TargetSensbRes => 8,
+    TargetSensfRes => 9,
+    CommMode => 10,
+    RfMode => 11,
+    DevicePowered => 12,
+    ImProtocols => 13,
+    TmProtocols => 14,
+    LlcParamLto => 15,
+    LlcParamRw => 16,
+    LlcParamMiux => 17,
+    Se => 18,
+    LlcSdp => 19,
+    FirmwareName => 20,
+    SeIndex => 21,
+    SeType => 22,
+    SeAid => 23,
+    FirmwareDownloadStatus => 24,
+    SeApdu => 25,
+    TargetIso15693Dsfid => 26,
+    TargetIso15693Uid => 27,
+    SeParams => 28,
+    VendorId => 29,
+    VendorSubcmd => 30,
+    VendorData => 31
+);
+
+impl_var_trait!(
+    /// Attributes of a service discovery request or response nested in `NfcAttr::LlcSdp`
+    NfcSdpAttr, u16, NlAttrType,
+    Unspec => 0,
+    Uri => 1,
+    Sap => 2
+);
+
+impl_var!(
+    /// Tag and peer protocols, whose masks `1 << proto` are combined in `NfcAttr::Protocols`,
+    /// `NfcAttr::ImProtocols` and `NfcAttr::TmProtocols`
+    NfcProto, u32,
+    Jewel => 1,
+    Mifare => 2,
+    Felica => 3,
+    Iso14443 => 4,
+    NfcDep => 5,
+    Iso14443B => 6,
+    Iso15693 => 7
+);
+
+/// Constants needed for NFC messages, for glob imports
+pub mod prelude {
+    pub use super::{Nfc, NfcAttr, NfcCmd, NfcProto, NfcSdpAttr, NFC_GENL_MCAST_EVENT_NAME};
+    pub use crate::consts::genl::prelude::*;
+}
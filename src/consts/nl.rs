@@ -1,6 +1,18 @@
 impl_trait!(
     /// Trait marking constants valid for use in `Nlmsghdr.nl_type`
-    NlType,
+    NlType, u16,
+    /// Constants of any protocol valid for use in `Nlmsghdr.nl_type`, for code receiving
+    /// messages of several protocols
+    NlTypeWrapper,
+    Nlmsg(Nlmsg) => as_nlmsg,
+    Rtm(super::rtnl::Rtm) => as_rtm,
+    NetfilterMsg(super::netfilter::NetfilterMsg) => as_netfilter_msg,
+    GenlId(super::genl::GenlId) => as_genl_id
+);
+
+impl_trait!(
+    /// Marker trait for types usable in `Nlattr.nla_type`
+    NlAttrType,
     u16
 );
 
@@ -13,69 +25,6 @@ impl_var_trait!(
     Overrun => libc::NLMSG_OVERRUN as u16
 );
 
-impl_var_trait!(
-    /// Values for `nl_type` in `Nlmsghdr`
-    GenlId, u16, NlType,
-    Ctrl => libc::GENL_ID_CTRL as u16,
-    #[cfg(target_env="gnu")]
-    VfsDquot => libc::GENL_ID_VFS_DQUOT as u16,
-    #[cfg(target_env="gnu")]
-    Pmcraid => libc::GENL_ID_PMCRAID as u16
-);
-
-impl_var_trait!(
-    /// rtnetlink-related values for `nl_type` in `Nlmsghdr`
-    Rtm, u16, NlType,
-    Newlink => libc::RTM_NEWLINK,
-    Dellink => libc::RTM_DELLINK,
-    Getlink => libc::RTM_GETLINK,
-    Setlink => libc::RTM_SETLINK,
-    Newaddr => libc::RTM_NEWADDR,
-    Deladdr => libc::RTM_DELADDR,
-    Getaddr => libc::RTM_GETADDR,
-    Newroute => libc::RTM_NEWROUTE,
-    Delroute => libc::RTM_DELROUTE,
-    Getroute => libc::RTM_GETROUTE,
-    Newneigh => libc::RTM_NEWNEIGH,
-    Delneigh => libc::RTM_DELNEIGH,
-    Getneigh => libc::RTM_GETNEIGH,
-    Newrule => libc::RTM_NEWRULE,
-    Delrule => libc::RTM_DELRULE,
-    Getrule => libc::RTM_GETRULE,
-    Newqdisc=> libc::RTM_NEWQDISC,
-    Delqdisc=> libc::RTM_DELQDISC,
-    Getqdisc=> libc::RTM_GETQDISC,
-    Newtclass => libc::RTM_NEWTCLASS,
-    Deltclass => libc::RTM_DELTCLASS,
-    Gettclass => libc::RTM_GETTCLASS,
-    Newtfilter => libc::RTM_NEWTFILTER,
-    Deltfilter => libc::RTM_DELTFILTER,
-    Gettfilter => libc::RTM_GETTFILTER,
-    Newaction => libc::RTM_NEWACTION,
-    Delaction => libc::RTM_DELACTION,
-    Getaction => libc::RTM_GETACTION,
-    Newprefix => libc::RTM_NEWPREFIX,
-    Getmulticast => libc::RTM_GETMULTICAST,
-    Getanycast => libc::RTM_GETANYCAST,
-    Newneightbl => libc::RTM_NEWNEIGHTBL,
-    Getneightbl => libc::RTM_GETNEIGHTBL,
-    Setneightbl => libc::RTM_SETNEIGHTBL,
-    Newnduseropt => libc::RTM_NEWNDUSEROPT,
-    Newaddrlabel => libc::RTM_NEWADDRLABEL,
-    Deladdrlabel => libc::RTM_DELADDRLABEL,
-    Getaddrlabel => libc::RTM_GETADDRLABEL,
-    Getdcb => libc::RTM_GETDCB,
-    Setdcb => libc::RTM_SETDCB,
-    Newnetconf => libc::RTM_NEWNETCONF,
-    Getnetconf => libc::RTM_GETNETCONF,
-    Newmdb => libc::RTM_NEWMDB,
-    Delmdb => libc::RTM_DELMDB,
-    Getmdb => libc::RTM_GETMDB,
-    Newnsid => libc::RTM_NEWNSID,
-    Delnsid => libc::RTM_DELNSID,
-    Getnsid => libc::RTM_GETNSID
-);
-
 impl_var!(
     /// Values for `nl_flags` in `Nlmsghdr`
     NlmF, u16,
@@ -94,3 +43,130 @@ impl_var!(
     Create => libc::NLM_F_CREATE as u16,
     Append => libc::NLM_F_APPEND as u16
 );
+
+/// Set of `NlmF` flags with constructors for the combinations used by most requests. The set
+/// keeps the flags as they were given rather than their bits, as several `NlmF` constants share
+/// bits whose meaning depends on the request type.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NlmFFlags(Vec<NlmF>);
+
+impl NlmFFlags {
+    /// Create a flag set from a slice of flags
+    pub fn new(flags: &[NlmF]) -> Self {
+        let mut set = NlmFFlags::default();
+        for flag in flags {
+            set.set(flag.clone());
+        }
+        set
+    }
+
+    /// `NLM_F_REQUEST` only, for requests that neither want an ACK nor dump
+    pub fn request() -> Self {
+        NlmFFlags(vec![NlmF::Request])
+    }
+
+    /// `NLM_F_REQUEST | NLM_F_ACK`, for requests that change state and want the result
+    /// acknowledged
+    pub fn request_ack() -> Self {
+        NlmFFlags(vec![NlmF::Request, NlmF::Ack])
+    }
+
+    /// `NLM_F_REQUEST | NLM_F_DUMP`, for requests returning all matching objects
+    pub fn dump_request() -> Self {
+        NlmFFlags(vec![NlmF::Request, NlmF::Dump])
+    }
+
+    /// `NLM_F_REQUEST | NLM_F_CREATE | NLM_F_EXCL`, for creating an object that must not exist
+    /// yet
+    pub fn create_excl() -> Self {
+        NlmFFlags(vec![NlmF::Request, NlmF::Create, NlmF::Excl])
+    }
+
+    /// `NLM_F_REQUEST | NLM_F_CREATE | NLM_F_REPLACE`, for creating an object or replacing it
+    /// if it exists
+    pub fn create_replace() -> Self {
+        NlmFFlags(vec![NlmF::Request, NlmF::Create, NlmF::Replace])
+    }
+
+    /// Add `NLM_F_ACK` to the set
+    pub fn ack(mut self) -> Self {
+        self.set(NlmF::Ack);
+        self
+    }
+
+    /// Add a flag to the set if it is not already present
+    pub fn set(&mut self, flag: NlmF) {
+        if !self.contains(&flag) {
+            self.0.push(flag);
+        }
+    }
+
+    /// Returns true if the flag is in the set
+    pub fn contains(&self, flag: &NlmF) -> bool {
+        self.0.contains(flag)
+    }
+
+    /// Return the flags as used by `Nlmsghdr::new`
+    pub fn into_vec(self) -> Vec<NlmF> {
+        self.0
+    }
+}
+
+impl From<NlmFFlags> for Vec<NlmF> {
+    fn from(flags: NlmFFlags) -> Self {
+        flags.into_vec()
+    }
+}
+
+impl<'a> From<&'a NlmFFlags> for u16 {
+    fn from(flags: &'a NlmFFlags) -> Self {
+        flags.0.iter().fold(0, |acc, flag| acc | u16::from(flag))
+    }
+}
+
+/// How a request creating or changing a kernel object treats an object that already exists
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UpdateMode {
+    /// Create the object, failing with `EEXIST` if it exists (`NLM_F_CREATE | NLM_F_EXCL`)
+    Create,
+    /// Replace the existing object, failing with `ENOENT` if it does not exist
+    /// (`NLM_F_REPLACE`)
+    Replace,
+    /// Create the object or replace it if it exists (`NLM_F_CREATE | NLM_F_REPLACE`)
+    CreateOrReplace,
+    /// Add the object next to existing ones, such as an additional route with the same
+    /// destination (`NLM_F_CREATE | NLM_F_APPEND`)
+    Append,
+}
+
+impl UpdateMode {
+    /// Request flags implementing this mode, including `NLM_F_REQUEST`
+    pub fn flags(self) -> NlmFFlags {
+        match self {
+            UpdateMode::Create => NlmFFlags::create_excl(),
+            UpdateMode::Replace => NlmFFlags(vec![NlmF::Request, NlmF::Replace]),
+            UpdateMode::CreateOrReplace => NlmFFlags::create_replace(),
+            UpdateMode::Append => NlmFFlags(vec![NlmF::Request, NlmF::Create, NlmF::Append]),
+        }
+    }
+}
+
+impl From<UpdateMode> for NlmFFlags {
+    fn from(mode: UpdateMode) -> Self {
+        mode.flags()
+    }
+}
+
+/// Moved to `consts::genl`
+#[deprecated(since = "0.5.0", note = "Use consts::genl::GenlId")]
+pub type GenlId = super::genl::GenlId;
+
+/// Moved to `consts::rtnl`
+#[deprecated(since = "0.5.0", note = "Use consts::rtnl::Rtm")]
+pub type Rtm = super::rtnl::Rtm;
+
+/// Constants used by netlink messages of every protocol, for glob imports
+pub mod prelude {
+    pub use super::{NlAttrType, NlType, NlmF, NlmFFlags, Nlmsg, UpdateMode};
+    pub use crate::consts::socket::NlFamily;
+}
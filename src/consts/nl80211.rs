@@ -0,0 +1,671 @@
+//! Constants of the nl80211 generic netlink family configuring wireless devices, from
+//! `linux/nl80211.h`
+
+use super::genl::Cmd;
+use super::nl::NlAttrType;
+use crate::genl::GenlFamily;
+
+/// The `nl80211` generic netlink family
+pub struct Nl80211;
+
+impl GenlFamily for Nl80211 {
+    const NAME: &'static str = "nl80211";
+    const VERSION: u8 = 1;
+    type Cmd = Nl80211Cmd;
+    type Attr = Nl80211Attr;
+}
+
+/// Multicast group of configuration changes
+pub const NL80211_MULTICAST_GROUP_CONFIG: &str = "config";
+/// Multicast group of scan results
+pub const NL80211_MULTICAST_GROUP_SCAN: &str = "scan";
+/// Multicast group of regulatory domain changes
+pub const NL80211_MULTICAST_GROUP_REG: &str = "regulatory";
+/// Multicast group of MLME events such as authentication and association
+pub const NL80211_MULTICAST_GROUP_MLME: &str = "mlme";
+/// Multicast group of vendor events
+pub const NL80211_MULTICAST_GROUP_VENDOR: &str = "vendor";
+/// Multicast group of NAN events
+pub const NL80211_MULTICAST_GROUP_NAN: &str = "nan";
+/// Multicast group of testmode events
+pub const NL80211_MULTICAST_GROUP_TESTMODE: &str = "testmode";
+
+impl_var_trait!(
+    /// Commands of the `nl80211` family
+    Nl80211Cmd, u8, Cmd,
+    Unspec => 0,
+    GetWiphy => 1,
+    SetWiphy => 2,
+    NewWiphy => 3,
+    DelWiphy => 4,
+    GetInterface => 5,
+    SetInterface => 6,
+    NewInterface => 7,
+    DelInterface => 8,
+    GetKey => 9,
+    SetKey => 10,
+    NewKey => 11,
+    DelKey => 12,
+    GetBeacon => 13,
+    SetBeacon => 14,
+    StartAp => 15,
+    StopAp => 16,
+    GetStation => 17,
+    SetStation => 18,
+    NewStation => 19,
+    DelStation => 20,
+    GetMpath => 21,
+    SetMpath => 22,
+    NewMpath => 23,
+    DelMpath => 24,
+    SetBss => 25,
+    SetReg => 26,
+    ReqSetReg => 27,
+    GetMeshConfig => 28,
+    SetMeshConfig => 29,
+    SetMgmtExtraIe => 30,
+    GetReg => 31,
+    GetScan => 32,
+    TriggerScan => 33,
+    NewScanResults => 34,
+    ScanAborted => 35,
+    RegChange => 36,
+    Authenticate => 37,
+    Associate => 38,
+    Deauthenticate => 39,
+    Disassociate => 40,
+    MichaelMicFailure => 41,
+    RegBeaconHint => 42,
+    JoinIbss => 43,
+    LeaveIbss => 44,
+    Testmode => 45,
+    Connect => 46,
+    Roam => 47,
+    Disconnect => 48,
+    SetWiphyNetns => 49,
+    GetSurvey => 50,
+    NewSurveyResults => 51,
+    SetPmksa => 52,
+    DelPmksa => 53,
+    FlushPmksa => 54,
+    RemainOnChannel => 55,
+    CancelRemainOnChannel => 56,
+    SetTxBitrateMask => 57,
+    RegisterFrame => 58,
+    Frame => 59,
+    FrameTxStatus => 60,
+    SetPowerSave => 61,
+    GetPowerSave => 62,
+    SetCqm => 63,
+    NotifyCqm => 64,
+    SetChannel => 65,
+    SetWdsPeer => 66,
+    FrameWaitCancel => 67,
+    JoinMesh => 68,
+    LeaveMesh => 69,
+    UnprotDeauthenticate => 70,
+    UnprotDisassociate => 71,
+    NewPeerCandidate => 72,
+    GetWowlan => 73,
+    SetWowlan => 74,
+    StartSchedScan => 75,
+    StopSchedScan => 76,
+    SchedScanResults => 77,
+    SchedScanStopped => 78,
+    SetRekeyOffload => 79,
+    PmksaCandidate => 80,
+    TdlsOper => 81,
+    TdlsMgmt => 82,
+    UnexpectedFrame => 83,
+    ProbeClient => 84,
+    RegisterBeacons => 85,
+    Unexpected4addrFrame => 86,
+    SetNoackMap => 87,
+    ChSwitchNotify => 88,
+    StartP2pDevice => 89,
+    StopP2pDevice => 90,
+    ConnFailed => 91,
+    SetMcastRate => 92,
+    SetMacAcl => 93,
+    RadarDetect => 94,
+    GetProtocolFeatures => 95,
+    UpdateFtIes => 96,
+    FtEvent => 97,
+    CritProtocolStart => 98,
+    CritProtocolStop => 99,
+    GetCoalesce => 100,
+    SetCoalesce => 101,
+    ChannelSwitch => 102,
+    Vendor => 103,
+    SetQosMap => 104,
+    AddTxTs => 105,
+    DelTxTs => 106,
+    GetMpp => 107,
+    JoinOcb => 108,
+    LeaveOcb => 109,
+    ChSwitchStartedNotify => 110,
+    TdlsChannelSwitch => 111,
+    TdlsCancelChannelSwitch => 112,
+    WiphyRegChange => 113,
+    AbortScan => 114,
+    StartNan => 115,
+    StopNan => 116,
+    AddNanFunction => 117,
+    DelNanFunction => 118,
+    ChangeNanConfig => 119,
+    NanMatch => 120,
+    SetMulticastToUnicast => 121,
+    UpdateConnectParams => 122,
+    SetPmk => 123,
+    DelPmk => 124,
+    PortAuthorized => 125,
+    ReloadRegdb => 126,
+    ExternalAuth => 127,
+    StaOpmodeChanged => 128,
+    ControlPortFrame => 129,
+    GetFtmResponderStats => 130,
+    PeerMeasurementStart => 131,
+    PeerMeasurementResult => 132,
+    PeerMeasurementComplete => 133,
+    NotifyRadar => 134,
+    UpdateOweInfo => 135,
+    ProbeMeshLink => 136,
+    SetTidConfig => 137,
+    UnprotBeacon => 138,
+    ControlPortFrameTxStatus => 139,
+    SetSarSpecs => 140,
+    ObssColorCollision => 141,
+    ColorChangeRequest => 142,
+    ColorChangeStarted => 143,
+    ColorChangeAborted => 144,
+    ColorChangeCompleted => 145,
+    SetFilsAad => 146,
+    AssocComeback => 147,
+    AddLink => 148,
+    RemoveLink => 149,
+    AddLinkSta => 150,
+    ModifyLinkSta => 151,
+    RemoveLinkSta => 152
+);
+
+impl_var_trait!(
+    /// Top level attributes of the `nl80211` family
+    Nl80211Attr, u16, NlAttrType,
+    Unspec => 0,
+    Wiphy => 1,
+    WiphyName => 2,
+    Ifindex => 3,
+    Ifname => 4,
+    Iftype => 5,
+    Mac => 6,
+    KeyData => 7,
+    KeyIdx => 8,
+    KeyCipher => 9,
+    KeySeq => 10,
+    KeyDefault => 11,
+    BeaconInterval => 12,
+    DtimPeriod => 13,
+    BeaconHead => 14,
+    BeaconTail => 15,
+    StaAid => 16,
+    StaFlags => 17,
+    StaListenInterval => 18,
+    StaSupportedRates => 19,
+    StaVlan => 20,
+    StaInfo => 21,
+    WiphyBands => 22,
+    MntrFlags => 23,
+    MeshId => 24,
+    StaPlinkAction => 25,
+    MpathNextHop => 26,
+    MpathInfo => 27,
+    BssCtsProt => 28,
+    BssShortPreamble => 29,
+    BssShortSlotTime => 30,
+    HtCapability => 31,
+    SupportedIftypes => 32,
+    RegAlpha2 => 33,
+    RegRules => 34,
+    MeshConfig => 35,
+    BssBasicRates => 36,
+    WiphyTxqParams => 37,
+    WiphyFreq => 38,
+    WiphyChannelType => 39,
+    KeyDefaultMgmt => 40,
+    MgmtSubtype => 41,
+    Ie => 42,
+    MaxNumScanSsids => 43,
+    ScanFrequencies => 44,
+    ScanSsids => 45,
+    Generation => 46,
+    Bss => 47,
+    RegInitiator => 48,
+    RegType => 49,
+    SupportedCommands => 50,
+    Frame => 51,
+    Ssid => 52,
+    AuthType => 53,
+    ReasonCode => 54,
+    KeyType => 55,
+    MaxScanIeLen => 56,
+    CipherSuites => 57,
+    FreqBefore => 58,
+    FreqAfter => 59,
+    FreqFixed => 60,
+    WiphyRetryShort => 61,
+    WiphyRetryLong => 62,
+    WiphyFragThreshold => 63,
+    WiphyRtsThreshold => 64,
+    TimedOut => 65,
+    UseMfp => 66,
+    StaFlags2 => 67,
+    ControlPort => 68,
+    Testdata => 69,
+    Privacy => 70,
+    DisconnectedByAp => 71,
+    StatusCode => 72,
+    CipherSuitesPairwise => 73,
+    CipherSuiteGroup => 74,
+    WpaVersions => 75,
+    AkmSuites => 76,
+    ReqIe => 77,
+    RespIe => 78,
+    PrevBssid => 79,
+    Key => 80,
+    Keys => 81,
+    Pid => 82,
+    FourAddr => 83,
+    SurveyInfo => 84,
+    Pmkid => 85,
+    MaxNumPmkids => 86,
+    Duration => 87,
+    Cookie => 88,
+    WiphyCoverageClass => 89,
+    TxRates => 90,
+    FrameMatch => 91,
+    Ack => 92,
+    PsState => 93,
+    Cqm => 94,
+    LocalStateChange => 95,
+    ApIsolate => 96,
+    WiphyTxPowerSetting => 97,
+    WiphyTxPowerLevel => 98,
+    TxFrameTypes => 99,
+    RxFrameTypes => 100,
+    FrameType => 101,
+    ControlPortEthertype => 102,
+    ControlPortNoEncrypt => 103,
+    SupportIbssRsn => 104,
+    WiphyAntennaTx => 105,
+    WiphyAntennaRx => 106,
+    McastRate => 107,
+    OffchannelTxOk => 108,
+    BssHtOpmode => 109,
+    KeyDefaultTypes => 110,
+    MaxRemainOnChannelDuration => 111,
+    MeshSetup => 112,
+    WiphyAntennaAvailTx => 113,
+    WiphyAntennaAvailRx => 114,
+    SupportMeshAuth => 115,
+    StaPlinkState => 116,
+    WowlanTriggers => 117,
+    WowlanTriggersSupported => 118,
+    SchedScanInterval => 119,
+    InterfaceCombinations => 120,
+    SoftwareIftypes => 121,
+    RekeyData => 122,
+    MaxNumSchedScanSsids => 123,
+    MaxSchedScanIeLen => 124,
+    ScanSuppRates => 125,
+    HiddenSsid => 126,
+    IeProbeResp => 127,
+    IeAssocResp => 128,
+    StaWme => 129,
+    SupportApUapsd => 130,
+    RoamSupport => 131,
+    SchedScanMatch => 132,
+    MaxMatchSets => 133,
+    PmksaCandidate => 134,
+    TxNoCckRate => 135,
+    TdlsAction => 136,
+    TdlsDialogToken => 137,
+    TdlsOperation => 138,
+    TdlsSupport => 139,
+    TdlsExternalSetup => 140,
+    DeviceApSme => 141,
+    DontWaitForAck => 142,
+    FeatureFlags => 143,
+    ProbeRespOffload => 144,
+    ProbeResp => 145,
+    DfsRegion => 146,
+    DisableHt => 147,
+    HtCapabilityMask => 148,
+    NoackMap => 149,
+    InactivityTimeout => 150,
+    RxSignalDbm => 151,
+    BgScanPeriod => 152,
+    Wdev => 153,
+    UserRegHintType => 154,
+    ConnFailedReason => 155,
+    AuthData => 156,
+    VhtCapability => 157,
+    ScanFlags => 158,
+    ChannelWidth => 159,
+    CenterFreq1 => 160,
+    CenterFreq2 => 161,
+    P2pCtwindow => 162,
+    P2pOppps => 163,
+    LocalMeshPowerMode => 164,
+    AclPolicy => 165,
+    MacAddrs => 166,
+    MacAclMax => 167,
+    RadarEvent => 168,
+    ExtCapa => 169,
+    ExtCapaMask => 170,
+    StaCapability => 171,
+    StaExtCapability => 172,
+    ProtocolFeatures => 173,
+    SplitWiphyDump => 174,
+    DisableVht => 175,
+    VhtCapabilityMask => 176,
+    Mdid => 177,
+    IeRic => 178,
+    CritProtId => 179,
+    MaxCritProtDuration => 180,
+    PeerAid => 181,
+    CoalesceRule => 182,
+    ChSwitchCount => 183,
+    ChSwitchBlockTx => 184,
+    CsaIes => 185,
+    CntdwnOffsBeacon => 186,
+    CntdwnOffsPresp => 187,
+    RxmgmtFlags => 188,
+    StaSupportedChannels => 189,
+    StaSupportedOperClasses => 190,
+    HandleDfs => 191,
+    Support5Mhz => 192,
+    Support10Mhz => 193,
+    OpmodeNotif => 194,
+    VendorId => 195,
+    VendorSubcmd => 196,
+    VendorData => 197,
+    VendorEvents => 198,
+    QosMap => 199,
+    MacHint => 200,
+    WiphyFreqHint => 201,
+    MaxApAssocSta => 202,
+    TdlsPeerCapability => 203,
+    SocketOwner => 204,
+    CsaCOffsetsTx => 205,
+    MaxCsaCounters => 206,
+    TdlsInitiator => 207,
+    UseRrm => 208,
+    WiphyDynAck => 209,
+    Tsid => 210,
+    UserPrio => 211,
+    AdmittedTime => 212,
+    SmpsMode => 213,
+    OperClass => 214,
+    MacMask => 215,
+    WiphySelfManagedReg => 216,
+    ExtFeatures => 217,
+    SurveyRadioStats => 218,
+    NetnsFd => 219,
+    SchedScanDelay => 220,
+    RegIndoor => 221,
+    MaxNumSchedScanPlans => 222,
+    MaxScanPlanInterval => 223,
+    MaxScanPlanIterations => 224,
+    SchedScanPlans => 225,
+    Pbss => 226,
+    BssSelect => 227,
+    StaSupportP2pPs => 228,
+    Pad => 229,
+    IftypeExtCapa => 230,
+    MuMimoGroupData => 231,
+    MuMimoFollowMacAddr => 232,
+    ScanStartTimeTsf => 233,
+    ScanStartTimeTsfBssid => 234,
+    MeasurementDuration => 235,
+    MeasurementDurationMandatory => 236,
+    MeshPeerAid => 237,
+    NanMasterPref => 238,
+    Bands => 239,
+    NanFunc => 240,
+    NanMatch => 241,
+    FilsKek => 242,
+    FilsNonces => 243,
+    MulticastToUnicastEnabled => 244,
+    Bssid => 245,
+    SchedScanRelativeRssi => 246,
+    SchedScanRssiAdjust => 247,
+    TimeoutReason => 248,
+    FilsErpUsername => 249,
+    FilsErpRealm => 250,
+    FilsErpNextSeqNum => 251,
+    FilsErpRrk => 252,
+    FilsCacheId => 253,
+    Pmk => 254,
+    SchedScanMulti => 255,
+    SchedScanMaxReqs => 256,
+    Want1x4wayHs => 257,
+    Pmkr0Name => 258,
+    PortAuthorized => 259,
+    ExternalAuthAction => 260,
+    ExternalAuthSupport => 261,
+    Nss => 262,
+    AckSignal => 263,
+    ControlPortOverNl80211 => 264,
+    TxqStats => 265,
+    TxqLimit => 266,
+    TxqMemoryLimit => 267,
+    TxqQuantum => 268,
+    HeCapability => 269,
+    FtmResponder => 270,
+    FtmResponderStats => 271,
+    Timeout => 272,
+    PeerMeasurements => 273,
+    AirtimeWeight => 274,
+    StaTxPowerSetting => 275,
+    StaTxPower => 276,
+    SaePassword => 277,
+    TwtResponder => 278,
+    HeObssPd => 279,
+    WiphyEdmgChannels => 280,
+    WiphyEdmgBwConfig => 281,
+    VlanId => 282,
+    HeBssColor => 283,
+    IftypeAkmSuites => 284,
+    TidConfig => 285,
+    ControlPortNoPreauth => 286,
+    PmkLifetime => 287,
+    PmkReauthThreshold => 288,
+    ReceiveMulticast => 289,
+    WiphyFreqOffset => 290,
+    CenterFreq1Offset => 291,
+    ScanFreqKhz => 292,
+    He6ghzCapability => 293,
+    FilsDiscovery => 294,
+    UnsolBcastProbeResp => 295,
+    S1gCapability => 296,
+    S1gCapabilityMask => 297,
+    SaePwe => 298,
+    ReconnectRequested => 299,
+    SarSpec => 300,
+    DisableHe => 301,
+    ObssColorBitmap => 302,
+    ColorChangeCount => 303,
+    ColorChangeColor => 304,
+    ColorChangeElems => 305,
+    MbssidConfig => 306,
+    MbssidElems => 307,
+    RadarBackground => 308,
+    ApSettingsFlags => 309,
+    EhtCapability => 310,
+    DisableEht => 311,
+    MloLinks => 312,
+    MloLinkId => 313,
+    MldAddr => 314,
+    MloSupport => 315,
+    MaxNumAkmSuites => 316,
+    EmlCapability => 317,
+    MldCapaAndOps => 318,
+    TxHwTimestamp => 319,
+    RxHwTimestamp => 320
+);
+
+impl_var!(
+    /// Interface types in `Nl80211Attr::Iftype`
+    Nl80211Iftype, u32,
+    Unspecified => 0,
+    Adhoc => 1,
+    Station => 2,
+    Ap => 3,
+    ApVlan => 4,
+    Wds => 5,
+    Monitor => 6,
+    MeshPoint => 7,
+    P2pClient => 8,
+    P2pGo => 9,
+    P2pDevice => 10,
+    Ocb => 11,
+    Nan => 12
+);
+
+impl_var_trait!(
+    /// Attributes of a BSS nested in `Nl80211Attr::Bss` of scan results
+    Nl80211Bss, u16, NlAttrType,
+    Invalid => 0,
+    Bssid => 1,
+    Frequency => 2,
+    Tsf => 3,
+    BeaconInterval => 4,
+    Capability => 5,
+    InformationElements => 6,
+    SignalMbm => 7,
+    SignalUnspec => 8,
+    Status => 9,
+    SeenMsAgo => 10,
+    BeaconIes => 11,
+    ChanWidth => 12,
+    BeaconTsf => 13,
+    PrespData => 14,
+    LastSeenBoottime => 15,
+    Pad => 16,
+    ParentTsf => 17,
+    ParentBssid => 18,
+    ChainSignal => 19,
+    FrequencyOffset => 20,
+    MloLinkId => 21,
+    MldAddr => 22
+);
+
+impl_var_trait!(
+    /// Attributes of a station nested in `Nl80211Attr::StaInfo`
+    Nl80211StaInfo, u16, NlAttrType,
+    Invalid => 0,
+    InactiveTime => 1,
+    RxBytes => 2,
+    TxBytes => 3,
+    Llid => 4,
+    Plid => 5,
+    PlinkState => 6,
+    Signal => 7,
+    TxBitrate => 8,
+    RxPackets => 9,
+    TxPackets => 10,
+    TxRetries => 11,
+    TxFailed => 12,
+    SignalAvg => 13,
+    RxBitrate => 14,
+    BssParam => 15,
+    ConnectedTime => 16,
+    StaFlags => 17,
+    BeaconLoss => 18,
+    TOffset => 19,
+    LocalPm => 20,
+    PeerPm => 21,
+    NonpeerPm => 22,
+    RxBytes64 => 23,
+    TxBytes64 => 24,
+    ChainSignal => 25,
+    ChainSignalAvg => 26,
+    ExpectedThroughput => 27,
+    RxDropMisc => 28,
+    BeaconRx => 29,
+    BeaconSignalAvg => 30,
+    TidStats => 31,
+    RxDuration => 32,
+    Pad => 33,
+    AckSignal => 34,
+    AckSignalAvg => 35,
+    RxMpdus => 36,
+    FcsErrorCount => 37,
+    ConnectedToGate => 38,
+    TxDuration => 39,
+    AirtimeWeight => 40,
+    AirtimeLinkMetric => 41,
+    AssocAtBoottime => 42,
+    ConnectedToAs => 43
+);
+
+impl_var_trait!(
+    /// Attributes of a band nested in `Nl80211Attr::WiphyBands`
+    Nl80211BandAttr, u16, NlAttrType,
+    Invalid => 0,
+    Freqs => 1,
+    Rates => 2,
+    HtMcsSet => 3,
+    HtCapa => 4,
+    HtAmpduFactor => 5,
+    HtAmpduDensity => 6,
+    VhtMcsSet => 7,
+    VhtCapa => 8,
+    IftypeData => 9,
+    EdmgChannels => 10,
+    EdmgBwConfig => 11
+);
+
+impl_var_trait!(
+    /// Attributes of a channel nested in `Nl80211BandAttr::Freqs`
+    Nl80211FrequencyAttr, u16, NlAttrType,
+    Invalid => 0,
+    Freq => 1,
+    Disabled => 2,
+    NoIr => 3,
+    Radar => 5,
+    MaxTxPower => 6,
+    DfsState => 7,
+    DfsTime => 8,
+    NoHt40Minus => 9,
+    NoHt40Plus => 10,
+    No80mhz => 11,
+    No160mhz => 12,
+    DfsCacTime => 13,
+    IndoorOnly => 14,
+    IrConcurrent => 15,
+    No20mhz => 16,
+    No10mhz => 17,
+    Wmm => 18,
+    NoHe => 19,
+    Offset => 20,
+    OneMhz => 21,
+    TwoMhz => 22,
+    FourMhz => 23,
+    EightMhz => 24,
+    SixteenMhz => 25,
+    No320mhz => 26,
+    NoEht => 27
+);
+
+/// Constants needed for nl80211 messages, for glob imports
+pub mod prelude {
+    pub use super::{
+        Nl80211, Nl80211Attr, Nl80211BandAttr, Nl80211Bss, Nl80211Cmd, Nl80211FrequencyAttr,
+        Nl80211Iftype, Nl80211StaInfo, NL80211_MULTICAST_GROUP_CONFIG,
+        NL80211_MULTICAST_GROUP_MLME, NL80211_MULTICAST_GROUP_NAN, NL80211_MULTICAST_GROUP_REG,
+        NL80211_MULTICAST_GROUP_SCAN, NL80211_MULTICAST_GROUP_TESTMODE,
+        NL80211_MULTICAST_GROUP_VENDOR,
+    };
+    pub use crate::consts::genl::prelude::*;
+}
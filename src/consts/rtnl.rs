@@ -1,3 +1,58 @@
+use super::nl::NlType;
+
+impl_var_trait!(
+    /// rtnetlink-related values for `nl_type` in `Nlmsghdr`
+    Rtm, u16, NlType,
+    Newlink => libc::RTM_NEWLINK,
+    Dellink => libc::RTM_DELLINK,
+    Getlink => libc::RTM_GETLINK,
+    Setlink => libc::RTM_SETLINK,
+    Newaddr => libc::RTM_NEWADDR,
+    Deladdr => libc::RTM_DELADDR,
+    Getaddr => libc::RTM_GETADDR,
+    Newroute => libc::RTM_NEWROUTE,
+    Delroute => libc::RTM_DELROUTE,
+    Getroute => libc::RTM_GETROUTE,
+    Newneigh => libc::RTM_NEWNEIGH,
+    Delneigh => libc::RTM_DELNEIGH,
+    Getneigh => libc::RTM_GETNEIGH,
+    Newrule => libc::RTM_NEWRULE,
+    Delrule => libc::RTM_DELRULE,
+    Getrule => libc::RTM_GETRULE,
+    Newqdisc=> libc::RTM_NEWQDISC,
+    Delqdisc=> libc::RTM_DELQDISC,
+    Getqdisc=> libc::RTM_GETQDISC,
+    Newtclass => libc::RTM_NEWTCLASS,
+    Deltclass => libc::RTM_DELTCLASS,
+    Gettclass => libc::RTM_GETTCLASS,
+    Newtfilter => libc::RTM_NEWTFILTER,
+    Deltfilter => libc::RTM_DELTFILTER,
+    Gettfilter => libc::RTM_GETTFILTER,
+    Newaction => libc::RTM_NEWACTION,
+    Delaction => libc::RTM_DELACTION,
+    Getaction => libc::RTM_GETACTION,
+    Newprefix => libc::RTM_NEWPREFIX,
+    Getmulticast => libc::RTM_GETMULTICAST,
+    Getanycast => libc::RTM_GETANYCAST,
+    Newneightbl => libc::RTM_NEWNEIGHTBL,
+    Getneightbl => libc::RTM_GETNEIGHTBL,
+    Setneightbl => libc::RTM_SETNEIGHTBL,
+    Newnduseropt => libc::RTM_NEWNDUSEROPT,
+    Newaddrlabel => libc::RTM_NEWADDRLABEL,
+    Deladdrlabel => libc::RTM_DELADDRLABEL,
+    Getaddrlabel => libc::RTM_GETADDRLABEL,
+    Getdcb => libc::RTM_GETDCB,
+    Setdcb => libc::RTM_SETDCB,
+    Newnetconf => libc::RTM_NEWNETCONF,
+    Getnetconf => libc::RTM_GETNETCONF,
+    Newmdb => libc::RTM_NEWMDB,
+    Delmdb => libc::RTM_DELMDB,
+    Getmdb => libc::RTM_GETMDB,
+    Newnsid => libc::RTM_NEWNSID,
+    Delnsid => libc::RTM_DELNSID,
+    Getnsid => libc::RTM_GETNSID
+);
+
 impl_var!(
     /// Internet address families
     Af, libc::c_uchar,
@@ -8,6 +63,7 @@ impl_var!(
 impl_var!(
     /// General address families for sockets
     RtAddrFamily, u8,
+    Unspec => libc::AF_UNSPEC as u8,
     UnixOrLocal => libc::AF_UNIX as u8,
     Inet => libc::AF_INET as u8,
     Inet6 => libc::AF_INET6 as u8,
@@ -21,6 +77,26 @@ impl_var!(
     Alg => libc::AF_ALG as u8
 );
 
+impl RtAddrFamily {
+    /// Length of the addresses of the family, `None` for families other than IPv4 and IPv6
+    pub fn addr_len(&self) -> Option<usize> {
+        match *self {
+            RtAddrFamily::Inet => Some(4),
+            RtAddrFamily::Inet6 => Some(16),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> From<&'a std::net::IpAddr> for RtAddrFamily {
+    fn from(addr: &'a std::net::IpAddr) -> Self {
+        match *addr {
+            std::net::IpAddr::V4(_) => RtAddrFamily::Inet,
+            std::net::IpAddr::V6(_) => RtAddrFamily::Inet6,
+        }
+    }
+}
+
 impl_var!(
     /// Interface address flags
     IfaF, u32,
@@ -43,6 +119,11 @@ impl_var!(
     StablePrivacy => libc::IFA_F_STABLE_PRIVACY
 );
 
+impl_flags!(
+    /// Set of `IfaF` flags
+    IfaFFlags, IfaF, u32
+);
+
 impl_var!(
     /// `rtm_type`
     /// The results of a lookup from a route table
@@ -109,6 +190,11 @@ impl_var!(
     FibMatch => libc::RTM_F_FIB_MATCH
 );
 
+impl_flags!(
+    /// Set of `RtmF` flags
+    RtmFFlags, RtmF, libc::c_uint
+);
+
 impl_var!(
     /// Arp neighbor cache entry states
     Nud, u16,
@@ -123,6 +209,11 @@ impl_var!(
     Permanent => libc::NUD_PERMANENT
 );
 
+impl_flags!(
+    /// Set of `Nud` states, as used to filter neighbor entries by state
+    NudFlags, Nud, u16
+);
+
 impl_var!(
     /// Arp neighbor cache entry flags
     Ntf, u8,
@@ -137,6 +228,11 @@ impl_var!(
     Router => libc::NTF_ROUTER
 );
 
+impl_flags!(
+    /// Set of `Ntf` flags
+    NtfFlags, Ntf, u8
+);
+
 impl_trait!(
     /// Marker trait for `Rtattr.rta_type` field
     RtaType,
@@ -326,6 +422,52 @@ impl_var!(
     None => libc::ARPHRD_NONE
 );
 
+impl_var!(
+    /// Operational states of an interface as defined in RFC 2863, found in `Ifla::Operstate`
+    /// attributes
+    IfOper, u8,
+    Unknown => libc::IF_OPER_UNKNOWN as u8,
+    Notpresent => libc::IF_OPER_NOTPRESENT as u8,
+    Down => libc::IF_OPER_DOWN as u8,
+    Lowerlayerdown => libc::IF_OPER_LOWERLAYERDOWN as u8,
+    Testing => libc::IF_OPER_TESTING as u8,
+    Dormant => libc::IF_OPER_DORMANT as u8,
+    Up => libc::IF_OPER_UP as u8
+);
+
+impl IfOper {
+    /// Whether the interface is up and able to pass packets. Devices that do not track their
+    /// operational state, such as the loopback interface, always report `IfOper::Unknown`
+    /// instead.
+    pub fn is_up_and_running(&self) -> bool {
+        *self == IfOper::Up
+    }
+
+    /// Whether the interface cannot pass packets, either because it is down itself or because
+    /// a device it is stacked on, such as the physical port below a VLAN, is down
+    pub fn is_down(&self) -> bool {
+        matches!(
+            *self,
+            IfOper::Down | IfOper::Lowerlayerdown | IfOper::Notpresent
+        )
+    }
+}
+
+impl_var!(
+    /// Link modes of an interface, found in `Ifla::Linkmode` attributes
+    IfLinkMode, u8,
+    Default => libc::IF_LINK_MODE_DEFAULT as u8,
+    Dormant => libc::IF_LINK_MODE_DORMANT as u8,
+    Testing => libc::IF_LINK_MODE_TESTING as u8
+);
+
+impl_var!(
+    /// Carrier state of an interface, found in `Ifla::Carrier` attributes
+    IfCarrier, u8,
+    Down => 0,
+    Up => 1
+);
+
 impl_var!(
     /// Values for `ifi_flags` in `Ifinfomsg`
     Iff, libc::c_uint,
@@ -351,3 +493,67 @@ impl_var!(
 
     // Possibly more types here - need to look into private flags for interfaces
 );
+
+impl_flags!(
+    /// Set of `Iff` flags
+    IffFlags, Iff, libc::c_uint
+);
+
+impl_var!(
+    /// rtnetlink multicast groups, for use with `NlSocket::subscribe_rt_events`
+    RtnlGroup, u32,
+    Link => libc::RTNLGRP_LINK,
+    Notify => libc::RTNLGRP_NOTIFY,
+    Neigh => libc::RTNLGRP_NEIGH,
+    Tc => libc::RTNLGRP_TC,
+    Ipv4Ifaddr => libc::RTNLGRP_IPV4_IFADDR,
+    Ipv4Mroute => libc::RTNLGRP_IPV4_MROUTE,
+    Ipv4Route => libc::RTNLGRP_IPV4_ROUTE,
+    Ipv4Rule => libc::RTNLGRP_IPV4_RULE,
+    Ipv6Ifaddr => libc::RTNLGRP_IPV6_IFADDR,
+    Ipv6Mroute => libc::RTNLGRP_IPV6_MROUTE,
+    Ipv6Route => libc::RTNLGRP_IPV6_ROUTE,
+    Ipv6Ifinfo => libc::RTNLGRP_IPV6_IFINFO,
+    Ipv6Prefix => libc::RTNLGRP_IPV6_PREFIX,
+    Ipv6Rule => libc::RTNLGRP_IPV6_RULE,
+    NdUseropt => libc::RTNLGRP_ND_USEROPT,
+    Dcb => libc::RTNLGRP_DCB,
+    Ipv4Netconf => libc::RTNLGRP_IPV4_NETCONF,
+    Ipv6Netconf => libc::RTNLGRP_IPV6_NETCONF,
+    Mdb => libc::RTNLGRP_MDB,
+    MplsRoute => libc::RTNLGRP_MPLS_ROUTE,
+    Nsid => libc::RTNLGRP_NSID,
+    MplsNetconf => libc::RTNLGRP_MPLS_NETCONF,
+    Nexthop => libc::RTNLGRP_NEXTHOP,
+    Brvlan => libc::RTNLGRP_BRVLAN
+);
+
+impl RtnlGroup {
+    /// Number of the group, as passed to `NlSocket::add_mcast_membership`
+    pub fn group(&self) -> u32 {
+        self.into()
+    }
+
+    /// Bit of the group in the legacy bitmask of `sockaddr_nl`, used by `RecvAddr::groups`.
+    /// Returns `None` for groups above 32, which can only be joined by number.
+    pub fn bitmask(&self) -> Option<u32> {
+        match self.group() {
+            group @ 1..=32 => Some(1 << (group - 1)),
+            _ => None,
+        }
+    }
+
+    /// Groups set in a legacy bitmask
+    pub fn from_bitmask(mask: u32) -> Vec<RtnlGroup> {
+        (0..32)
+            .filter(|bit| mask & (1 << bit) != 0)
+            .map(|bit| RtnlGroup::from(bit + 1))
+            .collect()
+    }
+}
+
+/// Constants needed for routing netlink messages, for glob imports
+pub mod prelude {
+    pub use super::*;
+    pub use crate::consts::nl::prelude::*;
+}
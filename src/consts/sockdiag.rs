@@ -0,0 +1,182 @@
+//! Constants of the `NETLINK_SOCK_DIAG` protocol, from `linux/sock_diag.h`, `linux/inet_diag.h`,
+//! `linux/unix_diag.h`, `linux/netlink_diag.h`, `linux/packet_diag.h` and the TCP states of
+//! `net/tcp_states.h`
+
+use super::nl::{NlAttrType, NlType};
+
+impl_var_trait!(
+    /// Messages of `NlFamily::SockOrInetDiag` sockets
+    SockDiagMsg, u16, NlType,
+    ByFamily => 20,
+    Destroy => 21
+);
+
+impl_var_trait!(
+    /// Attributes following `InetDiagMsg`. Except for `Unspec`, `Protocol` and `Pad`, an attribute
+    /// is only included if the extension bit for it is set in `InetDiagReqV2::idiag_ext`.
+    InetDiagAttr, u16, NlAttrType,
+    Unspec => 0,
+    Meminfo => 1,
+    Info => 2,
+    Vegasinfo => 3,
+    Cong => 4,
+    Tos => 5,
+    Tclass => 6,
+    Skmeminfo => 7,
+    Shutdown => 8,
+    Dctcpinfo => 9,
+    Protocol => 10,
+    Skv6only => 11,
+    Locals => 12,
+    Peers => 13,
+    Pad => 14,
+    Mark => 15,
+    Bbrinfo => 16,
+    ClassId => 17,
+    Md5sig => 18,
+    UlpInfo => 19,
+    SkBpfStorages => 20,
+    CgroupId => 21,
+    Sockopt => 22
+);
+
+impl InetDiagAttr {
+    /// Bit requesting this attribute in `InetDiagReqV2::idiag_ext`, if it can be requested
+    pub fn ext_bit(&self) -> Option<u8> {
+        match u16::from(self) {
+            v @ 1..=8 => Some(1 << (v - 1)),
+            _ => None,
+        }
+    }
+}
+
+impl_var!(
+    /// States of TCP sockets, also used for the state of other sockets reported by sock_diag
+    TcpState, u8,
+    Established => 1,
+    SynSent => 2,
+    SynRecv => 3,
+    FinWait1 => 4,
+    FinWait2 => 5,
+    TimeWait => 6,
+    Close => 7,
+    CloseWait => 8,
+    LastAck => 9,
+    Listen => 10,
+    Closing => 11,
+    NewSynRecv => 12
+);
+
+impl TcpState {
+    /// Bit of the state in `InetDiagReqV2::idiag_states`
+    pub fn bit(&self) -> u32 {
+        1u32.checked_shl(u32::from(u8::from(self))).unwrap_or(0)
+    }
+}
+
+impl_var!(
+    /// Information requested in `UnixDiagReq::udiag_show`
+    UdiagShow, u32,
+    Name => 0x1,
+    Vfs => 0x2,
+    Peer => 0x4,
+    Icons => 0x8,
+    Rqlen => 0x10,
+    Meminfo => 0x20,
+    Uid => 0x40
+);
+
+impl_flags!(
+    /// Set of `UdiagShow` flags
+    UdiagShowFlags, UdiagShow, u32
+);
+
+impl_var_trait!(
+    /// Attributes following `UnixDiagMsg`, included as requested with `UnixDiagReq::udiag_show`
+    UnixDiagAttr, u16, NlAttrType,
+    Name => 0,
+    Vfs => 1,
+    Peer => 2,
+    Icons => 3,
+    Rqlen => 4,
+    Meminfo => 5,
+    Shutdown => 6,
+    Uid => 7
+);
+
+impl_var!(
+    /// Information requested in `NetlinkDiagReq::ndiag_show`
+    NdiagShow, u32,
+    Meminfo => 0x1,
+    Groups => 0x2,
+    RingCfg => 0x4,
+    Flags => 0x8
+);
+
+impl_flags!(
+    /// Set of `NdiagShow` flags
+    NdiagShowFlags, NdiagShow, u32
+);
+
+impl_var_trait!(
+    /// Attributes following `NetlinkDiagMsg`, included as requested with
+    /// `NetlinkDiagReq::ndiag_show`
+    NetlinkDiagAttr, u16, NlAttrType,
+    Meminfo => 0,
+    Groups => 1,
+    RxRing => 2,
+    TxRing => 3,
+    Flags => 4
+);
+
+impl_var!(
+    /// Flags of a netlink socket in the `NetlinkDiagAttr::Flags` attribute
+    NdiagFlag, u32,
+    CbRunning => 0x1,
+    PktInfo => 0x2,
+    BroadcastError => 0x4,
+    NoEnobufs => 0x8,
+    ListenAllNsid => 0x10,
+    CapAck => 0x20
+);
+
+impl_flags!(
+    /// Set of `NdiagFlag` flags
+    NdiagFlags, NdiagFlag, u32
+);
+
+impl_var!(
+    /// Information requested in `PacketDiagReq::pdiag_show`
+    PacketShow, u32,
+    Info => 0x1,
+    Mclist => 0x2,
+    RingCfg => 0x4,
+    Fanout => 0x8,
+    Meminfo => 0x10,
+    Filter => 0x20
+);
+
+impl_flags!(
+    /// Set of `PacketShow` flags
+    PacketShowFlags, PacketShow, u32
+);
+
+impl_var_trait!(
+    /// Attributes following `PacketDiagMsg`, included as requested with
+    /// `PacketDiagReq::pdiag_show`
+    PacketDiagAttr, u16, NlAttrType,
+    Info => 0,
+    Mclist => 1,
+    RxRing => 2,
+    TxRing => 3,
+    Fanout => 4,
+    Uid => 5,
+    Meminfo => 6,
+    Filter => 7
+);
+
+/// Constants needed for sock_diag messages, for glob imports
+pub mod prelude {
+    pub use super::*;
+    pub use crate::consts::nl::prelude::*;
+}
@@ -39,3 +39,23 @@ impl_var!(
     Rdma => libc::NETLINK_RDMA,
     Crypto => libc::NETLINK_CRYPTO
 );
+
+impl_var!(
+    /// Netlink-specific socket options set with `setsockopt` at the `SOL_NETLINK` level
+    NlSockOpt, libc::c_int,
+    AddMembership => libc::NETLINK_ADD_MEMBERSHIP,
+    DropMembership => libc::NETLINK_DROP_MEMBERSHIP,
+    Pktinfo => libc::NETLINK_PKTINFO,
+    BroadcastError => libc::NETLINK_BROADCAST_ERROR,
+    NoEnobufs => libc::NETLINK_NO_ENOBUFS,
+    ListenAllNsid => libc::NETLINK_LISTEN_ALL_NSID,
+    ListMemberships => libc::NETLINK_LIST_MEMBERSHIPS,
+    CapAck => libc::NETLINK_CAP_ACK,
+    ExtAck => libc::NETLINK_EXT_ACK,
+    GetStrictChk => libc::NETLINK_GET_STRICT_CHK
+);
+
+/// Constants needed for socket operations, for glob imports
+pub mod prelude {
+    pub use super::*;
+}
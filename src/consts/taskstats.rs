@@ -0,0 +1,78 @@
+//! Constants of the taskstats generic netlink family reporting per-task and per-cgroup
+//! statistics, from `linux/taskstats.h` and `linux/cgroupstats.h`
+
+use super::genl::Cmd;
+use super::nl::NlAttrType;
+use crate::genl::GenlFamily;
+
+/// Latest version of `struct taskstats` known to `neli`
+pub const TASKSTATS_VERSION: u16 = 13;
+/// Length of `ac_comm` in `struct taskstats`
+pub const TS_COMM_LEN: usize = 32;
+
+/// The `TASKSTATS` generic netlink family, named apart from `taskstats::Taskstats` holding
+/// `struct taskstats`
+pub struct TaskstatsFamily;
+
+impl GenlFamily for TaskstatsFamily {
+    const NAME: &'static str = "TASKSTATS";
+    const VERSION: u8 = 1;
+    type Cmd = TaskstatsCmd;
+    type Attr = TaskstatsCmdAttr;
+}
+
+impl_var_trait!(
+    /// Commands of the `TASKSTATS` family, including those of cgroup statistics
+    TaskstatsCmd, u8, Cmd,
+    Unspec => 0,
+    Get => 1,
+    New => 2,
+    CgroupstatsUnspec => 3,
+    CgroupstatsGet => 4,
+    CgroupstatsNew => 5
+);
+
+impl_var_trait!(
+    /// Attributes of `TaskstatsCmd::Get` requests
+    TaskstatsCmdAttr, u16, NlAttrType,
+    Unspec => 0,
+    Pid => 1,
+    Tgid => 2,
+    RegisterCpumask => 3,
+    DeregisterCpumask => 4
+);
+
+impl_var_trait!(
+    /// Attributes of `TaskstatsCmd::New` replies and exit notifications
+    TaskstatsType, u16, NlAttrType,
+    Unspec => 0,
+    Pid => 1,
+    Tgid => 2,
+    Stats => 3,
+    AggrPid => 4,
+    AggrTgid => 5,
+    Null => 6
+);
+
+impl_var_trait!(
+    /// Attributes of `TaskstatsCmd::CgroupstatsGet` requests
+    CgroupstatsCmdAttr, u16, NlAttrType,
+    Unspec => 0,
+    Fd => 1
+);
+
+impl_var_trait!(
+    /// Attributes of `TaskstatsCmd::CgroupstatsNew` replies
+    CgroupstatsType, u16, NlAttrType,
+    Unspec => 0,
+    CgroupStats => 1
+);
+
+/// Constants needed for taskstats messages, for glob imports
+pub mod prelude {
+    pub use super::{
+        CgroupstatsCmdAttr, CgroupstatsType, TaskstatsCmd, TaskstatsCmdAttr, TaskstatsFamily,
+        TaskstatsType, TASKSTATS_VERSION, TS_COMM_LEN,
+    };
+    pub use crate::consts::genl::prelude::*;
+}
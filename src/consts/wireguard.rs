@@ -0,0 +1,97 @@
+//! Constants of the WireGuard generic netlink family, from `linux/wireguard.h`
+
+use super::genl::Cmd;
+use super::nl::NlAttrType;
+use crate::genl::GenlFamily;
+
+/// Length of public, private and preshared keys
+pub const WG_KEY_LEN: usize = 32;
+
+/// The `wireguard` generic netlink family
+pub struct Wireguard;
+
+impl GenlFamily for Wireguard {
+    const NAME: &'static str = "wireguard";
+    const VERSION: u8 = 1;
+    type Cmd = WgCmd;
+    type Attr = WgdeviceAttr;
+}
+
+impl_var_trait!(
+    /// Commands of the `wireguard` family
+    WgCmd, u8, Cmd,
+    GetDevice => 0,
+    SetDevice => 1
+);
+
+impl_var_trait!(
+    /// Attributes of a WireGuard device
+    WgdeviceAttr, u16, NlAttrType,
+    Unspec => 0,
+    Ifindex => 1,
+    Ifname => 2,
+    PrivateKey => 3,
+    PublicKey => 4,
+    Flags => 5,
+    ListenPort => 6,
+    Fwmark => 7,
+    Peers => 8
+);
+
+impl_var_trait!(
+    /// Attributes of a peer nested in `WgdeviceAttr::Peers`
+    WgpeerAttr, u16, NlAttrType,
+    Unspec => 0,
+    PublicKey => 1,
+    PresharedKey => 2,
+    Flags => 3,
+    Endpoint => 4,
+    PersistentKeepaliveInterval => 5,
+    LastHandshakeTime => 6,
+    RxBytes => 7,
+    TxBytes => 8,
+    Allowedips => 9,
+    ProtocolVersion => 10
+);
+
+impl_var_trait!(
+    /// Attributes of an allowed IP range nested in `WgpeerAttr::Allowedips`
+    WgallowedipAttr, u16, NlAttrType,
+    Unspec => 0,
+    Family => 1,
+    Ipaddr => 2,
+    CidrMask => 3
+);
+
+impl_var!(
+    /// Flags in `WgdeviceAttr::Flags`
+    WgdeviceF, u32,
+    ReplacePeers => 1 << 0
+);
+
+impl_flags!(
+    /// Set of `WgdeviceF` flags
+    WgdeviceFlags, WgdeviceF, u32
+);
+
+impl_var!(
+    /// Flags in `WgpeerAttr::Flags`
+    WgpeerF, u32,
+    RemoveMe => 1 << 0,
+    ReplaceAllowedips => 1 << 1,
+    UpdateOnly => 1 << 2
+);
+
+impl_flags!(
+    /// Set of `WgpeerF` flags
+    WgpeerFlags, WgpeerF, u32
+);
+
+/// Constants needed for WireGuard messages, for glob imports
+pub mod prelude {
+    pub use super::{
+        WgCmd, WgallowedipAttr, WgdeviceAttr, WgdeviceF, WgdeviceFlags, WgpeerAttr, WgpeerF,
+        WgpeerFlags, Wireguard, WG_KEY_LEN,
+    };
+    pub use crate::consts::genl::prelude::*;
+}
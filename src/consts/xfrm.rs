@@ -0,0 +1,157 @@
+//! Constants of the `NETLINK_XFRM` protocol managing IPsec security associations and policies,
+//! from `linux/xfrm.h`
+
+use super::nl::{NlAttrType, NlType};
+
+impl_var_trait!(
+    /// Messages of `NlFamily::Xfrm` sockets
+    XfrmMsg, u16, NlType,
+    NewSa => 0x10,
+    DelSa => 0x11,
+    GetSa => 0x12,
+    NewPolicy => 0x13,
+    DelPolicy => 0x14,
+    GetPolicy => 0x15,
+    AllocSpi => 0x16,
+    Acquire => 0x17,
+    Expire => 0x18,
+    UpdPolicy => 0x19,
+    UpdSa => 0x1a,
+    PolExpire => 0x1b,
+    FlushSa => 0x1c,
+    FlushPolicy => 0x1d,
+    NewAe => 0x1e,
+    GetAe => 0x1f,
+    Report => 0x20,
+    Migrate => 0x21,
+    NewSadInfo => 0x22,
+    GetSadInfo => 0x23,
+    NewSpdInfo => 0x24,
+    GetSpdInfo => 0x25,
+    Mapping => 0x26,
+    SetDefault => 0x27,
+    GetDefault => 0x28
+);
+
+impl_var_trait!(
+    /// Attributes following the headers of xfrm messages
+    XfrmAttr, u16, NlAttrType,
+    Unspec => 0,
+    AlgAuth => 1,
+    AlgCrypt => 2,
+    AlgComp => 3,
+    Encap => 4,
+    Tmpl => 5,
+    Sa => 6,
+    Policy => 7,
+    SecCtx => 8,
+    Ltime => 9,
+    Replay => 10,
+    ReplayThresh => 11,
+    EtimerThresh => 12,
+    Srcaddr => 13,
+    Coaddr => 14,
+    LastUsed => 15,
+    PolicyType => 16,
+    Migrate => 17,
+    AlgAead => 18,
+    Kmaddress => 19,
+    AlgAuthTrunc => 20,
+    Mark => 21,
+    Tfcpad => 22,
+    ReplayEsn => 23,
+    SaExtraFlags => 24,
+    ProtoId => 25,
+    AddressFilter => 26,
+    Pad => 27,
+    OffloadDev => 28,
+    SetMark => 29,
+    SetMarkMask => 30,
+    IfId => 31,
+    MtimerThresh => 32,
+    SaDir => 33,
+    NatKeepaliveInterval => 34
+);
+
+impl_var!(
+    /// Modes of security associations and templates
+    XfrmMode, u8,
+    Transport => 0,
+    Tunnel => 1,
+    RouteOptimization => 2,
+    InTrigger => 3,
+    Beet => 4
+);
+
+impl_var!(
+    /// Directions of policies
+    XfrmPolicyDir, u8,
+    In => 0,
+    Out => 1,
+    Fwd => 2
+);
+
+impl_var!(
+    /// Actions of policies
+    XfrmPolicyAction, u8,
+    Allow => 0,
+    Block => 1
+);
+
+impl_var!(
+    /// Sharing modes of policies and templates
+    XfrmShare, u8,
+    Any => 0,
+    Session => 1,
+    User => 2,
+    Unique => 3
+);
+
+impl_var!(
+    /// Flags of `XfrmUsersaInfo`
+    XfrmStateFlag, u8,
+    NoEcn => 1,
+    DecapDscp => 2,
+    NoPmtuDisc => 4,
+    WildRecv => 8,
+    Icmp => 16,
+    AfUnspec => 32,
+    Align4 => 64,
+    Esn => 128
+);
+
+impl_flags!(
+    /// Set of `XfrmStateFlag` flags
+    XfrmStateFlags, XfrmStateFlag, u8
+);
+
+impl_var!(
+    /// Flags of `XfrmUserpolicyInfo`
+    XfrmPolicyFlag, u8,
+    LocalOk => 1,
+    Icmp => 2
+);
+
+impl_flags!(
+    /// Set of `XfrmPolicyFlag` flags
+    XfrmPolicyFlags, XfrmPolicyFlag, u8
+);
+
+impl_var!(
+    /// xfrm multicast groups, for use with `NlSocket::add_mcast_membership`
+    XfrmGroup, u32,
+    Acquire => 1,
+    Expire => 2,
+    Sa => 3,
+    Policy => 4,
+    Aevents => 5,
+    Report => 6,
+    Migrate => 7,
+    Mapping => 8
+);
+
+/// Constants needed for xfrm messages, for glob imports
+pub mod prelude {
+    pub use super::*;
+    pub use crate::consts::nl::prelude::*;
+}
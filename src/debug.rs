@@ -0,0 +1,437 @@
+//! This module renders serialized messages as an annotated hexdump for logs and bug reports, so
+//! that a request the kernel rejects can be inspected without decoding the bytes by hand.
+//!
+//! Every line shows the offset of its bytes, up to 16 bytes in hexadecimal and what they hold:
+//! the fields of `struct nlmsghdr` with the message type and flags decoded by name, the family
+//! header and the attribute tree, with nested attributes indented below their parent.
+//!
+//! ```
+//! use neli::consts::{Ifla, NlmF, Rtm};
+//! use neli::debug;
+//! use neli::nl::Nlmsghdr;
+//!
+//! let msg = Nlmsghdr::new(None, Rtm::Getlink, vec![NlmF::Request], None, None, vec![0u8; 16]);
+//! println!("{}", debug::dump_msg::<Ifla, _, _>(&msg, 16).unwrap());
+//! ```
+//!
+//! # Design decisions
+//!
+//! The dump works on bytes rather than parsed messages so that malformed messages, which are
+//! the ones worth dumping, can be rendered as well. Invalid lengths end the dump of the
+//! enclosing message with a note instead of an error.
+//!
+//! The bytes do not tell the length of the family header or the types of nested attributes, so
+//! the length is passed in and only the top level attribute types are named. Attributes are
+//! shown as nested when `NLA_F_NESTED` is set or their payload is an exact sequence of valid
+//! attributes, which the annotation marks as a guess.
+
+use std::fmt::Write;
+use std::io;
+
+use consts::{alignto, NlType, NlmF, Nlmsg};
+use endian::{self, Endian};
+use err::SerError;
+use nl::Nlmsghdr;
+use value::{NlValue, ToNlValue};
+use {Nl, StreamWriteBuffer};
+
+/// Length of `struct nlmsghdr`
+const NLMSG_HDRLEN: usize = 16;
+/// Length of `struct nlattr`
+const NLA_HDRLEN: usize = 4;
+/// `NLA_F_NESTED`
+const NLA_F_NESTED: u16 = 1 << 15;
+/// `NLA_F_NET_BYTEORDER`
+const NLA_F_NET_BYTEORDER: u16 = 1 << 14;
+/// `NLM_F_CAPPED`, set on errors that do not contain the payload of the request
+const NLM_F_CAPPED: u16 = 0x100;
+/// `NLM_F_ACK_TLVS`, set on errors and acknowledgements carrying extended ACK attributes
+const NLM_F_ACK_TLVS: u16 = 0x200;
+/// Number of bytes on every line of the dump
+const BYTES_PER_LINE: usize = 16;
+
+/// Render the netlink messages in `buf` as an annotated hexdump. `family_hdr_len` is the length
+/// of the header between `struct nlmsghdr` and the attributes, such as 16 for `struct
+/// ifinfomsg`. Message types are named as `T` and top level attribute types as `A`.
+pub fn dump<T, A>(buf: &[u8], family_hdr_len: usize) -> String
+where
+    T: NlType + ToNlValue,
+    A: From<u16> + ToNlValue,
+{
+    let mut dumper = Dumper {
+        buf,
+        out: String::new(),
+    };
+    dumper.msgs::<T, A>(family_hdr_len);
+    dumper.out
+}
+
+/// Serialize `msg` and render it with `dump`
+pub fn dump_msg<A, T, P>(msg: &Nlmsghdr<T, P>, family_hdr_len: usize) -> Result<String, SerError>
+where
+    A: From<u16> + ToNlValue,
+    T: NlType + ToNlValue,
+    P: Nl,
+{
+    let mut mem = StreamWriteBuffer::new_growable(Some(msg.asize()));
+    msg.serialize(&mut mem)?;
+    Ok(dump::<T, A>(mem.as_ref(), family_hdr_len))
+}
+
+/// Name of a constant followed by its value, or only the value if it has no name
+fn name(value: NlValue, raw: u16) -> String {
+    match value {
+        NlValue::Const {
+            name: Some(name), ..
+        } => format!("{} ({})", name, raw),
+        _ => raw.to_string(),
+    }
+}
+
+/// Names of the bits set in `nlmsg_flags`
+fn flag_names(flags: u16, is_ack: bool) -> String {
+    let names = (0..16)
+        .map(|i| 1 << i)
+        .filter(|bit| flags & bit != 0)
+        .map(|bit| match bit {
+            NLM_F_CAPPED if is_ack => "Capped".to_string(),
+            NLM_F_ACK_TLVS if is_ack => "AckTlvs".to_string(),
+            _ => match NlmF::from(bit).to_nl_value() {
+                NlValue::Const {
+                    name: Some(name), ..
+                } => name,
+                _ => format!("{:#x}", bit),
+            },
+        })
+        .collect::<Vec<_>>();
+    if names.is_empty() {
+        format!("{:#06x}", flags)
+    } else {
+        format!("{} ({:#06x})", names.join("|"), flags)
+    }
+}
+
+/// Check whether `bytes` is an exact sequence of valid attributes
+fn looks_nested(bytes: &[u8]) -> bool {
+    let mut offset = 0;
+    while offset + NLA_HDRLEN <= bytes.len() {
+        let len = read_u16(&bytes[offset..]) as usize;
+        if len < NLA_HDRLEN || len > bytes.len() - offset {
+            return false;
+        }
+        if offset + len == bytes.len() {
+            return true;
+        }
+        if offset + alignto(len) > bytes.len() {
+            return false;
+        }
+        offset += alignto(len);
+    }
+    offset == bytes.len()
+}
+
+/// Describe an attribute payload that is not nested
+fn describe(payload: &[u8]) -> String {
+    if let Some((0, text)) = payload.split_last() {
+        if !text.is_empty() && text.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+            return format!("string {:?}", String::from_utf8_lossy(text));
+        }
+    }
+    match payload.len() {
+        1 => format!("u8 {}", payload[0]),
+        2 => format!("u16 {}", read_u16(payload)),
+        4 => format!("u32 {}", read_u32(payload)),
+        8 => {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(payload);
+            let v = match endian::endian() {
+                Endian::Little => u64::from_le_bytes(bytes),
+                Endian::Big => u64::from_be_bytes(bytes),
+            };
+            format!("u64 {}", v)
+        }
+        _ => "payload".to_string(),
+    }
+}
+
+fn read_u16(bytes: &[u8]) -> u16 {
+    let bytes = [bytes[0], bytes[1]];
+    match endian::endian() {
+        Endian::Little => u16::from_le_bytes(bytes),
+        Endian::Big => u16::from_be_bytes(bytes),
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+    match endian::endian() {
+        Endian::Little => u32::from_le_bytes(bytes),
+        Endian::Big => u32::from_be_bytes(bytes),
+    }
+}
+
+/// Accumulates the lines of a dump of `buf`
+struct Dumper<'a> {
+    buf: &'a [u8],
+    out: String,
+}
+
+impl<'a> Dumper<'a> {
+    /// Write `len` bytes from `offset` with the note on the first line, indented by `depth`
+    fn line(&mut self, offset: usize, len: usize, depth: usize, note: &str) {
+        let bytes = &self.buf[offset..offset + len];
+        for (i, chunk) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+            let hex = chunk
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let mut line = format!(
+                "{:04x}  {:<width$}",
+                offset + i * BYTES_PER_LINE,
+                hex,
+                width = BYTES_PER_LINE * 3 - 1
+            );
+            if i == 0 {
+                let _ = write!(line, "  {:indent$}{}", "", note, indent = depth * 2);
+            }
+            self.out.push_str(line.trim_end());
+            self.out.push('\n');
+        }
+    }
+
+    fn msgs<T, A>(&mut self, family_hdr_len: usize)
+    where
+        T: NlType + ToNlValue,
+        A: From<u16> + ToNlValue,
+    {
+        let mut offset = 0;
+        while offset < self.buf.len() {
+            let end = match self.header::<T>(offset, self.buf.len(), 0) {
+                Some(end) => end,
+                None => return,
+            };
+            let nl_type = read_u16(&self.buf[offset + 4..]);
+            let flags = read_u16(&self.buf[offset + 6..]);
+            let start = offset + NLMSG_HDRLEN;
+            if nl_type == u16::from(Nlmsg::Error) {
+                self.error::<T>(start, end, flags);
+            } else if nl_type == u16::from(Nlmsg::Done) && end - start >= 4 {
+                self.line(
+                    start,
+                    4,
+                    1,
+                    &format!("status: {}", read_u32(&self.buf[start..])),
+                );
+                self.attrs::<u16>(start + 4, end, 1);
+            } else {
+                let hdr_len = family_hdr_len.min(end - start);
+                if hdr_len > 0 {
+                    self.line(start, hdr_len, 1, "family header");
+                }
+                self.attrs::<A>((start + alignto(hdr_len)).min(end), end, 1);
+            }
+            offset = alignto(end).min(self.buf.len());
+        }
+    }
+
+    /// Write the fields of the `struct nlmsghdr` at `offset` and return the end of the message,
+    /// or `None` if the header or its length is invalid
+    fn header<T>(&mut self, offset: usize, end: usize, depth: usize) -> Option<usize>
+    where
+        T: NlType + ToNlValue,
+    {
+        if end - offset < NLMSG_HDRLEN {
+            self.line(offset, end - offset, depth, "truncated nlmsghdr");
+            return None;
+        }
+        let len = read_u32(&self.buf[offset..]) as usize;
+        let nl_type = read_u16(&self.buf[offset + 4..]);
+        let flags = read_u16(&self.buf[offset + 6..]);
+        let type_name = if nl_type < 16 {
+            name(Nlmsg::from(nl_type).to_nl_value(), nl_type)
+        } else {
+            name(T::from(nl_type).to_nl_value(), nl_type)
+        };
+        let is_ack = nl_type == u16::from(Nlmsg::Error) || nl_type == u16::from(Nlmsg::Done);
+        self.line(offset, 4, depth, &format!("nlmsg_len: {}", len));
+        self.line(offset + 4, 2, depth, &format!("nlmsg_type: {}", type_name));
+        self.line(
+            offset + 6,
+            2,
+            depth,
+            &format!("nlmsg_flags: {}", flag_names(flags, is_ack)),
+        );
+        self.line(
+            offset + 8,
+            4,
+            depth,
+            &format!("nlmsg_seq: {}", read_u32(&self.buf[offset + 8..])),
+        );
+        self.line(
+            offset + 12,
+            4,
+            depth,
+            &format!("nlmsg_pid: {}", read_u32(&self.buf[offset + 12..])),
+        );
+        if len < NLMSG_HDRLEN || len > end - offset {
+            if end - offset > NLMSG_HDRLEN {
+                self.line(
+                    offset + NLMSG_HDRLEN,
+                    end - offset - NLMSG_HDRLEN,
+                    depth + 1,
+                    &format!("invalid nlmsg_len {}, rest not decoded", len),
+                );
+            }
+            return None;
+        }
+        Some(offset + len)
+    }
+
+    /// Write the payload of an error or acknowledgement from `start` to `end`
+    fn error<T>(&mut self, start: usize, end: usize, flags: u16)
+    where
+        T: NlType + ToNlValue,
+    {
+        if end - start < 4 {
+            self.line(start, end - start, 1, "truncated nlmsgerr");
+            return;
+        }
+        let error = read_u32(&self.buf[start..]) as i32;
+        let note = if error == 0 {
+            "error: 0 (ACK)".to_string()
+        } else {
+            format!(
+                "error: {} ({})",
+                error,
+                io::Error::from_raw_os_error(error.wrapping_neg())
+            )
+        };
+        self.line(start, 4, 1, &note);
+
+        let orig = start + 4;
+        let orig_end = match self.header::<T>(orig, end, 1) {
+            Some(orig_end) => orig_end,
+            None => return,
+        };
+        let mut attrs = orig + NLMSG_HDRLEN;
+        if flags & NLM_F_CAPPED == 0 && orig_end > attrs {
+            self.line(attrs, orig_end - attrs, 2, "request payload");
+            attrs = alignto(orig_end).min(end);
+        }
+        self.attrs::<u16>(attrs, end, 1);
+    }
+
+    /// Write the attributes from `start` to `end`, naming their types as `A`
+    fn attrs<A>(&mut self, start: usize, end: usize, depth: usize)
+    where
+        A: From<u16> + ToNlValue,
+    {
+        let mut offset = start;
+        while offset < end {
+            if end - offset < NLA_HDRLEN {
+                self.line(offset, end - offset, depth, "trailing bytes");
+                return;
+            }
+            let len = read_u16(&self.buf[offset..]) as usize;
+            let nla_type = read_u16(&self.buf[offset + 2..]);
+            if len < NLA_HDRLEN || len > end - offset {
+                self.line(
+                    offset,
+                    end - offset,
+                    depth,
+                    &format!("invalid nla_len {}, rest not decoded", len),
+                );
+                return;
+            }
+            let payload = &self.buf[offset + NLA_HDRLEN..offset + len];
+            let nested = nla_type & NLA_F_NESTED != 0;
+            let guessed = !nested && payload.len() > 4 && looks_nested(payload);
+            let raw_type = nla_type & !(NLA_F_NESTED | NLA_F_NET_BYTEORDER);
+            let mut note = format!(
+                "nla_len: {}, nla_type: {}",
+                len,
+                name(A::from(raw_type).to_nl_value(), raw_type)
+            );
+            if nested {
+                note.push_str(", nested");
+            } else if guessed {
+                note.push_str(", nested (guessed)");
+            }
+            if nla_type & NLA_F_NET_BYTEORDER != 0 {
+                note.push_str(", net byte order");
+            }
+            self.line(offset, NLA_HDRLEN, depth, &note);
+            if nested || guessed {
+                self.attrs::<u16>(offset + NLA_HDRLEN, offset + len, depth + 1);
+            } else if !payload.is_empty() {
+                let note = describe(payload);
+                self.line(offset + NLA_HDRLEN, payload.len(), depth + 1, &note);
+            }
+            let next = alignto(offset + len).min(end);
+            if next > offset + len {
+                self.line(offset + len, next - offset - len, depth + 1, "padding");
+            }
+            offset = next;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use consts::{Ifla, Rtm};
+    use vectors::{self, VECTORS_DIR};
+
+    #[test]
+    fn test_dump() {
+        let vectors = vectors::load(VECTORS_DIR).unwrap();
+        let bytes = |name: &str| {
+            vectors
+                .iter()
+                .find(|v| v.name == name)
+                .map(|v| v.bytes.clone())
+                .unwrap()
+        };
+
+        let ack = endian::with_endian(Endian::Little, || dump::<Nlmsg, u16>(&bytes("ack"), 0));
+        let lines = ack.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[..3],
+            [
+                "0000  24 00 00 00                                      nlmsg_len: 36",
+                "0004  02 00                                            nlmsg_type: Error (2)",
+                "0006  00 01                                            nlmsg_flags: Capped (0x0100)",
+            ]
+        );
+        assert!(lines[5].ends_with("  error: 0 (ACK)"));
+        assert!(lines[6].ends_with("    nlmsg_len: 32"));
+        assert_eq!(lines.len(), 11);
+
+        let link = endian::with_endian(Endian::Little, || dump::<Rtm, Ifla>(&bytes("newlink"), 16));
+        assert!(link.contains("  nlmsg_type: Newlink (16)\n"));
+        assert!(link.contains("  family header\n"));
+        assert!(link.contains("  nla_len: 7, nla_type: Ifname (3)\n"));
+        assert!(link.contains("    string \"lo\"\n"));
+        assert!(link.contains("    padding\n"));
+
+        // A length running past the end of the buffer stops the dump with a note
+        let mut truncated = bytes("newlink");
+        truncated.truncate(40);
+        let out = endian::with_endian(Endian::Little, || dump::<Rtm, Ifla>(&truncated, 16));
+        assert!(out.contains("invalid nlmsg_len 1468, rest not decoded"));
+
+        let msg = Nlmsghdr::new(None, Rtm::Getlink, vec![], None, None, vec![0u8; 16]);
+        assert!(dump_msg::<Ifla, _, _>(&msg, 16)
+            .unwrap()
+            .contains("  nlmsg_type: Getlink (18)\n"));
+    }
+
+    #[test]
+    fn test_looks_nested_short_payload() {
+        // A MAC address whose first two bytes read as an attribute length of 5
+        let mac = [0x05, 0x00, 0x5e, 0x00, 0x53, 0x01];
+        assert!(!endian::with_endian(Endian::Little, || looks_nested(&mac)));
+    }
+}
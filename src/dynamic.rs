@@ -0,0 +1,195 @@
+//! This module provides object safe counterparts of the `Nl` trait for programs that only know
+//! at runtime which payload types they handle, such as plugin systems.
+//!
+//! Any type implementing `Nl` and `Debug` can be used as a `Box<dyn DynNl>` and downcast back to
+//! its concrete type. Because deserialization produces a value, it cannot be part of an object
+//! safe trait. Instead, a `DynRegistry` maps message types to deserialization functions and
+//! parses messages into `Nlmsghdr<u16, Box<dyn DynNl>>`, which can be serialized and sent like
+//! any other message.
+//!
+//! # Design decisions
+//!
+//! `Box<dyn DynNl>` implements `Nl` so that it can be used with the rest of the library, but its
+//! `deserialize` method always fails as the concrete type cannot be known from the bytes alone.
+//! Message types missing from a `DynRegistry` keep their payload as `Vec<u8>`.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use buffering::StreamReadBuffer;
+
+use err::{DeError, SerError};
+use nl::Nlmsghdr;
+use {Buffer, BufferMut, Nl};
+
+/// Object safe counterpart of `Nl`, implemented for every type that implements `Nl` and `Debug`
+pub trait DynNl: Debug {
+    /// Serialize the value as with `Nl::serialize`
+    fn dyn_serialize(&self, mem: &mut dyn BufferMut) -> Result<(), SerError>;
+
+    /// Size of the value as with `Nl::size`
+    fn dyn_size(&self) -> usize;
+
+    /// Size of the value aligned to word size as with `Nl::asize`
+    fn dyn_asize(&self) -> usize;
+
+    /// The value as `Any` for downcasting
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T> DynNl for T
+where
+    T: Nl + Debug + 'static,
+{
+    fn dyn_serialize(&self, mut mem: &mut dyn BufferMut) -> Result<(), SerError> {
+        self.serialize(&mut mem)
+    }
+
+    fn dyn_size(&self) -> usize {
+        self.size()
+    }
+
+    fn dyn_asize(&self) -> usize {
+        self.asize()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl dyn DynNl {
+    /// Return a reference to the concrete value if it is of type `T`
+    pub fn downcast_ref<T>(&self) -> Option<&T>
+    where
+        T: 'static,
+    {
+        self.as_any().downcast_ref()
+    }
+}
+
+impl Nl for Box<dyn DynNl> {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.as_ref().dyn_serialize(mem)
+    }
+
+    fn deserialize<B>(_: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        Err(DeError::new(
+            "Boxed payloads can only be deserialized with a DynRegistry",
+        ))
+    }
+
+    fn size(&self) -> usize {
+        self.as_ref().dyn_size()
+    }
+
+    fn asize(&self) -> usize {
+        self.as_ref().dyn_asize()
+    }
+}
+
+/// Function deserializing a whole payload into a boxed value
+pub type DynDeserializer = fn(&[u8]) -> Result<Box<dyn DynNl>, DeError>;
+
+/// Deserialize a whole payload as `T`, for use as a `DynDeserializer`
+pub fn deserialize_dyn<T>(payload: &[u8]) -> Result<Box<dyn DynNl>, DeError>
+where
+    T: Nl + Debug + 'static,
+{
+    let mut mem = StreamReadBuffer::new(payload);
+    mem.set_size_hint(payload.len());
+    Ok(Box::new(T::deserialize(&mut mem)?))
+}
+
+/// Mapping from netlink message types to the payload types they carry
+#[derive(Default)]
+pub struct DynRegistry {
+    deserializers: HashMap<u16, DynDeserializer>,
+}
+
+impl DynRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        DynRegistry::default()
+    }
+
+    /// Parse payloads of messages of type `nl_type` as `T`
+    pub fn register<T>(&mut self, nl_type: u16) -> &mut Self
+    where
+        T: Nl + Debug + 'static,
+    {
+        self.register_fn(nl_type, deserialize_dyn::<T>)
+    }
+
+    /// Parse payloads of messages of type `nl_type` with a custom function
+    pub fn register_fn(&mut self, nl_type: u16, f: DynDeserializer) -> &mut Self {
+        self.deserializers.insert(nl_type, f);
+        self
+    }
+
+    /// Parse the message at the start of `mem` with the payload type registered for its message
+    /// type
+    pub fn deserialize<B>(&self, mem: &mut B) -> Result<Nlmsghdr<u16, Box<dyn DynNl>>, DeError>
+    where
+        B: Buffer,
+    {
+        let raw = Nlmsghdr::<u16, Vec<u8>>::deserialize(mem)?;
+        let nl_payload = match self.deserializers.get(&raw.nl_type) {
+            Some(f) => f(&raw.nl_payload)?,
+            None => Box::new(raw.nl_payload),
+        };
+        Ok(Nlmsghdr {
+            nl_len: raw.nl_len,
+            nl_type: raw.nl_type,
+            nl_flags: raw.nl_flags,
+            nl_seq: raw.nl_seq,
+            nl_pid: raw.nl_pid,
+            nl_payload,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use buffering::StreamWriteBuffer;
+    use consts::{CtrlAttr, CtrlCmd, GenlId, NlmF};
+    use genl::{getfamily_request, Genlmsghdr};
+
+    #[test]
+    fn test_dyn_registry() {
+        let msg = getfamily_request("nlctrl", vec![NlmF::Request]);
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        msg.serialize(&mut mem).unwrap();
+        let bytes = mem.as_ref().to_vec();
+
+        let mut registry = DynRegistry::new();
+        registry.register::<Genlmsghdr<CtrlCmd, CtrlAttr>>(GenlId::Ctrl.into());
+        let parsed = registry
+            .deserialize(&mut StreamReadBuffer::new(&bytes))
+            .unwrap();
+        let genlhdr = parsed
+            .nl_payload
+            .downcast_ref::<Genlmsghdr<CtrlCmd, CtrlAttr>>()
+            .unwrap();
+        assert_eq!(genlhdr.cmd, CtrlCmd::Getfamily);
+
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        parsed.serialize(&mut mem).unwrap();
+        assert_eq!(mem.as_ref(), bytes.as_slice());
+
+        // Unregistered message types keep their raw payload
+        let parsed = DynRegistry::new()
+            .deserialize(&mut StreamReadBuffer::new(&bytes))
+            .unwrap();
+        assert!(parsed.nl_payload.downcast_ref::<Vec<u8>>().is_some());
+    }
+}
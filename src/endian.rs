@@ -0,0 +1,167 @@
+//! This module selects the byte order of integers while serializing and deserializing, so that
+//! netlink captures taken on a machine of the opposite endianness can be parsed and produced.
+//!
+//! Netlink itself always uses the native byte order of the host, which is the default. Running
+//! code within `with_endian` serializes and deserializes the `u16`, `u32`, `i32` and `u64`
+//! fields of all messages, headers and attributes in the given byte order instead. Fields that
+//! the kernel sends in network byte order, such as `BeU16`, are not affected.
+//!
+//! ```
+//! use neli::endian::{with_endian, Endian};
+//! use neli::{Nl, StreamReadBuffer};
+//!
+//! let v = with_endian(Endian::Big, || {
+//!     u32::deserialize(&mut StreamReadBuffer::new(&[0, 0, 0, 1]))
+//! });
+//! assert_eq!(v.unwrap(), 1);
+//! ```
+//!
+//! # Design decisions
+//!
+//! `Nl::serialize` and `Nl::deserialize` have no parameter to pass the byte order through, so it
+//! is set per thread for the duration of a closure, like the nesting depth tracked by `limits`.
+//! Code in `neli` that reads or patches header fields of serialized messages without going
+//! through `Nl`, such as splitting dump datagrams or the C bindings, uses the same byte order
+//! through `read_u16` and friends. `parse_dump_par` runs its rayon workers in the byte order of
+//! the calling thread. Other threads, such as the one receiving for `NlRouter`, are not affected.
+//!
+//! Every integer looks up the byte order, so the thread local is only consulted while a
+//! non-native byte order is selected on some thread. Otherwise the lookup is a single atomic load.
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+use scoped;
+
+thread_local! {
+    static ENDIAN: Cell<Endian> = const { Cell::new(Endian::NATIVE) };
+}
+
+/// Number of `with_endian` calls selecting a non-native byte order that are running on any thread
+static NON_NATIVE: AtomicUsize = AtomicUsize::new(0);
+
+/// Byte order of integers in serialized messages
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Endian {
+    /// Least significant byte first
+    Little,
+    /// Most significant byte first
+    Big,
+}
+
+impl Endian {
+    /// Byte order of the host, used by netlink
+    #[cfg(target_endian = "little")]
+    pub const NATIVE: Endian = Endian::Little;
+    /// Byte order of the host, used by netlink
+    #[cfg(target_endian = "big")]
+    pub const NATIVE: Endian = Endian::Big;
+}
+
+impl Default for Endian {
+    fn default() -> Self {
+        Endian::NATIVE
+    }
+}
+
+/// Counts a running `with_endian` call selecting a non-native byte order until dropped,
+/// including when unwinding
+struct NonNativeGuard(());
+
+impl NonNativeGuard {
+    fn enter() -> Self {
+        NON_NATIVE.fetch_add(1, Ordering::Relaxed);
+        NonNativeGuard(())
+    }
+}
+
+impl Drop for NonNativeGuard {
+    fn drop(&mut self) {
+        NON_NATIVE.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Run `f` with integers serialized and deserialized in the byte order `endian` on the current
+/// thread
+pub fn with_endian<F, R>(endian: Endian, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let _guard = if endian != Endian::NATIVE {
+        Some(NonNativeGuard::enter())
+    } else {
+        None
+    };
+    scoped::with_scoped(&ENDIAN, endian, f)
+}
+
+/// Byte order currently used for integers on this thread
+pub fn endian() -> Endian {
+    // Every thread is in the native byte order unless some thread selected another one
+    if NON_NATIVE.load(Ordering::Relaxed) == 0 {
+        return Endian::NATIVE;
+    }
+    ENDIAN.with(|endian| endian.get())
+}
+
+macro_rules! impl_raw_access {
+    ( $( $name:ident, $buf:ty, $ret:ty $(, $arg:ident: $ty:ty)? );* ) => {
+        $(
+            pub(crate) fn $name(buf: $buf $(, $arg: $ty)?) -> $ret {
+                match endian() {
+                    Endian::Little => LittleEndian::$name(buf $(, $arg)?),
+                    Endian::Big => BigEndian::$name(buf $(, $arg)?),
+                }
+            }
+        )*
+    };
+}
+
+// Access to header fields of serialized messages in the byte order of this thread
+impl_raw_access!(
+    read_u16, &[u8], u16;
+    read_u32, &[u8], u32;
+    read_i32, &[u8], i32;
+    write_u16, &mut [u8], (), v: u16;
+    write_u32, &mut [u8], (), v: u32
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use buffering::{StreamReadBuffer, StreamWriteBuffer};
+
+    use consts::{NlmF, Nlmsg};
+    use nl::{NlEmpty, Nlmsghdr};
+    use Nl;
+
+    #[test]
+    fn test_with_endian() {
+        let bytes = [
+            0, 0, 0, 16, 0, 3, 0, 1, 0, 0, 0, 5, 0, 0, 0, 0x2a, //
+        ];
+        let msg = with_endian(Endian::Big, || {
+            assert_eq!(endian(), Endian::Big);
+            with_endian(Endian::Little, || assert_eq!(endian(), Endian::Little));
+            assert_eq!(endian(), Endian::Big);
+            Nlmsghdr::<Nlmsg, NlEmpty>::deserialize(&mut StreamReadBuffer::new(&bytes[..]))
+        })
+        .unwrap();
+        assert_eq!(endian(), Endian::NATIVE);
+        assert_eq!(msg.nl_len, 16);
+        assert_eq!(msg.nl_type, Nlmsg::Done);
+        assert_eq!(msg.nl_flags, vec![NlmF::Request]);
+        assert_eq!(msg.nl_seq, 5);
+        assert_eq!(msg.nl_pid, 0x2a);
+
+        let mut buf = vec![0; 16];
+        with_endian(Endian::Big, || {
+            msg.serialize(&mut StreamWriteBuffer::new_sized(&mut buf))
+        })
+        .unwrap();
+        assert_eq!(buf, bytes);
+    }
+}
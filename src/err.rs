@@ -10,9 +10,16 @@
 //!
 //! # Design decisions
 //!
-//! `NlError` can either be created with a custom `String` message or using three variants, one for
-//! no ACK received, one for a bad PID that does not correspond to that assigned to the socket, or
-//! one for a bad sequence number that does not correspond to the request sequence number.
+//! `NlError` can either be created with a custom `String` message or using four variants, one for
+//! no ACK received, one for a bad PID that does not correspond to that assigned to the socket, one
+//! for a bad sequence number that does not correspond to the request sequence number, or one for
+//! a datagram that was larger than the buffer it was received into.
+//!
+//! `DeError::Context` wraps an error with the path of structures, fields and attributes that were
+//! being parsed when it occurred, such as `Nlmsghdr.nl_payload > Genlmsghdr.attrs > attribute 2`.
+//! Each level adds its part as the error propagates, so that errors deep in nested messages can
+//! be traced back to the data that caused them. `DeError::root` returns the error without the
+//! path.
 
 use std;
 use std::error::Error;
@@ -21,12 +28,12 @@ use std::io;
 use std::str;
 use std::string;
 
-use buffering::{StreamReadBuffer, StreamWriteBuffer};
 use libc;
 
 use consts::NlType;
 use nl::{NlEmpty, Nlmsghdr};
-use Nl;
+use value::{NlValue, ToNlValue};
+use {Buffer, BufferMut, Nl};
 
 macro_rules! try_err_compat {
     ( $err_name:ident, $( $from_err_name:path ),* ) => {
@@ -53,20 +60,24 @@ impl<T> Nl for Nlmsgerr<T>
 where
     T: NlType,
 {
-    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
         self.error.serialize(mem)?;
-        self.nlmsg.serialize(mem)?;
+        self.nlmsg.serialize_unchecked(mem)?;
         self.pad(mem)?;
         Ok(())
     }
 
-    fn deserialize<B>(mem: &mut StreamReadBuffer<B>) -> Result<Self, DeError>
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
     where
-        B: AsRef<[u8]>,
+        B: Buffer,
     {
         let nlmsg = Nlmsgerr {
             error: libc::c_int::deserialize(mem)?,
-            nlmsg: Nlmsghdr::<T, NlEmpty>::deserialize(mem)?,
+            nlmsg: Nlmsghdr::<T, NlEmpty>::deserialize(mem)
+                .map_err(|e| e.context("Nlmsgerr.nlmsg"))?,
         };
         nlmsg.strip(mem)?;
         Ok(nlmsg)
@@ -77,6 +88,18 @@ where
     }
 }
 
+impl<T> ToNlValue for Nlmsgerr<T>
+where
+    T: ToNlValue,
+{
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("error", self.error.to_nl_value()),
+            ("msg", self.nlmsg.to_nl_value()),
+        ])
+    }
+}
+
 /// Netlink protocol error
 #[derive(Debug)]
 pub enum NlError {
@@ -88,6 +111,20 @@ pub enum NlError {
     BadSeq,
     /// Incorrect PID socket identifier in received message
     BadPid,
+    /// The received datagram did not fit in the buffer provided for it
+    Truncated,
+    /// The generic netlink command of the response is not one the request expects
+    BadCmd {
+        /// Commands accepted for the response
+        expected: Vec<u8>,
+        /// Command found in the response
+        got: u8,
+    },
+    /// I/O error kept as such for callers that handle some kinds of it, such as timeouts
+    Io(io::Error),
+    /// Error code of an `NLMSG_ERROR` message, as a positive `errno` value such as
+    /// `libc::ENOENT`
+    Errno(libc::c_int),
 }
 
 try_err_compat!(NlError, io::Error, SerError, DeError);
@@ -97,6 +134,11 @@ impl NlError {
     pub fn new(s: &str) -> Self {
         NlError::Msg(s.to_string())
     }
+
+    /// Create an error from the negative error code of an `NLMSG_ERROR` message
+    pub fn from_nlmsgerr(error: libc::c_int) -> Self {
+        NlError::Errno(error.wrapping_neg())
+    }
 }
 
 /// Netlink protocol error
@@ -107,6 +149,16 @@ impl Display for NlError {
             NlError::NoAck => "No ack received",
             NlError::BadSeq => "Sequence number does not match the request",
             NlError::BadPid => "PID does not match the socket",
+            NlError::Truncated => "Message was truncated to fit the receive buffer",
+            NlError::BadCmd { ref expected, got } => {
+                return write!(
+                    f,
+                    "Generic netlink command {} does not match any of {:?}",
+                    got, expected
+                )
+            }
+            NlError::Io(ref e) => return write!(f, "{}", e),
+            NlError::Errno(errno) => return write!(f, "{}", io::Error::from_raw_os_error(errno)),
         };
         write!(f, "{}", msg)
     }
@@ -119,6 +171,17 @@ impl Error for NlError {
             NlError::NoAck => "No ack received",
             NlError::BadSeq => "Sequence number does not match the request",
             NlError::BadPid => "PID does not match the socket",
+            NlError::Truncated => "Message was truncated to fit the receive buffer",
+            NlError::BadCmd { .. } => "Generic netlink command does not match the request",
+            NlError::Io(_) => "I/O error",
+            NlError::Errno(_) => "Error returned by the kernel",
+        }
+    }
+
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            NlError::Io(ref e) => Some(e),
+            _ => None,
         }
     }
 }
@@ -150,15 +213,88 @@ impl Error for SerError {
 
 /// Deserialization error
 #[derive(Debug)]
-pub struct DeError(String);
+pub enum DeError {
+    /// Error with a custom message
+    Msg(String),
+    /// A length field or size hint is smaller than the data it must cover
+    InvalidLength {
+        /// Name of the length field or `size_hint` for the space left in the enclosing structure
+        field: &'static str,
+        /// Minimum valid length in bytes
+        expected: usize,
+        /// Length found in the data
+        got: usize,
+    },
+    /// A message exceeds one of the `ParseLimits`
+    LimitExceeded {
+        /// Name of the limit
+        limit: &'static str,
+        /// Configured maximum
+        max: usize,
+        /// Value found in the message
+        got: usize,
+    },
+    /// An error occurred while parsing a part of a message
+    Context {
+        /// Parts being parsed when the error occurred, outermost first
+        path: Vec<String>,
+        /// Error that occurred
+        error: Box<DeError>,
+    },
+}
 
 impl DeError {
     /// Create new error from `&str`
     pub fn new(s: &str) -> Self {
-        DeError(s.to_string())
+        DeError::Msg(s.to_string())
+    }
+
+    /// Record that the error occurred while parsing `part` of a message
+    pub fn context<S>(self, part: S) -> Self
+    where
+        S: ToString,
+    {
+        match self {
+            DeError::Context { mut path, error } => {
+                path.insert(0, part.to_string());
+                DeError::Context { path, error }
+            }
+            error => DeError::Context {
+                path: vec![part.to_string()],
+                error: Box::new(error),
+            },
+        }
+    }
+
+    /// The error without the context of where it occurred
+    pub fn root(&self) -> &DeError {
+        match *self {
+            DeError::Context { ref error, .. } => error,
+            ref error => error,
+        }
+    }
+}
+
+/// Name of the type `T` without its module path, for error contexts
+pub(crate) fn short_type_name<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    let base = name.split('<').next().unwrap_or(name);
+    match base.rfind("::") {
+        Some(i) => &base[i + 2..],
+        None => base,
     }
 }
 
+/// Subtract the `expected` number of bytes covered by a length field from its value `got`,
+/// returning `DeError::InvalidLength` instead of underflowing
+pub(crate) fn sub_len(field: &'static str, got: usize, expected: usize) -> Result<usize, DeError> {
+    got.checked_sub(expected).ok_or(DeError::InvalidLength {
+        field,
+        expected,
+        got,
+    })
+}
+
 try_err_compat!(
     DeError,
     io::Error,
@@ -169,12 +305,39 @@ try_err_compat!(
 
 impl Display for DeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.0)
+        match *self {
+            DeError::Msg(ref msg) => write!(f, "{}", msg),
+            DeError::InvalidLength {
+                field,
+                expected,
+                got,
+            } => write!(
+                f,
+                "Invalid {}: expected at least {} bytes, got {}",
+                field, expected, got
+            ),
+            DeError::LimitExceeded { limit, max, got } => {
+                write!(
+                    f,
+                    "Parse limit exceeded: {} is {}, maximum {}",
+                    limit, got, max
+                )
+            }
+            DeError::Context {
+                ref path,
+                ref error,
+            } => write!(f, "{} (while parsing {})", error, path.join(" > ")),
+        }
     }
 }
 
 impl Error for DeError {
     fn description(&self) -> &str {
-        self.0.as_str()
+        match *self {
+            DeError::Msg(ref msg) => msg.as_str(),
+            DeError::InvalidLength { .. } => "Length field is smaller than the data it covers",
+            DeError::LimitExceeded { .. } => "Message exceeds a parse limit",
+            DeError::Context { .. } => "Error while parsing a part of a message",
+        }
     }
 }
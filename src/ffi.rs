@@ -0,0 +1,322 @@
+//! This module exposes parsing and serialization of netlink messages and attributes through a C
+//! ABI so that existing C programs can use `neli` to handle untrusted netlink data while keeping
+//! their own socket code.
+//!
+//! Enable the `ffi` feature and build a shared library with
+//! `cargo rustc --release --features ffi --crate-type cdylib`. The matching declarations are in
+//! `include/neli.h`.
+//!
+//! # Design decisions
+//!
+//! * Parsing functions do not allocate memory that C code would need to free. Header fields are
+//!   copied into caller-provided structs and payloads are returned as pointers into the input
+//!   buffer.
+//! * All functions return a negative `errno` value on failure, like the kernel and libmnl do. On
+//!   success, they return the number of bytes consumed or written including padding so that
+//!   callers can step through buffers holding several messages or attributes.
+//! * Validation goes through the same `Nl` implementations used by the Rust API.
+//! * Panics are caught before they unwind into C code, which is undefined behavior, and reported
+//!   as `-EIO`.
+
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::slice;
+
+use buffering::{StreamReadBuffer, StreamWriteBuffer};
+use libc;
+
+use consts::{alignto, NlmF};
+use endian;
+use nl::Nlmsghdr;
+use nlattr::Nlattr;
+use Nl;
+
+/// C representation of `struct nlmsghdr`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct NeliNlmsghdr {
+    /// Length of the message including the header
+    pub nlmsg_len: u32,
+    /// Message type
+    pub nlmsg_type: u16,
+    /// Bitmask of `NLM_F_*` flags
+    pub nlmsg_flags: u16,
+    /// Sequence number
+    pub nlmsg_seq: u32,
+    /// Port ID of the sender
+    pub nlmsg_pid: u32,
+}
+
+/// C representation of `struct nlattr`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct NeliNlattr {
+    /// Length of the attribute including the header
+    pub nla_len: u16,
+    /// Attribute type
+    pub nla_type: u16,
+}
+
+fn flags_to_bits(flags: &[NlmF]) -> u16 {
+    flags
+        .iter()
+        .fold(0, |acc, flag| acc | u16::from(flag.clone()))
+}
+
+fn bits_to_flags(bits: u16) -> Vec<NlmF> {
+    (0..16)
+        .map(|i| 1 << i)
+        .filter(|bit| bits & bit == *bit)
+        .map(NlmF::from)
+        .collect()
+}
+
+/// Run the body of an exported function, returning `-EIO` if it panics instead of unwinding into
+/// the C caller
+fn catch_panic<F>(f: F) -> isize
+where
+    F: FnOnce() -> isize,
+{
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(-(libc::EIO as isize))
+}
+
+/// Parse the netlink message at the start of `buf`. The header is copied into `hdr` and
+/// `payload` and `payload_len` are set to the payload within `buf`. Returns the number of bytes
+/// the message occupies in `buf` including padding.
+///
+/// # Safety
+///
+/// `buf` must be valid for reads of `len` bytes and `hdr`, `payload` and `payload_len` must be
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn neli_nlmsg_parse(
+    buf: *const u8,
+    len: usize,
+    hdr: *mut NeliNlmsghdr,
+    payload: *mut *const u8,
+    payload_len: *mut usize,
+) -> isize {
+    catch_panic(|| {
+        if buf.is_null() || hdr.is_null() || payload.is_null() || payload_len.is_null() {
+            return -(libc::EINVAL as isize);
+        }
+        let input = slice::from_raw_parts(buf, len);
+        if len < mem::size_of::<NeliNlmsghdr>() {
+            return -(libc::EINVAL as isize);
+        }
+        let nl_len = endian::read_u32(input) as usize;
+        if nl_len > len {
+            return -(libc::EINVAL as isize);
+        }
+        let consumed = alignto(nl_len).min(len);
+        let msg = match Nlmsghdr::<u16, Vec<u8>>::deserialize(&mut StreamReadBuffer::new(
+            &input[..consumed],
+        )) {
+            Ok(msg) => msg,
+            Err(_) => return -(libc::EINVAL as isize),
+        };
+        *hdr = NeliNlmsghdr {
+            nlmsg_len: msg.nl_len,
+            nlmsg_type: msg.nl_type,
+            nlmsg_flags: flags_to_bits(&msg.nl_flags),
+            nlmsg_seq: msg.nl_seq,
+            nlmsg_pid: msg.nl_pid,
+        };
+        *payload = buf.add(mem::size_of::<NeliNlmsghdr>());
+        *payload_len = msg.nl_payload.len();
+        consumed as isize
+    })
+}
+
+/// Serialize a netlink message with the header fields from `hdr` and the given payload into
+/// `buf`. `nlmsg_len` in `hdr` is ignored and computed from the payload length. Returns the
+/// number of bytes written including padding or `-ENOSPC` if `buf` is too small.
+///
+/// # Safety
+///
+/// `hdr` must be valid for reads, `payload` must be valid for reads of `payload_len` bytes and
+/// `buf` must be valid for writes of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn neli_nlmsg_serialize(
+    hdr: *const NeliNlmsghdr,
+    payload: *const u8,
+    payload_len: usize,
+    buf: *mut u8,
+    len: usize,
+) -> isize {
+    catch_panic(|| {
+        if hdr.is_null() || buf.is_null() || (payload.is_null() && payload_len > 0) {
+            return -(libc::EINVAL as isize);
+        }
+        if payload_len > u32::MAX as usize - mem::size_of::<NeliNlmsghdr>() {
+            return -(libc::EINVAL as isize);
+        }
+        let hdr = &*hdr;
+        let payload = if payload_len > 0 {
+            slice::from_raw_parts(payload, payload_len).to_vec()
+        } else {
+            Vec::new()
+        };
+        let msg = Nlmsghdr::new(
+            None,
+            hdr.nlmsg_type,
+            bits_to_flags(hdr.nlmsg_flags),
+            Some(hdr.nlmsg_seq),
+            Some(hdr.nlmsg_pid),
+            payload,
+        );
+        if msg.asize() > len {
+            return -(libc::ENOSPC as isize);
+        }
+        let mut mem = StreamWriteBuffer::new_sized(slice::from_raw_parts_mut(buf, len));
+        match msg.serialize(&mut mem) {
+            Ok(()) => msg.asize() as isize,
+            Err(_) => -(libc::EINVAL as isize),
+        }
+    })
+}
+
+/// Parse the attribute at the start of `buf`, which usually points to attributes in the payload
+/// returned by `neli_nlmsg_parse` after any family-specific header. The attribute header is
+/// copied into `attr` and `payload` and `payload_len` are set to the attribute payload within
+/// `buf`. Returns the number of bytes the attribute occupies in `buf` including padding, or 0 if
+/// `len` is 0.
+///
+/// # Safety
+///
+/// `buf` must be valid for reads of `len` bytes and `attr`, `payload` and `payload_len` must be
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn neli_attr_parse(
+    buf: *const u8,
+    len: usize,
+    attr: *mut NeliNlattr,
+    payload: *mut *const u8,
+    payload_len: *mut usize,
+) -> isize {
+    catch_panic(|| {
+        if len == 0 {
+            return 0;
+        }
+        if buf.is_null() || attr.is_null() || payload.is_null() || payload_len.is_null() {
+            return -(libc::EINVAL as isize);
+        }
+        let input = slice::from_raw_parts(buf, len);
+        if len < mem::size_of::<NeliNlattr>() {
+            return -(libc::EINVAL as isize);
+        }
+        let nla_len = endian::read_u16(input) as usize;
+        if nla_len < mem::size_of::<NeliNlattr>() || nla_len > len {
+            return -(libc::EINVAL as isize);
+        }
+        let consumed = alignto(nla_len).min(len);
+        let nla = match Nlattr::<u16, Vec<u8>>::deserialize(&mut StreamReadBuffer::new(
+            &input[..consumed],
+        )) {
+            Ok(nla) => nla,
+            Err(_) => return -(libc::EINVAL as isize),
+        };
+        *attr = NeliNlattr {
+            nla_len: nla.nla_len,
+            nla_type: nla.nla_type,
+        };
+        *payload = buf.add(mem::size_of::<NeliNlattr>());
+        *payload_len = nla.payload.len();
+        consumed as isize
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::ptr;
+
+    #[test]
+    fn test_ffi_roundtrip() {
+        let attrs = [8u8, 0, 1, 0, 0x10, 0, 0, 0, 5, 0, 2, 0, b'a', 0, 0, 0];
+        let hdr = NeliNlmsghdr {
+            nlmsg_len: 0,
+            nlmsg_type: 0x10,
+            nlmsg_flags: (libc::NLM_F_REQUEST | libc::NLM_F_ACK) as u16,
+            nlmsg_seq: 7,
+            nlmsg_pid: 0,
+        };
+        let mut buf = [0u8; 64];
+        let written = unsafe {
+            neli_nlmsg_serialize(&hdr, attrs.as_ptr(), attrs.len(), buf.as_mut_ptr(), 64)
+        };
+        assert_eq!(written, 32);
+        assert_eq!(
+            unsafe {
+                neli_nlmsg_serialize(&hdr, attrs.as_ptr(), attrs.len(), buf.as_mut_ptr(), 31)
+            },
+            -(libc::ENOSPC as isize)
+        );
+
+        let mut parsed = NeliNlmsghdr::default();
+        let mut payload = ptr::null();
+        let mut payload_len = 0;
+        let consumed = unsafe {
+            neli_nlmsg_parse(
+                buf.as_ptr(),
+                written as usize,
+                &mut parsed,
+                &mut payload,
+                &mut payload_len,
+            )
+        };
+        assert_eq!(consumed, 32);
+        assert_eq!(
+            parsed,
+            NeliNlmsghdr {
+                nlmsg_len: 32,
+                ..hdr
+            }
+        );
+        assert_eq!(payload_len, attrs.len());
+
+        let mut types = Vec::new();
+        let mut offset = 0;
+        loop {
+            let mut attr = NeliNlattr::default();
+            let mut attr_payload = ptr::null();
+            let mut attr_payload_len = 0;
+            let consumed = unsafe {
+                neli_attr_parse(
+                    payload.add(offset),
+                    payload_len - offset,
+                    &mut attr,
+                    &mut attr_payload,
+                    &mut attr_payload_len,
+                )
+            };
+            assert!(consumed >= 0);
+            if consumed == 0 {
+                break;
+            }
+            types.push((attr.nla_type, attr_payload_len));
+            offset += consumed as usize;
+        }
+        assert_eq!(types, vec![(1, 4), (2, 1)]);
+
+        // A length field pointing past the end of the buffer is rejected
+        assert_eq!(
+            unsafe {
+                neli_nlmsg_parse(
+                    buf.as_ptr(),
+                    16,
+                    &mut parsed,
+                    &mut payload,
+                    &mut payload_len,
+                )
+            },
+            -(libc::EINVAL as isize)
+        );
+
+        assert_eq!(
+            catch_panic(|| panic!("Unwinding into C")),
+            -(libc::EIO as isize)
+        );
+    }
+}
@@ -0,0 +1,384 @@
+//! This module provides payload types with a capacity fixed at compile time for programs that
+//! must not allocate memory once they are running, such as real-time control loops.
+//!
+//! `FixedBuf<N>` is a byte payload that holds at most `N` bytes inline and `[u8; N]` is a payload
+//! of exactly `N` bytes. Both implement `Nl` and can be used as the payload of an `Nlmsghdr`.
+//! `MessagePool` hands out buffers allocated up front so that messages can be serialized and
+//! received without touching the allocator. A reference to a `MessagePool` is an `NlAlloc`, so
+//! it can be passed to `NlSocket::send_nl_in` and `NlSocket::recv_in`.
+//!
+//! # Design decisions
+//!
+//! Payloads that do not fit are reported as errors instead of being truncated. Attributes and
+//! header flags are still stored in `Vec`s, so messages parsed with `Nlattr` payloads or with
+//! flags set will allocate. Bounded message types should carry their payload as a `FixedBuf` or
+//! an array and parse it on demand.
+
+use std::fmt::{self, Debug};
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+
+use buffering::StreamWriteBuffer;
+
+use err::{DeError, SerError};
+use storage::{NlAlloc, NlStorage};
+use value::{NlValue, ToNlValue};
+use {Buffer, BufferMut, Nl};
+
+/// Byte payload with inline storage for at most `N` bytes
+#[derive(Clone)]
+pub struct FixedBuf<const N: usize> {
+    len: usize,
+    data: [u8; N],
+}
+
+impl<const N: usize> FixedBuf<N> {
+    /// Create an empty buffer
+    pub fn new() -> Self {
+        FixedBuf {
+            len: 0,
+            data: [0; N],
+        }
+    }
+
+    /// Create a buffer holding a copy of `bytes`
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, SerError> {
+        let mut buf = FixedBuf::new();
+        buf.extend_from_slice(bytes)?;
+        Ok(buf)
+    }
+
+    /// Maximum number of bytes the buffer can hold
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Number of bytes in the buffer
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return `true` if the buffer holds no bytes
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Remove all bytes from the buffer
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Append `bytes` to the buffer, leaving it unchanged if they do not fit
+    pub fn extend_from_slice(&mut self, bytes: &[u8]) -> Result<(), SerError> {
+        let end = self.len + bytes.len();
+        if end > N {
+            return Err(SerError::new(format!(
+                "{} bytes do not fit in a buffer of capacity {}",
+                end, N
+            )));
+        }
+        self.data[self.len..end].copy_from_slice(bytes);
+        self.len = end;
+        Ok(())
+    }
+
+    /// Replace the contents of the buffer with the bytes written by `f` into the whole storage,
+    /// such as a datagram read with `NlSocket::recv`
+    pub fn fill_from<F>(&mut self, f: F) -> Result<usize, io::Error>
+    where
+        F: FnOnce(&mut [u8]) -> Result<usize, io::Error>,
+    {
+        self.len = 0;
+        let len = f(&mut self.data)?;
+        self.len = len.min(N);
+        Ok(self.len)
+    }
+
+    /// Replace the contents of the buffer with the serialized form of `msg`
+    pub fn serialize_from<T>(&mut self, msg: &T) -> Result<(), SerError>
+    where
+        T: Nl,
+    {
+        self.len = 0;
+        let size = msg.asize();
+        if size > N {
+            return Err(SerError::new(format!(
+                "Message of {} bytes does not fit in a buffer of capacity {}",
+                size, N
+            )));
+        }
+        let mut mem = StreamWriteBuffer::new_sized(&mut self.data);
+        msg.serialize(&mut mem)?;
+        self.len = mem.as_ref().len();
+        Ok(())
+    }
+}
+
+impl<const N: usize> Default for FixedBuf<N> {
+    fn default() -> Self {
+        FixedBuf::new()
+    }
+}
+
+impl<const N: usize> Deref for FixedBuf<N> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+}
+
+impl<const N: usize> DerefMut for FixedBuf<N> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.data[..self.len]
+    }
+}
+
+impl<const N: usize> AsRef<[u8]> for FixedBuf<N> {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl<const N: usize> AsMut<[u8]> for FixedBuf<N> {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+impl<const N: usize> NlStorage for FixedBuf<N> {
+    fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+}
+
+impl<const N: usize> Debug for FixedBuf<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Debug::fmt(self.as_ref(), f)
+    }
+}
+
+impl<const N: usize> PartialEq for FixedBuf<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_ref() == other.as_ref()
+    }
+}
+
+impl<const N: usize> Nl for FixedBuf<N> {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        let size_hint = mem.take_size_hint();
+        let slice_hinted = match size_hint {
+            Some(sh) => self.get(..sh).ok_or_else(|| {
+                SerError::new(format!(
+                    "Size hint of {} bytes exceeds a payload of {} bytes",
+                    sh,
+                    self.len()
+                ))
+            })?,
+            None => &self[..],
+        };
+        mem.write_all(slice_hinted)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        let mut buf = FixedBuf::new();
+        match mem.take_size_hint() {
+            Some(sh) if sh > N => {
+                return Err(DeError::new(&format!(
+                    "Payload of {} bytes does not fit in a buffer of capacity {}",
+                    sh, N
+                )));
+            }
+            Some(sh) => {
+                mem.read_exact(&mut buf.data[..sh])?;
+                buf.len = sh;
+            }
+            None => {
+                loop {
+                    match mem.read(&mut buf.data[buf.len..])? {
+                        0 => break,
+                        n => buf.len += n,
+                    }
+                    if buf.len == N {
+                        break;
+                    }
+                }
+                if mem.read(&mut [0])? != 0 {
+                    return Err(DeError::new(&format!(
+                        "Payload does not fit in a buffer of capacity {}",
+                        N
+                    )));
+                }
+            }
+        }
+        Ok(buf)
+    }
+
+    fn size(&self) -> usize {
+        self.len
+    }
+}
+
+impl<const N: usize> ToNlValue for FixedBuf<N> {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::Bytes(self.to_vec())
+    }
+}
+
+/// Set of buffers allocated up front and handed out for building or receiving messages
+pub struct MessagePool<const N: usize> {
+    free: Mutex<Vec<Box<FixedBuf<N>>>>,
+}
+
+impl<const N: usize> MessagePool<N> {
+    /// Allocate `count` buffers of capacity `N`
+    pub fn new(count: usize) -> Self {
+        let mut free = Vec::with_capacity(count);
+        for _ in 0..count {
+            free.push(Box::new(FixedBuf::new()));
+        }
+        MessagePool {
+            free: Mutex::new(free),
+        }
+    }
+
+    /// Take an empty buffer from the pool, or return `None` if all of them are in use. The
+    /// buffer goes back to the pool when it is dropped.
+    pub fn acquire(&self) -> Option<PoolBuf<'_, N>> {
+        let mut buf = self.free.lock().unwrap_or_else(|e| e.into_inner()).pop()?;
+        buf.clear();
+        Some(PoolBuf {
+            pool: self,
+            buf: Some(buf),
+        })
+    }
+
+    /// Number of buffers not currently in use
+    pub fn available(&self) -> usize {
+        self.free.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+}
+
+/// Buffer borrowed from a `MessagePool`
+pub struct PoolBuf<'a, const N: usize> {
+    pool: &'a MessagePool<N>,
+    buf: Option<Box<FixedBuf<N>>>,
+}
+
+impl<'a, const N: usize> Deref for PoolBuf<'a, N> {
+    type Target = FixedBuf<N>;
+
+    fn deref(&self) -> &FixedBuf<N> {
+        self.buf.as_ref().expect("Buffer is only taken on drop")
+    }
+}
+
+impl<'a, const N: usize> DerefMut for PoolBuf<'a, N> {
+    fn deref_mut(&mut self) -> &mut FixedBuf<N> {
+        self.buf.as_mut().expect("Buffer is only taken on drop")
+    }
+}
+
+impl<'a, const N: usize> AsRef<[u8]> for PoolBuf<'a, N> {
+    fn as_ref(&self) -> &[u8] {
+        self.deref().as_ref()
+    }
+}
+
+impl<'a, const N: usize> AsMut<[u8]> for PoolBuf<'a, N> {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.deref_mut().as_mut()
+    }
+}
+
+impl<'a, const N: usize> NlStorage for PoolBuf<'a, N> {
+    fn truncate(&mut self, len: usize) {
+        self.deref_mut().truncate(len)
+    }
+}
+
+impl<'a, const N: usize> NlAlloc for &'a MessagePool<N> {
+    type Storage = PoolBuf<'a, N>;
+
+    /// Take a buffer from the pool holding `len` zeroed bytes
+    fn alloc(&self, len: usize) -> Result<PoolBuf<'a, N>, io::Error> {
+        if len > N {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} bytes do not fit in a buffer of capacity {}", len, N),
+            ));
+        }
+        let mut buf = self.acquire().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "All buffers of the pool are in use",
+            )
+        })?;
+        buf.data[..len].iter_mut().for_each(|b| *b = 0);
+        buf.len = len;
+        Ok(buf)
+    }
+}
+
+impl<'a, const N: usize> Drop for PoolBuf<'a, N> {
+    fn drop(&mut self) {
+        if let Some(buf) = self.buf.take() {
+            // The pool was allocated with room for every buffer so this does not reallocate
+            self.pool
+                .free
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(buf);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use buffering::{StreamReadBuffer, StreamWriteBuffer};
+    use nl::Nlmsghdr;
+    use storage::serialize_in;
+
+    #[test]
+    fn test_fixed_payloads() {
+        let pool = MessagePool::<64>::new(1);
+        let mut buf = pool.acquire().unwrap();
+        assert!(pool.acquire().is_none());
+
+        let payload = FixedBuf::<16>::from_slice(&[1, 2, 3, 4, 5]).unwrap();
+        let msg = Nlmsghdr::new(None, 0x10u16, vec![], Some(1), Some(0), payload);
+        buf.serialize_from(&msg).unwrap();
+        assert_eq!(buf.len(), 24);
+
+        let parsed =
+            Nlmsghdr::<u16, FixedBuf<16>>::deserialize(&mut StreamReadBuffer::new(&*buf)).unwrap();
+        assert_eq!(parsed.nl_payload, msg.nl_payload);
+        assert!(
+            Nlmsghdr::<u16, FixedBuf<4>>::deserialize(&mut StreamReadBuffer::new(&*buf)).is_err()
+        );
+        assert!(Nlmsghdr::<u16, [u8; 5]>::deserialize(&mut StreamReadBuffer::new(&*buf)).is_ok());
+        assert!(Nlmsghdr::<u16, [u8; 8]>::deserialize(&mut StreamReadBuffer::new(&*buf)).is_err());
+
+        assert!(FixedBuf::<4>::new().serialize_from(&msg).is_err());
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        mem.set_size_hint(8);
+        assert!(msg.nl_payload.serialize(&mut mem).is_err());
+        drop(buf);
+        assert_eq!(pool.available(), 1);
+
+        // The pool can be used as storage for the serialization layer
+        let storage = serialize_in(&msg, &&pool).unwrap();
+        assert_eq!(storage.len(), 24);
+        assert_eq!(pool.available(), 0);
+    }
+}
@@ -11,15 +11,35 @@
 //! original `Vec<u8>` to allow simpler
 //! parsing at the top level when one `Nlattr` structure is not nested within another, a use case
 //! that is instead handled in `nlattr.rs`.
+//!
+//! Families with support in `neli` implement `GenlFamily` and are each gated behind a cargo
+//! feature named after the family, such as `wireguard`, with their constants in a module of
+//! `consts` of the same name. `GenlFamilyCache` resolves the ID of any `GenlFamily` once per
+//! cache instead of once per request.
 
-use buffering::{StreamReadBuffer, StreamWriteBuffer};
+use std::collections::HashMap;
 
-use consts::{Cmd, NlAttrType};
+use consts::{Cmd, CtrlAttr, CtrlAttrOp, CtrlCmd, GenlOpFlag, GenlOpFlags, NlAttrType, NlmF};
+use err::{sub_len, NlError};
+use nl::Nlmsghdr;
 use nlattr::{AttrHandle, Nlattr};
-use {DeError, Nl, SerError};
+use socket::NlSocket;
+use value::{NlValue, ToNlValue};
+#[cfg(any(test, feature = "test-util"))]
+use {
+    consts::{CtrlAttrMcastGrp, GenlId},
+    nl::verify_roundtrip,
+    vectors::parse_hex,
+};
+use {Buffer, BufferMut, DeError, Nl, SerError};
+
+/// `CtrlCmd::Newfamily` reply describing the `nlctrl` family
+#[cfg(any(test, feature = "test-util"))]
+const VECTOR_NEWFAMILY: &str = include_str!("../vectors/genl/linux-6.18/newfamily.hex");
 
 /// Struct representing generic netlink header and payload
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct Genlmsghdr<C, T> {
     /// Generic netlink message command
     pub cmd: C,
@@ -35,6 +55,23 @@ where
     C: Cmd,
     T: NlAttrType,
 {
+    /// Return `NlError::BadCmd` unless `cmd` is one of `expected`. A response to a request
+    /// usually carries a specific reply command, while notifications sent to a shared socket
+    /// carry the family's notify command, so both can be listed.
+    pub fn check_cmd(&self, expected: &[C]) -> Result<(), NlError>
+    where
+        C: Clone,
+    {
+        if expected.contains(&self.cmd) {
+            Ok(())
+        } else {
+            Err(NlError::BadCmd {
+                expected: expected.iter().cloned().map(Into::into).collect(),
+                got: self.cmd.clone().into(),
+            })
+        }
+    }
+
     /// Create new generic netlink packet
     pub fn new(cmd: C, version: u8, attrs: Vec<Nlattr<T, Vec<u8>>>) -> Result<Self, SerError> {
         Ok(Genlmsghdr {
@@ -52,7 +89,7 @@ where
     T: NlAttrType,
 {
     /// Get handle for attribute parsing and traversal
-    pub fn get_attr_handle(&self) -> AttrHandle<T> {
+    pub fn get_attr_handle(&self) -> AttrHandle<'_, T> {
         AttrHandle::new_borrowed(&self.attrs)
     }
 }
@@ -62,7 +99,10 @@ where
     C: Cmd,
     T: NlAttrType,
 {
-    fn serialize(&self, cur: &mut StreamWriteBuffer) -> Result<(), SerError> {
+    fn serialize<B>(&self, cur: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
         self.cmd.serialize(cur)?;
         self.version.serialize(cur)?;
         self.reserved.serialize(cur)?;
@@ -71,18 +111,19 @@ where
         Ok(())
     }
 
-    fn deserialize<B>(mem: &mut StreamReadBuffer<B>) -> Result<Self, DeError>
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
     where
-        B: AsRef<[u8]>,
+        B: Buffer,
     {
         let cmd = C::deserialize(mem)?;
         let version = u8::deserialize(mem)?;
         let reserved = u16::deserialize(mem)?;
-        let size_hint = match mem
-            .take_size_hint()
-            .map(|sh| sh - (cmd.size() + version.size() + reserved.size()))
-        {
-            Some(sh) => sh,
+        let size_hint = match mem.take_size_hint() {
+            Some(sh) => sub_len(
+                "size_hint",
+                sh,
+                cmd.size() + version.size() + reserved.size(),
+            )?,
             None => {
                 return Err(DeError::new(
                     "Must provide size hint to deserialize Genlmsghdr",
@@ -90,7 +131,8 @@ where
             }
         };
         mem.set_size_hint(size_hint);
-        let attrs = Vec::<Nlattr<T, Vec<u8>>>::deserialize(mem)?;
+        let attrs = Vec::<Nlattr<T, Vec<u8>>>::deserialize(mem)
+            .map_err(|e| e.context("Genlmsghdr.attrs"))?;
 
         let genl = Genlmsghdr {
             cmd,
@@ -107,11 +149,188 @@ where
     }
 }
 
+impl<C, T> ToNlValue for Genlmsghdr<C, T>
+where
+    C: ToNlValue,
+    T: ToNlValue,
+{
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("cmd", self.cmd.to_nl_value()),
+            ("version", self.version.to_nl_value()),
+            ("attrs", NlValue::list(&self.attrs)),
+        ])
+    }
+}
+
+/// Command supported by a generic netlink family, from the `CtrlAttr::Ops` attribute of a
+/// `CtrlCmd::Newfamily` message
+#[derive(Clone, Debug, PartialEq)]
+pub struct GenlOp {
+    /// Command ID
+    pub id: u32,
+    /// Capabilities and permissions of the command
+    pub flags: Vec<GenlOpFlag>,
+}
+
+impl GenlOp {
+    /// Whether the command is supported with `flag`, such as `GenlOpFlag::CmdCapDump`
+    pub fn has(&self, flag: &GenlOpFlag) -> bool {
+        self.flags.contains(flag)
+    }
+}
+
+impl Genlmsghdr<CtrlCmd, CtrlAttr> {
+    /// Commands listed in the `CtrlAttr::Ops` attribute of a `CtrlCmd::Newfamily` message, empty
+    /// if the attribute is missing. A malformed attribute is an error.
+    pub fn ops(&self) -> Result<Vec<GenlOp>, NlError> {
+        let mut handle = self.get_attr_handle();
+        if handle.get_attribute(CtrlAttr::Ops).is_none() {
+            return Ok(Vec::new());
+        }
+        let ops = handle.get_nested_attributes::<CtrlAttrOp>(CtrlAttr::Ops)?;
+        ops.iter()
+            .map(|item| {
+                let attrs = item.get_nested_attributes::<CtrlAttrOp>()?;
+                let id = attrs.get_attr_payload_as::<u32>(CtrlAttrOp::Id)?;
+                let flags = attrs
+                    .get_attr_payload_as::<u32>(CtrlAttrOp::Flags)
+                    .map(|bits| GenlOpFlags::from_bits(bits).into_vec())
+                    .unwrap_or_default();
+                Ok(GenlOp { id, flags })
+            })
+            .collect()
+    }
+}
+
+/// Generic netlink family known at compile time
+pub trait GenlFamily {
+    /// Name the family is registered with in the kernel
+    const NAME: &'static str;
+    /// Version of the family protocol put in requests
+    const VERSION: u8;
+    /// Commands of the family
+    type Cmd: Cmd;
+    /// Top level attributes of the family
+    type Attr: NlAttrType;
+}
+
+/// Message of generic netlink family `F`
+pub type GenlRequest<F> =
+    Nlmsghdr<u16, Genlmsghdr<<F as GenlFamily>::Cmd, <F as GenlFamily>::Attr>>;
+
+/// The generic netlink controller, which resolves the names of other families
+pub struct Nlctrl;
+
+impl GenlFamily for Nlctrl {
+    const NAME: &'static str = "nlctrl";
+    const VERSION: u8 = 2;
+    type Cmd = CtrlCmd;
+    type Attr = CtrlAttr;
+}
+
+/// IDs of generic netlink families, resolved when first used
+#[derive(Debug, Default)]
+pub struct GenlFamilyCache {
+    ids: HashMap<&'static str, u16>,
+}
+
+impl GenlFamilyCache {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        GenlFamilyCache::default()
+    }
+
+    /// ID of family `F`, resolved through `socket` if it is not cached yet
+    pub fn id<F>(&mut self, socket: &mut NlSocket) -> Result<u16, NlError>
+    where
+        F: GenlFamily,
+    {
+        if let Some(&id) = self.ids.get(F::NAME) {
+            return Ok(id);
+        }
+        let id = socket.resolve_genl_family(F::NAME)?;
+        self.ids.insert(F::NAME, id);
+        Ok(id)
+    }
+
+    /// ID of family `F` if it is cached
+    pub fn cached<F>(&self) -> Option<u16>
+    where
+        F: GenlFamily,
+    {
+        self.ids.get(F::NAME).cloned()
+    }
+
+    /// Forget the ID of family `F`, such as after the kernel module providing it was reloaded
+    pub fn invalidate<F>(&mut self)
+    where
+        F: GenlFamily,
+    {
+        self.ids.remove(F::NAME);
+    }
+
+    /// Build a request of family `F`, resolving its ID through `socket` if needed
+    pub fn request<F>(
+        &mut self,
+        socket: &mut NlSocket,
+        cmd: F::Cmd,
+        flags: Vec<NlmF>,
+        attrs: Vec<Nlattr<F::Attr, Vec<u8>>>,
+    ) -> Result<GenlRequest<F>, NlError>
+    where
+        F: GenlFamily,
+    {
+        let id = self.id::<F>(socket)?;
+        let genlhdr = Genlmsghdr::new(cmd, F::VERSION, attrs)?;
+        Ok(Nlmsghdr::new(None, id, flags, None, None, genlhdr))
+    }
+}
+
+/// Round-trip byte vectors of generic netlink messages, captured from a little endian kernel,
+/// through deserialization and serialization. This can be called from downstream test suites to
+/// verify that `neli` matches the wire format of the target platform.
+#[cfg(any(test, feature = "test-util"))]
+pub fn verify_wire_compat() -> Result<(), NlError> {
+    let msg = verify_roundtrip::<Nlmsghdr<GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>>>(&parse_hex(
+        VECTOR_NEWFAMILY,
+    )?)?;
+    let mut handle = msg.nl_payload.get_attr_handle();
+    if handle.get_attr_payload_as::<String>(CtrlAttr::FamilyName)? != "nlctrl"
+        || handle.get_attr_payload_as::<u16>(CtrlAttr::FamilyId)? != libc::GENL_ID_CTRL as u16
+    {
+        return Err(NlError::new("Failed to parse nlctrl family attributes"));
+    }
+    let groups = handle.get_nested_attributes::<u16>(CtrlAttr::McastGroups)?;
+    let group = groups
+        .iter()
+        .next()
+        .ok_or_else(|| NlError::new("No multicast groups found"))?
+        .get_nested_attributes::<CtrlAttrMcastGrp>()?;
+    if group.get_attr_payload_as::<String>(CtrlAttrMcastGrp::Name)? != "notify" {
+        return Err(NlError::new("Failed to parse nlctrl multicast group"));
+    }
+    Ok(())
+}
+
+/// `CtrlCmd::Getfamily` request for the family `name`, sent by tests that need a reply from the
+/// kernel
+#[cfg(test)]
+pub(crate) fn getfamily_request(
+    name: &str,
+    flags: Vec<NlmF>,
+) -> Nlmsghdr<GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>> {
+    let attrs = vec![Nlattr::new(None, CtrlAttr::FamilyName, name).unwrap()];
+    let genlhdr = Genlmsghdr::new(CtrlCmd::Getfamily, 2, attrs).unwrap();
+    Nlmsghdr::new(None, GenlId::Ctrl, flags, None, None, genlhdr)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use buffering::{StreamReadBuffer, StreamWriteBuffer};
     use byteorder::{NativeEndian, WriteBytesExt};
-    use consts::{CtrlAttr, CtrlCmd, NlFamily};
+    use consts::{NlFamily, NlmF};
     use socket::NlSocket;
     use std::io::{Cursor, Write};
 
@@ -163,6 +382,65 @@ mod test {
         assert_eq!(genl, genl_mock)
     }
 
+    #[test]
+    pub fn test_unknown_attributes_roundtrip() {
+        let v = {
+            let mut c = Cursor::new(Vec::new());
+            c.write_u32::<NativeEndian>(48).unwrap();
+            c.write_u16::<NativeEndian>(GenlId::Ctrl.into()).unwrap();
+            c.write_u16::<NativeEndian>(0x8000 | u16::from(NlmF::Multi))
+                .unwrap();
+            c.write_u32::<NativeEndian>(7).unwrap();
+            c.write_u32::<NativeEndian>(0).unwrap();
+            c.write_u8(0xfe).unwrap();
+            c.write_u8(2).unwrap();
+            c.write_u16::<NativeEndian>(0xbeef).unwrap();
+            // Known attribute
+            c.write_u16::<NativeEndian>(6).unwrap();
+            c.write_u16::<NativeEndian>(CtrlAttr::FamilyId.into())
+                .unwrap();
+            c.write_all(&[0x10, 0, 0, 0]).unwrap();
+            // Unknown attribute with an unaligned payload
+            c.write_u16::<NativeEndian>(7).unwrap();
+            c.write_u16::<NativeEndian>(0x99).unwrap();
+            c.write_all(&[1, 2, 3, 0]).unwrap();
+            // Nested attribute with NLA_F_NESTED set
+            c.write_u16::<NativeEndian>(12).unwrap();
+            c.write_u16::<NativeEndian>(0x8000 | u16::from(CtrlAttr::McastGroups))
+                .unwrap();
+            c.write_u16::<NativeEndian>(8).unwrap();
+            c.write_u16::<NativeEndian>(0x42).unwrap();
+            c.write_u32::<NativeEndian>(0xdead_beef).unwrap();
+            c.into_inner()
+        };
+        let mut mem = StreamReadBuffer::new(&v);
+        let msg = Nlmsghdr::<GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>>::deserialize(&mut mem).unwrap();
+        assert!(mem.at_end());
+        assert_eq!(msg.nl_payload.cmd, CtrlCmd::UnrecognizedVariant(0xfe));
+        let attrs = msg.nl_payload.get_attr_handle();
+        let types = attrs
+            .iter()
+            .map(|attr| attr.nla_type.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec![
+                CtrlAttr::FamilyId,
+                CtrlAttr::UnrecognizedVariant(0x99),
+                CtrlAttr::UnrecognizedVariant(0x8000 | u16::from(CtrlAttr::McastGroups)),
+            ]
+        );
+
+        let mut out = StreamWriteBuffer::new_growable(None);
+        msg.serialize(&mut out).unwrap();
+        assert_eq!(out.as_ref(), v.as_slice());
+    }
+
+    #[test]
+    pub fn test_verify_wire_compat() {
+        verify_wire_compat().unwrap();
+    }
+
     #[test]
     #[ignore]
     pub fn test_resolve_genl_family() {
@@ -181,6 +459,51 @@ mod test {
         assert_eq!(2, id)
     }
 
+    #[test]
+    pub fn test_recv_genl_reply() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
+        for expected in &[vec![CtrlCmd::Newfamily], vec![CtrlCmd::Delfamily]] {
+            let nlhdr = getfamily_request("nlctrl", vec![NlmF::Request]);
+            s.send_nl(nlhdr).unwrap();
+            match s.recv_genl_reply::<GenlId, CtrlCmd, CtrlAttr>(expected) {
+                Ok(msg) => {
+                    assert_eq!(expected[0], CtrlCmd::Newfamily);
+                    assert_eq!(msg.nl_payload.cmd, CtrlCmd::Newfamily);
+                }
+                Err(NlError::BadCmd { expected, got }) => {
+                    assert_eq!(expected, vec![libc::CTRL_CMD_DELFAMILY as u8]);
+                    assert_eq!(got, libc::CTRL_CMD_NEWFAMILY as u8);
+                }
+                Err(e) => panic!("Unexpected error: {}", e),
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_genl_ops() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
+        let ops = s.resolve_genl_ops("nlctrl").unwrap();
+        let getfamily = ops
+            .iter()
+            .find(|op| op.id == u32::from(u8::from(CtrlCmd::Getfamily)))
+            .unwrap();
+        assert!(getfamily.has(&GenlOpFlag::CmdCapDo));
+        assert!(getfamily.has(&GenlOpFlag::CmdCapDump));
+
+        let genl = Genlmsghdr::<CtrlCmd, CtrlAttr>::new(CtrlCmd::Newfamily, 2, Vec::new()).unwrap();
+        assert!(genl.ops().unwrap().is_empty());
+        let truncated = Nlattr::new(None, CtrlAttr::Ops, vec![8u8, 0, 1, 0]).unwrap();
+        let genl = Genlmsghdr::new(CtrlCmd::Newfamily, 2, vec![truncated]).unwrap();
+        assert!(genl.ops().is_err());
+    }
+
+    #[test]
+    pub fn test_resolve_and_join_mcast() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
+        let id = s.resolve_and_join_mcast("nlctrl", "notify").unwrap();
+        assert_eq!(libc::GENL_ID_CTRL as u32, id)
+    }
+
     //#[test]
     //pub fn test_deserialize_multiple_messages() {
     //    let genl_mock = Genlmsghdr::new(CtrlCmd::Getops, 2,
@@ -216,4 +539,64 @@ mod test {
     //    assert_eq!(genl, genl_mock);
     //    assert_eq!(genl_second, genl_second_mock)
     //}
+
+    #[cfg(feature = "serde")]
+    #[test]
+    pub fn test_serde() {
+        use consts::GenlId;
+        use nl::Nlmsghdr;
+
+        let msg = Nlmsghdr::<GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>>::deserialize(
+            &mut StreamReadBuffer::new(parse_hex(VECTOR_NEWFAMILY).unwrap()),
+        )
+        .unwrap();
+        let json = serde_json::to_string(&msg).unwrap();
+        assert!(json.starts_with(r#"{"nl_len":136,"nl_type":"Ctrl","nl_flags":[],"nl_seq":3,"#));
+        assert!(json.contains(r#""cmd":"Newfamily","version":2,"#));
+        assert!(json.contains(r#"{"nla_len":11,"nla_type":"FamilyName","payload":[110,"#));
+        assert_eq!(
+            serde_json::from_str::<Nlmsghdr<GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>>>(&json).unwrap(),
+            msg
+        );
+
+        assert_eq!(
+            serde_json::to_string(&CtrlCmd::UnrecognizedVariant(200)).unwrap(),
+            "200"
+        );
+        assert_eq!(
+            serde_json::from_str::<CtrlCmd>("200").unwrap(),
+            CtrlCmd::UnrecognizedVariant(200)
+        );
+        assert_eq!(
+            serde_json::from_str::<CtrlCmd>("3").unwrap(),
+            CtrlCmd::Getfamily
+        );
+        assert!(serde_json::from_str::<CtrlCmd>("256").is_err());
+        assert!(serde_json::from_str::<CtrlCmd>(r#""Missing""#).is_err());
+    }
+
+    #[test]
+    pub fn test_family_cache() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
+        let mut cache = GenlFamilyCache::new();
+        assert_eq!(cache.cached::<Nlctrl>(), None);
+        assert_eq!(
+            cache.id::<Nlctrl>(&mut s).unwrap(),
+            libc::GENL_ID_CTRL as u16
+        );
+        assert_eq!(cache.cached::<Nlctrl>(), Some(libc::GENL_ID_CTRL as u16));
+
+        let attrs = vec![Nlattr::new(None, CtrlAttr::FamilyName, "nlctrl").unwrap()];
+        let req = cache
+            .request::<Nlctrl>(&mut s, CtrlCmd::Getfamily, vec![NlmF::Request], attrs)
+            .unwrap();
+        s.send_nl(req).unwrap();
+        let reply = s
+            .recv_genl_reply::<u16, _, CtrlAttr>(&[CtrlCmd::Newfamily])
+            .unwrap();
+        assert_eq!(reply.nl_type, libc::GENL_ID_CTRL as u16);
+
+        cache.invalidate::<Nlctrl>();
+        assert_eq!(cache.cached::<Nlctrl>(), None);
+    }
 }
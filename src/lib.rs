@@ -7,20 +7,62 @@
 //! constants in the context of netlink.
 //!
 //! ## The project is broken down into the following modules:
+//! * `connector` - This is the kernel connector protocol, used to receive process events.
 //! * `consts` - This is where all of the C-defined constants are wrapped into type safe enums for
-//! use in the library.
+//!   use in the library.
+//! * `debug` - This renders serialized messages as annotated hexdumps for logs and bug reports.
+//! * `dynamic` - This handles payload types chosen at runtime through trait objects.
+//! * `endian` - This selects the byte order of integers for parsing captures taken on machines of
+//!   the opposite endianness.
 //! * `err` - This module contains all of the protocol and library-level errors encountered in the
-//! code.
+//!   code.
 //! * `genl` - This code provides parsing for the generic netlink subsystem of the netlink
-//! protocol.
+//!   protocol.
 //! * `netfilter` - Netfilter related protocols (NFLOG, NFQUEUE, CONNTRACK).
+//! * `limits` - This bounds message size, attribute count and nesting depth while parsing.
+//! * `mock` - This provides an in-memory socket for testing code built on `NlSocketOps` without a
+//!   kernel.
+//! * `netns` - This connects network namespaces to the host with veth pairs, addresses and
+//!   routes, as done by container network plugins.
 //! * `nlattr` - This code provides more granular parsing methods for the generic netlink
-//! attributes in the context of generic netlink requests and responses.
+//!   attributes in the context of generic netlink requests and responses.
 //! * `nl` - This is the top level netlink header code that handles the header that all netlink
-//! messages are encapsulated in.
+//!   messages are encapsulated in.
 //! * `rtnl` - This module is for the routing netlink subsystem of the netlink protocol.
+//! * `router` - This shares one socket between threads by routing each response to the thread
+//!   that sent the request.
+//! * `snapshot` - This stores sequences of serialized messages in files for diffing and replaying
+//!   them later.
+//! * `sockdiag` - This is the socket diagnostics protocol used to list sockets, as `ss` does.
 //! * `socket` - This provides a socket structure for use in sending and receiving messages and a
-//! number of convenience functions for commonly encountered use cases.
+//!   number of convenience functions for commonly encountered use cases.
+//! * `storage` - This lets serialized messages be stored in memory from custom allocators.
+//! * `time` - This implements `Nl` for the kernel's `timeval` and `timespec` layouts and converts
+//!   them to `Duration` and `SystemTime`.
+//! * `tools` - This provides library versions of debugging tools, such as a multi-protocol
+//!   monitor.
+//! * `transaction` - This groups requests that create or delete kernel objects so they can be
+//!   undone together if one of them fails.
+//! * `value` - This converts parsed messages into a tree of plain values for inspection from
+//!   scripting languages.
+//! * `xfrm` - This is the IPsec protocol used to manage security associations and policies, as
+//!   `ip xfrm` does.
+//! * `uapi_gen` - With the `uapi-gen` feature, this generates `impl_var!` definitions from kernel
+//!   UAPI headers for use in build scripts.
+//! * `ffi` - With the `ffi` feature, this exposes message and attribute parsing and serialization
+//!   to C.
+//! * `fixed` - With the `fixed` feature, this provides payloads and message buffers of fixed
+//!   capacity for use without memory allocation.
+//! * `uring` - With the `uring` feature, this provides a socket receiving through `io_uring` with
+//!   pre-registered buffers.
+//! * `taskstats` - With the `taskstats` feature, this requests the statistics of tasks and cgroups
+//!   from the taskstats generic netlink family.
+//! * `wireguard` - With the `wireguard` feature, this provides the keys, endpoints, allowed IPs
+//!   and peers of WireGuard device configuration.
+//! * `test_utils` - With the `test-util` feature, this provides the round-trip and golden byte
+//!   assertions that `neli` tests its wire format with.
+//! * `vectors` - With the `test-util` feature, this loads golden byte vectors of messages captured
+//!   from real kernels for conformance tests.
 //!
 //! ## Traits
 //!
@@ -30,6 +72,24 @@
 //! where, to deserialize a type, a buffer needs to be provided by the caller function and passed
 //! to the callee.
 //!
+//! `BeU16`, `BeU32` and `BeU64` implement `Nl` for integers that the kernel sends in network
+//! byte order instead of the native byte order used for other integers. `Padding<N>` stands for
+//! `N` bytes of padding or reserved space inside a struct. `NlStringUnterminated` is a string
+//! payload without the trailing NUL byte that `String` adds.
+//!
+//! With the `serde` feature, message headers, attributes, generic netlink and routing netlink
+//! structs and all constant enums implement `Serialize` and `Deserialize`, so parsed messages can
+//! be dumped as JSON or YAML for logging and golden tests. This is unrelated to the wire format.
+//!
+//! With the `arbitrary` feature, message headers, attributes and all constant enums implement
+//! `arbitrary::Arbitrary`, so fuzzers and property tests can generate well-formed messages. Fuzz
+//! targets for the deserializers live in the `fuzz/` directory and run with `cargo fuzz`.
+//!
+//! With the `derive` feature, `#[derive(Nl)]` implements `Nl` for structs whose fields are
+//! serialized in declaration order. Field attributes cover padding and fields whose length is
+//! given by an earlier field or by the size of the enclosing message; see the `neli-derive` crate
+//! for details. `#[neli_enum]` generates what `impl_var!` does for a plain, documented enum.
+//!
 //! ## Design decisions
 //!
 //! This is a fairly low level library that currently does not have a whole lot of higher level
@@ -55,40 +115,122 @@
 
 #![deny(missing_docs)]
 
+// Lets code generated by `impl_var!` in other crates use arbitrary through this crate
+#[cfg(feature = "arbitrary")]
+#[doc(hidden)]
+pub extern crate arbitrary;
 extern crate buffering;
 extern crate byteorder;
+#[cfg(feature = "uring")]
+extern crate io_uring;
 extern crate libc;
 #[cfg(feature = "stream")]
 extern crate mio;
+#[cfg(feature = "derive")]
+extern crate neli_derive;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+// Lets the paths in code generated by `#[derive(Nl)]` resolve within this crate
+#[cfg(feature = "derive")]
+extern crate self as neli;
+// Lets code generated by `impl_var!` in other crates use serde through this crate
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
 #[cfg(feature = "stream")]
 extern crate tokio;
 
+/// Kernel connector and process events
+pub mod connector;
 /// C constants defined as types
 pub mod consts;
+/// Annotated hexdumps of serialized messages
+pub mod debug;
+/// Trait objects for payload types chosen at runtime
+pub mod dynamic;
+/// Byte order of serialized integers
+pub mod endian;
 /// Error module
 pub mod err;
+/// C interface to message and attribute parsing
+#[cfg(feature = "ffi")]
+pub mod ffi;
+/// Fixed capacity payloads and message buffers
+#[cfg(feature = "fixed")]
+pub mod fixed;
 /// Genetlink (generic netlink) header and attribute helpers
 pub mod genl;
+/// Resource limits for parsing
+pub mod limits;
+/// In-memory socket for tests
+pub mod mock;
 pub mod netfilter;
+/// Veth plumbing between network namespaces
+pub mod netns;
 /// Top-level netlink header
 pub mod nl;
 /// Netlink attribute handler
 pub mod nlattr;
+/// Concurrent requests over a shared socket
+pub mod router;
 /// Route netlink bindings
 pub mod rtnl;
+/// Thread local settings scoped to a closure
+mod scoped;
+/// Snapshots of messages stored in files
+pub mod snapshot;
+/// Socket diagnostics over `NETLINK_SOCK_DIAG`
+pub mod sockdiag;
 /// Wrapper for `libc` sockets
 pub mod socket;
+/// Custom storage for serialized messages
+pub mod storage;
+/// Per-task and per-cgroup statistics of the taskstats generic netlink family
+#[cfg(feature = "taskstats")]
+pub mod taskstats;
+/// Round-trip and golden byte assertions for tests
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_utils;
+/// Kernel time structures
+pub mod time;
+/// Debugging tools usable from thin binaries
+pub mod tools;
+/// Requests that can be rolled back
+pub mod transaction;
+/// Generator for constant definitions from kernel headers
+#[cfg(feature = "uapi-gen")]
+pub mod uapi_gen;
+/// `io_uring` socket backend
+#[cfg(feature = "uring")]
+pub mod uring;
+/// Structured representation of parsed messages
+pub mod value;
+/// Golden byte vectors of captured messages
+#[cfg(any(test, feature = "test-util"))]
+pub mod vectors;
+/// Typed attributes of the WireGuard generic netlink family
+#[cfg(feature = "wireguard")]
+pub mod wireguard;
+/// IPsec security associations and policies
+pub mod xfrm;
 
 use std::ffi::CString;
-use std::io::{Read, Write};
 use std::mem;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::str;
 
 pub use buffering::{StreamReadBuffer, StreamWriteBuffer};
-use byteorder::{NativeEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt};
+#[cfg(feature = "derive")]
+pub use neli_derive::{neli_enum, Nl};
 
 use consts::alignto;
+use endian::Endian;
 use err::{DeError, SerError};
+pub use storage::{Buffer, BufferMut};
+use value::{NlValue, ToNlValue};
 
 /// Max supported message length for netlink messages supported by the kernel
 pub const MAX_NL_LENGTH: usize = 32768;
@@ -99,32 +241,43 @@ pub const MAX_NL_LENGTH: usize = 32768;
 /// values of more unusual types.
 pub trait Nl: Sized {
     /// Serialization method
-    fn serialize(&self, m: &mut StreamWriteBuffer) -> Result<(), SerError>;
+    fn serialize<B>(&self, m: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut;
 
     /// Stateless deserialization method
-    fn deserialize<T>(m: &mut StreamReadBuffer<T>) -> Result<Self, DeError>
+    fn deserialize<T>(m: &mut T) -> Result<Self, DeError>
     where
-        T: AsRef<[u8]>;
+        T: Buffer;
 
     /// The size of the binary representation of a struct - not aligned to word size
     fn size(&self) -> usize;
 
+    /// The size of the binary representation of every value of the type, or `None` if it depends
+    /// on the value
+    fn type_size() -> Option<usize> {
+        None
+    }
+
     /// The size of the binary representation of a struct - aligned to word size
     fn asize(&self) -> usize {
         alignto(self.size())
     }
 
     /// Pad the data serialized data structure to alignment
-    fn pad(&self, m: &mut StreamWriteBuffer) -> Result<(), SerError> {
+    fn pad<B>(&self, m: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
         let padding_len = self.asize() - self.size();
         m.write_all(&[0; libc::NLA_ALIGNTO as usize][..padding_len])?;
         Ok(())
     }
 
     /// Strip padding from the deserialization buffer
-    fn strip<T>(&self, m: &mut StreamReadBuffer<T>) -> Result<(), DeError>
+    fn strip<T>(&self, m: &mut T) -> Result<(), DeError>
     where
-        T: AsRef<[u8]>,
+        T: Buffer,
     {
         let padding_len = self.asize() - self.size();
         if padding_len > 0 {
@@ -138,20 +291,23 @@ pub trait Nl: Sized {
 /// lifetimes can be returned
 pub trait NlBuf<'a>: Sized {
     /// Deserialization method
-    fn deserialize_buf<T>(m: &mut StreamReadBuffer<T>, b: &'a mut [u8]) -> Result<Self, DeError>
+    fn deserialize_buf<T>(m: &mut T, b: &'a mut [u8]) -> Result<Self, DeError>
     where
-        T: AsRef<[u8]>;
+        T: Buffer;
 }
 
 impl Nl for u8 {
-    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
         mem.write_u8(*self)?;
         Ok(())
     }
 
-    fn deserialize<T>(mem: &mut StreamReadBuffer<T>) -> Result<Self, DeError>
+    fn deserialize<T>(mem: &mut T) -> Result<Self, DeError>
     where
-        T: AsRef<[u8]>,
+        T: Buffer,
     {
         Ok(mem.read_u8()?)
     }
@@ -159,113 +315,314 @@ impl Nl for u8 {
     fn size(&self) -> usize {
         mem::size_of::<u8>()
     }
+
+    fn type_size() -> Option<usize> {
+        Some(mem::size_of::<u8>())
+    }
 }
 
-impl Nl for u16 {
-    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
-        mem.write_u16::<NativeEndian>(*self)?;
+macro_rules! impl_nl_int {
+    ( $( $ty:ty, $read:ident, $write:ident );* ) => {
+        $(
+            impl Nl for $ty {
+                fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+                where
+                    B: BufferMut,
+                {
+                    match endian::endian() {
+                        Endian::Little => mem.$write::<LittleEndian>(*self)?,
+                        Endian::Big => mem.$write::<BigEndian>(*self)?,
+                    }
+                    Ok(())
+                }
+
+                fn deserialize<T>(mem: &mut T) -> Result<Self, DeError>
+                where
+                    T: Buffer,
+                {
+                    Ok(match endian::endian() {
+                        Endian::Little => mem.$read::<LittleEndian>()?,
+                        Endian::Big => mem.$read::<BigEndian>()?,
+                    })
+                }
+
+                fn size(&self) -> usize {
+                    mem::size_of::<$ty>()
+                }
+
+                fn type_size() -> Option<usize> {
+                    Some(mem::size_of::<$ty>())
+                }
+            }
+        )*
+    };
+}
+
+// Integers are in the byte order selected with `endian::with_endian`, native by default
+impl_nl_int!(
+    u16, read_u16, write_u16;
+    u32, read_u32, write_u32;
+    i32, read_i32, write_i32;
+    u64, read_u64, write_u64;
+    i64, read_i64, write_i64
+);
+
+macro_rules! impl_be_int {
+    ( $( $(#[$doc:meta])* $name:ident, $ty:ty, $read:ident, $write:ident );* ) => {
+        $(
+            $(#[$doc])*
+            #[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+            pub struct $name(pub $ty);
+
+            impl From<$ty> for $name {
+                fn from(v: $ty) -> Self {
+                    $name(v)
+                }
+            }
+
+            impl From<$name> for $ty {
+                fn from(v: $name) -> Self {
+                    v.0
+                }
+            }
+
+            impl Nl for $name {
+                fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+                where
+                    B: BufferMut,
+                {
+                    mem.$write::<BigEndian>(self.0)?;
+                    Ok(())
+                }
+
+                fn deserialize<T>(mem: &mut T) -> Result<Self, DeError>
+                where
+                    T: Buffer,
+                {
+                    Ok($name(mem.$read::<BigEndian>()?))
+                }
+
+                fn size(&self) -> usize {
+                    mem::size_of::<$ty>()
+                }
+
+                fn type_size() -> Option<usize> {
+                    Some(mem::size_of::<$ty>())
+                }
+            }
+
+            impl ToNlValue for $name {
+                fn to_nl_value(&self) -> NlValue {
+                    NlValue::Uint(u64::from(self.0))
+                }
+            }
+        )*
+    };
+}
+
+impl_be_int!(
+    /// `u16` sent in network byte order, such as port numbers in conntrack attributes. The value
+    /// is held in host byte order.
+    BeU16, u16, read_u16, write_u16;
+    /// `u32` sent in network byte order, such as NFQUEUE packet IDs. The value is held in host
+    /// byte order.
+    BeU32, u32, read_u32, write_u32;
+    /// `u64` sent in network byte order, such as conntrack counters. The value is held in host
+    /// byte order.
+    BeU64, u64, read_u64, write_u64
+);
+
+impl Nl for &[u8] {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        let _ = mem.write(self)?;
         Ok(())
     }
 
-    fn deserialize<T>(mem: &mut StreamReadBuffer<T>) -> Result<Self, DeError>
+    fn deserialize<T>(_m: &mut T) -> Result<Self, DeError>
     where
-        T: AsRef<[u8]>,
+        T: Buffer,
     {
-        Ok(mem.read_u16::<NativeEndian>()?)
+        unimplemented!("Use deserialize_buf instead")
     }
 
     fn size(&self) -> usize {
-        mem::size_of::<u16>()
+        self.len()
     }
 }
 
-impl Nl for u32 {
-    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
-        mem.write_u32::<NativeEndian>(*self)?;
+impl<'a> NlBuf<'a> for &'a [u8] {
+    fn deserialize_buf<T>(mem: &mut T, input: &'a mut [u8]) -> Result<Self, DeError>
+    where
+        T: Buffer,
+    {
+        mem.read_exact(input)?;
+        Ok(input)
+    }
+}
+
+/// Fixed-width fields such as hardware addresses or reserved bytes. A size hint, if given, has to
+/// match the length of the array.
+impl<const N: usize> Nl for [u8; N] {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        mem.write_all(self)?;
         Ok(())
     }
 
-    fn deserialize<T>(mem: &mut StreamReadBuffer<T>) -> Result<Self, DeError>
+    fn deserialize<T>(mem: &mut T) -> Result<Self, DeError>
     where
-        T: AsRef<[u8]>,
+        T: Buffer,
     {
-        Ok(mem.read_u32::<NativeEndian>()?)
+        if let Some(sh) = mem.take_size_hint() {
+            if sh != N {
+                return Err(DeError::new(&format!(
+                    "Payload of {} bytes does not match array length {}",
+                    sh, N
+                )));
+            }
+        }
+        let mut arr = [0; N];
+        mem.read_exact(&mut arr)?;
+        Ok(arr)
     }
 
     fn size(&self) -> usize {
-        mem::size_of::<u32>()
+        N
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(N)
     }
 }
 
-impl Nl for i32 {
-    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
-        mem.write_i32::<NativeEndian>(*self)?;
+/// `N` bytes of padding inside a struct, such as the pad byte of `struct ifinfomsg` or the pad
+/// bytes of `struct ndmsg`. Zeros are written when serializing and the bytes are skipped without
+/// being checked when deserializing, as the kernel does not guarantee they are zero.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Padding<const N: usize>;
+
+impl<const N: usize> Nl for Padding<N> {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        mem.write_all(&[0; N])?;
         Ok(())
     }
 
-    fn deserialize<T>(mem: &mut StreamReadBuffer<T>) -> Result<Self, DeError>
+    fn deserialize<T>(mem: &mut T) -> Result<Self, DeError>
     where
-        T: AsRef<[u8]>,
+        T: Buffer,
     {
-        Ok(mem.read_i32::<NativeEndian>()?)
+        mem.read_exact(&mut [0; N])?;
+        Ok(Padding)
     }
 
     fn size(&self) -> usize {
-        mem::size_of::<i32>()
+        N
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(N)
     }
 }
 
-impl Nl for u64 {
-    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
-        mem.write_u64::<NativeEndian>(*self)?;
-        Ok(())
+impl Nl for Ipv4Addr {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.octets().serialize(mem)
     }
 
-    fn deserialize<T>(mem: &mut StreamReadBuffer<T>) -> Result<Self, DeError>
+    fn deserialize<T>(mem: &mut T) -> Result<Self, DeError>
     where
-        T: AsRef<[u8]>,
+        T: Buffer,
     {
-        Ok(mem.read_u64::<NativeEndian>()?)
+        Ok(Ipv4Addr::from(<[u8; 4]>::deserialize(mem)?))
     }
 
     fn size(&self) -> usize {
-        mem::size_of::<u64>()
+        4
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(4)
     }
 }
 
-impl<'a> Nl for &'a [u8] {
-    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
-        let _ = mem.write(self)?;
-        Ok(())
+impl Nl for Ipv6Addr {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.octets().serialize(mem)
     }
 
-    fn deserialize<T>(_m: &mut StreamReadBuffer<T>) -> Result<Self, DeError>
+    fn deserialize<T>(mem: &mut T) -> Result<Self, DeError>
     where
-        T: AsRef<[u8]>,
+        T: Buffer,
     {
-        unimplemented!("Use deserialize_buf instead")
+        Ok(Ipv6Addr::from(<[u8; 16]>::deserialize(mem)?))
     }
 
     fn size(&self) -> usize {
-        self.len()
+        16
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(16)
     }
 }
 
-impl<'a> NlBuf<'a> for &'a [u8] {
-    fn deserialize_buf<T>(
-        mem: &mut StreamReadBuffer<T>,
-        input: &'a mut [u8],
-    ) -> Result<Self, DeError>
+/// Addresses in network byte order as found in attributes. Deserializing requires a size hint of
+/// 4 or 16 bytes to tell the two versions apart; `rtnl::deserialize_addr` gives it according to
+/// an address family.
+impl Nl for IpAddr {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
     where
-        T: AsRef<[u8]>,
+        B: BufferMut,
     {
-        mem.read_exact(input)?;
-        Ok(input)
+        match *self {
+            IpAddr::V4(ref addr) => addr.serialize(mem),
+            IpAddr::V6(ref addr) => addr.serialize(mem),
+        }
+    }
+
+    fn deserialize<T>(mem: &mut T) -> Result<Self, DeError>
+    where
+        T: Buffer,
+    {
+        match mem.take_size_hint() {
+            Some(4) => Ok(IpAddr::V4(Ipv4Addr::deserialize(mem)?)),
+            Some(16) => Ok(IpAddr::V6(Ipv6Addr::deserialize(mem)?)),
+            Some(size_hint) => Err(DeError::Msg(format!(
+                "Invalid IP address length {}",
+                size_hint
+            ))),
+            None => Err(DeError::new("Must provide size hint to deserialize IpAddr")),
+        }
+    }
+
+    fn size(&self) -> usize {
+        match *self {
+            IpAddr::V4(ref addr) => addr.size(),
+            IpAddr::V6(ref addr) => addr.size(),
+        }
     }
 }
 
 impl Nl for Vec<u8> {
-    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
         let size_hint = mem.take_size_hint();
         let slice: &[u8] = self.as_ref();
         let slice_hinted = match size_hint {
@@ -276,9 +633,9 @@ impl Nl for Vec<u8> {
         Ok(())
     }
 
-    fn deserialize<B>(mem: &mut StreamReadBuffer<B>) -> Result<Self, DeError>
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
     where
-        B: AsRef<[u8]>,
+        B: Buffer,
     {
         let v = match mem.take_size_hint() {
             Some(sh) => {
@@ -300,8 +657,11 @@ impl Nl for Vec<u8> {
     }
 }
 
-impl<'a> Nl for &'a str {
-    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+impl Nl for &str {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
         let str_bytes = self.as_bytes();
         let nul = &[0u8];
         let bytes = match mem.take_size_hint() {
@@ -312,9 +672,9 @@ impl<'a> Nl for &'a str {
         Ok(())
     }
 
-    fn deserialize<B>(_: &mut StreamReadBuffer<B>) -> Result<Self, DeError>
+    fn deserialize<B>(_: &mut B) -> Result<Self, DeError>
     where
-        B: AsRef<[u8]>,
+        B: Buffer,
     {
         unimplemented!("Use deserialize_buf instead")
     }
@@ -325,17 +685,14 @@ impl<'a> Nl for &'a str {
 }
 
 impl<'a> NlBuf<'a> for &'a str {
-    fn deserialize_buf<T>(
-        mem: &mut StreamReadBuffer<T>,
-        input: &'a mut [u8],
-    ) -> Result<Self, DeError>
+    fn deserialize_buf<T>(mem: &mut T, input: &'a mut [u8]) -> Result<Self, DeError>
     where
-        T: AsRef<[u8]>,
+        T: Buffer,
     {
         mem.read_exact(input)?;
         let idx = input.iter().position(|elem| *elem == 0);
         let slice_ref = if let Some(i) = idx {
-            &input[..(i as usize)]
+            &input[..i]
         } else {
             input
         };
@@ -344,7 +701,10 @@ impl<'a> NlBuf<'a> for &'a str {
 }
 
 impl Nl for String {
-    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
         let size_hint = mem.take_size_hint().unwrap_or(0);
         let c_str = CString::new(self.as_bytes())
             .map_err(|_| SerError::new("Unable to serialize string containing null byte"))?;
@@ -356,9 +716,9 @@ impl Nl for String {
         Ok(())
     }
 
-    fn deserialize<T>(mem: &mut StreamReadBuffer<T>) -> Result<Self, DeError>
+    fn deserialize<T>(mem: &mut T) -> Result<Self, DeError>
     where
-        T: AsRef<[u8]>,
+        T: Buffer,
     {
         let size_hint = match mem.take_size_hint() {
             Some(sh) => sh,
@@ -378,12 +738,94 @@ impl Nl for String {
     }
 }
 
+/// String attribute payload without a trailing NUL byte, as sent by generic netlink families
+/// that use `NLA_STRING` rather than `NLA_NUL_STRING`. Parsing stops at the first NUL byte if
+/// there is one, like for `String`, but none is written when serializing.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct NlStringUnterminated(pub String);
+
+impl From<String> for NlStringUnterminated {
+    fn from(s: String) -> Self {
+        NlStringUnterminated(s)
+    }
+}
+
+impl<'a> From<&'a str> for NlStringUnterminated {
+    fn from(s: &'a str) -> Self {
+        NlStringUnterminated(s.to_string())
+    }
+}
+
+impl From<NlStringUnterminated> for String {
+    fn from(s: NlStringUnterminated) -> Self {
+        s.0
+    }
+}
+
+impl std::ops::Deref for NlStringUnterminated {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for NlStringUnterminated {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Nl for NlStringUnterminated {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        if self.0.as_bytes().contains(&0) {
+            return Err(SerError::new(
+                "Unable to serialize string containing null byte",
+            ));
+        }
+        mem.write_all(self.0.as_bytes())?;
+        Ok(())
+    }
+
+    fn deserialize<T>(mem: &mut T) -> Result<Self, DeError>
+    where
+        T: Buffer,
+    {
+        let size_hint = match mem.take_size_hint() {
+            Some(sh) => sh,
+            None => return Err(DeError::new("Size hint required to deserialize strings")),
+        };
+        let mut v = vec![0; size_hint];
+        mem.read_exact(v.as_mut_slice())?;
+        if let Some(i) = v.iter().position(|elem| *elem == 0) {
+            v.truncate(i);
+        }
+        Ok(NlStringUnterminated(String::from_utf8(v)?))
+    }
+
+    fn size(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl ToNlValue for NlStringUnterminated {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::Str(self.0.clone())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
     use std::io::Cursor;
 
+    use byteorder::NativeEndian;
+
     #[test]
     fn test_nl_u8() {
         let v: u8 = 5;
@@ -453,6 +895,87 @@ mod test {
         assert_eq!(v, 600_000)
     }
 
+    #[test]
+    fn test_nl_be_ints() {
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        BeU16(0x1234).serialize(&mut mem).unwrap();
+        BeU32(0x1234_5678).serialize(&mut mem).unwrap();
+        BeU64::from(0x0102_0304_0506_0708)
+            .serialize(&mut mem)
+            .unwrap();
+        assert_eq!(
+            mem.as_ref(),
+            &[0x12, 0x34, 0x12, 0x34, 0x56, 0x78, 1, 2, 3, 4, 5, 6, 7, 8][..]
+        );
+        assert_eq!(BeU32(0).size(), 4);
+
+        let mut mem = StreamReadBuffer::new(mem.as_ref().to_vec());
+        assert_eq!(BeU16::deserialize(&mut mem).unwrap(), BeU16(0x1234));
+        assert_eq!(
+            u32::from(BeU32::deserialize(&mut mem).unwrap()),
+            0x1234_5678
+        );
+        assert_eq!(
+            BeU64::deserialize(&mut mem).unwrap().0,
+            0x0102_0304_0506_0708
+        );
+    }
+
+    #[test]
+    fn test_nl_u8_array() {
+        let hwaddr = [0x02, 0, 0, 0, 0, 1];
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        hwaddr.serialize(&mut mem).unwrap();
+        assert_eq!(mem.as_ref(), &hwaddr[..]);
+        assert_eq!(hwaddr.size(), 6);
+        assert_eq!(<[u8; 6]>::type_size(), Some(6));
+        assert_eq!(u16::type_size(), Some(2));
+        assert_eq!(Vec::<u8>::type_size(), None);
+
+        let mut mem = StreamReadBuffer::new(mem.as_ref().to_vec());
+        assert_eq!(<[u8; 6]>::deserialize(&mut mem).unwrap(), hwaddr);
+
+        let mut mem = StreamReadBuffer::new(&hwaddr[..]);
+        mem.set_size_hint(4);
+        assert!(<[u8; 6]>::deserialize(&mut mem).is_err());
+        assert!(<[u8; 8]>::deserialize(&mut StreamReadBuffer::new(&hwaddr[..])).is_err());
+    }
+
+    #[test]
+    fn test_padding() {
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        Padding::<3>.serialize(&mut mem).unwrap();
+        assert_eq!(mem.as_ref(), &[0, 0, 0]);
+        assert_eq!(Padding::<3>::type_size(), Some(3));
+
+        // Nonzero padding is skipped as well
+        let mut mem = StreamReadBuffer::new(&[1, 2, 3, 4][..]);
+        assert_eq!(Padding::<3>::deserialize(&mut mem).unwrap(), Padding);
+        assert_eq!(u8::deserialize(&mut mem).unwrap(), 4);
+        assert!(Padding::<2>::deserialize(&mut mem).is_err());
+    }
+
+    #[test]
+    fn test_nl_ip_addrs() {
+        let v4 = Ipv4Addr::new(192, 168, 0, 1);
+        let v6 = "fe80::1".parse::<Ipv6Addr>().unwrap();
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        IpAddr::V4(v4).serialize(&mut mem).unwrap();
+        v6.serialize(&mut mem).unwrap();
+        assert_eq!(&mem.as_ref()[..4], &[192, 168, 0, 1]);
+        assert_eq!(&mem.as_ref()[4..], &v6.octets()[..]);
+
+        let mut mem = StreamReadBuffer::new(mem.as_ref().to_vec());
+        assert_eq!(Ipv4Addr::deserialize(&mut mem).unwrap(), v4);
+        mem.set_size_hint(16);
+        assert_eq!(IpAddr::deserialize(&mut mem).unwrap(), IpAddr::V6(v6));
+
+        let mut mem = StreamReadBuffer::new(&[0; 8]);
+        assert!(IpAddr::deserialize(&mut mem).is_err());
+        mem.set_size_hint(8);
+        assert!(IpAddr::deserialize(&mut mem).is_err());
+    }
+
     #[test]
     fn test_nl_u64() {
         let test_int: u64 = 12_345_678_901_234;
@@ -553,4 +1076,94 @@ mod test {
         let string = String::deserialize(&mut mem).unwrap();
         assert_eq!(string, "AAAAAA".to_string())
     }
+
+    #[test]
+    fn test_nl_string_unterminated() {
+        let s = NlStringUnterminated::from("wg0");
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        s.serialize(&mut mem).unwrap();
+        assert_eq!(mem.as_ref(), b"wg0");
+        assert_eq!(s.size(), 3);
+        assert_eq!(s.asize(), 4);
+
+        let mut mem = StreamReadBuffer::new(&b"wg0"[..]);
+        mem.set_size_hint(3);
+        assert_eq!(NlStringUnterminated::deserialize(&mut mem).unwrap(), s);
+        // A trailing NUL byte is accepted as well
+        let mut mem = StreamReadBuffer::new(&b"wg0\0"[..]);
+        mem.set_size_hint(4);
+        assert_eq!(NlStringUnterminated::deserialize(&mut mem).unwrap(), s);
+
+        assert!(NlStringUnterminated::from("a\0b")
+            .serialize(&mut StreamWriteBuffer::new_growable(None))
+            .is_err());
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn test_derive_nl() {
+        #[derive(Debug, PartialEq, Nl)]
+        struct Derived {
+            #[neli(pad = 3)]
+            family: u8,
+            data_len: u16,
+            #[neli(len = "data_len")]
+            data: Vec<u8>,
+            #[neli(remaining)]
+            trailer: Vec<u8>,
+        }
+
+        #[derive(Debug, PartialEq, Nl)]
+        struct DerivedPair<T>(T, u32);
+
+        let derived = Derived {
+            family: 2,
+            data_len: 2,
+            data: vec![1, 2],
+            trailer: vec![9, 9, 9],
+        };
+        assert_eq!(derived.size(), 11);
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        derived.serialize(&mut mem).unwrap();
+        let mut expected = vec![2, 0, 0, 0];
+        expected.extend_from_slice(&2u16.to_ne_bytes());
+        expected.extend_from_slice(&[1, 2, 9, 9, 9]);
+        assert_eq!(mem.as_ref(), expected.as_slice());
+
+        let mut mem = StreamReadBuffer::new(&expected);
+        assert!(Derived::deserialize(&mut mem).is_err());
+        let mut mem = StreamReadBuffer::new(&expected);
+        mem.set_size_hint(expected.len());
+        assert_eq!(Derived::deserialize(&mut mem).unwrap(), derived);
+
+        let pair = DerivedPair(7u8, 8);
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        pair.serialize(&mut mem).unwrap();
+        let mut mem = StreamReadBuffer::new(mem.as_ref().to_vec());
+        assert_eq!(DerivedPair::<u8>::deserialize(&mut mem).unwrap(), pair);
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn test_neli_enum() {
+        #[neli_enum(serialized_type = "u16")]
+        /// Documented enum
+        enum Derived {
+            /// First value
+            First = 1,
+            #[cfg(not(test))]
+            Hidden = 2,
+            /// Value from an expression
+            Third = 1 + 2,
+        }
+
+        assert_eq!(u16::from(Derived::Third), 3);
+        assert_eq!(Derived::from(1u16), Derived::First);
+        assert_eq!(Derived::from(2u16), Derived::UnrecognizedVariant(2));
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        Derived::Third.serialize(&mut mem).unwrap();
+        assert_eq!(Derived::Third.size(), 2);
+        let mut mem = StreamReadBuffer::new(mem.as_ref().to_vec());
+        assert_eq!(Derived::deserialize(&mut mem).unwrap(), Derived::Third);
+    }
 }
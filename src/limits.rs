@@ -0,0 +1,157 @@
+//! This module bounds the resources that parsing a single message may use, so that long-running
+//! daemons listening on protocols that other user space processes can send to are protected
+//! against pathological or malicious messages.
+//!
+//! Three limits are checked while parsing:
+//!
+//! * the size of a message, against its `nl_len` field and the length of received datagrams,
+//! * the number of attributes in one attribute list,
+//! * the depth of attribute lists nested within each other while they are parsed in one go.
+//!
+//! Exceeding a limit returns `DeError::LimitExceeded`. Like other errors found while parsing a
+//! payload or attribute, it is usually wrapped in `DeError::Context`, so match on
+//! `DeError::root` rather than on the returned error itself.
+//!
+//! # Design decisions
+//!
+//! `Nl::deserialize` has no parameter to pass limits through, so the limits are process-wide and
+//! set with `set_parse_limits`. The current nesting depth is tracked per thread. The defaults are
+//! far above what the kernel sends and only reject messages that no sane sender produces.
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use err::DeError;
+
+static MAX_MSG_SIZE: AtomicUsize = AtomicUsize::new(ParseLimits::DEFAULT.max_msg_size);
+static MAX_ATTRS: AtomicUsize = AtomicUsize::new(ParseLimits::DEFAULT.max_attrs);
+static MAX_DEPTH: AtomicUsize = AtomicUsize::new(ParseLimits::DEFAULT.max_depth);
+
+thread_local! {
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Limits checked while parsing messages
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParseLimits {
+    /// Maximum size of a message or received datagram in bytes
+    pub max_msg_size: usize,
+    /// Maximum number of attributes in one attribute list
+    pub max_attrs: usize,
+    /// Maximum depth of attribute lists nested within each other
+    pub max_depth: usize,
+}
+
+impl ParseLimits {
+    /// Default limits: 1 MiB messages, 65536 attributes per list and 32 levels of nesting
+    pub const DEFAULT: ParseLimits = ParseLimits {
+        max_msg_size: 1 << 20,
+        max_attrs: 1 << 16,
+        max_depth: 32,
+    };
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        ParseLimits::DEFAULT
+    }
+}
+
+/// Replace the limits used for parsing in all threads
+pub fn set_parse_limits(limits: ParseLimits) {
+    MAX_MSG_SIZE.store(limits.max_msg_size, Ordering::Relaxed);
+    MAX_ATTRS.store(limits.max_attrs, Ordering::Relaxed);
+    MAX_DEPTH.store(limits.max_depth, Ordering::Relaxed);
+}
+
+/// Limits currently used for parsing
+pub fn parse_limits() -> ParseLimits {
+    ParseLimits {
+        max_msg_size: MAX_MSG_SIZE.load(Ordering::Relaxed),
+        max_attrs: MAX_ATTRS.load(Ordering::Relaxed),
+        max_depth: MAX_DEPTH.load(Ordering::Relaxed),
+    }
+}
+
+fn check(limit: &'static str, max: usize, got: usize) -> Result<(), DeError> {
+    if got > max {
+        Err(DeError::LimitExceeded { limit, max, got })
+    } else {
+        Ok(())
+    }
+}
+
+/// Check the size of a message or datagram
+pub(crate) fn check_msg_size(len: usize) -> Result<(), DeError> {
+    check("message size", MAX_MSG_SIZE.load(Ordering::Relaxed), len)
+}
+
+/// Check the number of attributes parsed from one list so far
+pub(crate) fn check_attr_count(count: usize) -> Result<(), DeError> {
+    check("attribute count", MAX_ATTRS.load(Ordering::Relaxed), count)
+}
+
+/// Nesting level of an attribute list being parsed, left when dropped
+pub(crate) struct NestGuard(());
+
+impl NestGuard {
+    /// Enter one more level of nesting on the current thread
+    pub(crate) fn enter() -> Result<Self, DeError> {
+        let depth = DEPTH.with(|depth| depth.get()) + 1;
+        check("nesting depth", MAX_DEPTH.load(Ordering::Relaxed), depth)?;
+        DEPTH.with(|d| d.set(depth));
+        Ok(NestGuard(()))
+    }
+}
+
+impl Drop for NestGuard {
+    fn drop(&mut self) {
+        DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use buffering::StreamReadBuffer;
+    use byteorder::{ByteOrder, NativeEndian};
+
+    use consts::Nlmsg;
+    use nl::{NlEmpty, Nlmsghdr};
+    use Nl;
+
+    #[test]
+    fn test_parse_limits() {
+        // Limits are process-wide, so only the defaults are set to not affect other tests
+        set_parse_limits(ParseLimits::default());
+        assert_eq!(parse_limits(), ParseLimits::DEFAULT);
+
+        let mut bytes = [0u8; 16];
+        NativeEndian::write_u32(&mut bytes[..4], 1 << 21);
+        match Nlmsghdr::<Nlmsg, NlEmpty>::deserialize(&mut StreamReadBuffer::new(&bytes[..])) {
+            Err(DeError::LimitExceeded {
+                limit: "message size",
+                max,
+                got,
+            }) => {
+                assert_eq!(max, 1 << 20);
+                assert_eq!(got, 1 << 21);
+            }
+            res => panic!("Unexpected result {:?}", res),
+        }
+
+        assert!(check_attr_count(1 << 16).is_ok());
+        assert!(check_attr_count((1 << 16) + 1).is_err());
+
+        {
+            let guards = (0..32)
+                .map(|_| NestGuard::enter())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert!(NestGuard::enter().is_err());
+            drop(guards);
+        }
+        assert!(NestGuard::enter().is_ok());
+    }
+}
@@ -0,0 +1,172 @@
+//! This module provides an in-memory stand-in for `NlSocket` so that code built on `NlSocketOps`
+//! can be unit tested without a kernel or the privileges needed to change networking state.
+//!
+//! # Design decisions
+//!
+//! `MockSocket` works on raw datagrams: replies are queued ahead of time, either as bytes or as
+//! `Nlmsghdr` values, and every datagram sent is recorded for later inspection. No attempt is
+//! made to emulate kernel behavior beyond returning the queued replies in order.
+
+use std::collections::VecDeque;
+use std::io;
+
+use buffering::StreamWriteBuffer;
+
+use consts::{NlType, Nlmsg};
+use err::{NlError, Nlmsgerr};
+use nl::{NlEmpty, Nlmsghdr};
+use socket::{NlBufferIter, NlSocketOps};
+use Nl;
+
+/// Socket returning queued replies and recording sent datagrams
+#[derive(Debug, Default)]
+pub struct MockSocket {
+    seq: Option<u32>,
+    sent: Vec<Vec<u8>>,
+    replies: VecDeque<Vec<u8>>,
+}
+
+impl MockSocket {
+    /// Create a mock socket with no queued replies. Sequence numbers are tracked starting from
+    /// 1 if `track_seq` is true, mirroring `NlSocket::connect`.
+    pub fn new(track_seq: bool) -> Self {
+        MockSocket {
+            seq: if track_seq { Some(0) } else { None },
+            ..Default::default()
+        }
+    }
+
+    /// Queue a raw datagram to be returned by the next receive call
+    pub fn push_reply(&mut self, datagram: Vec<u8>) {
+        self.replies.push_back(datagram);
+    }
+
+    /// Queue a datagram holding all of `msgs`
+    pub fn push_msgs<T, P>(&mut self, msgs: Vec<Nlmsghdr<T, P>>) -> Result<(), NlError>
+    where
+        T: Nl + NlType,
+        P: Nl,
+    {
+        let mut mem = StreamWriteBuffer::new_growable(Some(msgs.asize()));
+        msgs.serialize(&mut mem)?;
+        self.push_reply(mem.as_ref().to_vec());
+        Ok(())
+    }
+
+    /// Queue an error message answering the request with sequence number `seq`. An `error` of
+    /// 0 is an ACK, otherwise it is a negated errno value as sent by the kernel.
+    pub fn push_ack(&mut self, seq: u32, error: i32) -> Result<(), NlError> {
+        let nlmsg = Nlmsghdr::new(None, Nlmsg::Noop, vec![], Some(seq), None, NlEmpty);
+        let payload = Nlmsgerr { error, nlmsg };
+        self.push_msgs(vec![Nlmsghdr::new(
+            None,
+            Nlmsg::Error,
+            vec![],
+            Some(seq),
+            None,
+            payload,
+        )])
+    }
+
+    /// Datagrams sent so far, oldest first
+    pub fn sent(&self) -> &[Vec<u8>] {
+        &self.sent
+    }
+
+    /// Parse all messages sent so far
+    pub fn sent_msgs<T, P>(&self) -> Result<Vec<Nlmsghdr<T, P>>, NlError>
+    where
+        T: Nl + NlType,
+        P: Nl,
+    {
+        let mut msgs = Vec::new();
+        for datagram in self.sent.iter() {
+            for msg in NlBufferIter::new(datagram) {
+                msgs.push(msg?);
+            }
+        }
+        Ok(msgs)
+    }
+
+    /// Number of queued replies that have not been received yet
+    pub fn pending_replies(&self) -> usize {
+        self.replies.len()
+    }
+}
+
+impl NlSocketOps for MockSocket {
+    fn send_datagram(&mut self, buf: &[u8]) -> Result<(), NlError> {
+        self.sent.push(buf.to_vec());
+        Ok(())
+    }
+
+    fn recv_datagram(&mut self) -> Result<Vec<u8>, NlError> {
+        self.replies.pop_front().ok_or_else(|| {
+            NlError::Io(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "No replies queued on mock socket",
+            ))
+        })
+    }
+
+    fn next_seq(&mut self) -> Option<u32> {
+        self.seq.as_mut().map(|seq| {
+            *seq += 1;
+            *seq
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use consts::{Arphrd, Iff, NlmF, RtAddrFamily, Rtm};
+    use rtnl::{IfIndex, Ifinfomsg, Rtattrs};
+    use transaction::NlTransaction;
+
+    fn link_msg(flags: Vec<Iff>) -> Nlmsghdr<Rtm, Ifinfomsg> {
+        Nlmsghdr::new(
+            None,
+            Rtm::Setlink,
+            vec![NlmF::Request, NlmF::Ack],
+            None,
+            None,
+            Ifinfomsg::new(
+                RtAddrFamily::Unspec,
+                Arphrd::None,
+                IfIndex(1),
+                flags,
+                Rtattrs::empty(),
+            ),
+        )
+    }
+
+    #[test]
+    fn test_mock_transaction() {
+        let mut socket = MockSocket::new(true);
+        socket.push_ack(1, 0).unwrap();
+        socket.push_ack(2, -libc::EPERM).unwrap();
+        {
+            let mut trans = NlTransaction::new(&mut socket);
+            trans
+                .apply(link_msg(vec![Iff::Up]), link_msg(vec![]))
+                .unwrap();
+            assert_eq!(trans.len(), 1);
+            match trans.rollback() {
+                Err(NlError::Errno(libc::EPERM)) => (),
+                res => panic!("Unexpected result {:?}", res),
+            }
+        }
+        assert_eq!(socket.pending_replies(), 0);
+        let sent = socket.sent_msgs::<Rtm, Ifinfomsg>().unwrap();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].nl_seq, 1);
+        assert_eq!(sent[1].nl_seq, 2);
+        assert_eq!(sent[1].nl_payload.ifi_flags, vec![]);
+        match socket.recv_datagram() {
+            Err(NlError::Io(ref e)) if e.kind() == io::ErrorKind::WouldBlock => (),
+            res => panic!("Unexpected result {:?}", res),
+        }
+    }
+}
@@ -3,9 +3,15 @@
 //! Protocols used for communicating with netfilter. Currently, this contains (partial) support for
 //! NFLOG, NFQUEUE and CONNTRACK will be added later.
 //!
+//! `FlowTuple` describes a connection the way conntrack does, so that applications ingesting
+//! conntrack events can key flow tables by it. `FlowTuple::canonical` maps the tuples of both
+//! directions of a connection to the same key.
+//!
 //! See the examples in the git repository for actual, working code.
 
+use std::convert::TryFrom;
 use std::ffi::CString;
+use std::net::IpAddr;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use libc::c_int;
@@ -13,38 +19,25 @@ use libc::c_int;
 use crate::consts::netfilter::{LogCopyMode, NfLogAttr, NfLogCfg};
 use crate::err::{DeError, SerError};
 use crate::nlattr::Nlattr;
-use crate::{Nl, StreamReadBuffer, StreamWriteBuffer};
+use crate::rtnl::HwAddr;
+use crate::time::BeTimeval;
+use crate::value::{NlValue, ToNlValue};
+#[cfg(any(test, feature = "test-util"))]
+use crate::{
+    consts::netfilter::NetfilterMsg, err::NlError, nl::verify_roundtrip, nl::Nlmsghdr,
+    vectors::parse_hex,
+};
+use crate::{BeU16, Buffer, BufferMut, Nl, Padding, StreamReadBuffer};
 
-type Nlattrs = Vec<Nlattr<NfLogAttr, Vec<u8>>>;
+/// `NfLogCfg::Mode` attribute requesting the first 50 bytes of each packet
+#[cfg(any(test, feature = "test-util"))]
+const VECTOR_LOG_MODE: &str = include_str!("../vectors/netfilter/linux-6.18/log_mode.hex");
 
-#[derive(Copy, Clone, Debug)]
-struct Timestamp {
-    secs: u64,
-    usecs: u64,
-}
-
-impl Nl for Timestamp {
-    fn serialize(&self, m: &mut StreamWriteBuffer) -> Result<(), SerError> {
-        u64::to_be(self.secs).serialize(m)?;
-        u64::to_be(self.usecs).serialize(m)?;
-        Ok(())
-    }
-    fn deserialize<B: AsRef<[u8]>>(m: &mut StreamReadBuffer<B>) -> Result<Self, DeError> {
-        let secs = u64::from_be(u64::deserialize(m)?);
-        let usecs = u64::from_be(u64::deserialize(m)?);
-        Ok(Self { secs, usecs })
-    }
-    fn size(&self) -> usize {
-        self.secs.size() + self.usecs.size()
-    }
-}
+/// `NetfilterMsg::LogPacket` message for a UDP packet logged with the prefix "A packet"
+#[cfg(any(test, feature = "test-util"))]
+const VECTOR_LOG_PACKET: &str = include_str!("../vectors/netfilter/linux-6.18/log_packet.hex");
 
-impl Into<SystemTime> for Timestamp {
-    fn into(self) -> SystemTime {
-        let dur = Duration::new(self.secs, (self.usecs * 1000) as u32);
-        UNIX_EPOCH + dur
-    }
-}
+type Nlattrs = Vec<Nlattr<NfLogAttr, Vec<u8>>>;
 
 /// A logged packet sent from the kernel to userspace.
 ///
@@ -67,7 +60,7 @@ pub struct LogPacket {
     /// This might be missing in case it is not yet known at the point of packet capture (outgoing
     /// packets before routing decisions) or on interfaces that don't have hardware addresses
     /// (`lo`).
-    pub hwaddr: Vec<u8>,
+    pub hwaddr: Option<HwAddr>,
     /// Payload of the packet.
     pub payload: Vec<u8>,
     /// Prefix, set at the capturing rule. May be empty.
@@ -107,7 +100,7 @@ impl LogPacket {
             hook: 0,
             mark: 0,
             timestamp: UNIX_EPOCH,
-            hwaddr: Vec::new(),
+            hwaddr: None,
             payload: Vec::new(),
             prefix: CString::default(),
             ifindex_in: None,
@@ -122,14 +115,17 @@ impl LogPacket {
 }
 
 impl Nl for LogPacket {
-    fn serialize(&self, _: &mut StreamWriteBuffer) -> Result<(), SerError> {
+    fn serialize<B>(&self, _: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
         unimplemented!("The NFLOG protocol never sends packets to kernel, no reason to know how to serialize them");
     }
-    fn deserialize<B: AsRef<[u8]>>(m: &mut StreamReadBuffer<B>) -> Result<Self, DeError> {
+    fn deserialize<B: Buffer>(m: &mut B) -> Result<Self, DeError> {
         let hint = m.take_size_hint().map(|h| h.saturating_sub(4));
         let hw_protocol = u16::from_be(Nl::deserialize(m)?);
         let hook = Nl::deserialize(m)?;
-        let _pad: u8 = Nl::deserialize(m)?;
+        Padding::<1>::deserialize(m)?;
         m.set_size_hint(hint.unwrap_or_default());
         let attrs = Nlattrs::deserialize(m)?;
         let attr_len = attrs.asize();
@@ -142,17 +138,18 @@ impl Nl for LogPacket {
             match attr.nla_type {
                 NfLogAttr::Mark => result.mark = attr.get_payload_as()?,
                 NfLogAttr::Timestamp => {
-                    result.timestamp = attr.get_payload_as::<Timestamp>()?.into();
+                    result.timestamp = attr.get_payload_as::<BeTimeval>()?.to_system_time()?;
                 }
                 NfLogAttr::Hwaddr => {
                     let mut buffer = StreamReadBuffer::new(&attr.payload);
-                    let len = u16::from_be(u16::deserialize(&mut buffer)?);
-                    let mut hwaddr = attr.payload;
-                    // Drop the len and padding
-                    hwaddr.drain(..4);
-                    hwaddr.truncate(len as usize);
-                    hwaddr.shrink_to_fit();
-                    result.hwaddr = hwaddr;
+                    let len = usize::from(BeU16::deserialize(&mut buffer)?.0);
+                    // Skip the len and padding
+                    let hwaddr = attr.payload.get(4..4 + len).ok_or(DeError::InvalidLength {
+                        field: "hwaddr",
+                        expected: 4 + len,
+                        got: attr.payload.len(),
+                    })?;
+                    result.hwaddr = Some(HwAddr::try_from(hwaddr)?);
                 }
                 NfLogAttr::Payload => result.payload = attr.payload,
                 NfLogAttr::Prefix => {
@@ -188,6 +185,29 @@ impl Nl for LogPacket {
     }
 }
 
+impl ToNlValue for LogPacket {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("hw_protocol", self.hw_protocol.to_nl_value()),
+            ("hook", self.hook.to_nl_value()),
+            ("mark", self.mark.to_nl_value()),
+            ("timestamp", self.timestamp.to_nl_value()),
+            ("hwaddr", self.hwaddr.to_nl_value()),
+            ("payload", self.payload.to_nl_value()),
+            ("prefix", self.prefix.to_nl_value()),
+            ("ifindex_in", self.ifindex_in.to_nl_value()),
+            ("ifindex_out", self.ifindex_out.to_nl_value()),
+            ("ifindex_physin", self.ifindex_physin.to_nl_value()),
+            ("ifindex_physout", self.ifindex_physout.to_nl_value()),
+            ("uid", self.uid.to_nl_value()),
+            ("gid", self.gid.to_nl_value()),
+        ])
+    }
+}
+
+/// Largest batching buffer accepted by the kernel, larger sizes are silently reduced to it
+const NFULNL_NLBUFSIZ_MAX: u32 = 131_072;
+
 /// A configuration request, to bind a socket to specific logging group.
 #[derive(Debug)]
 pub struct LogConfigReq {
@@ -229,10 +249,58 @@ impl LogConfigReq {
             attrs: cfg,
         }
     }
+
+    /// Adds a [NfLogCfg::Timeout] attribute, so that queued packets are sent to userspace at
+    /// the latest after `timeout`.
+    ///
+    /// The kernel counts in hundredths of a second, so `timeout` has to be a whole number of
+    /// them and fit in 32 bits.
+    pub fn with_timeout(mut self, timeout: Duration) -> Result<Self, SerError> {
+        if !timeout.subsec_nanos().is_multiple_of(10_000_000) {
+            return Err(SerError::new(format!(
+                "Timeout {:?} is not a multiple of 10ms",
+                timeout
+            )));
+        }
+        let centisecs = timeout.as_secs() as u128 * 100 + timeout.subsec_millis() as u128 / 10;
+        let centisecs = u32::try_from(centisecs)
+            .map_err(|_| SerError::new(format!("Timeout {:?} is too long", timeout)))?;
+        self.attrs
+            .push(Nlattr::new(None, NfLogCfg::Timeout, u32::to_be(centisecs))?);
+        Ok(self)
+    }
+
+    /// Adds a [NfLogCfg::QThresh] attribute, so that queued packets are sent to userspace once
+    /// `packets` of them are queued. At least one packet is required.
+    pub fn with_qthresh(mut self, packets: u32) -> Result<Self, SerError> {
+        if packets == 0 {
+            return Err(SerError::new("Queue threshold must be at least one packet"));
+        }
+        self.attrs
+            .push(Nlattr::new(None, NfLogCfg::QThresh, u32::to_be(packets))?);
+        Ok(self)
+    }
+
+    /// Adds a [NfLogCfg::NlBufSize] attribute, setting the size of the buffer in which the
+    /// kernel batches packets to `bytes`. Sizes the kernel would reduce are rejected.
+    pub fn with_nlbufsiz(mut self, bytes: u32) -> Result<Self, SerError> {
+        if bytes > NFULNL_NLBUFSIZ_MAX {
+            return Err(SerError::new(format!(
+                "Buffer size {} exceeds the maximum of {} bytes",
+                bytes, NFULNL_NLBUFSIZ_MAX
+            )));
+        }
+        self.attrs
+            .push(Nlattr::new(None, NfLogCfg::NlBufSize, u32::to_be(bytes))?);
+        Ok(self)
+    }
 }
 
 impl Nl for LogConfigReq {
-    fn serialize(&self, m: &mut StreamWriteBuffer) -> Result<(), SerError> {
+    fn serialize<B>(&self, m: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
         self.family.serialize(m)?;
         // protocol version
         0u8.serialize(m)?;
@@ -241,7 +309,7 @@ impl Nl for LogConfigReq {
         self.pad(m)?;
         Ok(())
     }
-    fn deserialize<B: AsRef<[u8]>>(_m: &mut StreamReadBuffer<B>) -> Result<Self, DeError> {
+    fn deserialize<B: Buffer>(_m: &mut B) -> Result<Self, DeError> {
         unimplemented!("Config requests are never sent by the kernel")
     }
     fn size(&self) -> usize {
@@ -249,6 +317,16 @@ impl Nl for LogConfigReq {
     }
 }
 
+impl ToNlValue for LogConfigReq {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("family", self.family.to_nl_value()),
+            ("group", self.group.to_nl_value()),
+            ("attrs", NlValue::list(&self.attrs)),
+        ])
+    }
+}
+
 /// Configuration mode, as a parameter to [NfLogCfg::Mode].
 #[derive(Clone, Debug)]
 pub struct LogConfigMode {
@@ -262,24 +340,324 @@ pub struct LogConfigMode {
 }
 
 impl Nl for LogConfigMode {
-    fn serialize(&self, m: &mut StreamWriteBuffer) -> Result<(), SerError> {
+    fn serialize<B>(&self, m: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
         u32::to_be(self.copy_range).serialize(m)?;
         self.copy_mode.serialize(m)?;
-        // A padding
-        0u8.serialize(m)?;
+        Padding::<1>.serialize(m)?;
         Ok(())
     }
-    fn deserialize<B: AsRef<[u8]>>(m: &mut StreamReadBuffer<B>) -> Result<Self, DeError> {
+    fn deserialize<B: Buffer>(m: &mut B) -> Result<Self, DeError> {
         let copy_range = u32::from_be(u32::deserialize(m)?);
         let copy_mode = LogCopyMode::deserialize(m)?;
-        // A padding
-        u8::deserialize(m)?;
+        Padding::<1>::deserialize(m)?;
         Ok(Self {
             copy_range,
             copy_mode,
         })
     }
     fn size(&self) -> usize {
-        self.copy_range.size() + self.copy_mode.size() + 0u8.size()
+        self.copy_range.size() + self.copy_mode.size() + Padding::<1>.size()
+    }
+}
+
+impl ToNlValue for LogConfigMode {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("copy_range", self.copy_range.to_nl_value()),
+            ("copy_mode", self.copy_mode.to_nl_value()),
+        ])
+    }
+}
+
+/// Layer 4 part of a `FlowTuple`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FlowProto {
+    /// Protocol identifying connections by ports, such as TCP, UDP, SCTP or DCCP
+    Ports {
+        /// IP protocol number
+        proto: u8,
+        /// Source port
+        src_port: u16,
+        /// Destination port
+        dst_port: u16,
+    },
+    /// ICMP or ICMPv6, identifying connections by ID, type and code
+    Icmp {
+        /// IP protocol number, `IPPROTO_ICMP` or `IPPROTO_ICMPV6`
+        proto: u8,
+        /// Identifier of echo and similar requests
+        id: u16,
+        /// ICMP type
+        icmp_type: u8,
+        /// ICMP code
+        code: u8,
+    },
+    /// Any other protocol, identified by its IP protocol number only
+    Other(u8),
+}
+
+impl FlowProto {
+    /// Layer 4 part of a tuple of the given IP protocol, taking the ports or the ICMP ID, type
+    /// and code from the corresponding conntrack attributes. Ports and ID are in host byte order.
+    pub fn new(proto: u8, src_port: u16, dst_port: u16, icmp_type: u8, code: u8) -> Self {
+        match c_int::from(proto) {
+            libc::IPPROTO_TCP
+            | libc::IPPROTO_UDP
+            | libc::IPPROTO_UDPLITE
+            | libc::IPPROTO_SCTP
+            | libc::IPPROTO_DCCP => FlowProto::Ports {
+                proto,
+                src_port,
+                dst_port,
+            },
+            libc::IPPROTO_ICMP | libc::IPPROTO_ICMPV6 => FlowProto::Icmp {
+                proto,
+                id: src_port,
+                icmp_type,
+                code,
+            },
+            _ => FlowProto::Other(proto),
+        }
+    }
+
+    /// IP protocol number
+    pub fn proto(&self) -> u8 {
+        match *self {
+            FlowProto::Ports { proto, .. } | FlowProto::Icmp { proto, .. } => proto,
+            FlowProto::Other(proto) => proto,
+        }
+    }
+
+    /// Layer 4 part of the tuple in the opposite direction. Ports are swapped and ICMP requests
+    /// are turned into their replies and vice versa, as conntrack does.
+    pub fn reverse(&self) -> Self {
+        match *self {
+            FlowProto::Ports {
+                proto,
+                src_port,
+                dst_port,
+            } => FlowProto::Ports {
+                proto,
+                src_port: dst_port,
+                dst_port: src_port,
+            },
+            FlowProto::Icmp {
+                proto,
+                id,
+                icmp_type,
+                code,
+            } => FlowProto::Icmp {
+                proto,
+                id,
+                icmp_type: icmp_reverse_type(proto, icmp_type),
+                code,
+            },
+            FlowProto::Other(proto) => FlowProto::Other(proto),
+        }
+    }
+}
+
+/// Type of the reply to an ICMP request and vice versa, or `icmp_type` for types that do not
+/// come in pairs
+fn icmp_reverse_type(proto: u8, icmp_type: u8) -> u8 {
+    const ICMP_PAIRS: &[(u8, u8)] = &[(8, 0), (13, 14), (15, 16), (17, 18)];
+    const ICMPV6_PAIRS: &[(u8, u8)] = &[(128, 129), (139, 140)];
+    let pairs = if c_int::from(proto) == libc::IPPROTO_ICMPV6 {
+        ICMPV6_PAIRS
+    } else {
+        ICMP_PAIRS
+    };
+    pairs
+        .iter()
+        .find_map(|&(request, reply)| match icmp_type {
+            t if t == request => Some(reply),
+            t if t == reply => Some(request),
+            _ => None,
+        })
+        .unwrap_or(icmp_type)
+}
+
+/// Direction of a `FlowTuple` relative to its canonical form
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FlowDirection {
+    /// The tuple is the canonical form
+    Original,
+    /// The tuple is the reverse of the canonical form
+    Reply,
+}
+
+/// Addresses and layer 4 part identifying one direction of a connection, such as the original or
+/// reply tuple of a conntrack entry
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FlowTuple {
+    /// Source address
+    pub src: IpAddr,
+    /// Destination address
+    pub dst: IpAddr,
+    /// Layer 4 protocol and its identifiers
+    pub proto: FlowProto,
+}
+
+impl FlowTuple {
+    /// Tuple of the opposite direction of the connection, as in the reply tuple of a conntrack
+    /// entry without NAT
+    pub fn reverse(&self) -> Self {
+        FlowTuple {
+            src: self.dst,
+            dst: self.src,
+            proto: self.proto.reverse(),
+        }
+    }
+
+    /// Canonical form of the tuple, which is the same for both directions of a connection, and
+    /// the direction of this tuple relative to it. The canonical form is the smaller of the tuple
+    /// and its reverse, so it is stable but does not tell which side opened the connection.
+    pub fn canonical(&self) -> (Self, FlowDirection) {
+        let reverse = self.reverse();
+        if reverse < *self {
+            (reverse, FlowDirection::Reply)
+        } else {
+            (*self, FlowDirection::Original)
+        }
+    }
+}
+
+/// Verify byte vectors of netfilter messages, captured from a little endian kernel, against
+/// deserialization and, for messages that are sent to the kernel, serialization. This can be
+/// called from downstream test suites to verify that `neli` matches the wire format of the target
+/// platform.
+#[cfg(any(test, feature = "test-util"))]
+pub fn verify_wire_compat() -> Result<(), NlError> {
+    let mode = verify_roundtrip::<Nlattr<NfLogCfg, LogConfigMode>>(&parse_hex(VECTOR_LOG_MODE)?)?;
+    if mode.payload.copy_range != 50 || mode.payload.copy_mode != LogCopyMode::Packet {
+        return Err(NlError::new("Failed to parse log configuration mode"));
+    }
+    // Log packets are never sent to the kernel so they can only be deserialized
+    let bytes = parse_hex(VECTOR_LOG_PACKET)?;
+    let packet =
+        Nlmsghdr::<NetfilterMsg, LogPacket>::deserialize(&mut StreamReadBuffer::new(&bytes))?;
+    if packet.nl_type != NetfilterMsg::LogPacket
+        || packet.nl_payload.prefix.as_bytes() != b"A packet"
+        || packet.nl_payload.ifindex_out != Some(1)
+        || packet.nl_payload.payload.len() != 32
+        || packet.nl_payload.payload[32 - 4..] != *b"neli"
+    {
+        return Err(NlError::new("Failed to parse logged packet"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_verify_wire_compat() {
+        verify_wire_compat().unwrap();
+    }
+
+    #[test]
+    fn test_log_packet_hwaddr() {
+        let hwaddr_attr = |len| {
+            vec![
+                16,
+                0,
+                libc::NFULA_HWADDR as u8,
+                0,
+                0,
+                len,
+                0,
+                0,
+                2,
+                0,
+                0,
+                0,
+                0,
+                1,
+                0,
+                0,
+            ]
+        };
+        let mut bytes = vec![0, 0, 0, 0];
+        bytes.extend(hwaddr_attr(6));
+        let mut mem = StreamReadBuffer::new(&bytes);
+        mem.set_size_hint(bytes.len());
+        let packet = LogPacket::deserialize(&mut mem).unwrap();
+        assert_eq!(packet.hwaddr.unwrap().to_string(), "02:00:00:00:00:01");
+
+        let mut bytes = vec![0, 0, 0, 0];
+        bytes.extend(hwaddr_attr(9));
+        let mut mem = StreamReadBuffer::new(&bytes);
+        mem.set_size_hint(bytes.len());
+        assert!(LogPacket::deserialize(&mut mem).is_err());
+    }
+
+    #[test]
+    fn test_flow_tuple() {
+        let tcp = FlowTuple {
+            src: "10.0.0.2".parse().unwrap(),
+            dst: "10.0.0.1".parse().unwrap(),
+            proto: FlowProto::new(libc::IPPROTO_TCP as u8, 40000, 22, 0, 0),
+        };
+        let reply = tcp.reverse();
+        assert_eq!(
+            reply.proto,
+            FlowProto::Ports {
+                proto: libc::IPPROTO_TCP as u8,
+                src_port: 22,
+                dst_port: 40000,
+            }
+        );
+        assert_eq!(reply.reverse(), tcp);
+        assert_eq!(tcp.canonical(), (reply, FlowDirection::Reply));
+        assert_eq!(reply.canonical(), (reply, FlowDirection::Original));
+
+        let echo = FlowTuple {
+            src: "fe80::1".parse().unwrap(),
+            dst: "fe80::2".parse().unwrap(),
+            proto: FlowProto::new(libc::IPPROTO_ICMPV6 as u8, 7, 0, 128, 0),
+        };
+        assert_eq!(
+            echo.reverse().proto,
+            FlowProto::Icmp {
+                proto: libc::IPPROTO_ICMPV6 as u8,
+                id: 7,
+                icmp_type: 129,
+                code: 0,
+            }
+        );
+        assert_eq!(echo.canonical().0, echo.reverse().canonical().0);
+
+        let gre = FlowProto::new(libc::IPPROTO_GRE as u8, 1, 2, 0, 0);
+        assert_eq!(gre, FlowProto::Other(libc::IPPROTO_GRE as u8));
+        assert_eq!(gre.reverse(), gre);
+        assert_eq!(gre.proto(), libc::IPPROTO_GRE as u8);
+    }
+
+    #[test]
+    fn test_log_config_timers() {
+        let req = LogConfigReq::new(libc::AF_INET, 10, vec![])
+            .with_timeout(Duration::from_millis(1500))
+            .unwrap()
+            .with_qthresh(32)
+            .unwrap()
+            .with_nlbufsiz(65536)
+            .unwrap();
+        assert_eq!(req.attrs[0].nla_type, NfLogCfg::Timeout);
+        assert_eq!(req.attrs[0].payload, vec![0, 0, 0, 150]);
+        assert_eq!(req.attrs[1].payload, vec![0, 0, 0, 32]);
+        assert_eq!(req.attrs[2].payload, vec![0, 1, 0, 0]);
+
+        let req = LogConfigReq::new(libc::AF_INET, 10, vec![]);
+        assert!(req.with_timeout(Duration::from_millis(5)).is_err());
+        let req = LogConfigReq::new(libc::AF_INET, 10, vec![]);
+        assert!(req.with_timeout(Duration::from_secs(1 << 26)).is_err());
+        let req = LogConfigReq::new(libc::AF_INET, 10, vec![]);
+        assert!(req.with_qthresh(0).is_err());
+        let req = LogConfigReq::new(libc::AF_INET, 10, vec![]);
+        assert!(req.with_nlbufsiz(NFULNL_NLBUFSIZ_MAX + 1).is_err());
     }
 }
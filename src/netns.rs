@@ -0,0 +1,298 @@
+//! This module combines routing netlink requests into the steps that container network plugins
+//! take to connect a network namespace to the host: creating a veth pair, moving one end into
+//! the namespace, assigning addresses, bringing the links up and adding a default route.
+//!
+//! Each step is available as a function taking a routing netlink socket so that the steps can be
+//! combined differently, and `plumb_veth` performs the whole sequence.
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use std::os::unix::io::AsRawFd;
+//!
+//! use neli::consts::NlFamily;
+//! use neli::netns::{plumb_veth, VethPlumbing};
+//! use neli::socket::NlSocket;
+//!
+//! let netns = File::open("/var/run/netns/container").unwrap();
+//! let mut socket = NlSocket::connect(NlFamily::Route, None, None, true).unwrap();
+//! let mut plumbing = VethPlumbing::new("veth-host", "eth0", netns.as_raw_fd());
+//! plumbing.host_addr = Some(("10.0.0.1".parse().unwrap(), 24));
+//! plumbing.peer_addr = Some(("10.0.0.2".parse().unwrap(), 24));
+//! plumbing.gateway = Some("10.0.0.1".parse().unwrap());
+//! plumb_veth(&mut socket, &plumbing).unwrap();
+//! ```
+//!
+//! # Design decisions
+//!
+//! Every request asks for an ACK so that a failing step is reported where it happens. Nothing is
+//! undone when a later step fails; use `transaction::NlTransaction` for requests that need to be
+//! rolled back together.
+
+use std::net::IpAddr;
+use std::os::unix::io::RawFd;
+
+use consts::{
+    Arphrd, Ifa, IfaF, Iff, Ifla, IflaInfo, NlFamily, NlmFFlags, RtAddrFamily, RtScope, RtTable,
+    Rta, Rtm, Rtn, Rtprot,
+};
+use err::NlError;
+use nl::Nlmsghdr;
+use rtnl::{IfIndex, IfIndexCache, Ifaddrmsg, Ifinfomsg, Rtattr, Rtattrs, Rtmsg};
+use socket::NlSocket;
+use Nl;
+
+/// `VETH_INFO_PEER` from `linux/veth.h`, holding the link message of the peer
+const VETH_INFO_PEER: libc::c_ushort = 1;
+
+/// Send a request and wait for its ACK
+fn request<P>(
+    socket: &mut NlSocket,
+    nl_type: Rtm,
+    flags: NlmFFlags,
+    payload: P,
+) -> Result<(), NlError>
+where
+    P: Nl,
+{
+    let msg = Nlmsghdr::new(None, nl_type, flags.ack().into(), None, None, payload);
+    socket.request::<_, _, Rtm, Vec<u8>>(msg)?;
+    Ok(())
+}
+
+/// Look up the index of the link called `name`
+pub fn link_index(socket: &mut NlSocket, name: &str) -> Result<IfIndex, NlError> {
+    IfIndexCache::load(socket)?
+        .index(name)
+        .ok_or_else(|| NlError::new(&format!("No link named {}", name)))
+}
+
+/// Create a veth pair of links called `name` and `peer_name`
+pub fn create_veth(socket: &mut NlSocket, name: &str, peer_name: &str) -> Result<(), NlError> {
+    let peer = Ifinfomsg::new(
+        RtAddrFamily::Unspec,
+        Arphrd::None,
+        IfIndex(0),
+        vec![],
+        Rtattrs::new(vec![Rtattr::new(None, Ifla::Ifname, peer_name)?]),
+    );
+    let mut data = Rtattr::new(None, libc::c_ushort::from(IflaInfo::Data), Vec::<u8>::new())?;
+    data.add_nested_attribute(&Rtattr::new(None, VETH_INFO_PEER, peer)?)?;
+    let mut linkinfo = Rtattr::new(None, Ifla::Linkinfo, Vec::<u8>::new())?;
+    linkinfo.add_nested_attribute(&Rtattr::new(
+        None,
+        libc::c_ushort::from(IflaInfo::Kind),
+        "veth",
+    )?)?;
+    linkinfo.add_nested_attribute(&data)?;
+    let ifinfomsg = Ifinfomsg::new(
+        RtAddrFamily::Unspec,
+        Arphrd::None,
+        IfIndex(0),
+        vec![],
+        Rtattrs::new(vec![Rtattr::new(None, Ifla::Ifname, name)?, linkinfo]),
+    );
+    request(socket, Rtm::Newlink, NlmFFlags::create_excl(), ifinfomsg)
+}
+
+/// Move the link `index` into the network namespace referred to by the file descriptor `netns`.
+/// The link keeps its name but may get a different index in the namespace.
+pub fn move_to_netns(socket: &mut NlSocket, index: IfIndex, netns: RawFd) -> Result<(), NlError> {
+    let ifinfomsg = Ifinfomsg::new(
+        RtAddrFamily::Unspec,
+        Arphrd::None,
+        index,
+        vec![],
+        Rtattrs::new(vec![Rtattr::new(None, Ifla::NetNsFd, netns as u32)?]),
+    );
+    request(socket, Rtm::Newlink, NlmFFlags::request(), ifinfomsg)
+}
+
+/// Bring the link `index` up without changing its other flags
+pub fn set_up(socket: &mut NlSocket, index: IfIndex) -> Result<(), NlError> {
+    let mut ifinfomsg = Ifinfomsg::new(
+        RtAddrFamily::Unspec,
+        Arphrd::None,
+        index,
+        vec![Iff::Up],
+        Rtattrs::empty(),
+    );
+    ifinfomsg.set_change(&[Iff::Up]);
+    request(socket, Rtm::Newlink, NlmFFlags::request(), ifinfomsg)
+}
+
+/// Assign `addr` with a prefix of `prefix_len` bits to the link `index`
+pub fn add_address(
+    socket: &mut NlSocket,
+    index: IfIndex,
+    addr: IpAddr,
+    prefix_len: u8,
+) -> Result<(), NlError> {
+    let ifaddrmsg = Ifaddrmsg {
+        ifa_family: RtAddrFamily::from(&addr),
+        ifa_prefixlen: prefix_len,
+        ifa_flags: Vec::<IfaF>::new(),
+        ifa_scope: RtScope::Universe.into(),
+        ifa_index: index,
+        rtattrs: Rtattrs::new(vec![
+            Rtattr::new(None, Ifa::Local, addr)?,
+            Rtattr::new(None, Ifa::Address, addr)?,
+        ]),
+    };
+    request(socket, Rtm::Newaddr, NlmFFlags::create_excl(), ifaddrmsg)
+}
+
+/// Add a default route through `gateway` to the main routing table
+pub fn add_default_route(socket: &mut NlSocket, gateway: IpAddr) -> Result<(), NlError> {
+    let rtmsg = Rtmsg {
+        rtm_family: RtAddrFamily::from(&gateway),
+        rtm_dst_len: 0,
+        rtm_src_len: 0,
+        rtm_tos: 0,
+        rtm_table: RtTable::Main,
+        rtm_protocol: Rtprot::Boot,
+        rtm_scope: RtScope::Universe,
+        rtm_type: Rtn::Unicast,
+        rtm_flags: vec![],
+        rtattrs: Rtattrs::new(vec![Rtattr::new(None, Rta::Gateway, gateway)?]),
+    };
+    request(socket, Rtm::Newroute, NlmFFlags::create_excl(), rtmsg)
+}
+
+/// Settings for `plumb_veth`
+#[derive(Clone, Debug)]
+pub struct VethPlumbing<'a> {
+    /// Name of the veth end staying in the namespace of the socket
+    pub host_name: &'a str,
+    /// Name of the veth end moved into the target namespace
+    pub peer_name: &'a str,
+    /// File descriptor referring to the target network namespace
+    pub netns: RawFd,
+    /// Address and prefix length to assign to the host end
+    pub host_addr: Option<(IpAddr, u8)>,
+    /// Address and prefix length to assign to the peer end
+    pub peer_addr: Option<(IpAddr, u8)>,
+    /// Gateway of the default route added in the target namespace
+    pub gateway: Option<IpAddr>,
+}
+
+impl<'a> VethPlumbing<'a> {
+    /// Connect `netns` through a veth pair without assigning addresses or routes
+    pub fn new(host_name: &'a str, peer_name: &'a str, netns: RawFd) -> Self {
+        VethPlumbing {
+            host_name,
+            peer_name,
+            netns,
+            host_addr: None,
+            peer_addr: None,
+            gateway: None,
+        }
+    }
+}
+
+/// Create a veth pair, move its peer end into the target namespace, assign the configured
+/// addresses, bring both ends and the loopback link of the namespace up and add the default
+/// route. `socket` is a routing netlink socket in the namespace the host end stays in. Entering
+/// the target namespace requires `CAP_SYS_ADMIN`.
+pub fn plumb_veth(socket: &mut NlSocket, plumbing: &VethPlumbing<'_>) -> Result<(), NlError> {
+    create_veth(socket, plumbing.host_name, plumbing.peer_name)?;
+    let peer = link_index(socket, plumbing.peer_name)?;
+    move_to_netns(socket, peer, plumbing.netns)?;
+    let host = link_index(socket, plumbing.host_name)?;
+    if let Some((addr, prefix_len)) = plumbing.host_addr {
+        add_address(socket, host, addr, prefix_len)?;
+    }
+    set_up(socket, host)?;
+
+    let mut ns_socket =
+        NlSocket::connect_in_netns(plumbing.netns, NlFamily::Route, None, None, true)?;
+    let links = IfIndexCache::load(&mut ns_socket)?;
+    if let Some(lo) = links.index("lo") {
+        set_up(&mut ns_socket, lo)?;
+    }
+    let peer = links
+        .index(plumbing.peer_name)
+        .ok_or_else(|| NlError::new(&format!("No link named {}", plumbing.peer_name)))?;
+    if let Some((addr, prefix_len)) = plumbing.peer_addr {
+        add_address(&mut ns_socket, peer, addr, prefix_len)?;
+    }
+    set_up(&mut ns_socket, peer)?;
+    if let Some(gateway) = plumbing.gateway {
+        add_default_route(&mut ns_socket, gateway)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::fs::File;
+    use std::os::unix::io::AsRawFd;
+    use std::thread;
+
+    use rtnl::Rtgenmsg;
+
+    /// Open a new network namespace, or return `None` without the privileges to create one
+    fn new_netns() -> Option<File> {
+        thread::spawn(|| {
+            if unsafe { libc::unshare(libc::CLONE_NEWNET) } != 0 {
+                return None;
+            }
+            File::open("/proc/thread-self/ns/net").ok()
+        })
+        .join()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_plumb_veth() {
+        let (host_ns, target_ns) = match (new_netns(), new_netns()) {
+            (Some(host_ns), Some(target_ns)) => (host_ns, target_ns),
+            _ => return,
+        };
+        let mut socket =
+            NlSocket::connect_in_netns(host_ns.as_raw_fd(), NlFamily::Route, None, None, true)
+                .unwrap();
+        let mut plumbing = VethPlumbing::new("neli-host", "neli-peer", target_ns.as_raw_fd());
+        plumbing.host_addr = Some(("10.200.0.1".parse().unwrap(), 24));
+        plumbing.peer_addr = Some(("10.200.0.2".parse().unwrap(), 24));
+        plumbing.gateway = Some("10.200.0.1".parse().unwrap());
+        plumb_veth(&mut socket, &plumbing).unwrap();
+        assert!(link_index(&mut socket, "neli-peer").is_err());
+
+        let mut ns_socket =
+            NlSocket::connect_in_netns(target_ns.as_raw_fd(), NlFamily::Route, None, None, true)
+                .unwrap();
+        let peer = link_index(&mut ns_socket, "neli-peer").unwrap();
+        let addrs = ns_socket
+            .request::<_, _, Rtm, Ifaddrmsg>(Nlmsghdr::new(
+                None,
+                Rtm::Getaddr,
+                NlmFFlags::dump_request().into(),
+                None,
+                None,
+                Rtgenmsg {
+                    rtgen_family: RtAddrFamily::Inet,
+                },
+            ))
+            .unwrap();
+        assert!(addrs.iter().any(|msg| msg.nl_payload.ifa_index == peer
+            && msg.nl_payload.get_addr(Ifa::Local).unwrap()
+                == Some("10.200.0.2".parse().unwrap())));
+        let routes = ns_socket
+            .request::<_, _, Rtm, Rtmsg>(Nlmsghdr::new(
+                None,
+                Rtm::Getroute,
+                NlmFFlags::dump_request().into(),
+                None,
+                None,
+                Rtgenmsg {
+                    rtgen_family: RtAddrFamily::Inet,
+                },
+            ))
+            .unwrap();
+        assert!(routes.iter().any(|msg| msg.nl_payload.rtm_dst_len == 0
+            && msg.nl_payload.get_addr(Rta::Gateway).unwrap()
+                == Some("10.200.0.1".parse().unwrap())));
+    }
+}
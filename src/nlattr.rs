@@ -52,41 +52,56 @@
 //!     attribute payloads contained in the `Genlmsghdr` payload
 //!     * Only `Nlattr` knows what is padding and what is not in its own payload - to every other
 //!     serialization and deserialization method, it may or may not be padding
+//!
+//! Attributes are never dropped or reordered during parsing. An attribute type that `neli` does
+//! not know about is deserialized as the `UnrecognizedVariant` of the attribute type enum with
+//! its raw payload, so a parsed message containing unknown attributes serializes back to the same
+//! bytes it was parsed from. This also holds for `Rtattr` in `rtnl.rs`.
+//!
+//! `Nlattr<T, &[u8]>` and `AttrRefIter` parse attributes lazily with payloads borrowed from the
+//! receive buffer. Consumers handling many messages, such as NFLOG collectors, can use them to
+//! avoid copying every attribute payload into its own `Vec<u8>`.
 
+use std::marker::PhantomData;
 use std::slice;
 
 use buffering::{StreamReadBuffer, StreamWriteBuffer};
 
-use consts::NlAttrType;
-use err::{DeError, NlError, SerError};
-use Nl;
+use consts::{alignto, NlAttrType};
+use err::{short_type_name, sub_len, DeError, NlError, SerError};
+use limits::{check_attr_count, NestGuard};
+use value::{NlValue, ToNlValue};
+use {Buffer, BufferMut, Nl};
 
 impl<T, P> Nl for Vec<Nlattr<T, P>>
 where
     T: NlAttrType,
     P: Nl,
 {
-    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
         for item in self.iter() {
             item.serialize(mem)?;
         }
         Ok(())
     }
 
-    fn deserialize<B>(mem: &mut StreamReadBuffer<B>) -> Result<Self, DeError>
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
     where
-        B: AsRef<[u8]>,
+        B: Buffer,
     {
+        let _nest = NestGuard::enter()?;
         let mut vec = Vec::new();
         let mut size_hint = mem.take_size_hint();
-        while size_hint > Some(0) || (size_hint == None && !mem.at_end()) {
-            let next = Nlattr::<T, P>::deserialize(mem)?;
+        while size_hint > Some(0) || (size_hint.is_none() && !mem.at_end()) {
+            check_attr_count(vec.len() + 1)?;
+            let next = Nlattr::<T, P>::deserialize(mem)
+                .map_err(|e| e.context(format!("attribute {}", vec.len())))?;
             if let Some(val) = size_hint {
                 if val > 0 {
-                    let result = val.checked_sub(next.asize()).ok_or_else(|| {
-                        DeError::new("Deserialization read passed the end of the specified buffer")
-                    })?;
-                    size_hint = Some(result);
+                    size_hint = Some(sub_len("size_hint", val, next.asize())?);
                 }
             }
             vec.push(next);
@@ -112,20 +127,23 @@ where
     }
 }
 
-impl<'a, T> Nl for &'a [Nlattr<T, Vec<u8>>]
+impl<T> Nl for &[Nlattr<T, Vec<u8>>]
 where
     T: NlAttrType,
 {
-    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
         for item in self.iter() {
             item.serialize(mem)?;
         }
         Ok(())
     }
 
-    fn deserialize<B>(_: &mut StreamReadBuffer<B>) -> Result<Self, DeError>
+    fn deserialize<B>(_: &mut B) -> Result<Self, DeError>
     where
-        B: AsRef<[u8]>,
+        B: Buffer,
     {
         unimplemented!("Use deserialize_buf instead")
     }
@@ -145,6 +163,7 @@ where
 
 /// Struct representing netlink attributes and payloads
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct Nlattr<T, P> {
     /// Length of the attribute header and payload together
     pub nla_len: u16,
@@ -224,7 +243,7 @@ where
     }
 
     /// Return an `AttrHandle` for attributes nested in the given attribute payload
-    pub fn get_nested_attributes<R>(&self) -> Result<AttrHandle<R>, DeError>
+    pub fn get_nested_attributes<R>(&self) -> Result<AttrHandle<'_, R>, DeError>
     where
         R: NlAttrType,
     {
@@ -234,12 +253,116 @@ where
     }
 }
 
+impl<'a, T> Nlattr<T, &'a [u8]>
+where
+    T: NlAttrType,
+{
+    /// Parse the attribute at the start of `buf` without copying its payload. Returns the
+    /// attribute and the data following it and its padding.
+    pub fn parse_borrowed(buf: &'a [u8]) -> Result<(Self, &'a [u8]), DeError> {
+        let mut mem = StreamReadBuffer::new(buf);
+        let nla_len = u16::deserialize(&mut mem)?;
+        let nla_type = T::deserialize(&mut mem)?;
+        let hdr_len = nla_len.size() + nla_type.size();
+        sub_len("nla_len", nla_len as usize, hdr_len)?;
+        let payload = buf
+            .get(hdr_len..nla_len as usize)
+            .ok_or_else(|| DeError::new("Attribute extends past the end of the buffer"))?;
+        let rest = buf.get(alignto(nla_len as usize)..).unwrap_or(&[]);
+        Ok((
+            Nlattr {
+                nla_len,
+                nla_type,
+                payload,
+            },
+            rest,
+        ))
+    }
+
+    /// Get the borrowed payload as a provided type
+    pub fn get_payload_as<R>(&self) -> Result<R, DeError>
+    where
+        R: Nl,
+    {
+        let mut buf = StreamReadBuffer::new(self.payload);
+        buf.set_size_hint(self.payload.len());
+        R::deserialize(&mut buf)
+    }
+
+    /// Iterate over the attributes nested in the payload without copying them
+    pub fn nested<S>(&self) -> AttrRefIter<'a, S>
+    where
+        S: NlAttrType,
+    {
+        AttrRefIter::new(self.payload)
+    }
+
+    /// Copy the payload into an owned attribute
+    pub fn into_owned(self) -> Nlattr<T, Vec<u8>> {
+        Nlattr {
+            nla_len: self.nla_len,
+            nla_type: self.nla_type,
+            payload: self.payload.to_vec(),
+        }
+    }
+}
+
+/// Iterator over the attributes stored in a buffer, yielding attributes with payloads borrowed
+/// from the buffer. Iteration stops after the first error.
+pub struct AttrRefIter<'a, T> {
+    buf: &'a [u8],
+    count: usize,
+    attr_type: PhantomData<T>,
+}
+
+impl<'a, T> AttrRefIter<'a, T>
+where
+    T: NlAttrType,
+{
+    /// Construct an iterator over the attributes in `buf`
+    pub fn new(buf: &'a [u8]) -> Self {
+        AttrRefIter {
+            buf,
+            count: 0,
+            attr_type: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> Iterator for AttrRefIter<'a, T>
+where
+    T: NlAttrType,
+{
+    type Item = Result<Nlattr<T, &'a [u8]>, DeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.is_empty() {
+            return None;
+        }
+        self.count += 1;
+        let res = check_attr_count(self.count).and_then(|_| Nlattr::parse_borrowed(self.buf));
+        match res {
+            Ok((attr, rest)) => {
+                self.buf = rest;
+                Some(Ok(attr))
+            }
+            Err(e) => {
+                self.buf = &[];
+                Some(Err(e))
+            }
+        }
+    }
+}
+
 impl<T, P> Nl for Nlattr<T, P>
 where
     T: NlAttrType,
     P: Nl,
 {
-    fn serialize(&self, mem: &mut StreamWriteBuffer) -> Result<(), SerError> {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
         self.nla_len.serialize(mem)?;
         self.nla_type.serialize(mem)?;
         self.payload.serialize(mem)?;
@@ -247,14 +370,24 @@ where
         Ok(())
     }
 
-    fn deserialize<B>(mem: &mut StreamReadBuffer<B>) -> Result<Self, DeError>
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
     where
-        B: AsRef<[u8]>,
+        B: Buffer,
     {
         let nla_len = u16::deserialize(mem)?;
         let nla_type = T::deserialize(mem)?;
-        mem.set_size_hint(nla_len as usize - (nla_len.size() + nla_type.size()));
-        let payload = P::deserialize(mem)?;
+        mem.set_size_hint(sub_len(
+            "nla_len",
+            nla_len as usize,
+            nla_len.size() + nla_type.size(),
+        )?);
+        let payload = match P::deserialize(mem) {
+            Ok(payload) => payload,
+            Err(e) => {
+                let ty: u16 = nla_type.into();
+                return Err(e.context(format!("{} {}", short_type_name::<T>(), ty)));
+            }
+        };
         let nla = Nlattr {
             nla_len,
             nla_type,
@@ -269,6 +402,36 @@ where
     }
 }
 
+impl<T, P> ToNlValue for Nlattr<T, P>
+where
+    T: ToNlValue,
+    P: ToNlValue,
+{
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("nla_len", self.nla_len.to_nl_value()),
+            ("nla_type", self.nla_type.to_nl_value()),
+            ("payload", self.payload.to_nl_value()),
+        ])
+    }
+}
+
+/// Generates an attribute with a random binary payload and `nla_len` matching it
+#[cfg(feature = "arbitrary")]
+impl<'a, T> ::arbitrary::Arbitrary<'a> for Nlattr<T, Vec<u8>>
+where
+    T: NlAttrType + ::arbitrary::Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut ::arbitrary::Unstructured<'a>) -> ::arbitrary::Result<Self> {
+        let nla_type = u.arbitrary()?;
+        let payload: Vec<u8> = u.arbitrary()?;
+        if payload.len() > usize::from(u16::MAX) - 4 {
+            return Err(::arbitrary::Error::IncorrectFormat);
+        }
+        Nlattr::new(None, nla_type, payload).map_err(|_| ::arbitrary::Error::IncorrectFormat)
+    }
+}
+
 /// Handle returned by `Genlmsghdr` for traversing nested attribute structures
 pub enum AttrHandle<'a, T> {
     /// Owned vector
@@ -313,12 +476,12 @@ where
     }
 
     /// If attributes are parsed, pass back iterator over attributes
-    pub fn iter(&self) -> slice::Iter<Nlattr<T, Vec<u8>>> {
+    pub fn iter(&self) -> slice::Iter<'_, Nlattr<T, Vec<u8>>> {
         self.get_slice().iter()
     }
 
     /// Get the payload of an attribute as a handle for parsing nested attributes
-    pub fn get_nested_attributes<S>(&mut self, subattr: T) -> Result<AttrHandle<S>, NlError>
+    pub fn get_nested_attributes<S>(&mut self, subattr: T) -> Result<AttrHandle<'_, S>, NlError>
     where
         S: NlAttrType,
     {
@@ -333,24 +496,15 @@ where
     }
 
     /// Get nested attributes from a parsed handle
-    pub fn get_attribute<'b>(&'b self, t: T) -> Option<&'b Nlattr<T, Vec<u8>>> {
-        for item in self.get_slice().iter() {
-            if item.nla_type == t {
-                return Some(&item);
-            }
-        }
-        None
+    pub fn get_attribute(&self, t: T) -> Option<&Nlattr<T, Vec<u8>>> {
+        self.get_slice().iter().find(|item| item.nla_type == t)
     }
 
     /// Mutably get nested attributes from a parsed handle
-    pub fn get_attribute_mut<'b>(&'b mut self, t: T) -> Option<&'b mut Nlattr<T, Vec<u8>>> {
-        let vec_mut = self.get_vec_mut()?;
-        for item in vec_mut.iter_mut() {
-            if item.nla_type == t {
-                return Some(item);
-            }
-        }
-        None
+    pub fn get_attribute_mut(&mut self, t: T) -> Option<&mut Nlattr<T, Vec<u8>>> {
+        self.get_vec_mut()?
+            .iter_mut()
+            .find(|item| item.nla_type == t)
     }
 
     /// Parse binary payload as a type that implements `Nl` using `deserialize` with an option size
@@ -524,5 +678,54 @@ mod test {
         let deserialized = Vec::<Nlattr<u16, Vec<u8>>>::deserialize(&mut reader).unwrap();
 
         assert_eq!(vec, deserialized);
+
+        let buf = sw.as_ref();
+        let borrowed = AttrRefIter::<u16>::new(buf)
+            .map(|attr| attr.unwrap().into_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(vec, borrowed);
+        let mut iter = AttrRefIter::<u16>::new(buf);
+        let nested = iter.next().unwrap().unwrap();
+        let types = nested
+            .nested::<u16>()
+            .map(|attr| attr.unwrap().nla_type)
+            .collect::<Vec<_>>();
+        assert_eq!(types, vec![1, 2, 3, 4]);
+        match AttrRefIter::<u16>::new(&buf[..buf.len() - 4]).nth(2) {
+            Some(Err(DeError::Msg(_))) => (),
+            res => panic!("Unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_error_context() {
+        let mut buf = Cursor::new(vec![]);
+        buf.write_u16::<NativeEndian>(8).unwrap();
+        buf.write_u16::<NativeEndian>(CtrlAttr::FamilyId.into())
+            .unwrap();
+        buf.write_u32::<NativeEndian>(0x10).unwrap();
+        // u32 payload cut short by the end of the buffer
+        buf.write_u16::<NativeEndian>(6).unwrap();
+        buf.write_u16::<NativeEndian>(CtrlAttr::FamilyName.into())
+            .unwrap();
+        buf.write_all(&[1, 2]).unwrap();
+
+        let err =
+            Vec::<Nlattr<CtrlAttr, u32>>::deserialize(&mut StreamReadBuffer::new(buf.into_inner()))
+                .unwrap_err();
+        match err {
+            DeError::Context {
+                ref path,
+                ref error,
+            } => {
+                assert_eq!(path, &["attribute 1", "CtrlAttr 2"]);
+                assert!(error.root().to_string().contains("fill whole buffer"));
+            }
+            ref e => panic!("Unexpected error {:?}", e),
+        }
+        assert!(err
+            .context("Genlmsghdr.attrs")
+            .to_string()
+            .ends_with("(while parsing Genlmsghdr.attrs > attribute 1 > CtrlAttr 2)"));
     }
 }
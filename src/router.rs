@@ -0,0 +1,819 @@
+//! This module provides a router that lets several threads share one netlink socket.
+//!
+//! `NlRouter` owns the socket along with a background thread that receives every message. Each
+//! request sent through the router gets its own sequence number and an `NlRouterHandle` that
+//! yields the responses to that request only. Messages that do not answer a pending request, such
+//! as multicast notifications, are passed to the `NlUnsolicited` receiver returned with the
+//! router.
+//!
+//! Several independent components of a process, such as libraries that each need netlink access,
+//! can share one router and with it one socket and port ID. Replies are only ever passed to the
+//! handle of the request they answer, so components do not see each other's responses. A
+//! component that wants notifications opts in to the message types it handles with
+//! `NlRouter::subscribe` and gets its own `NlSubscription`. A component can also reserve a range
+//! of sequence numbers with `NlRouter::reserve_seq_range` and send its requests with
+//! `NlRouter::send_in`, so that replies arriving after it dropped their handle are discarded
+//! instead of being passed to other components as unsolicited messages.
+//!
+//! # Design decisions
+//!
+//! * Messages are passed through channels as raw bytes and parsed by the receiving side so that
+//!   requests with different payload types can be in flight at the same time.
+//! * A request is complete once an ACK, an error, or `NLMSG_DONE` is received, or, for requests
+//!   without `NlmF::Ack`, a single message without `NlmF::Multi`. Its handle then stops yielding
+//!   messages. ACKs themselves are not yielded and errors are returned as `NlError::Errno`.
+//! * Sequence numbers from `SEQ_RANGES_START` up are handed out as reserved ranges and the
+//!   numbers below it are used for requests sent with `send`. Unicast messages with a number in
+//!   the reserved space are never unsolicited, as only replies carry such numbers.
+//! * Notifications caused by our own requests carry their sequence number, so datagrams sent to
+//!   a multicast group are always treated as unsolicited regardless of their sequence number.
+//! * Every subscription matching an unsolicited message receives a copy of it. Only messages that
+//!   no subscription matches are passed to `NlUnsolicited`.
+//! * Subscriptions have bounded queues so that a component that stops receiving cannot stall the
+//!   receiving thread or use up memory at the expense of the others. Messages that do not fit
+//!   are dropped for that subscription only, and its next `recv` reports `ENOBUFS` like the
+//!   kernel does when a socket receive buffer overflows.
+//! * The background thread reads from a duplicate of the socket file descriptor and wakes up
+//!   periodically to notice that the router has been dropped, as netlink sockets do not support
+//!   `shutdown`.
+
+use std::cmp;
+use std::collections::HashMap;
+use std::io;
+use std::marker::PhantomData;
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender, TryRecvError, TrySendError};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+
+use buffering::{StreamReadBuffer, StreamWriteBuffer};
+use libc;
+
+use consts::{alignto, NlType, NlmF, Nlmsg};
+use endian;
+use err::NlError;
+use nl::Nlmsghdr;
+use socket::NlSocket;
+use {Nl, MAX_NL_LENGTH};
+
+// Length of a netlink message header
+const NLMSG_HDR_LEN: usize = 16;
+// Time in milliseconds the receiving thread waits for data before checking if it should stop
+const POLL_INTERVAL_MS: libc::c_int = 100;
+// First sequence number of the space reserved ranges are allocated from
+const SEQ_RANGES_START: u32 = 0x8000_0000;
+
+type Response = Result<Vec<u8>, NlError>;
+
+struct PendingRequest {
+    sender: Sender<Response>,
+    ack: bool,
+}
+
+// `None` once the receiving thread has stopped and no more responses will be routed
+type PendingMap = Arc<Mutex<Option<HashMap<u32, PendingRequest>>>>;
+
+struct Subscriber {
+    // Message types passed to the subscription, all types if empty
+    types: Vec<u16>,
+    sender: SyncSender<Vec<u8>>,
+    dropped: Arc<AtomicUsize>,
+}
+
+// `None` once the receiving thread has stopped, like `PendingMap`
+type SubscriberList = Arc<Mutex<Option<Vec<Subscriber>>>>;
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // The protected state is only modified by single operations, so it is consistent even if a
+    // thread panicked while holding the lock
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Socket wrapper that routes responses to the thread that sent the request
+pub struct NlRouter {
+    socket: Mutex<NlSocket>,
+    seq: AtomicU32,
+    next_range: Mutex<u32>,
+    pending: PendingMap,
+    subscribers: SubscriberList,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl NlRouter {
+    /// Take ownership of `socket` and start receiving from it. Messages that are not responses
+    /// to requests sent through the router are available from the returned `NlUnsolicited`.
+    pub fn new(socket: NlSocket) -> Result<(Self, NlUnsolicited), io::Error> {
+        let reader = socket.try_clone()?;
+        let pending = Arc::new(Mutex::new(Some(HashMap::new())));
+        let subscribers = Arc::new(Mutex::new(Some(Vec::new())));
+        let running = Arc::new(AtomicBool::new(true));
+        let (sender, receiver) = mpsc::channel();
+        let thread = {
+            let pending = Arc::clone(&pending);
+            let subscribers = Arc::clone(&subscribers);
+            let running = Arc::clone(&running);
+            thread::Builder::new()
+                .name("neli-router".to_string())
+                .spawn(move || route(&reader, &pending, &subscribers, &sender, &running))?
+        };
+        Ok((
+            NlRouter {
+                socket: Mutex::new(socket),
+                seq: AtomicU32::new(1),
+                next_range: Mutex::new(SEQ_RANGES_START),
+                pending,
+                subscribers,
+                running,
+                thread: Some(thread),
+            },
+            NlUnsolicited { receiver },
+        ))
+    }
+
+    /// Send a request with the next free sequence number and return a handle yielding the
+    /// responses to it. Any sequence number already set in `msg` is overwritten.
+    pub fn send<T, P, R, Q>(&self, msg: Nlmsghdr<T, P>) -> Result<NlRouterHandle<R, Q>, NlError>
+    where
+        T: Nl + NlType,
+        P: Nl,
+        R: Nl + NlType,
+        Q: Nl,
+    {
+        self.send_with_seq(|| self.next_seq(), SEQ_RANGES_START - 1, msg)
+    }
+
+    /// Send a request with the next free sequence number of `range`, which must have been
+    /// reserved on this router, and return a handle yielding the responses to it. Fails if every
+    /// number of the range is used by a request still in flight.
+    pub fn send_in<T, P, R, Q>(
+        &self,
+        range: &NlSeqRange,
+        msg: Nlmsghdr<T, P>,
+    ) -> Result<NlRouterHandle<R, Q>, NlError>
+    where
+        T: Nl + NlType,
+        P: Nl,
+        R: Nl + NlType,
+        Q: Nl,
+    {
+        self.send_with_seq(|| range.next_seq(), range.len, msg)
+    }
+
+    /// Reserve `len` sequence numbers for the requests of one component sent with `send_in`.
+    /// Ranges are not reused, so components should reserve one range each and keep it.
+    pub fn reserve_seq_range(&self, len: u32) -> Result<NlSeqRange, NlError> {
+        let mut next_range = lock(&self.next_range);
+        let start = *next_range;
+        match start.checked_add(len) {
+            Some(end) if len > 0 => {
+                *next_range = end;
+                Ok(NlSeqRange {
+                    start,
+                    len,
+                    next: AtomicU32::new(0),
+                })
+            }
+            _ => Err(NlError::new(
+                "No sequence range of the requested length is left",
+            )),
+        }
+    }
+
+    /// Send `msg` with the first sequence number returned by `next_seq` that is not used by a
+    /// request still in flight. `len` is the number of distinct sequence numbers `next_seq`
+    /// cycles through.
+    fn send_with_seq<F, T, P, R, Q>(
+        &self,
+        next_seq: F,
+        len: u32,
+        mut msg: Nlmsghdr<T, P>,
+    ) -> Result<NlRouterHandle<R, Q>, NlError>
+    where
+        F: Fn() -> u32,
+        T: Nl + NlType,
+        P: Nl,
+        R: Nl + NlType,
+        Q: Nl,
+    {
+        msg.check_len()?;
+
+        // Register the request before sending it so that no response can be missed
+        let (sender, receiver) = mpsc::channel();
+        let seq = match *lock(&self.pending) {
+            Some(ref mut pending) => {
+                // One more attempt than there are requests in flight always finds a free number
+                let attempts = cmp::min(len as usize, pending.len() + 1);
+                let seq = (0..attempts)
+                    .map(|_| next_seq())
+                    .find(|seq| !pending.contains_key(seq))
+                    .ok_or_else(|| NlError::new("All sequence numbers are in use"))?;
+                pending.insert(
+                    seq,
+                    PendingRequest {
+                        sender,
+                        ack: msg.nl_flags.contains(&NlmF::Ack),
+                    },
+                );
+                seq
+            }
+            None => return Err(NlError::new("Router is no longer receiving messages")),
+        };
+        msg.nl_seq = seq;
+        let mut mem = StreamWriteBuffer::new_growable(Some(msg.asize()));
+        let res = msg
+            .serialize(&mut mem)
+            .map_err(NlError::from)
+            .and_then(|_| lock(&self.socket).send(mem, 0).map_err(NlError::from));
+        if let Err(e) = res {
+            if let Some(ref mut pending) = *lock(&self.pending) {
+                pending.remove(&seq);
+            }
+            return Err(e);
+        }
+        Ok(NlRouterHandle {
+            receiver,
+            seq,
+            data_type: PhantomData,
+            data_payload: PhantomData,
+        })
+    }
+
+    /// Receive copies of unsolicited messages with one of the given types, or of all unsolicited
+    /// messages if `types` is empty. At most `queue_len` messages are kept for the subscription;
+    /// further messages are dropped until it catches up. `queue_len` must be at least 1.
+    pub fn subscribe<T>(&self, types: Vec<T>, queue_len: usize) -> Result<NlSubscription, NlError>
+    where
+        T: NlType,
+    {
+        if queue_len == 0 {
+            return Err(NlError::new("Subscription queue length must be at least 1"));
+        }
+        let (sender, receiver) = mpsc::sync_channel(queue_len);
+        let dropped = Arc::new(AtomicUsize::new(0));
+        match *lock(&self.subscribers) {
+            Some(ref mut subscribers) => subscribers.push(Subscriber {
+                types: types.into_iter().map(|ty| ty.into()).collect(),
+                sender,
+                dropped: Arc::clone(&dropped),
+            }),
+            None => return Err(NlError::new("Router is no longer receiving messages")),
+        }
+        Ok(NlSubscription { receiver, dropped })
+    }
+
+    fn next_seq(&self) -> u32 {
+        // Sequence number 0 is used by unsolicited messages, so skip it on wrap around
+        loop {
+            let seq = self.seq.fetch_add(1, Ordering::SeqCst) % SEQ_RANGES_START;
+            if seq != 0 {
+                return seq;
+            }
+        }
+    }
+}
+
+/// Sequence numbers reserved for the requests of one component sharing an `NlRouter`
+#[derive(Debug)]
+pub struct NlSeqRange {
+    start: u32,
+    len: u32,
+    next: AtomicU32,
+}
+
+impl NlSeqRange {
+    /// First sequence number of the range
+    pub fn start(&self) -> u32 {
+        self.start
+    }
+
+    /// Sequence number following the last one of the range
+    pub fn end(&self) -> u32 {
+        self.start + self.len
+    }
+
+    /// Returns true if `seq` is in the range
+    pub fn contains(&self, seq: u32) -> bool {
+        seq >= self.start && seq - self.start < self.len
+    }
+
+    fn next_seq(&self) -> u32 {
+        self.start + self.next.fetch_add(1, Ordering::SeqCst) % self.len
+    }
+}
+
+impl Drop for NlRouter {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Handle yielding the responses to one request sent through an `NlRouter`
+pub struct NlRouterHandle<T, P> {
+    receiver: Receiver<Response>,
+    seq: u32,
+    data_type: PhantomData<T>,
+    data_payload: PhantomData<P>,
+}
+
+impl<T, P> NlRouterHandle<T, P> {
+    /// Sequence number assigned to the request
+    pub fn seq(&self) -> u32 {
+        self.seq
+    }
+}
+
+impl<T, P> Iterator for NlRouterHandle<T, P>
+where
+    T: Nl + NlType,
+    P: Nl,
+{
+    type Item = Result<Nlmsghdr<T, P>, NlError>;
+
+    fn next(&mut self) -> Option<Result<Nlmsghdr<T, P>, NlError>> {
+        match self.receiver.recv() {
+            Ok(Ok(buf)) => Some(parse(&buf)),
+            Ok(Err(e)) => Some(Err(e)),
+            // The request is complete
+            Err(_) => None,
+        }
+    }
+}
+
+/// Receiver for messages that are not responses to requests sent through an `NlRouter`
+pub struct NlUnsolicited {
+    receiver: Receiver<Vec<u8>>,
+}
+
+impl NlUnsolicited {
+    /// Block until the next unsolicited message is received. Returns an error once the router
+    /// has been dropped and all remaining messages have been received.
+    pub fn recv<T, P>(&self) -> Result<Nlmsghdr<T, P>, NlError>
+    where
+        T: Nl + NlType,
+        P: Nl,
+    {
+        match self.receiver.recv() {
+            Ok(buf) => parse(&buf),
+            Err(_) => Err(NlError::new("Router is no longer receiving messages")),
+        }
+    }
+
+    /// Return the next unsolicited message if one has already been received
+    pub fn try_recv<T, P>(&self) -> Option<Result<Nlmsghdr<T, P>, NlError>>
+    where
+        T: Nl + NlType,
+        P: Nl,
+    {
+        match self.receiver.try_recv() {
+            Ok(buf) => Some(parse(&buf)),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                Some(Err(NlError::new("Router is no longer receiving messages")))
+            }
+        }
+    }
+}
+
+/// Receiver for the unsolicited messages an `NlRouter` subscription opted in to
+pub struct NlSubscription {
+    receiver: Receiver<Vec<u8>>,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl NlSubscription {
+    /// Block until the next message is received. Returns an `ENOBUFS` error once if messages were
+    /// dropped because the queue was full, and an error once the router has been dropped and all
+    /// remaining messages have been received.
+    pub fn recv<T, P>(&self) -> Result<Nlmsghdr<T, P>, NlError>
+    where
+        T: Nl + NlType,
+        P: Nl,
+    {
+        self.check_dropped()?;
+        match self.receiver.recv() {
+            Ok(buf) => parse(&buf),
+            Err(_) => Err(NlError::new("Router is no longer receiving messages")),
+        }
+    }
+
+    /// Return the next message if one has already been received
+    pub fn try_recv<T, P>(&self) -> Option<Result<Nlmsghdr<T, P>, NlError>>
+    where
+        T: Nl + NlType,
+        P: Nl,
+    {
+        if let Err(e) = self.check_dropped() {
+            return Some(Err(e));
+        }
+        match self.receiver.try_recv() {
+            Ok(buf) => Some(parse(&buf)),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                Some(Err(NlError::new("Router is no longer receiving messages")))
+            }
+        }
+    }
+
+    /// Number of messages dropped since the last `ENOBUFS` error was returned
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::SeqCst)
+    }
+
+    fn check_dropped(&self) -> Result<(), NlError> {
+        if self.dropped.swap(0, Ordering::SeqCst) > 0 {
+            Err(NlError::Io(io::Error::from_raw_os_error(libc::ENOBUFS)))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+fn parse<T, P>(buf: &[u8]) -> Result<Nlmsghdr<T, P>, NlError>
+where
+    T: Nl + NlType,
+    P: Nl,
+{
+    Ok(Nlmsghdr::deserialize(&mut StreamReadBuffer::new(buf))?)
+}
+
+/// Body of the receiving thread
+fn route(
+    reader: &NlSocket,
+    pending: &PendingMap,
+    subscribers: &SubscriberList,
+    unsolicited: &Sender<Vec<u8>>,
+    running: &AtomicBool,
+) {
+    let mut buf = vec![0; MAX_NL_LENGTH];
+    let result = loop {
+        if !running.load(Ordering::SeqCst) {
+            break Ok(());
+        }
+        let mut pfd = libc::pollfd {
+            fd: reader.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        match unsafe { libc::poll(&mut pfd, 1, POLL_INTERVAL_MS) } {
+            0 => continue,
+            i if i < 0 => match io::Error::last_os_error() {
+                ref e if e.kind() == io::ErrorKind::Interrupted => continue,
+                e => break Err(e),
+            },
+            _ => (),
+        }
+
+        let received = reader
+            .recv(&mut [0u8; 0][..], libc::MSG_PEEK | libc::MSG_TRUNC)
+            .and_then(|len| {
+                if len > buf.len() {
+                    buf.resize(len, 0);
+                }
+                reader.recv_from(&mut buf[..], 0)
+            });
+        match received {
+            Ok((len, addr)) => dispatch(
+                &buf[..len],
+                addr.groups != 0,
+                pending,
+                subscribers,
+                unsolicited,
+            ),
+            Err(ref e)
+                if e.kind() == io::ErrorKind::Interrupted
+                    || e.kind() == io::ErrorKind::WouldBlock => {}
+            // Messages were dropped by the kernel, so pending requests may never complete
+            Err(ref e) if e.raw_os_error() == Some(libc::ENOBUFS) => {
+                if let Some(ref mut pending) = *lock(pending) {
+                    for (_, req) in pending.drain() {
+                        let _ = req
+                            .sender
+                            .send(Err(NlError::Io(io::Error::from_raw_os_error(
+                                libc::ENOBUFS,
+                            ))));
+                    }
+                }
+            }
+            Err(e) => break Err(e),
+        }
+    };
+
+    // Fail everything still waiting and refuse new requests and subscriptions
+    lock(subscribers).take();
+    if let Some(pending) = lock(pending).take() {
+        if let Err(e) = result {
+            for (_, req) in pending {
+                let _ = req.sender.send(Err(NlError::new(&e.to_string())));
+            }
+        }
+    }
+}
+
+/// Pass every message of a datagram to the handle of its request, or to the matching subscriptions
+/// or the unsolicited receiver
+fn dispatch(
+    datagram: &[u8],
+    multicast: bool,
+    pending: &PendingMap,
+    subscribers: &SubscriberList,
+    unsolicited: &Sender<Vec<u8>>,
+) {
+    let mut offset = 0;
+    while offset + NLMSG_HDR_LEN <= datagram.len() {
+        let len = endian::read_u32(&datagram[offset..]) as usize;
+        if len < NLMSG_HDR_LEN || offset + len > datagram.len() {
+            break;
+        }
+        let msg = &datagram[offset..offset + len];
+        offset += alignto(len);
+
+        let ty = endian::read_u16(&msg[4..6]);
+        let flags = endian::read_u16(&msg[6..8]);
+        let seq = endian::read_u32(&msg[8..12]);
+        // Both NLMSG_ERROR and NLMSG_DONE start their payload with an error code
+        let error = if msg.len() >= NLMSG_HDR_LEN + 4 {
+            endian::read_i32(&msg[NLMSG_HDR_LEN..])
+        } else {
+            0
+        };
+
+        let mut guard = lock(pending);
+        let pending = match *guard {
+            Some(ref mut pending) if !multicast && pending.contains_key(&seq) => pending,
+            // Late replies to a component that reserved a range are not for anybody else
+            _ if !multicast && seq >= SEQ_RANGES_START => continue,
+            _ => {
+                drop(guard);
+                deliver_unsolicited(msg, ty, subscribers, unsolicited);
+                continue;
+            }
+        };
+        let complete = match pending.get(&seq) {
+            Some(req) if ty == u16::from(Nlmsg::Error) || ty == u16::from(Nlmsg::Done) => {
+                if error < 0 {
+                    let _ = req.sender.send(Err(NlError::from_nlmsgerr(error)));
+                }
+                true
+            }
+            Some(req) => {
+                // The receiving handle may already have been dropped
+                let _ = req.sender.send(Ok(msg.to_vec()));
+                !req.ack && flags & u16::from(NlmF::Multi) == 0
+            }
+            None => false,
+        };
+        if complete {
+            pending.remove(&seq);
+        }
+    }
+}
+
+/// Pass an unsolicited message to every subscription for its type, or to the unsolicited receiver
+/// if there is none
+fn deliver_unsolicited(
+    msg: &[u8],
+    ty: u16,
+    subscribers: &SubscriberList,
+    unsolicited: &Sender<Vec<u8>>,
+) {
+    let mut matched = false;
+    if let Some(ref mut subscribers) = *lock(subscribers) {
+        subscribers.retain(|sub| {
+            if !sub.types.is_empty() && !sub.types.contains(&ty) {
+                return true;
+            }
+            match sub.sender.try_send(msg.to_vec()) {
+                Ok(()) => {
+                    matched = true;
+                    true
+                }
+                Err(TrySendError::Full(_)) => {
+                    matched = true;
+                    sub.dropped.fetch_add(1, Ordering::SeqCst);
+                    true
+                }
+                // The subscription was dropped
+                Err(TrySendError::Disconnected(_)) => false,
+            }
+        });
+    }
+    if !matched {
+        let _ = unsolicited.send(msg.to_vec());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use consts::{GenlId, NlFamily};
+    use genl::getfamily_request;
+    use nl::NlEmpty;
+    use socket::NlAddr;
+
+    #[test]
+    fn test_router() {
+        let s = NlSocket::connect(NlFamily::Generic, None, None, false).unwrap();
+        let (router, unsolicited) = NlRouter::new(s).unwrap();
+        let router = Arc::new(router);
+
+        let threads = (0..4)
+            .map(|_| {
+                let router = Arc::clone(&router);
+                thread::spawn(move || {
+                    let handle = router
+                        .send::<_, _, GenlId, Vec<u8>>(getfamily_request(
+                            "nlctrl",
+                            vec![NlmF::Request, NlmF::Ack],
+                        ))
+                        .unwrap();
+                    let seq = handle.seq();
+                    let replies = handle.collect::<Result<Vec<_>, _>>().unwrap();
+                    assert_eq!(replies.len(), 1);
+                    assert_eq!(replies[0].nl_seq, seq);
+                    seq
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut seqs = threads
+            .into_iter()
+            .map(|t| t.join().unwrap())
+            .collect::<Vec<_>>();
+        seqs.sort();
+        seqs.dedup();
+        assert_eq!(seqs.len(), 4);
+
+        let dump = router
+            .send::<_, _, GenlId, Vec<u8>>(getfamily_request("", vec![NlmF::Request, NlmF::Dump]))
+            .unwrap();
+        assert!(!dump.collect::<Result<Vec<_>, _>>().unwrap().is_empty());
+
+        let mut missing = router
+            .send::<_, _, GenlId, Vec<u8>>(getfamily_request(
+                "neli-missing",
+                vec![NlmF::Request, NlmF::Ack],
+            ))
+            .unwrap();
+        assert!(missing.next().unwrap().is_err());
+        assert!(missing.next().is_none());
+
+        assert!(unsolicited.try_recv::<GenlId, Vec<u8>>().is_none());
+        drop(router);
+        assert!(unsolicited.recv::<GenlId, Vec<u8>>().is_err());
+    }
+
+    /// Send the messages with the given types in one datagram to `addr`
+    fn send_datagram(peer: &NlSocket, addr: NlAddr, tys: &[u16], seq: u32) {
+        let mut buf = Vec::new();
+        for &ty in tys {
+            let msg = Nlmsghdr::<u16, NlEmpty>::new(None, ty, vec![], Some(seq), None, NlEmpty);
+            let mut mem = StreamWriteBuffer::new_growable(None);
+            msg.serialize(&mut mem).unwrap();
+            buf.extend_from_slice(mem.as_ref());
+        }
+        peer.send_to(buf, 0, addr).unwrap();
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let s = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+        let addr = s.local_addr().unwrap();
+        let peer = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+        let (router, unsolicited) = NlRouter::new(s).unwrap();
+
+        assert!(router.subscribe::<u16>(vec![], 0).is_err());
+        let links = router.subscribe::<u16>(vec![16, 17], 1).unwrap();
+        let all = router.subscribe::<u16>(vec![], 8).unwrap();
+        let dropped = router.subscribe::<u16>(vec![20], 8).unwrap();
+        drop(dropped);
+
+        send_datagram(&peer, addr, &[16, 17, 20], 0);
+        for ty in &[16, 17, 20] {
+            assert_eq!(all.recv::<u16, NlEmpty>().unwrap().nl_type, *ty);
+        }
+        // The whole datagram has been dispatched once the last message arrived
+        assert_eq!(links.dropped(), 1);
+        match links.recv::<u16, NlEmpty>() {
+            Err(NlError::Io(ref e)) if e.raw_os_error() == Some(libc::ENOBUFS) => (),
+            res => panic!("Unexpected result {:?}", res),
+        }
+        assert_eq!(links.recv::<u16, NlEmpty>().unwrap().nl_type, 16);
+        assert!(links.try_recv::<u16, NlEmpty>().is_none());
+        assert_eq!(lock(&router.subscribers).as_ref().unwrap().len(), 2);
+
+        // Only messages no subscription matches are left for the unsolicited receiver
+        drop(all);
+        send_datagram(&peer, addr, &[17, 30], 0);
+        assert_eq!(unsolicited.recv::<u16, NlEmpty>().unwrap().nl_type, 30);
+        assert_eq!(links.recv::<u16, NlEmpty>().unwrap().nl_type, 17);
+        assert!(unsolicited.try_recv::<u16, NlEmpty>().is_none());
+    }
+
+    #[test]
+    fn test_seq_range_in_flight() {
+        let s = NlSocket::connect(NlFamily::Generic, None, None, false).unwrap();
+        let (router, _) = NlRouter::new(s).unwrap();
+        let router = Arc::new(router);
+        let range = Arc::new(router.reserve_seq_range(4).unwrap());
+        let (sender, _receiver) = mpsc::channel();
+        let busy = PendingRequest { sender, ack: true };
+        lock(&router.pending)
+            .as_mut()
+            .unwrap()
+            .insert(range.start(), busy);
+
+        // Concurrent requests never share a sequence number with a request still in flight
+        let handles = (0..3)
+            .map(|_| {
+                router
+                    .send_in::<_, _, GenlId, Vec<u8>>(
+                        &range,
+                        getfamily_request("nlctrl", vec![NlmF::Request, NlmF::Ack]),
+                    )
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let threads = handles
+            .into_iter()
+            .map(|handle| {
+                thread::spawn(move || {
+                    let seq = handle.seq();
+                    let replies = handle.collect::<Result<Vec<_>, _>>().unwrap();
+                    assert_eq!(replies.len(), 1);
+                    assert_eq!(replies[0].nl_seq, seq);
+                    seq
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut seqs = threads
+            .into_iter()
+            .map(|t| t.join().unwrap())
+            .collect::<Vec<_>>();
+        seqs.sort();
+        assert_eq!(
+            seqs,
+            vec![range.start() + 1, range.start() + 2, range.start() + 3]
+        );
+    }
+
+    #[test]
+    fn test_seq_range() {
+        let s = NlSocket::connect(NlFamily::Generic, None, None, false).unwrap();
+        let (router, unsolicited) = NlRouter::new(s).unwrap();
+        let a = router.reserve_seq_range(2).unwrap();
+        let b = router.reserve_seq_range(16).unwrap();
+        assert_eq!(a.end(), b.start());
+        assert!(a.start() >= SEQ_RANGES_START);
+        assert!(router.reserve_seq_range(0).is_err());
+        assert!(router.reserve_seq_range(u32::MAX).is_err());
+
+        let mut seqs = Vec::new();
+        for _ in 0..3 {
+            let handle = router
+                .send_in::<_, _, GenlId, Vec<u8>>(
+                    &a,
+                    getfamily_request("nlctrl", vec![NlmF::Request, NlmF::Ack]),
+                )
+                .unwrap();
+            assert!(a.contains(handle.seq()));
+            seqs.push(handle.seq());
+            let replies = handle.collect::<Result<Vec<_>, _>>().unwrap();
+            assert_eq!(replies[0].nl_seq, seqs[seqs.len() - 1]);
+        }
+        // The range wraps around
+        assert_eq!(seqs[0], seqs[2]);
+
+        // A range whose numbers are all in flight is refused
+        for seq in a.start()..a.end() {
+            let (sender, _) = mpsc::channel();
+            let busy = PendingRequest { sender, ack: true };
+            lock(&router.pending).as_mut().unwrap().insert(seq, busy);
+        }
+        assert!(router
+            .send_in::<_, _, GenlId, Vec<u8>>(
+                &a,
+                getfamily_request("nlctrl", vec![NlmF::Request, NlmF::Ack]),
+            )
+            .is_err());
+
+        // A reply to a dropped handle is discarded rather than unsolicited
+        let handle = router
+            .send_in::<_, _, GenlId, Vec<u8>>(&b, getfamily_request("nlctrl", vec![NlmF::Request]))
+            .unwrap();
+        drop(handle);
+        let handle = router
+            .send::<_, _, GenlId, Vec<u8>>(getfamily_request(
+                "nlctrl",
+                vec![NlmF::Request, NlmF::Ack],
+            ))
+            .unwrap();
+        assert!(handle.seq() < SEQ_RANGES_START);
+        assert_eq!(handle.count(), 1);
+        assert!(unsolicited.try_recv::<GenlId, Vec<u8>>().is_none());
+    }
+}
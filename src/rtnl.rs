@@ -7,19 +7,41 @@
 //! series of structs organized in a style similar to the rest of the library with implementations
 //! of `Nl` for each.
 
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::fmt::{self, Display};
+use std::io;
 use std::mem;
+use std::net::IpAddr;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::str::FromStr;
 
 use buffering::{StreamReadBuffer, StreamWriteBuffer};
 use libc;
 
 use crate::{
-    consts::rtnl::*,
-    err::{DeError, SerError},
-    Nl,
+    consts::{rtnl::*, NlFamily, NlmF, NlmFFlags, Nlmsg, Rtm},
+    err::{short_type_name, sub_len, DeError, NlError, Nlmsgerr, SerError},
+    limits::{check_attr_count, NestGuard},
+    nl::Nlmsghdr,
+    socket::{NlSocket, NlSocketOps},
+    value::{NlValue, ToNlValue},
+    Buffer, BufferMut, Nl, Padding,
 };
+#[cfg(any(test, feature = "test-util"))]
+use crate::{nl::verify_roundtrip, vectors::parse_hex};
+
+/// `Rtm::Newlink` dump entry for the loopback interface
+#[cfg(any(test, feature = "test-util"))]
+const VECTOR_NEWLINK: &str = include_str!("../vectors/rtnl/linux-6.18/newlink.hex");
+
+/// `Rtm::Newaddr` dump entry for the loopback address
+#[cfg(any(test, feature = "test-util"))]
+const VECTOR_NEWADDR: &str = include_str!("../vectors/rtnl/linux-6.18/newaddr.hex");
 
 /// Set of `Rtattr` structs
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct Rtattrs<T, P>(Vec<Rtattr<T, P>>);
 
 impl<T, P> Rtattrs<T, P>
@@ -38,7 +60,7 @@ where
     }
 
     /// Return a reference iterator over underlying vector
-    pub fn iter(&self) -> std::slice::Iter<Rtattr<T, P>> {
+    pub fn iter(&self) -> std::slice::Iter<'_, Rtattr<T, P>> {
         self.0.iter()
     }
 }
@@ -74,7 +96,19 @@ where
             None => return Ok(None),
         };
         match elem {
-            Some(ref e) => e.get_payload_as::<R>().map(Some),
+            Some(e) => e.get_payload_as::<R>().map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Get an attribute contained in the set as an IP address of the given family
+    pub fn get_attr_payload_as_addr(
+        &self,
+        attr_type: T,
+        family: &RtAddrFamily,
+    ) -> Result<Option<IpAddr>, DeError> {
+        match self.0.iter().find(|rtattr| rtattr.rta_type == attr_type) {
+            Some(e) => e.get_payload_as_addr(family).map(Some),
             None => Ok(None),
         }
     }
@@ -85,30 +119,30 @@ where
     T: RtaType,
     P: Nl,
 {
-    fn serialize(&self, buf: &mut StreamWriteBuffer) -> Result<(), SerError> {
+    fn serialize<B>(&self, buf: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
         for item in self.0.iter() {
             item.serialize(buf)?;
         }
         Ok(())
     }
 
-    fn deserialize<B>(buf: &mut StreamReadBuffer<B>) -> Result<Self, DeError>
+    fn deserialize<B>(buf: &mut B) -> Result<Self, DeError>
     where
-        B: AsRef<[u8]>,
+        B: Buffer,
     {
         let mut size_hint = buf
             .take_size_hint()
             .ok_or_else(|| DeError::new("Vec of Rtattr requires a size hint to deserialize"))?;
+        let _nest = NestGuard::enter()?;
         let mut vec = Vec::new();
         while size_hint > 0 {
-            let attr: Rtattr<T, P> = Rtattr::deserialize(buf)?;
-            size_hint = size_hint.checked_sub(attr.asize()).ok_or_else(|| {
-                DeError::new(&format!(
-                    "Rtattr size {} overflowed buffer size {}",
-                    attr.size(),
-                    size_hint
-                ))
-            })?;
+            check_attr_count(vec.len() + 1)?;
+            let attr: Rtattr<T, P> = Rtattr::deserialize(buf)
+                .map_err(|e| e.context(format!("attribute {}", vec.len())))?;
+            size_hint = sub_len("size_hint", size_hint, attr.asize())?;
             vec.push(attr);
         }
         Ok(Rtattrs::new(vec))
@@ -119,15 +153,386 @@ where
     }
 }
 
+impl<T, P> ToNlValue for Rtattrs<T, P>
+where
+    T: ToNlValue,
+    P: ToNlValue,
+{
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::list(&self.0)
+    }
+}
+
+/// Index of a network interface, as found in `ifi_index` and similar fields
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct IfIndex(pub u32);
+
+impl From<u32> for IfIndex {
+    fn from(v: u32) -> Self {
+        IfIndex(v)
+    }
+}
+
+impl From<IfIndex> for u32 {
+    fn from(v: IfIndex) -> Self {
+        v.0
+    }
+}
+
+impl Display for IfIndex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Nl for IfIndex {
+    fn serialize<B>(&self, buf: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.0.serialize(buf)
+    }
+
+    fn deserialize<B>(buf: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        Ok(IfIndex(u32::deserialize(buf)?))
+    }
+
+    fn size(&self) -> usize {
+        self.0.size()
+    }
+}
+
+impl ToNlValue for IfIndex {
+    fn to_nl_value(&self) -> NlValue {
+        self.0.to_nl_value()
+    }
+}
+
+/// Ethernet hardware address, as found in `Ifla::Address` and `Ifla::Broadcast` of Ethernet
+/// interfaces
+///
+/// The address is displayed and parsed in the usual colon separated hexadecimal form, such as
+/// `02:00:5e:10:00:01`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct MacAddress(pub [u8; 6]);
+
+impl From<[u8; 6]> for MacAddress {
+    fn from(v: [u8; 6]) -> Self {
+        MacAddress(v)
+    }
+}
+
+impl From<MacAddress> for [u8; 6] {
+    fn from(v: MacAddress) -> Self {
+        v.0
+    }
+}
+
+impl Display for MacAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        HwAddr::from(*self).fmt(f)
+    }
+}
+
+impl FromStr for MacAddress {
+    type Err = DeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<HwAddr>()?
+            .to_mac()
+            .ok_or_else(|| DeError::Msg(format!("Invalid MAC address {:?}", s)))
+    }
+}
+
+impl Nl for MacAddress {
+    fn serialize<B>(&self, buf: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.0.serialize(buf)
+    }
+
+    fn deserialize<B>(buf: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        Ok(MacAddress(<[u8; 6]>::deserialize(buf)?))
+    }
+
+    fn size(&self) -> usize {
+        self.0.size()
+    }
+
+    fn type_size() -> Option<usize> {
+        <[u8; 6]>::type_size()
+    }
+}
+
+impl ToNlValue for MacAddress {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::Str(self.to_string())
+    }
+}
+
+/// Maximum length of a hardware address, `MAX_ADDR_LEN` in the kernel
+pub const MAX_ADDR_LEN: usize = 32;
+
+/// Hardware address of any link type, such as a 6 byte Ethernet address, an 8 byte EUI-64 or a
+/// 20 byte Infiniband address, as found in `Ifla::Address` and `Ifla::Broadcast`
+///
+/// The address is displayed and parsed in colon separated hexadecimal form like `MacAddress`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HwAddr {
+    len: u8,
+    addr: [u8; MAX_ADDR_LEN],
+}
+
+impl HwAddr {
+    /// Bytes of the address
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.addr[..usize::from(self.len)]
+    }
+
+    /// Length of the address in bytes
+    pub fn len(&self) -> usize {
+        usize::from(self.len)
+    }
+
+    /// Whether the address has no bytes, as for links without hardware addresses
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The address as an Ethernet address, if it is 6 bytes long
+    pub fn to_mac(&self) -> Option<MacAddress> {
+        <[u8; 6]>::try_from(self.as_bytes()).ok().map(MacAddress)
+    }
+}
+
+impl TryFrom<&[u8]> for HwAddr {
+    type Error = DeError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        if bytes.len() > MAX_ADDR_LEN {
+            return Err(DeError::InvalidLength {
+                field: "hardware address",
+                expected: MAX_ADDR_LEN,
+                got: bytes.len(),
+            });
+        }
+        let mut addr = [0; MAX_ADDR_LEN];
+        addr[..bytes.len()].copy_from_slice(bytes);
+        Ok(HwAddr {
+            len: bytes.len() as u8,
+            addr,
+        })
+    }
+}
+
+impl From<MacAddress> for HwAddr {
+    fn from(v: MacAddress) -> Self {
+        let mut addr = [0; MAX_ADDR_LEN];
+        addr[..6].copy_from_slice(&v.0);
+        HwAddr { len: 6, addr }
+    }
+}
+
+impl Display for HwAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, byte) in self.as_bytes().iter().enumerate() {
+            if i > 0 {
+                f.write_str(":")?;
+            }
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for HwAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "HwAddr({})", self)
+    }
+}
+
+impl FromStr for HwAddr {
+    type Err = DeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || DeError::Msg(format!("Invalid hardware address {:?}", s));
+        let mut addr = [0; MAX_ADDR_LEN];
+        let mut len = 0;
+        for octet in s.split(':') {
+            if octet.is_empty() || octet.len() > 2 || len == MAX_ADDR_LEN {
+                return Err(invalid());
+            }
+            addr[len] = u8::from_str_radix(octet, 16).map_err(|_| invalid())?;
+            len += 1;
+        }
+        Ok(HwAddr {
+            len: len as u8,
+            addr,
+        })
+    }
+}
+
+impl Nl for HwAddr {
+    fn serialize<B>(&self, buf: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.as_bytes().serialize(buf)
+    }
+
+    fn deserialize<B>(buf: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        HwAddr::try_from(&Vec::<u8>::deserialize(buf)?[..])
+    }
+
+    fn size(&self) -> usize {
+        self.len()
+    }
+}
+
+impl ToNlValue for HwAddr {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::Str(self.to_string())
+    }
+}
+
+/// Mapping between interface indices and names
+///
+/// The cache is filled from a link dump with `load` and kept up to date by passing link
+/// notifications from the `RTNLGRP_LINK` multicast group to `update`.
+#[derive(Debug, Default)]
+pub struct IfIndexCache {
+    names: HashMap<IfIndex, String>,
+    indices: HashMap<String, IfIndex>,
+}
+
+impl IfIndexCache {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        IfIndexCache::default()
+    }
+
+    /// Create a cache holding all interfaces currently known to the kernel by sending an
+    /// `Rtm::Getlink` dump request on the given routing netlink socket
+    pub fn load<S>(socket: &mut S) -> Result<Self, NlError>
+    where
+        S: NlSocketOps,
+    {
+        let ifinfomsg = Ifinfomsg::new(
+            RtAddrFamily::Unspec,
+            Arphrd::None,
+            IfIndex(0),
+            Vec::new(),
+            Rtattrs::empty(),
+        );
+        socket.send_msg(Nlmsghdr::new(
+            None,
+            Rtm::Getlink,
+            NlmFFlags::dump_request().into(),
+            None,
+            None,
+            ifinfomsg,
+        ))?;
+
+        let mut cache = IfIndexCache::new();
+        loop {
+            for msg in socket.recv_msgs::<u16, Vec<u8>>()? {
+                match Nlmsg::from(msg.nl_type) {
+                    Nlmsg::Done => return Ok(cache),
+                    Nlmsg::Error => {
+                        let mut mem = StreamReadBuffer::new(&msg.nl_payload);
+                        let err = Nlmsgerr::<u16>::deserialize(&mut mem)?;
+                        return Err(NlError::from_nlmsgerr(err.error));
+                    }
+                    _ => {
+                        let mut mem = StreamReadBuffer::new(&msg.nl_payload);
+                        mem.set_size_hint(msg.nl_payload.len());
+                        let ifinfomsg = Ifinfomsg::deserialize(&mut mem)?;
+                        cache.insert(&ifinfomsg)?;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Apply an `Rtm::Newlink` or `Rtm::Dellink` notification to the cache. Other messages are
+    /// ignored.
+    pub fn update(&mut self, msg: &Nlmsghdr<Rtm, Ifinfomsg>) -> Result<(), DeError> {
+        match msg.nl_type {
+            Rtm::Newlink => self.insert(&msg.nl_payload),
+            Rtm::Dellink => {
+                if let Some(name) = self.names.remove(&msg.nl_payload.ifi_index) {
+                    self.indices.remove(&name);
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn insert(&mut self, ifinfomsg: &Ifinfomsg) -> Result<(), DeError> {
+        let name = match ifinfomsg
+            .rtattrs
+            .get_attr_payload_as::<String>(Ifla::Ifname)?
+        {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+        // Interfaces can be renamed, so drop the mapping for the old name
+        if let Some(old) = self.names.insert(ifinfomsg.ifi_index, name.clone()) {
+            self.indices.remove(&old);
+        }
+        // A name reused by a new interface replaces the old interface
+        if let Some(old) = self.indices.insert(name, ifinfomsg.ifi_index) {
+            if old != ifinfomsg.ifi_index {
+                self.names.remove(&old);
+            }
+        }
+        Ok(())
+    }
+
+    /// Name of the interface with the given index
+    pub fn name(&self, index: IfIndex) -> Option<&str> {
+        self.names.get(&index).map(String::as_str)
+    }
+
+    /// Index of the interface with the given name
+    pub fn index(&self, name: &str) -> Option<IfIndex> {
+        self.indices.get(name).cloned()
+    }
+
+    /// Number of interfaces in the cache
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Returns true if the cache holds no interfaces
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
 /// Struct representing interface information messages
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct Ifinfomsg {
     /// Interface address family
     pub ifi_family: RtAddrFamily,
     /// Interface type
     pub ifi_type: Arphrd,
     /// Interface index
-    pub ifi_index: libc::c_int,
+    pub ifi_index: IfIndex,
     /// Interface flags
     pub ifi_flags: Vec<Iff>,
     ifi_change: libc::c_uint,
@@ -140,7 +545,7 @@ impl Ifinfomsg {
     pub fn new(
         ifi_family: RtAddrFamily,
         ifi_type: Arphrd,
-        ifi_index: libc::c_int,
+        ifi_index: IfIndex,
         ifi_flags: Vec<Iff>,
         rtattrs: Rtattrs<Ifla, Vec<u8>>,
     ) -> Self {
@@ -153,12 +558,50 @@ impl Ifinfomsg {
             rtattrs,
         }
     }
+
+    /// Get a hardware address attribute such as `Ifla::Address` or `Ifla::Broadcast` of an
+    /// Ethernet interface
+    pub fn get_mac(&self, attr_type: Ifla) -> Result<Option<MacAddress>, DeError> {
+        self.rtattrs.get_attr_payload_as(attr_type)
+    }
+
+    /// Get a hardware address attribute such as `Ifla::Address` or `Ifla::Broadcast` of an
+    /// interface of any link type
+    pub fn get_hwaddr(&self, attr_type: Ifla) -> Result<Option<HwAddr>, DeError> {
+        self.rtattrs.get_attr_payload_as(attr_type)
+    }
+
+    /// Get the operational state from the `Ifla::Operstate` attribute
+    pub fn operstate(&self) -> Result<Option<IfOper>, DeError> {
+        self.rtattrs.get_attr_payload_as(Ifla::Operstate)
+    }
+
+    /// Get the carrier state from the `Ifla::Carrier` attribute
+    pub fn carrier(&self) -> Result<Option<IfCarrier>, DeError> {
+        self.rtattrs.get_attr_payload_as(Ifla::Carrier)
+    }
+
+    /// Get the link mode from the `Ifla::Linkmode` attribute
+    pub fn link_mode(&self) -> Result<Option<IfLinkMode>, DeError> {
+        self.rtattrs.get_attr_payload_as(Ifla::Linkmode)
+    }
+
+    /// Limit the flags a request changes to `change`. By default, all flags are set to
+    /// `ifi_flags`.
+    pub fn set_change(&mut self, change: &[Iff]) {
+        self.ifi_change = change
+            .iter()
+            .fold(0, |acc: libc::c_uint, next| acc | libc::c_uint::from(next));
+    }
 }
 
 impl Nl for Ifinfomsg {
-    fn serialize(&self, buf: &mut StreamWriteBuffer) -> Result<(), SerError> {
+    fn serialize<B>(&self, buf: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
         self.ifi_family.serialize(buf)?;
-        0u8.serialize(buf)?; // padding
+        Padding::<1>.serialize(buf)?;
         self.ifi_type.serialize(buf)?;
         self.ifi_index.serialize(buf)?;
         self.ifi_flags
@@ -173,17 +616,17 @@ impl Nl for Ifinfomsg {
         Ok(())
     }
 
-    fn deserialize<B>(buf: &mut StreamReadBuffer<B>) -> Result<Self, DeError>
+    fn deserialize<B>(buf: &mut B) -> Result<Self, DeError>
     where
-        B: AsRef<[u8]>,
+        B: Buffer,
     {
         let mut size_hint = buf
             .take_size_hint()
             .ok_or_else(|| DeError::new("Ifinfomsg requires a size hint to deserialize"))?;
         let ifi_family = RtAddrFamily::deserialize(buf)?;
-        let padding = u8::deserialize(buf)?;
+        let padding = Padding::<1>::deserialize(buf)?;
         let ifi_type = Arphrd::deserialize(buf)?;
-        let ifi_index = libc::c_int::deserialize(buf)?;
+        let ifi_index = IfIndex::deserialize(buf)?;
         let ifi_flags = {
             let flags = libc::c_uint::deserialize(buf)?;
             let mut nl_flags = Vec::new();
@@ -197,16 +640,16 @@ impl Nl for Ifinfomsg {
         };
         let ifi_change = libc::c_uint::deserialize(buf)?;
 
-        size_hint = size_hint
-            .checked_sub(
-                ifi_family.size()
-                    + padding.size()
-                    + ifi_type.size()
-                    + ifi_index.size()
-                    + mem::size_of::<libc::c_int>()
-                    + ifi_change.size(),
-            )
-            .ok_or_else(|| DeError::new(&format!("Truncated Ifinfomsg size_hint {}", size_hint)))?;
+        size_hint = sub_len(
+            "size_hint",
+            size_hint,
+            ifi_family.size()
+                + padding.size()
+                + ifi_type.size()
+                + ifi_index.size()
+                + mem::size_of::<libc::c_int>()
+                + ifi_change.size(),
+        )?;
         buf.set_size_hint(size_hint);
         let rtattrs = Rtattrs::<Ifla, Vec<u8>>::deserialize(buf)?;
 
@@ -222,8 +665,7 @@ impl Nl for Ifinfomsg {
 
     fn size(&self) -> usize {
         self.ifi_family.size() +
-        // padding byte
-        0u8.size() +
+        Padding::<1>.size() +
         self.ifi_type.size() + self.ifi_index.size() +
         // flags
         mem::size_of::<libc::c_uint>() +
@@ -232,8 +674,22 @@ impl Nl for Ifinfomsg {
     }
 }
 
+impl ToNlValue for Ifinfomsg {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("ifi_family", self.ifi_family.to_nl_value()),
+            ("ifi_type", self.ifi_type.to_nl_value()),
+            ("ifi_index", self.ifi_index.to_nl_value()),
+            ("ifi_flags", NlValue::list(&self.ifi_flags)),
+            ("ifi_change", self.ifi_change.to_nl_value()),
+            ("rtattrs", self.rtattrs.to_nl_value()),
+        ])
+    }
+}
+
 /// Struct representing interface address messages
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct Ifaddrmsg {
     /// Interface address family
     pub ifa_family: RtAddrFamily,
@@ -244,13 +700,25 @@ pub struct Ifaddrmsg {
     /// Interface address scope
     pub ifa_scope: libc::c_uchar,
     /// Interface address index
-    pub ifa_index: libc::c_int,
+    pub ifa_index: IfIndex,
     /// Payload of `Rtattr`s
     pub rtattrs: Rtattrs<Ifa, Vec<u8>>,
 }
 
+impl Ifaddrmsg {
+    /// Get an address attribute such as `Ifa::Address` or `Ifa::Local` decoded according to
+    /// `ifa_family`
+    pub fn get_addr(&self, attr_type: Ifa) -> Result<Option<IpAddr>, DeError> {
+        self.rtattrs
+            .get_attr_payload_as_addr(attr_type, &self.ifa_family)
+    }
+}
+
 impl Nl for Ifaddrmsg {
-    fn serialize(&self, buf: &mut StreamWriteBuffer) -> Result<(), SerError> {
+    fn serialize<B>(&self, buf: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
         self.ifa_family.serialize(buf)?;
         self.ifa_prefixlen.serialize(buf)?;
         self.ifa_flags
@@ -266,9 +734,9 @@ impl Nl for Ifaddrmsg {
         Ok(())
     }
 
-    fn deserialize<B>(buf: &mut StreamReadBuffer<B>) -> Result<Self, DeError>
+    fn deserialize<B>(buf: &mut B) -> Result<Self, DeError>
     where
-        B: AsRef<[u8]>,
+        B: Buffer,
     {
         let mut result = Ifaddrmsg {
             ifa_family: RtAddrFamily::deserialize(buf)?,
@@ -285,14 +753,14 @@ impl Nl for Ifaddrmsg {
                 nl_flags
             },
             ifa_scope: libc::c_uchar::deserialize(buf)?,
-            ifa_index: libc::c_int::deserialize(buf)?,
+            ifa_index: IfIndex::deserialize(buf)?,
             rtattrs: Rtattrs::empty(),
         };
 
         let size_hint = buf
             .take_size_hint()
-            .ok_or_else(|| DeError::new("Ifinfomsg requires a size hint to deserialize"))?
-            - result.asize();
+            .ok_or_else(|| DeError::new("Ifinfomsg requires a size hint to deserialize"))?;
+        let size_hint = sub_len("size_hint", size_hint, result.asize())?;
         buf.set_size_hint(size_hint);
 
         result.rtattrs = Rtattrs::deserialize(buf)?;
@@ -305,24 +773,42 @@ impl Nl for Ifaddrmsg {
             + mem::size_of::<libc::c_uchar>()
             + self.ifa_scope.size()
             + self.ifa_index.size()
+            + self.rtattrs.asize()
+    }
+}
+
+impl ToNlValue for Ifaddrmsg {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("ifa_family", self.ifa_family.to_nl_value()),
+            ("ifa_prefixlen", self.ifa_prefixlen.to_nl_value()),
+            ("ifa_flags", NlValue::list(&self.ifa_flags)),
+            ("ifa_scope", self.ifa_scope.to_nl_value()),
+            ("ifa_index", self.ifa_index.to_nl_value()),
+            ("rtattrs", self.rtattrs.to_nl_value()),
+        ])
     }
 }
 
 /// General form of address family dependent message.  Used for requesting things from via rtnetlink.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct Rtgenmsg {
     /// Address family for the request
     pub rtgen_family: RtAddrFamily,
 }
 
 impl Nl for Rtgenmsg {
-    fn serialize(&self, m: &mut StreamWriteBuffer) -> Result<(), SerError> {
+    fn serialize<B>(&self, m: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
         self.rtgen_family.serialize(m)
     }
 
-    fn deserialize<T>(m: &mut StreamReadBuffer<T>) -> Result<Self, DeError>
+    fn deserialize<T>(m: &mut T) -> Result<Self, DeError>
     where
-        T: AsRef<[u8]>,
+        T: Buffer,
     {
         Ok(Self {
             rtgen_family: RtAddrFamily::deserialize(m)?,
@@ -334,8 +820,15 @@ impl Nl for Rtgenmsg {
     }
 }
 
+impl ToNlValue for Rtgenmsg {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![("rtgen_family", self.rtgen_family.to_nl_value())])
+    }
+}
+
 /// Route message
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct Rtmsg {
     /// Address family of route
     pub rtm_family: RtAddrFamily,
@@ -359,8 +852,20 @@ pub struct Rtmsg {
     pub rtattrs: Rtattrs<Rta, Vec<u8>>,
 }
 
+impl Rtmsg {
+    /// Get an address attribute such as `Rta::Dst` or `Rta::Gateway` decoded according to
+    /// `rtm_family`
+    pub fn get_addr(&self, attr_type: Rta) -> Result<Option<IpAddr>, DeError> {
+        self.rtattrs
+            .get_attr_payload_as_addr(attr_type, &self.rtm_family)
+    }
+}
+
 impl Nl for Rtmsg {
-    fn serialize(&self, buf: &mut StreamWriteBuffer) -> Result<(), SerError> {
+    fn serialize<B>(&self, buf: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
         self.rtm_family.serialize(buf)?;
         self.rtm_dst_len.serialize(buf)?;
         self.rtm_src_len.serialize(buf)?;
@@ -380,9 +885,9 @@ impl Nl for Rtmsg {
         Ok(())
     }
 
-    fn deserialize<B>(buf: &mut StreamReadBuffer<B>) -> Result<Self, DeError>
+    fn deserialize<B>(buf: &mut B) -> Result<Self, DeError>
     where
-        B: AsRef<[u8]>,
+        B: Buffer,
     {
         let size_hint = buf
             .take_size_hint()
@@ -408,18 +913,19 @@ impl Nl for Rtmsg {
             rtm_flags
         };
 
-        buf.set_size_hint(
-            size_hint
-                - rtm_family.size()
-                - rtm_dst_len.size()
-                - rtm_src_len.size()
-                - rtm_tos.size()
-                - rtm_table.size()
-                - rtm_protocol.size()
-                - rtm_scope.size()
-                - rtm_type.size()
-                - mem::size_of::<libc::c_int>(),
-        );
+        buf.set_size_hint(sub_len(
+            "size_hint",
+            size_hint,
+            rtm_family.size()
+                + rtm_dst_len.size()
+                + rtm_src_len.size()
+                + rtm_tos.size()
+                + rtm_table.size()
+                + rtm_protocol.size()
+                + rtm_scope.size()
+                + rtm_type.size()
+                + mem::size_of::<libc::c_int>(),
+        )?);
         let rtattrs = Rtattrs::<Rta, Vec<u8>>::deserialize(buf)?;
 
         Ok(Rtmsg {
@@ -450,13 +956,31 @@ impl Nl for Rtmsg {
     }
 }
 
+impl ToNlValue for Rtmsg {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("rtm_family", self.rtm_family.to_nl_value()),
+            ("rtm_dst_len", self.rtm_dst_len.to_nl_value()),
+            ("rtm_src_len", self.rtm_src_len.to_nl_value()),
+            ("rtm_tos", self.rtm_tos.to_nl_value()),
+            ("rtm_table", self.rtm_table.to_nl_value()),
+            ("rtm_protocol", self.rtm_protocol.to_nl_value()),
+            ("rtm_scope", self.rtm_scope.to_nl_value()),
+            ("rtm_type", self.rtm_type.to_nl_value()),
+            ("rtm_flags", NlValue::list(&self.rtm_flags)),
+            ("rtattrs", self.rtattrs.to_nl_value()),
+        ])
+    }
+}
+
 /// Represents an ARP (neighbor table) entry
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct Ndmsg {
     /// Address family of entry
     pub ndm_family: RtAddrFamily,
     /// Index of entry
-    pub ndm_index: libc::c_int,
+    pub ndm_index: IfIndex,
     /// State of entry
     pub ndm_state: Vec<Nud>,
     /// Flags for entry
@@ -467,11 +991,21 @@ pub struct Ndmsg {
     pub rtattrs: Rtattrs<Nda, Vec<u8>>,
 }
 
+impl Ndmsg {
+    /// Get an address attribute such as `Nda::Dst` decoded according to `ndm_family`
+    pub fn get_addr(&self, attr_type: Nda) -> Result<Option<IpAddr>, DeError> {
+        self.rtattrs
+            .get_attr_payload_as_addr(attr_type, &self.ndm_family)
+    }
+}
+
 impl Nl for Ndmsg {
-    fn serialize(&self, buf: &mut StreamWriteBuffer) -> Result<(), SerError> {
+    fn serialize<B>(&self, buf: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
         self.ndm_family.serialize(buf)?;
-        0u8.serialize(buf)?; // padding
-        0u16.serialize(buf)?; // padding
+        Padding::<3>.serialize(buf)?;
         self.ndm_index.serialize(buf)?;
         self.ndm_state
             .iter()
@@ -492,25 +1026,24 @@ impl Nl for Ndmsg {
         Ok(())
     }
 
-    fn deserialize<B>(buf: &mut StreamReadBuffer<B>) -> Result<Self, DeError>
+    fn deserialize<B>(buf: &mut B) -> Result<Self, DeError>
     where
-        B: AsRef<[u8]>,
+        B: Buffer,
     {
         let size_hint = buf
             .take_size_hint()
             .ok_or_else(|| DeError::new("Must provide size hint to deserialize Ndmsg"))?;
 
         let ndm_family = RtAddrFamily::deserialize(buf)?;
-        u8::deserialize(buf)?; // padding
-        u16::deserialize(buf)?; // padding
-        let ndm_index = libc::c_int::deserialize(buf)?;
+        let padding = Padding::<3>::deserialize(buf)?;
+        let ndm_index = IfIndex::deserialize(buf)?;
         let ndm_state = {
             let state = u16::deserialize(buf)?;
             let mut ndm_state = Vec::new();
             for i in 0..mem::size_of::<u16>() * 8 {
                 let bit = 1 << i;
                 if bit & state == bit {
-                    ndm_state.push((bit as u16).into());
+                    ndm_state.push(bit.into());
                 }
             }
             ndm_state
@@ -521,22 +1054,23 @@ impl Nl for Ndmsg {
             for i in 0..mem::size_of::<u8>() * 8 {
                 let bit = 1 << i;
                 if bit & flags == bit {
-                    ndm_flags.push((bit as u8).into());
+                    ndm_flags.push(bit.into());
                 }
             }
             ndm_flags
         };
         let ndm_type = Rtn::deserialize(buf)?;
 
-        buf.set_size_hint(
-            size_hint
-                - ndm_family.size()
-                - 3 // padding of u8 + u16
-                - ndm_index.size()
-                - mem::size_of::<u16>() // ndm_state
-                - mem::size_of::<u8>() // ndm_flags
-                - ndm_type.size(),
-        );
+        buf.set_size_hint(sub_len(
+            "size_hint",
+            size_hint,
+            ndm_family.size()
+                + padding.size()
+                + ndm_index.size()
+                + mem::size_of::<u16>() // ndm_state
+                + mem::size_of::<u8>() // ndm_flags
+                + ndm_type.size(),
+        )?);
 
         let rtattrs = Rtattrs::<Nda, Vec<u8>>::deserialize(buf)?;
 
@@ -552,7 +1086,7 @@ impl Nl for Ndmsg {
 
     fn size(&self) -> usize {
         self.ndm_family.size()
-            + 3 // padding of u8 + u16
+            + Padding::<3>.size()
             + self.ndm_index.size()
             + mem::size_of::<u16>() // ndm_state
             + mem::size_of::<u8>() // ndm_flags
@@ -561,8 +1095,22 @@ impl Nl for Ndmsg {
     }
 }
 
+impl ToNlValue for Ndmsg {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("ndm_family", self.ndm_family.to_nl_value()),
+            ("ndm_index", self.ndm_index.to_nl_value()),
+            ("ndm_state", NlValue::list(&self.ndm_state)),
+            ("ndm_flags", NlValue::list(&self.ndm_flags)),
+            ("ndm_type", self.ndm_type.to_nl_value()),
+            ("rtattrs", self.rtattrs.to_nl_value()),
+        ])
+    }
+}
+
 /// Struct representing ARP cache info
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct NdaCacheinfo {
     /// Confirmed
     pub ndm_confirmed: u32,
@@ -575,7 +1123,10 @@ pub struct NdaCacheinfo {
 }
 
 impl Nl for NdaCacheinfo {
-    fn serialize(&self, buf: &mut StreamWriteBuffer) -> Result<(), SerError> {
+    fn serialize<B>(&self, buf: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
         self.ndm_confirmed.serialize(buf)?;
         self.ndm_used.serialize(buf)?;
         self.ndm_updated.serialize(buf)?;
@@ -583,9 +1134,9 @@ impl Nl for NdaCacheinfo {
         Ok(())
     }
 
-    fn deserialize<B>(buf: &mut StreamReadBuffer<B>) -> Result<Self, DeError>
+    fn deserialize<B>(buf: &mut B) -> Result<Self, DeError>
     where
-        B: AsRef<[u8]>,
+        B: Buffer,
     {
         Ok(NdaCacheinfo {
             ndm_confirmed: u32::deserialize(buf)?,
@@ -603,13 +1154,25 @@ impl Nl for NdaCacheinfo {
     }
 }
 
+impl ToNlValue for NdaCacheinfo {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("ndm_confirmed", self.ndm_confirmed.to_nl_value()),
+            ("ndm_used", self.ndm_used.to_nl_value()),
+            ("ndm_updated", self.ndm_updated.to_nl_value()),
+            ("ndm_refcnt", self.ndm_refcnt.to_nl_value()),
+        ])
+    }
+}
+
 /// Message in response to queuing discipline operations
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct Tcmsg {
     /// Family
     pub tcm_family: libc::c_uchar,
     /// Interface index
-    pub tcm_ifindex: libc::c_int,
+    pub tcm_ifindex: IfIndex,
     /// Queuing discipline handle
     pub tcm_handle: u32,
     /// Parent queuing discipline
@@ -621,10 +1184,12 @@ pub struct Tcmsg {
 }
 
 impl Nl for Tcmsg {
-    fn serialize(&self, buf: &mut StreamWriteBuffer) -> Result<(), SerError> {
+    fn serialize<B>(&self, buf: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
         self.tcm_family.serialize(buf)?;
-        (0 as libc::c_uchar).serialize(buf)?;
-        (0 as libc::c_ushort).serialize(buf)?;
+        Padding::<3>.serialize(buf)?;
         self.tcm_ifindex.serialize(buf)?;
         self.tcm_handle.serialize(buf)?;
         self.tcm_parent.serialize(buf)?;
@@ -633,33 +1198,31 @@ impl Nl for Tcmsg {
         Ok(())
     }
 
-    fn deserialize<B>(buf: &mut StreamReadBuffer<B>) -> Result<Self, DeError>
+    fn deserialize<B>(buf: &mut B) -> Result<Self, DeError>
     where
-        B: AsRef<[u8]>,
+        B: Buffer,
     {
         let mut size_hint = buf
             .take_size_hint()
             .ok_or_else(|| DeError::new("Tcmsg requires a size hint to deserialize"))?;
 
         let tcm_family = libc::c_uchar::deserialize(buf)?;
-        libc::c_uchar::deserialize(buf)?;
-        libc::c_ushort::deserialize(buf)?;
-        let tcm_ifindex = libc::c_int::deserialize(buf)?;
+        let padding = Padding::<3>::deserialize(buf)?;
+        let tcm_ifindex = IfIndex::deserialize(buf)?;
         let tcm_handle = u32::deserialize(buf)?;
         let tcm_parent = u32::deserialize(buf)?;
         let tcm_info = u32::deserialize(buf)?;
 
-        size_hint = size_hint
-            .checked_sub(
-                tcm_family.size()
-                    + mem::size_of::<libc::c_uchar>()
-                    + mem::size_of::<libc::c_ushort>()
-                    + tcm_ifindex.size()
-                    + tcm_handle.size()
-                    + tcm_parent.size()
-                    + tcm_info.size(),
-            )
-            .ok_or_else(|| DeError::new(&format!("Truncated Tcmsg size_hint {}", size_hint)))?;
+        size_hint = sub_len(
+            "size_hint",
+            size_hint,
+            tcm_family.size()
+                + padding.size()
+                + tcm_ifindex.size()
+                + tcm_handle.size()
+                + tcm_parent.size()
+                + tcm_info.size(),
+        )?;
         buf.set_size_hint(size_hint);
         let rtattrs = Rtattrs::<Tca, Vec<u8>>::deserialize(buf)?;
 
@@ -675,17 +1238,31 @@ impl Nl for Tcmsg {
 
     fn size(&self) -> usize {
         self.tcm_family.size()
-            + mem::size_of::<libc::c_uchar>()
-            + mem::size_of::<libc::c_ushort>()
+            + Padding::<3>.size()
             + self.tcm_ifindex.size()
             + self.tcm_handle.size()
             + self.tcm_parent.size()
             + self.tcm_info.size()
+            + self.rtattrs.asize()
+    }
+}
+
+impl ToNlValue for Tcmsg {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("tcm_family", self.tcm_family.to_nl_value()),
+            ("tcm_ifindex", self.tcm_ifindex.to_nl_value()),
+            ("tcm_handle", self.tcm_handle.to_nl_value()),
+            ("tcm_parent", self.tcm_parent.to_nl_value()),
+            ("tcm_info", self.tcm_info.to_nl_value()),
+            ("rtattrs", self.rtattrs.to_nl_value()),
+        ])
     }
 }
 
 /// Struct representing route netlink attributes
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct Rtattr<T, P> {
     /// Length of the attribute
     pub rta_len: libc::c_ushort,
@@ -710,21 +1287,94 @@ impl<T> Rtattr<T, Vec<u8>>
 where
     T: RtaType,
 {
+    /// Create an attribute holding the serialized `payload`, like `Nlattr::new`
+    pub fn new<P>(
+        rta_len: Option<libc::c_ushort>,
+        rta_type: T,
+        payload: P,
+    ) -> Result<Self, SerError>
+    where
+        P: Nl,
+    {
+        let mut buffer = StreamWriteBuffer::new_growable(Some(payload.size()));
+        payload.serialize(&mut buffer)?;
+        let mut attr = Rtattr {
+            rta_len: 0,
+            rta_type,
+            rta_payload: buffer.as_ref().to_vec(),
+        };
+        attr.rta_len = rta_len.unwrap_or(
+            (attr.rta_len.size() + attr.rta_type.size() + payload.size()) as libc::c_ushort,
+        );
+        Ok(attr)
+    }
+
+    /// Add a nested attribute to the end of the payload
+    pub fn add_nested_attribute<TT, P>(&mut self, attr: &Rtattr<TT, P>) -> Result<(), SerError>
+    where
+        TT: RtaType,
+        P: Nl,
+    {
+        let mut buffer = StreamWriteBuffer::new_growable(Some(attr.asize()));
+        attr.serialize(&mut buffer)?;
+        self.rta_payload.extend_from_slice(buffer.as_ref());
+        self.rta_len += attr.asize() as libc::c_ushort;
+        Ok(())
+    }
+
     /// Get payload as type implementing `Nl`
     pub fn get_payload_as<R>(&self) -> Result<R, DeError>
     where
         R: Nl,
     {
-        R::deserialize(&mut StreamReadBuffer::new(&self.rta_payload))
+        let mut buf = StreamReadBuffer::new(&self.rta_payload);
+        buf.set_size_hint(self.payload_size());
+        R::deserialize(&mut buf)
+    }
+
+    /// Get payload as an IP address of the given family, which is usually the family from the
+    /// header of the message containing the attribute
+    pub fn get_payload_as_addr(&self, family: &RtAddrFamily) -> Result<IpAddr, DeError> {
+        let len = addr_len(family)?;
+        if self.rta_payload.len() != len {
+            return Err(DeError::new(&format!(
+                "Invalid {:?} address length {}",
+                family,
+                self.rta_payload.len()
+            )));
+        }
+        deserialize_addr(&mut StreamReadBuffer::new(&self.rta_payload), family)
     }
 }
 
+fn addr_len(family: &RtAddrFamily) -> Result<usize, DeError> {
+    family.addr_len().ok_or_else(|| {
+        DeError::new(&format!(
+            "Address family {:?} does not use IP addresses",
+            family
+        ))
+    })
+}
+
+/// Deserialize an IP address whose version is given by an address family field, such as the
+/// family in the header of the message containing the address
+pub fn deserialize_addr<B>(mem: &mut B, family: &RtAddrFamily) -> Result<IpAddr, DeError>
+where
+    B: Buffer,
+{
+    mem.set_size_hint(addr_len(family)?);
+    IpAddr::deserialize(mem)
+}
+
 impl<T, P> Nl for Rtattr<T, P>
 where
     T: RtaType,
     P: Nl,
 {
-    fn serialize(&self, buf: &mut StreamWriteBuffer) -> Result<(), SerError> {
+    fn serialize<B>(&self, buf: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
         self.rta_len.serialize(buf)?;
         self.rta_type.serialize(buf)?;
         self.rta_payload.serialize(buf)?;
@@ -732,20 +1382,24 @@ where
         Ok(())
     }
 
-    fn deserialize<B>(buf: &mut StreamReadBuffer<B>) -> Result<Self, DeError>
+    fn deserialize<B>(buf: &mut B) -> Result<Self, DeError>
     where
-        B: AsRef<[u8]>,
+        B: Buffer,
     {
         let rta_len = libc::c_ushort::deserialize(buf)?;
         let rta_type = T::deserialize(buf)?;
-        buf.set_size_hint(
-            (rta_len as usize)
-                .checked_sub(rta_len.size() + rta_type.size())
-                .ok_or_else(|| {
-                    DeError::new(&format!("Invalid size while reading Rtattr: {}", rta_len))
-                })?,
-        );
-        let rta_payload = P::deserialize(buf)?;
+        buf.set_size_hint(sub_len(
+            "rta_len",
+            rta_len as usize,
+            rta_len.size() + rta_type.size(),
+        )?);
+        let rta_payload = match P::deserialize(buf) {
+            Ok(payload) => payload,
+            Err(e) => {
+                let ty: libc::c_ushort = rta_type.into();
+                return Err(e.context(format!("{} {}", short_type_name::<T>(), ty)));
+            }
+        };
         let rtattr = Rtattr {
             rta_len,
             rta_type,
@@ -760,10 +1414,862 @@ where
     }
 }
 
+/// Generates an attribute with a random binary payload and `rta_len` matching it
+#[cfg(feature = "arbitrary")]
+impl<'a, T> ::arbitrary::Arbitrary<'a> for Rtattr<T, Vec<u8>>
+where
+    T: RtaType + ::arbitrary::Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut ::arbitrary::Unstructured<'a>) -> ::arbitrary::Result<Self> {
+        let rta_type = u.arbitrary()?;
+        let payload: Vec<u8> = u.arbitrary()?;
+        if payload.len() > usize::from(libc::c_ushort::MAX) - 4 {
+            return Err(::arbitrary::Error::IncorrectFormat);
+        }
+        Rtattr::new(None, rta_type, payload).map_err(|_| ::arbitrary::Error::IncorrectFormat)
+    }
+}
+
+impl<T, P> ToNlValue for Rtattr<T, P>
+where
+    T: ToNlValue,
+    P: ToNlValue,
+{
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("rta_len", self.rta_len.to_nl_value()),
+            ("rta_type", self.rta_type.to_nl_value()),
+            ("rta_payload", self.rta_payload.to_nl_value()),
+        ])
+    }
+}
+
+/// Routing netlink notification, as received with `NlSocket::recv_rt_event`
+#[derive(Debug)]
+pub enum RtEvent {
+    /// An interface was created or changed
+    NewLink(Ifinfomsg),
+    /// An interface was removed
+    DelLink(Ifinfomsg),
+    /// An address was added
+    NewAddr(Ifaddrmsg),
+    /// An address was removed
+    DelAddr(Ifaddrmsg),
+    /// A route was added or changed
+    NewRoute(Rtmsg),
+    /// A route was removed
+    DelRoute(Rtmsg),
+    /// A neighbor entry was added or changed
+    NewNeigh(Ndmsg),
+    /// A neighbor entry was removed
+    DelNeigh(Ndmsg),
+    /// Any other message, with its payload left unparsed
+    Other(Rtm, Vec<u8>),
+}
+
+impl RtEvent {
+    /// Parse the payload of a message according to its type
+    pub fn from_msg(msg: Nlmsghdr<Rtm, Vec<u8>>) -> Result<Self, DeError> {
+        fn parse<P: Nl>(payload: &[u8]) -> Result<P, DeError> {
+            let mut mem = StreamReadBuffer::new(payload);
+            mem.set_size_hint(payload.len());
+            P::deserialize(&mut mem)
+        }
+
+        let payload = &msg.nl_payload;
+        Ok(match msg.nl_type {
+            Rtm::Newlink => RtEvent::NewLink(parse(payload)?),
+            Rtm::Dellink => RtEvent::DelLink(parse(payload)?),
+            Rtm::Newaddr => RtEvent::NewAddr(parse(payload)?),
+            Rtm::Deladdr => RtEvent::DelAddr(parse(payload)?),
+            Rtm::Newroute => RtEvent::NewRoute(parse(payload)?),
+            Rtm::Delroute => RtEvent::DelRoute(parse(payload)?),
+            Rtm::Newneigh => RtEvent::NewNeigh(parse(payload)?),
+            Rtm::Delneigh => RtEvent::DelNeigh(parse(payload)?),
+            nl_type => RtEvent::Other(nl_type, msg.nl_payload),
+        })
+    }
+}
+
+impl NlSocket {
+    /// Join the given routing netlink multicast groups. Notifications sent to them can be
+    /// received with `recv_rt_event`.
+    pub fn subscribe_rt_events(&mut self, groups: &[RtnlGroup]) -> Result<(), io::Error> {
+        let groups = groups.iter().map(u32::from).collect::<Vec<_>>();
+        self.add_mcast_membership(&groups)
+    }
+
+    /// Receive the next routing netlink message, parsed according to its type
+    pub fn recv_rt_event(&mut self) -> Result<RtEvent, NlError> {
+        let msg = self.recv_nl::<Rtm, Vec<u8>>(None)?;
+        Ok(RtEvent::from_msg(msg)?)
+    }
+}
+
+/// Index and operational state of an interface watched by `LinkWatcher`
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinkState {
+    /// Index of the interface
+    pub index: IfIndex,
+    /// Operational state of the interface
+    pub operstate: IfOper,
+}
+
+impl LinkState {
+    /// Whether the interface is up and able to pass packets, see `IfOper::is_up_and_running`
+    pub fn is_up_and_running(&self) -> bool {
+        self.operstate.is_up_and_running()
+    }
+}
+
+/// Change of the interface watched by `LinkWatcher`
+#[derive(Clone, Debug, PartialEq)]
+pub enum LinkEvent {
+    /// An interface with the watched name was created or renamed to that name
+    Appeared(LinkState),
+    /// The operational state of the interface changed
+    OperstateChanged(LinkState),
+    /// The interface with the given index was removed or renamed
+    Disappeared(IfIndex),
+}
+
+/// Watcher for the presence and operational state of an interface given by name
+///
+/// Events are received with `next_event` or by iterating over the watcher, which blocks until
+/// the interface changes. The watcher implements `AsRawFd` so it can be polled together with
+/// other file descriptors, for example to wait for events in an async runtime or to send them
+/// to a channel from a separate thread.
+pub struct LinkWatcher {
+    socket: NlSocket,
+    name: String,
+    state: Option<LinkState>,
+    events: VecDeque<LinkEvent>,
+}
+
+impl LinkWatcher {
+    /// Start watching the interface with the given name, which does not have to exist yet
+    pub fn new(name: &str) -> Result<Self, NlError> {
+        // Join the group before asking for the current state so that no change is missed
+        let socket =
+            NlSocket::connect(NlFamily::Route, None, Some(vec![libc::RTNLGRP_LINK]), false)?;
+        let mut watcher = LinkWatcher {
+            socket,
+            name: name.to_string(),
+            state: None,
+            events: VecDeque::new(),
+        };
+
+        let mut ifname = name.as_bytes().to_vec();
+        ifname.push(0);
+        let ifinfomsg = Ifinfomsg::new(
+            RtAddrFamily::Unspec,
+            Arphrd::None,
+            IfIndex(0),
+            Vec::new(),
+            Rtattrs::new(vec![Rtattr {
+                rta_len: (ifname.len() + 4) as libc::c_ushort,
+                rta_type: Ifla::Ifname,
+                rta_payload: ifname,
+            }]),
+        );
+        watcher.socket.send_nl(Nlmsghdr::new(
+            None,
+            Rtm::Getlink,
+            vec![NlmF::Request],
+            None,
+            None,
+            ifinfomsg,
+        ))?;
+        loop {
+            let (msgs, addr) = watcher.socket.recv_nl_from::<u16, Vec<u8>>(None)?;
+            // Notifications received before the reply are superseded by it
+            if addr.groups != 0 {
+                for msg in msgs {
+                    watcher.process(&msg)?;
+                }
+                continue;
+            }
+            let reply = match msgs.into_iter().next() {
+                Some(reply) => reply,
+                None => continue,
+            };
+            if reply.nl_type == u16::from(Nlmsg::Error) {
+                let mut mem = StreamReadBuffer::new(&reply.nl_payload);
+                let err = Nlmsgerr::<u16>::deserialize(&mut mem)?;
+                if err.error != 0 && err.error != -libc::ENODEV {
+                    return Err(NlError::from_nlmsgerr(err.error));
+                }
+                watcher.state = None;
+            } else {
+                watcher.process(&reply)?;
+            }
+            watcher.events.clear();
+            return Ok(watcher);
+        }
+    }
+
+    /// Current index and operational state of the interface, or `None` if it does not exist
+    pub fn state(&self) -> Option<&LinkState> {
+        self.state.as_ref()
+    }
+
+    /// Block until the watched interface changes
+    pub fn next_event(&mut self) -> Result<LinkEvent, NlError> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(event);
+            }
+            let (msgs, _) = self.socket.recv_nl_from::<u16, Vec<u8>>(None)?;
+            for msg in msgs {
+                self.process(&msg)?;
+            }
+        }
+    }
+
+    /// Block until the state of the interface satisfies `f`, which is checked against the
+    /// current state first
+    pub fn wait_until<F>(&mut self, f: F) -> Result<LinkState, NlError>
+    where
+        F: Fn(&LinkState) -> bool,
+    {
+        loop {
+            if let Some(ref state) = self.state {
+                if f(state) {
+                    return Ok(state.clone());
+                }
+            }
+            self.next_event()?;
+        }
+    }
+
+    fn process(&mut self, msg: &Nlmsghdr<u16, Vec<u8>>) -> Result<(), NlError> {
+        let nl_type = Rtm::from(msg.nl_type);
+        if nl_type != Rtm::Newlink && nl_type != Rtm::Dellink {
+            return Ok(());
+        }
+        let mut mem = StreamReadBuffer::new(&msg.nl_payload);
+        mem.set_size_hint(msg.nl_payload.len());
+        let ifinfomsg = Ifinfomsg::deserialize(&mut mem)?;
+        let ours = ifinfomsg
+            .rtattrs
+            .get_attr_payload_as::<String>(Ifla::Ifname)?
+            .map(|name| name == self.name)
+            .unwrap_or(false);
+        let current = LinkState {
+            index: ifinfomsg.ifi_index,
+            operstate: ifinfomsg.operstate()?.unwrap_or(IfOper::Unknown),
+        };
+
+        let event = match self.state {
+            Some(ref state) if state.index == current.index => {
+                if nl_type == Rtm::Dellink || !ours {
+                    Some(LinkEvent::Disappeared(current.index))
+                } else if state.operstate != current.operstate {
+                    Some(LinkEvent::OperstateChanged(current.clone()))
+                } else {
+                    None
+                }
+            }
+            None if ours && nl_type == Rtm::Newlink => Some(LinkEvent::Appeared(current.clone())),
+            _ => None,
+        };
+        match event {
+            Some(LinkEvent::Disappeared(_)) => self.state = None,
+            Some(_) => self.state = Some(current),
+            None => return Ok(()),
+        }
+        self.events.extend(event);
+        Ok(())
+    }
+}
+
+impl Iterator for LinkWatcher {
+    type Item = Result<LinkEvent, NlError>;
+
+    fn next(&mut self) -> Option<Result<LinkEvent, NlError>> {
+        Some(self.next_event())
+    }
+}
+
+impl AsRawFd for LinkWatcher {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
+
+/// Change to a `NeighTable` or `RouteTable` caused by applying an event
+#[derive(Debug)]
+pub enum TableChange<T> {
+    /// A new entry was added
+    Added,
+    /// An existing entry was replaced and the previous entry is returned
+    Replaced(T),
+    /// An entry was removed and is returned
+    Removed(T),
+    /// The event matched an existing entry exactly, as happens when a dump overlaps with
+    /// notifications, so nothing changed
+    Duplicate,
+    /// The event did not affect the table, such as a delete of an unknown entry or a message
+    /// type that does not belong to the table
+    Ignored,
+}
+
+fn serialized<T>(val: &T) -> Result<Vec<u8>, SerError>
+where
+    T: Nl,
+{
+    let mut mem = StreamWriteBuffer::new_growable(Some(val.asize()));
+    val.serialize(&mut mem)?;
+    Ok(mem.as_ref().to_vec())
+}
+
+/// Identity of a neighbour entry
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NeighKey {
+    /// Address family of the entry
+    pub family: RtAddrFamily,
+    /// Interface of the entry
+    pub ifindex: IfIndex,
+    /// Payload of `Nda::Dst`, or of `Nda::Lladdr` for entries without a destination such as
+    /// bridge forwarding entries
+    pub addr: Vec<u8>,
+}
+
+impl NeighKey {
+    /// Key of the entry described by a neighbour message
+    pub fn from_ndmsg(ndmsg: &Ndmsg) -> Result<Self, DeError> {
+        let addr = match ndmsg.rtattrs.get_attr_payload_as::<Vec<u8>>(Nda::Dst)? {
+            Some(addr) => addr,
+            None => ndmsg
+                .rtattrs
+                .get_attr_payload_as::<Vec<u8>>(Nda::Lladdr)?
+                .unwrap_or_default(),
+        };
+        Ok(NeighKey {
+            family: ndmsg.ndm_family.clone(),
+            ifindex: ndmsg.ndm_index,
+            addr,
+        })
+    }
+}
+
+/// Local copy of the kernel neighbour tables built from `Rtm::Newneigh` and `Rtm::Delneigh`
+/// messages, from dumps or from the `RTNLGRP_NEIGH` multicast group
+///
+/// The kernel keeps a single entry per key, so `Rtm::Newneigh` always replaces an existing entry.
+#[derive(Debug, Default)]
+pub struct NeighTable {
+    entries: HashMap<NeighKey, Ndmsg>,
+}
+
+impl NeighTable {
+    /// Create an empty table
+    pub fn new() -> Self {
+        NeighTable::default()
+    }
+
+    /// Apply a neighbour message to the table
+    pub fn apply(&mut self, msg: Nlmsghdr<Rtm, Ndmsg>) -> Result<TableChange<Ndmsg>, NlError> {
+        let key = NeighKey::from_ndmsg(&msg.nl_payload)?;
+        match msg.nl_type {
+            Rtm::Newneigh => {
+                if let Some(old) = self.entries.get(&key) {
+                    if serialized(old)? == serialized(&msg.nl_payload)? {
+                        return Ok(TableChange::Duplicate);
+                    }
+                }
+                Ok(match self.entries.insert(key, msg.nl_payload) {
+                    Some(old) => TableChange::Replaced(old),
+                    None => TableChange::Added,
+                })
+            }
+            Rtm::Delneigh => Ok(match self.entries.remove(&key) {
+                Some(old) => TableChange::Removed(old),
+                None => TableChange::Ignored,
+            }),
+            _ => Ok(TableChange::Ignored),
+        }
+    }
+
+    /// Look up the entry with the given key
+    pub fn get(&self, key: &NeighKey) -> Option<&Ndmsg> {
+        self.entries.get(key)
+    }
+
+    /// Iterate over all entries in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (&NeighKey, &Ndmsg)> {
+        self.entries.iter()
+    }
+
+    /// Number of entries in the table
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if the table has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Identity of a route, made of the fields the kernel uses to tell routes apart
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RouteKey {
+    /// Address family of the route
+    pub family: RtAddrFamily,
+    /// Routing table, from `Rta::Table` if present as it can hold IDs above 255
+    pub table: u32,
+    /// Payload of `Rta::Dst`, empty for default routes
+    pub dst: Vec<u8>,
+    /// Length of the destination prefix
+    pub dst_len: u8,
+    /// Payload of `Rta::Src`, only used by IPv6 source routing
+    pub src: Vec<u8>,
+    /// Length of the source prefix
+    pub src_len: u8,
+    /// TOS filter
+    pub tos: u8,
+    /// Payload of `Rta::Priority`, 0 if absent
+    pub priority: u32,
+}
+
+impl RouteKey {
+    /// Key of the route described by a route message
+    pub fn from_rtmsg(rtmsg: &Rtmsg) -> Result<Self, DeError> {
+        let attr = |ty| rtmsg.rtattrs.get_attr_payload_as::<Vec<u8>>(ty);
+        Ok(RouteKey {
+            family: rtmsg.rtm_family.clone(),
+            table: match rtmsg.rtattrs.get_attr_payload_as::<u32>(Rta::Table)? {
+                Some(table) => table,
+                None => u32::from(u8::from(rtmsg.rtm_table.clone())),
+            },
+            dst: attr(Rta::Dst)?.unwrap_or_default(),
+            dst_len: rtmsg.rtm_dst_len,
+            src: attr(Rta::Src)?.unwrap_or_default(),
+            src_len: rtmsg.rtm_src_len,
+            tos: rtmsg.rtm_tos,
+            priority: rtmsg
+                .rtattrs
+                .get_attr_payload_as::<u32>(Rta::Priority)?
+                .unwrap_or(0),
+        })
+    }
+}
+
+/// Local copy of the kernel routing tables built from `Rtm::Newroute` and `Rtm::Delroute`
+/// messages, from dumps or from the `RTNLGRP_IPV4_ROUTE` and `RTNLGRP_IPV6_ROUTE` multicast
+/// groups
+///
+/// Like the kernel, several routes can share a key. `Rtm::Newroute` with `NlmF::Replace`
+/// replaces the first route with the same key, with `NlmF::Append` it adds the route after all
+/// others with the same key and otherwise it adds the route before them. `Rtm::Delroute` removes
+/// the first route with the same key that also matches the `Rta::Oif` and `Rta::Gateway`
+/// attributes of the message if present.
+#[derive(Debug, Default)]
+pub struct RouteTable {
+    entries: Vec<(RouteKey, Rtmsg)>,
+}
+
+impl RouteTable {
+    /// Create an empty table
+    pub fn new() -> Self {
+        RouteTable::default()
+    }
+
+    /// Apply a route message to the table
+    pub fn apply(&mut self, msg: Nlmsghdr<Rtm, Rtmsg>) -> Result<TableChange<Rtmsg>, NlError> {
+        let key = RouteKey::from_rtmsg(&msg.nl_payload)?;
+        match msg.nl_type {
+            Rtm::Newroute => {
+                let bytes = serialized(&msg.nl_payload)?;
+                for (k, route) in self.entries.iter() {
+                    if *k == key && serialized(route)? == bytes {
+                        return Ok(TableChange::Duplicate);
+                    }
+                }
+                let first = self.entries.iter().position(|(k, _)| *k == key);
+                // Parsed flags name the shared bits after the first matching constant, so
+                // NLM_F_REPLACE arrives as NlmF::Root and only its bit can be compared
+                let flags = u16::from(&NlmFFlags::new(&msg.nl_flags));
+                if flags & libc::NLM_F_REPLACE as u16 != 0 {
+                    if let Some(i) = first {
+                        let old = mem::replace(&mut self.entries[i].1, msg.nl_payload);
+                        return Ok(TableChange::Replaced(old));
+                    }
+                }
+                let pos = if flags & libc::NLM_F_APPEND as u16 != 0 {
+                    self.entries
+                        .iter()
+                        .rposition(|(k, _)| *k == key)
+                        .map(|i| i + 1)
+                } else {
+                    first
+                };
+                match pos {
+                    Some(i) => self.entries.insert(i, (key, msg.nl_payload)),
+                    None => self.entries.push((key, msg.nl_payload)),
+                }
+                Ok(TableChange::Added)
+            }
+            Rtm::Delroute => {
+                let oif = msg
+                    .nl_payload
+                    .rtattrs
+                    .get_attr_payload_as::<u32>(Rta::Oif)?;
+                let gateway = msg
+                    .nl_payload
+                    .rtattrs
+                    .get_attr_payload_as::<Vec<u8>>(Rta::Gateway)?;
+                let mut found = None;
+                for (i, (k, route)) in self.entries.iter().enumerate() {
+                    if *k != key {
+                        continue;
+                    }
+                    if oif.is_some() && route.rtattrs.get_attr_payload_as::<u32>(Rta::Oif)? != oif {
+                        continue;
+                    }
+                    if gateway.is_some()
+                        && route.rtattrs.get_attr_payload_as::<Vec<u8>>(Rta::Gateway)? != gateway
+                    {
+                        continue;
+                    }
+                    found = Some(i);
+                    break;
+                }
+                Ok(match found {
+                    Some(i) => TableChange::Removed(self.entries.remove(i).1),
+                    None => TableChange::Ignored,
+                })
+            }
+            _ => Ok(TableChange::Ignored),
+        }
+    }
+
+    /// Iterate over all routes with the given key in kernel order
+    pub fn get<'a>(&'a self, key: &'a RouteKey) -> impl Iterator<Item = &'a Rtmsg> + 'a {
+        self.entries
+            .iter()
+            .filter(move |(k, _)| k == key)
+            .map(|(_, route)| route)
+    }
+
+    /// Iterate over all routes
+    pub fn iter(&self) -> impl Iterator<Item = (&RouteKey, &Rtmsg)> {
+        self.entries.iter().map(|(k, route)| (k, route))
+    }
+
+    /// Number of routes in the table
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if the table has no routes
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Round-trip byte vectors of routing netlink messages, captured from a little endian kernel,
+/// through deserialization and serialization. This can be called from downstream test suites to
+/// verify that `neli` matches the wire format of the target platform.
+#[cfg(any(test, feature = "test-util"))]
+pub fn verify_wire_compat() -> Result<(), NlError> {
+    let link = verify_roundtrip::<Nlmsghdr<Rtm, Ifinfomsg>>(&parse_hex(VECTOR_NEWLINK)?)?;
+    if link
+        .nl_payload
+        .rtattrs
+        .get_attr_payload_as::<String>(Ifla::Ifname)?
+        != Some("lo".to_string())
+        || !link.nl_payload.ifi_flags.contains(&Iff::Loopback)
+    {
+        return Err(NlError::new("Failed to parse loopback link"));
+    }
+    let addr = verify_roundtrip::<Nlmsghdr<Rtm, Ifaddrmsg>>(&parse_hex(VECTOR_NEWADDR)?)?;
+    if addr.nl_payload.get_addr(Ifa::Address)? != Some(IpAddr::from([127, 0, 0, 1]))
+        || addr.nl_payload.ifa_prefixlen != 8
+    {
+        return Err(NlError::new("Failed to parse loopback address"));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::consts::Rta;
+    use crate::consts::{NlFamily, Rta};
+
+    #[test]
+    fn test_to_nl_value() {
+        let msg = verify_roundtrip::<Nlmsghdr<Rtm, Ifaddrmsg>>(&parse_hex(VECTOR_NEWADDR).unwrap())
+            .unwrap();
+        let val = msg.to_nl_value();
+        assert_eq!(
+            val.get("nlmsg_type"),
+            Some(&NlValue::Const {
+                name: Some("Newaddr".to_string()),
+                value: libc::RTM_NEWADDR as i128,
+            })
+        );
+        let payload = val.get("payload").unwrap();
+        assert_eq!(payload.get("ifa_index"), Some(&NlValue::Uint(1)));
+        match payload.get("rtattrs") {
+            Some(NlValue::List(attrs)) => assert_eq!(attrs.len(), 5),
+            v => panic!("Unexpected attributes {:?}", v),
+        }
+    }
+
+    #[test]
+    fn test_ifindex_cache() {
+        let mut s = NlSocket::connect(NlFamily::Route, None, None, true).unwrap();
+        let mut cache = IfIndexCache::load(&mut s).unwrap();
+        let lo = cache.index("lo").unwrap();
+        assert_eq!(cache.name(lo), Some("lo"));
+
+        let mut link =
+            verify_roundtrip::<Nlmsghdr<Rtm, Ifinfomsg>>(&parse_hex(VECTOR_NEWLINK).unwrap())
+                .unwrap();
+        link.nl_payload.ifi_index = IfIndex(1000);
+        cache.update(&link).unwrap();
+        assert_eq!(cache.index("lo"), Some(IfIndex(1000)));
+        assert_eq!(cache.name(lo), None);
+        link.nl_type = Rtm::Dellink;
+        cache.update(&link).unwrap();
+        assert_eq!(cache.index("lo"), None);
+    }
+
+    #[test]
+    fn test_get_addr() {
+        let mut msg =
+            verify_roundtrip::<Nlmsghdr<Rtm, Ifaddrmsg>>(&parse_hex(VECTOR_NEWADDR).unwrap())
+                .unwrap();
+        assert_eq!(
+            msg.nl_payload.get_addr(Ifa::Local).unwrap(),
+            Some(IpAddr::from([127, 0, 0, 1]))
+        );
+        assert_eq!(msg.nl_payload.get_addr(Ifa::Broadcast).unwrap(), None);
+        // The length of an IPv4 address does not match the family
+        msg.nl_payload.ifa_family = RtAddrFamily::Inet6;
+        assert!(msg.nl_payload.get_addr(Ifa::Local).is_err());
+    }
+
+    #[test]
+    fn test_mac_address() {
+        let mac = "02:00:5e:10:0:A1".parse::<MacAddress>().unwrap();
+        assert_eq!(mac, MacAddress([2, 0, 0x5e, 0x10, 0, 0xa1]));
+        assert_eq!(mac.to_string(), "02:00:5e:10:00:a1");
+        for s in &[
+            "02:00:5e:10:00",
+            "02:00:5e:10:00:a1:ff",
+            "02:00:5e:10::a1",
+            "02:00:5e:10:00:1a1",
+        ] {
+            assert!(s.parse::<MacAddress>().is_err());
+        }
+
+        let link =
+            verify_roundtrip::<Nlmsghdr<Rtm, Ifinfomsg>>(&parse_hex(VECTOR_NEWLINK).unwrap())
+                .unwrap();
+        assert_eq!(
+            link.nl_payload.get_mac(Ifla::Address).unwrap(),
+            Some(MacAddress::default())
+        );
+        assert!(link.nl_payload.get_mac(Ifla::Mtu).is_err());
+    }
+
+    #[test]
+    fn test_hwaddr() {
+        let ib = "80:00:02:08:fe:80:00:00:00:00:00:00:00:02:c9:03:00:0a:8b:b1";
+        let addr = ib.parse::<HwAddr>().unwrap();
+        assert_eq!(addr.len(), 20);
+        assert_eq!(addr.to_string(), ib);
+        assert_eq!(addr.to_mac(), None);
+        let eui64 = HwAddr::try_from(&[2, 0, 0, 0xff, 0xfe, 0, 0, 1][..]).unwrap();
+        assert_eq!(eui64.to_string(), "02:00:00:ff:fe:00:00:01");
+        assert!(HwAddr::try_from(&[0; MAX_ADDR_LEN + 1][..]).is_err());
+        assert!("".parse::<HwAddr>().is_err());
+
+        let link =
+            verify_roundtrip::<Nlmsghdr<Rtm, Ifinfomsg>>(&parse_hex(VECTOR_NEWLINK).unwrap())
+                .unwrap();
+        let addr = link.nl_payload.get_hwaddr(Ifla::Address).unwrap().unwrap();
+        assert_eq!(addr.to_mac(), Some(MacAddress::default()));
+        assert_eq!(HwAddr::from(MacAddress::default()), addr);
+    }
+
+    fn rtattr<T>(rta_type: T, rta_payload: Vec<u8>) -> Rtattr<T, Vec<u8>> {
+        Rtattr {
+            rta_len: 4 + rta_payload.len() as libc::c_ushort,
+            rta_type,
+            rta_payload,
+        }
+    }
+
+    fn route(nl_type: Rtm, flags: Vec<NlmF>, gateway: u8) -> Nlmsghdr<Rtm, Rtmsg> {
+        let rtmsg = Rtmsg {
+            rtm_family: RtAddrFamily::Inet,
+            rtm_dst_len: 24,
+            rtm_src_len: 0,
+            rtm_tos: 0,
+            rtm_table: RtTable::Main,
+            rtm_protocol: Rtprot::Static,
+            rtm_scope: RtScope::Universe,
+            rtm_type: Rtn::Unicast,
+            rtm_flags: vec![],
+            rtattrs: Rtattrs::new(vec![
+                rtattr(Rta::Dst, vec![10, 0, 0, 0]),
+                rtattr(Rta::Gateway, vec![192, 168, 0, gateway]),
+            ]),
+        };
+        // Parse the message from its bytes to see the flags as they arrive from the kernel
+        let bytes = serialized(&Nlmsghdr::new(None, nl_type, flags, None, None, rtmsg)).unwrap();
+        Nlmsghdr::deserialize(&mut StreamReadBuffer::new(&bytes)).unwrap()
+    }
+
+    #[test]
+    fn test_route_table() {
+        let mut table = RouteTable::new();
+        let added = table.apply(route(Rtm::Newroute, vec![], 1)).unwrap();
+        assert!(matches!(added, TableChange::Added));
+        // The same route seen again in a dump
+        let dup = table
+            .apply(route(Rtm::Newroute, vec![NlmF::Multi], 1))
+            .unwrap();
+        assert!(matches!(dup, TableChange::Duplicate));
+        let appended = table
+            .apply(route(Rtm::Newroute, vec![NlmF::Append], 2))
+            .unwrap();
+        assert!(matches!(appended, TableChange::Added));
+        assert_eq!(table.len(), 2);
+
+        let replaced = table
+            .apply(route(Rtm::Newroute, vec![NlmF::Replace], 3))
+            .unwrap();
+        match replaced {
+            TableChange::Replaced(old) => {
+                assert_eq!(
+                    old.get_addr(Rta::Gateway).unwrap(),
+                    Some(IpAddr::from([192, 168, 0, 1]))
+                )
+            }
+            c => panic!("Unexpected change {:?}", c),
+        }
+
+        // Deletes match the gateway when one is given
+        let removed = table.apply(route(Rtm::Delroute, vec![], 2)).unwrap();
+        match removed {
+            TableChange::Removed(old) => {
+                assert_eq!(
+                    old.get_addr(Rta::Gateway).unwrap(),
+                    Some(IpAddr::from([192, 168, 0, 2]))
+                )
+            }
+            c => panic!("Unexpected change {:?}", c),
+        }
+        let missing = table.apply(route(Rtm::Delroute, vec![], 2)).unwrap();
+        assert!(matches!(missing, TableChange::Ignored));
+        assert_eq!(table.len(), 1);
+    }
+
+    #[test]
+    fn test_neigh_table() {
+        let neigh = |nl_type, lladdr| {
+            let ndmsg = Ndmsg {
+                ndm_family: RtAddrFamily::Inet,
+                ndm_index: IfIndex(2),
+                ndm_state: vec![Nud::Reachable],
+                ndm_flags: vec![],
+                ndm_type: Rtn::Unicast,
+                rtattrs: Rtattrs::new(vec![
+                    rtattr(Nda::Dst, vec![10, 0, 0, 1]),
+                    rtattr(Nda::Lladdr, vec![0, 1, 2, 3, 4, lladdr]),
+                ]),
+            };
+            Nlmsghdr::new(None, nl_type, vec![], None, None, ndmsg)
+        };
+        let mut table = NeighTable::new();
+        assert!(matches!(
+            table.apply(neigh(Rtm::Newneigh, 5)).unwrap(),
+            TableChange::Added
+        ));
+        assert!(matches!(
+            table.apply(neigh(Rtm::Newneigh, 5)).unwrap(),
+            TableChange::Duplicate
+        ));
+        assert!(matches!(
+            table.apply(neigh(Rtm::Newneigh, 6)).unwrap(),
+            TableChange::Replaced(_)
+        ));
+        assert_eq!(table.len(), 1);
+        assert!(matches!(
+            table.apply(neigh(Rtm::Delneigh, 6)).unwrap(),
+            TableChange::Removed(_)
+        ));
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn test_rt_events() {
+        let mut s = NlSocket::connect(NlFamily::Route, None, None, false).unwrap();
+        s.subscribe_rt_events(&[RtnlGroup::Link, RtnlGroup::Ipv4Route])
+            .unwrap();
+        let groups = s.list_mcast_membership().unwrap();
+        assert!(groups.contains(&libc::RTNLGRP_LINK));
+        assert!(groups.contains(&libc::RTNLGRP_IPV4_ROUTE));
+
+        let link = verify_roundtrip::<Nlmsghdr<Rtm, Vec<u8>>>(&parse_hex(VECTOR_NEWLINK).unwrap())
+            .unwrap();
+        match RtEvent::from_msg(link).unwrap() {
+            RtEvent::NewLink(ifinfomsg) => assert_eq!(ifinfomsg.ifi_index, IfIndex(1)),
+            e => panic!("Unexpected event {:?}", e),
+        }
+        let mut addr =
+            verify_roundtrip::<Nlmsghdr<Rtm, Vec<u8>>>(&parse_hex(VECTOR_NEWADDR).unwrap())
+                .unwrap();
+        addr.nl_type = Rtm::Deladdr;
+        match RtEvent::from_msg(addr).unwrap() {
+            RtEvent::DelAddr(ifaddrmsg) => assert_eq!(ifaddrmsg.ifa_prefixlen, 8),
+            e => panic!("Unexpected event {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_link_state() {
+        let link =
+            verify_roundtrip::<Nlmsghdr<Rtm, Ifinfomsg>>(&parse_hex(VECTOR_NEWLINK).unwrap())
+                .unwrap();
+        assert_eq!(link.nl_payload.operstate().unwrap(), Some(IfOper::Unknown));
+        assert_eq!(link.nl_payload.carrier().unwrap(), Some(IfCarrier::Up));
+        assert_eq!(
+            link.nl_payload.link_mode().unwrap(),
+            Some(IfLinkMode::Default)
+        );
+        assert!(!IfOper::Unknown.is_up_and_running());
+        assert!(!IfOper::Unknown.is_down());
+        assert!(IfOper::Lowerlayerdown.is_down());
+        let state = LinkState {
+            index: IfIndex(1),
+            operstate: IfOper::Up,
+        };
+        assert!(state.is_up_and_running());
+    }
+
+    #[test]
+    fn test_link_watcher() {
+        let mut watcher = LinkWatcher::new("lo").unwrap();
+        assert_eq!(watcher.state().map(|state| state.index), Some(IfIndex(1)));
+        let mut link =
+            verify_roundtrip::<Nlmsghdr<u16, Vec<u8>>>(&parse_hex(VECTOR_NEWLINK).unwrap())
+                .unwrap();
+        link.nl_type = Rtm::Dellink.into();
+        watcher.process(&link).unwrap();
+        assert_eq!(
+            watcher.next_event().unwrap(),
+            LinkEvent::Disappeared(IfIndex(1))
+        );
+        assert!(watcher.state().is_none());
+
+        assert!(LinkWatcher::new("neli-missing0").unwrap().state().is_none());
+    }
 
     #[test]
     fn test_rta_deserialize() {
@@ -799,4 +2305,39 @@ mod test {
         // padding check
         assert_eq!(buf.as_ref().len(), 8);
     }
+
+    #[test]
+    fn test_verify_wire_compat() {
+        verify_wire_compat().unwrap();
+    }
+
+    #[test]
+    fn test_unknown_rtattrs_roundtrip() {
+        let nlhdr = [48, 0, 0, 0, 20, 0, 2, 0, 1, 0, 0, 0, 0, 0, 0, 0];
+        let ifaddrmsg = [2, 24, 0x80, 0, 3, 0, 0, 0];
+        let address = [8, 0, 1, 0, 10, 0, 0, 1];
+        // Attribute type unknown to neli with an unaligned payload
+        let unknown = [5, 0, 0x77, 0, 9, 0, 0, 0];
+        let label = [7, 0, 3, 0, 101, 116, 0, 0];
+        let v = [&nlhdr[..], &ifaddrmsg, &address, &unknown, &label].concat();
+        let mut mem = StreamReadBuffer::new(&v);
+        let msg = Nlmsghdr::<Rtm, Ifaddrmsg>::deserialize(&mut mem).unwrap();
+        assert!(mem.at_end());
+        assert_eq!(msg.nl_payload.ifa_flags, vec![IfaF::Permanent]);
+        let types = msg
+            .nl_payload
+            .rtattrs
+            .iter()
+            .map(|attr| attr.rta_type.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec![Ifa::Address, Ifa::UnrecognizedVariant(0x77), Ifa::Label]
+        );
+
+        let mut out = StreamWriteBuffer::new_growable(None);
+        msg.serialize(&mut out).unwrap();
+        assert_eq!(out.as_ref(), v.as_slice());
+        assert_eq!(msg.size(), v.len());
+    }
 }
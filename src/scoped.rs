@@ -0,0 +1,37 @@
+//! This module sets thread locals for the duration of a closure. It backs settings such as the
+//! byte order selected with `endian::with_endian`, which cannot be passed through the methods of
+//! `Nl` and are therefore kept per thread.
+
+use std::cell::Cell;
+use std::thread::LocalKey;
+
+/// Restores the previous value of a thread local when dropped, including when unwinding
+struct Restore<T>
+where
+    T: Copy + 'static,
+{
+    key: &'static LocalKey<Cell<T>>,
+    previous: T,
+}
+
+impl<T> Drop for Restore<T>
+where
+    T: Copy + 'static,
+{
+    fn drop(&mut self) {
+        self.key.with(|cell| cell.set(self.previous));
+    }
+}
+
+/// Run `f` with the thread local `key` set to `value` on the current thread
+pub(crate) fn with_scoped<T, F, R>(key: &'static LocalKey<Cell<T>>, value: T, f: F) -> R
+where
+    T: Copy + 'static,
+    F: FnOnce() -> R,
+{
+    let _restore = Restore {
+        key,
+        previous: key.with(|cell| cell.replace(value)),
+    };
+    f()
+}
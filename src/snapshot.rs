@@ -0,0 +1,151 @@
+//! This module stores sequences of serialized messages in files, so that configuration
+//! snapshots, such as the full route table dumped at some point, can be diffed and replayed
+//! later.
+//!
+//! A snapshot starts with the magic bytes `NELI`, a little endian `u16` format version and one
+//! byte naming the byte order the messages were serialized in, 0 for little and 1 for big
+//! endian. Every message follows as a little endian `u32` length and the message in the same
+//! wire format as sent to or received from the kernel.
+//!
+//! # Design decisions
+//!
+//! Messages are stored in wire format instead of through serde so that a snapshot can be sent
+//! to the kernel again as is. `read_from` parses the messages in the byte order recorded in the
+//! header, so snapshots taken on machines of the opposite endianness can be read as well.
+
+use std::io::{self, Read, Write};
+
+use buffering::{StreamReadBuffer, StreamWriteBuffer};
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use endian::{self, Endian};
+use err::NlError;
+use limits;
+use Nl;
+
+/// Magic bytes at the start of every snapshot
+const MAGIC: &[u8; 4] = b"NELI";
+
+/// Version of the snapshot format written by `write_to`
+pub const SNAPSHOT_VERSION: u16 = 1;
+
+/// Write a snapshot of `msgs` to `writer`
+pub fn write_to<W, M>(writer: &mut W, msgs: &[M]) -> Result<(), NlError>
+where
+    W: Write,
+    M: Nl,
+{
+    writer.write_all(MAGIC)?;
+    writer.write_u16::<LittleEndian>(SNAPSHOT_VERSION)?;
+    writer.write_u8(match endian::endian() {
+        Endian::Little => 0,
+        Endian::Big => 1,
+    })?;
+    for msg in msgs {
+        let mut mem = StreamWriteBuffer::new_growable(Some(msg.size()));
+        msg.serialize(&mut mem)?;
+        writer.write_u32::<LittleEndian>(mem.as_ref().len() as u32)?;
+        writer.write_all(mem.as_ref())?;
+    }
+    Ok(())
+}
+
+/// Read all messages of a snapshot written by `write_to` from `reader`
+pub fn read_from<R, M>(reader: &mut R) -> Result<Vec<M>, NlError>
+where
+    R: Read,
+    M: Nl,
+{
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(NlError::new("Not a neli snapshot"));
+    }
+    let version = reader.read_u16::<LittleEndian>()?;
+    if version != SNAPSHOT_VERSION {
+        return Err(NlError::Msg(format!(
+            "Unsupported snapshot version {}",
+            version
+        )));
+    }
+    let byte_order = match reader.read_u8()? {
+        0 => Endian::Little,
+        1 => Endian::Big,
+        b => return Err(NlError::Msg(format!("Invalid snapshot byte order {}", b))),
+    };
+
+    let mut msgs = Vec::new();
+    let mut buf = Vec::new();
+    loop {
+        // Only running out of data before a length prefix ends the snapshot cleanly
+        let mut prefix = [0; 4];
+        match reader.read(&mut prefix[..1]) {
+            Ok(0) => break,
+            Ok(_) => (),
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+        reader.read_exact(&mut prefix[1..])?;
+        let len = LittleEndian::read_u32(&prefix) as usize;
+        limits::check_msg_size(len)?;
+        buf.resize(len, 0);
+        reader.read_exact(&mut buf)?;
+        let msg = endian::with_endian(byte_order, || {
+            let mut mem = StreamReadBuffer::new(&buf[..]);
+            mem.set_size_hint(len);
+            M::deserialize(&mut mem)
+        })?;
+        msgs.push(msg);
+    }
+    Ok(msgs)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use consts::{NlmF, Rtm};
+    use nl::Nlmsghdr;
+
+    #[test]
+    fn test_snapshot() {
+        let msgs = vec![
+            Nlmsghdr::new(
+                None,
+                Rtm::Newroute,
+                vec![NlmF::Multi],
+                Some(1),
+                None,
+                vec![1, 2],
+            ),
+            Nlmsghdr::new(
+                None,
+                Rtm::Newroute,
+                vec![NlmF::Multi],
+                Some(1),
+                None,
+                vec![3],
+            ),
+        ];
+        let mut file = Vec::new();
+        write_to(&mut file, &msgs).unwrap();
+        assert_eq!(&file[..4], b"NELI");
+        // Messages are padded to the netlink alignment
+        assert_eq!(file.len(), 7 + 2 * (4 + 20));
+
+        let read = read_from::<_, Nlmsghdr<Rtm, Vec<u8>>>(&mut Cursor::new(&file)).unwrap();
+        assert_eq!(read, msgs);
+
+        file[4] = 2;
+        assert!(read_from::<_, Nlmsghdr<Rtm, Vec<u8>>>(&mut Cursor::new(&file)).is_err());
+        // A message cut short is an error rather than the end of the snapshot
+        file[4] = 1;
+        file.pop();
+        assert!(read_from::<_, Nlmsghdr<Rtm, Vec<u8>>>(&mut Cursor::new(&file)).is_err());
+        // So is a length prefix cut short
+        file.truncate(7 + 4 + 20 + 2);
+        assert!(read_from::<_, Nlmsghdr<Rtm, Vec<u8>>>(&mut Cursor::new(&file)).is_err());
+    }
+}
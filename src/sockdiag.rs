@@ -0,0 +1,1294 @@
+//! Socket diagnostics over `NETLINK_SOCK_DIAG`
+//!
+//! Sending an `InetDiagReqV2` with `SockDiagMsg::ByFamily` and `NlmF::Dump` to a socket of
+//! `NlFamily::SockOrInetDiag` dumps the IPv4 or IPv6 sockets of one protocol in the requested
+//! states, as `ss` does. Each socket is described by an `InetDiagMsg`, followed by the
+//! attributes requested with the extension bits of the request. `UnixDiagReq`, `NetlinkDiagReq`
+//! and `PacketDiagReq` and their replies do the same for `AF_UNIX`, netlink and `AF_PACKET`
+//! sockets.
+//!
+//! ```no_run
+//! use neli::consts::sockdiag::{SockDiagMsg, TcpState};
+//! use neli::consts::{NlFamily, NlmF, RtAddrFamily};
+//! use neli::nl::Nlmsghdr;
+//! use neli::sockdiag::{InetDiagMsg, InetDiagReqV2};
+//! use neli::socket::NlSocket;
+//!
+//! let mut socket = NlSocket::connect(NlFamily::SockOrInetDiag, None, None, true).unwrap();
+//! let req = InetDiagReqV2::new(RtAddrFamily::Inet, libc::IPPROTO_TCP as u8)
+//!     .with_states(&[TcpState::Listen]);
+//! let msg = Nlmsghdr::new(
+//!     None,
+//!     SockDiagMsg::ByFamily,
+//!     vec![NlmF::Request, NlmF::Dump],
+//!     None,
+//!     None,
+//!     req,
+//! );
+//! let replies = socket.request::<_, _, SockDiagMsg, InetDiagMsg>(msg).unwrap();
+//! for reply in replies {
+//!     let sock = reply.nl_payload;
+//!     println!("{:?}:{}", sock.id.src_addr(&sock.idiag_family), sock.id.idiag_sport);
+//! }
+//! ```
+//!
+//! # Design decisions
+//!
+//! Ports are held in host byte order and converted from and to the network byte order of the
+//! wire format. Addresses are kept as the 16 bytes of the kernel structure, as their meaning
+//! depends on the address family of the enclosing message; `InetDiagSockid::src_addr` and
+//! `InetDiagSockid::dst_addr` interpret them.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use consts::sockdiag::{
+    InetDiagAttr, NdiagFlag, NdiagFlags, NdiagShow, NdiagShowFlags, NetlinkDiagAttr,
+    PacketDiagAttr, PacketShow, PacketShowFlags, TcpState, UdiagShow, UdiagShowFlags, UnixDiagAttr,
+};
+use consts::{NlAttrType, RtAddrFamily};
+use endian::{self, Endian};
+use err::{sub_len, DeError, SerError};
+use nlattr::Nlattr;
+use value::{NlValue, ToNlValue};
+use {BeU16, Buffer, BufferMut, Nl, Padding};
+
+/// Cookie value matching any socket in `InetDiagSockid::idiag_cookie`
+pub const INET_DIAG_NOCOOKIE: [u32; 2] = [!0, !0];
+
+/// Protocol of `NetlinkDiagReq` matching netlink sockets of all protocols
+pub const NDIAG_PROTO_ALL: u8 = 255;
+
+/// Interpret the 16 address bytes of `InetDiagSockid` according to `family`
+fn addr(family: &RtAddrFamily, bytes: &[u8; 16]) -> Option<IpAddr> {
+    match *family {
+        RtAddrFamily::Inet => Some(IpAddr::V4(Ipv4Addr::new(
+            bytes[0], bytes[1], bytes[2], bytes[3],
+        ))),
+        RtAddrFamily::Inet6 => Some(IpAddr::V6(Ipv6Addr::from(*bytes))),
+        _ => None,
+    }
+}
+
+/// Store an address in the 16 address bytes of `InetDiagSockid`
+fn addr_bytes(addr: IpAddr) -> [u8; 16] {
+    let mut bytes = [0; 16];
+    match addr {
+        IpAddr::V4(v4) => bytes[..4].copy_from_slice(&v4.octets()),
+        IpAddr::V6(v6) => bytes = v6.octets(),
+    }
+    bytes
+}
+
+/// Get the attribute of type `attr_type` in `attrs` as type `R`
+fn attr_payload<T, R>(attrs: &[Nlattr<T, Vec<u8>>], attr_type: T) -> Result<Option<R>, DeError>
+where
+    T: NlAttrType,
+    R: Nl,
+{
+    match attrs.iter().find(|attr| attr.nla_type == attr_type) {
+        Some(attr) => attr.get_payload_as().map(Some),
+        None => Ok(None),
+    }
+}
+
+/// `struct inet_diag_sockid`, identifying a socket by its addresses, ports and cookie
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct InetDiagSockid {
+    /// Source port, in host byte order
+    pub idiag_sport: u16,
+    /// Destination port, in host byte order
+    pub idiag_dport: u16,
+    /// Source address, in the first 4 bytes for IPv4
+    pub idiag_src: [u8; 16],
+    /// Destination address, in the first 4 bytes for IPv4
+    pub idiag_dst: [u8; 16],
+    /// Index of the interface the socket is bound to, or 0
+    pub idiag_if: u32,
+    /// Kernel cookie of the socket, or `INET_DIAG_NOCOOKIE` in requests matching any socket
+    pub idiag_cookie: [u32; 2],
+}
+
+impl InetDiagSockid {
+    /// Source address of a socket of the given family
+    pub fn src_addr(&self, family: &RtAddrFamily) -> Option<IpAddr> {
+        addr(family, &self.idiag_src)
+    }
+
+    /// Destination address of a socket of the given family
+    pub fn dst_addr(&self, family: &RtAddrFamily) -> Option<IpAddr> {
+        addr(family, &self.idiag_dst)
+    }
+
+    /// Set the source address and port
+    pub fn set_src(&mut self, addr: IpAddr, port: u16) {
+        self.idiag_src = addr_bytes(addr);
+        self.idiag_sport = port;
+    }
+
+    /// Set the destination address and port
+    pub fn set_dst(&mut self, addr: IpAddr, port: u16) {
+        self.idiag_dst = addr_bytes(addr);
+        self.idiag_dport = port;
+    }
+}
+
+impl Default for InetDiagSockid {
+    fn default() -> Self {
+        InetDiagSockid {
+            idiag_sport: 0,
+            idiag_dport: 0,
+            idiag_src: [0; 16],
+            idiag_dst: [0; 16],
+            idiag_if: 0,
+            idiag_cookie: INET_DIAG_NOCOOKIE,
+        }
+    }
+}
+
+impl Nl for InetDiagSockid {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        BeU16(self.idiag_sport).serialize(mem)?;
+        BeU16(self.idiag_dport).serialize(mem)?;
+        self.idiag_src.serialize(mem)?;
+        self.idiag_dst.serialize(mem)?;
+        self.idiag_if.serialize(mem)?;
+        self.idiag_cookie[0].serialize(mem)?;
+        self.idiag_cookie[1].serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        Ok(InetDiagSockid {
+            idiag_sport: BeU16::deserialize(mem)?.0,
+            idiag_dport: BeU16::deserialize(mem)?.0,
+            idiag_src: <[u8; 16]>::deserialize(mem)?,
+            idiag_dst: <[u8; 16]>::deserialize(mem)?,
+            idiag_if: u32::deserialize(mem)?,
+            idiag_cookie: [u32::deserialize(mem)?, u32::deserialize(mem)?],
+        })
+    }
+
+    fn size(&self) -> usize {
+        48
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(48)
+    }
+}
+
+impl ToNlValue for InetDiagSockid {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("idiag_sport", self.idiag_sport.to_nl_value()),
+            ("idiag_dport", self.idiag_dport.to_nl_value()),
+            ("idiag_src", NlValue::Bytes(self.idiag_src.to_vec())),
+            ("idiag_dst", NlValue::Bytes(self.idiag_dst.to_vec())),
+            ("idiag_if", self.idiag_if.to_nl_value()),
+            ("idiag_cookie", NlValue::list(&self.idiag_cookie)),
+        ])
+    }
+}
+
+/// `struct inet_diag_req_v2`, requesting the sockets of one address family and protocol
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct InetDiagReqV2 {
+    /// Address family, `Inet` or `Inet6`
+    pub sdiag_family: RtAddrFamily,
+    /// IP protocol number, such as `IPPROTO_TCP`
+    pub sdiag_protocol: u8,
+    /// Bits of the attributes to include in the replies, see `InetDiagAttr::ext_bit`
+    pub idiag_ext: u8,
+    /// Bits of the states of the sockets to dump, see `TcpState::bit`
+    pub idiag_states: u32,
+    /// Socket to look up, or a socket with wildcard cookie for dumps
+    pub id: InetDiagSockid,
+}
+
+impl InetDiagReqV2 {
+    /// Request for the sockets of the given family and protocol in all states, without
+    /// attributes
+    pub fn new(family: RtAddrFamily, protocol: u8) -> Self {
+        InetDiagReqV2 {
+            sdiag_family: family,
+            sdiag_protocol: protocol,
+            idiag_ext: 0,
+            idiag_states: !0,
+            id: InetDiagSockid::default(),
+        }
+    }
+
+    /// Only request sockets in the given states
+    pub fn with_states(mut self, states: &[TcpState]) -> Self {
+        self.idiag_states = states.iter().fold(0, |acc, state| acc | state.bit());
+        self
+    }
+
+    /// Include the given attribute in the replies. Returns an error for attributes that are not
+    /// requested with an extension bit.
+    pub fn with_ext(mut self, attr: InetDiagAttr) -> Result<Self, SerError> {
+        let bit = attr.ext_bit().ok_or_else(|| {
+            SerError::new(format!("{:?} cannot be requested as an extension", attr))
+        })?;
+        self.idiag_ext |= bit;
+        Ok(self)
+    }
+}
+
+impl Nl for InetDiagReqV2 {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.sdiag_family.serialize(mem)?;
+        self.sdiag_protocol.serialize(mem)?;
+        self.idiag_ext.serialize(mem)?;
+        Padding::<1>.serialize(mem)?;
+        self.idiag_states.serialize(mem)?;
+        self.id.serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        let sdiag_family = RtAddrFamily::deserialize(mem)?;
+        let sdiag_protocol = u8::deserialize(mem)?;
+        let idiag_ext = u8::deserialize(mem)?;
+        Padding::<1>::deserialize(mem)?;
+        Ok(InetDiagReqV2 {
+            sdiag_family,
+            sdiag_protocol,
+            idiag_ext,
+            idiag_states: u32::deserialize(mem)?,
+            id: InetDiagSockid::deserialize(mem)?,
+        })
+    }
+
+    fn size(&self) -> usize {
+        56
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(56)
+    }
+}
+
+impl ToNlValue for InetDiagReqV2 {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("sdiag_family", self.sdiag_family.to_nl_value()),
+            ("sdiag_protocol", self.sdiag_protocol.to_nl_value()),
+            ("idiag_ext", self.idiag_ext.to_nl_value()),
+            ("idiag_states", self.idiag_states.to_nl_value()),
+            ("id", self.id.to_nl_value()),
+        ])
+    }
+}
+
+/// `struct inet_diag_msg` describing one socket, followed by its attributes
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct InetDiagMsg {
+    /// Address family of the socket
+    pub idiag_family: RtAddrFamily,
+    /// State of the socket
+    pub idiag_state: TcpState,
+    /// Kind of the pending timer, 0 for none
+    pub idiag_timer: u8,
+    /// Number of retransmissions, or of unanswered keepalive probes
+    pub idiag_retrans: u8,
+    /// Addresses, ports and cookie of the socket
+    pub id: InetDiagSockid,
+    /// Time until the pending timer expires, in milliseconds
+    pub idiag_expires: u32,
+    /// Length of the receive queue, or of the accept queue for listening sockets
+    pub idiag_rqueue: u32,
+    /// Length of the send queue, or the backlog for listening sockets
+    pub idiag_wqueue: u32,
+    /// User ID of the socket owner
+    pub idiag_uid: u32,
+    /// Inode of the socket
+    pub idiag_inode: u32,
+    /// Attributes requested with `InetDiagReqV2::idiag_ext`
+    pub attrs: Vec<Nlattr<InetDiagAttr, Vec<u8>>>,
+}
+
+/// Size of `InetDiagMsg` without its attributes
+const INET_DIAG_MSG_LEN: usize = 72;
+
+impl Nl for InetDiagMsg {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.idiag_family.serialize(mem)?;
+        self.idiag_state.serialize(mem)?;
+        self.idiag_timer.serialize(mem)?;
+        self.idiag_retrans.serialize(mem)?;
+        self.id.serialize(mem)?;
+        self.idiag_expires.serialize(mem)?;
+        self.idiag_rqueue.serialize(mem)?;
+        self.idiag_wqueue.serialize(mem)?;
+        self.idiag_uid.serialize(mem)?;
+        self.idiag_inode.serialize(mem)?;
+        self.attrs.serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        let size_hint = mem
+            .take_size_hint()
+            .ok_or_else(|| DeError::new("Must provide size hint to deserialize InetDiagMsg"))?;
+        let attrs_len = sub_len("size_hint", size_hint, INET_DIAG_MSG_LEN)?;
+        let idiag_family = RtAddrFamily::deserialize(mem)?;
+        let idiag_state = TcpState::deserialize(mem)?;
+        let idiag_timer = u8::deserialize(mem)?;
+        let idiag_retrans = u8::deserialize(mem)?;
+        let id = InetDiagSockid::deserialize(mem)?;
+        let idiag_expires = u32::deserialize(mem)?;
+        let idiag_rqueue = u32::deserialize(mem)?;
+        let idiag_wqueue = u32::deserialize(mem)?;
+        let idiag_uid = u32::deserialize(mem)?;
+        let idiag_inode = u32::deserialize(mem)?;
+        mem.set_size_hint(attrs_len);
+        let attrs = Vec::<Nlattr<InetDiagAttr, Vec<u8>>>::deserialize(mem)
+            .map_err(|e| e.context("InetDiagMsg.attrs"))?;
+        Ok(InetDiagMsg {
+            idiag_family,
+            idiag_state,
+            idiag_timer,
+            idiag_retrans,
+            id,
+            idiag_expires,
+            idiag_rqueue,
+            idiag_wqueue,
+            idiag_uid,
+            idiag_inode,
+            attrs,
+        })
+    }
+
+    fn size(&self) -> usize {
+        INET_DIAG_MSG_LEN + self.attrs.asize()
+    }
+}
+
+impl ToNlValue for InetDiagMsg {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("idiag_family", self.idiag_family.to_nl_value()),
+            ("idiag_state", self.idiag_state.to_nl_value()),
+            ("idiag_timer", self.idiag_timer.to_nl_value()),
+            ("idiag_retrans", self.idiag_retrans.to_nl_value()),
+            ("id", self.id.to_nl_value()),
+            ("idiag_expires", self.idiag_expires.to_nl_value()),
+            ("idiag_rqueue", self.idiag_rqueue.to_nl_value()),
+            ("idiag_wqueue", self.idiag_wqueue.to_nl_value()),
+            ("idiag_uid", self.idiag_uid.to_nl_value()),
+            ("idiag_inode", self.idiag_inode.to_nl_value()),
+            ("attrs", NlValue::list(&self.attrs)),
+        ])
+    }
+}
+
+/// `struct unix_diag_req`, requesting the `AF_UNIX` sockets in the given states
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct UnixDiagReq {
+    /// Address family, always `UnixOrLocal`
+    pub sdiag_family: RtAddrFamily,
+    /// Protocol, always 0
+    pub sdiag_protocol: u8,
+    /// Bits of the states of the sockets to dump, see `TcpState::bit`
+    pub udiag_states: u32,
+    /// Inode of the socket to look up, unused in dumps
+    pub udiag_ino: u32,
+    /// Information to include in the replies as attributes
+    pub udiag_show: Vec<UdiagShow>,
+    /// Cookie of the socket to look up, unused in dumps
+    pub udiag_cookie: [u32; 2],
+}
+
+impl UnixDiagReq {
+    /// Request for the `AF_UNIX` sockets in all states, without attributes
+    pub fn new() -> Self {
+        UnixDiagReq {
+            sdiag_family: RtAddrFamily::UnixOrLocal,
+            sdiag_protocol: 0,
+            udiag_states: !0,
+            udiag_ino: 0,
+            udiag_show: Vec::new(),
+            udiag_cookie: INET_DIAG_NOCOOKIE,
+        }
+    }
+
+    /// Only request sockets in the given states
+    pub fn with_states(mut self, states: &[TcpState]) -> Self {
+        self.udiag_states = states.iter().fold(0, |acc, state| acc | state.bit());
+        self
+    }
+
+    /// Include the given information in the replies
+    pub fn with_show(mut self, show: &[UdiagShow]) -> Self {
+        self.udiag_show.extend_from_slice(show);
+        self
+    }
+}
+
+impl Default for UnixDiagReq {
+    fn default() -> Self {
+        UnixDiagReq::new()
+    }
+}
+
+impl Nl for UnixDiagReq {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.sdiag_family.serialize(mem)?;
+        self.sdiag_protocol.serialize(mem)?;
+        Padding::<2>.serialize(mem)?;
+        self.udiag_states.serialize(mem)?;
+        self.udiag_ino.serialize(mem)?;
+        UdiagShowFlags::new(&self.udiag_show)
+            .bits()
+            .serialize(mem)?;
+        self.udiag_cookie[0].serialize(mem)?;
+        self.udiag_cookie[1].serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        let sdiag_family = RtAddrFamily::deserialize(mem)?;
+        let sdiag_protocol = u8::deserialize(mem)?;
+        Padding::<2>::deserialize(mem)?;
+        Ok(UnixDiagReq {
+            sdiag_family,
+            sdiag_protocol,
+            udiag_states: u32::deserialize(mem)?,
+            udiag_ino: u32::deserialize(mem)?,
+            udiag_show: UdiagShowFlags::from_bits(u32::deserialize(mem)?).into_vec(),
+            udiag_cookie: [u32::deserialize(mem)?, u32::deserialize(mem)?],
+        })
+    }
+
+    fn size(&self) -> usize {
+        24
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(24)
+    }
+}
+
+impl ToNlValue for UnixDiagReq {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("sdiag_family", self.sdiag_family.to_nl_value()),
+            ("sdiag_protocol", self.sdiag_protocol.to_nl_value()),
+            ("udiag_states", self.udiag_states.to_nl_value()),
+            ("udiag_ino", self.udiag_ino.to_nl_value()),
+            ("udiag_show", NlValue::list(&self.udiag_show)),
+            ("udiag_cookie", NlValue::list(&self.udiag_cookie)),
+        ])
+    }
+}
+
+/// `struct unix_diag_msg` describing one `AF_UNIX` socket, followed by its attributes
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct UnixDiagMsg {
+    /// Address family, always `UnixOrLocal`
+    pub udiag_family: RtAddrFamily,
+    /// Socket type, such as `SOCK_STREAM`
+    pub udiag_type: u8,
+    /// State of the socket
+    pub udiag_state: TcpState,
+    /// Inode of the socket
+    pub udiag_ino: u32,
+    /// Kernel cookie of the socket
+    pub udiag_cookie: [u32; 2],
+    /// Attributes requested with `UnixDiagReq::udiag_show`
+    pub attrs: Vec<Nlattr<UnixDiagAttr, Vec<u8>>>,
+}
+
+/// Size of `UnixDiagMsg` without its attributes
+const UNIX_DIAG_MSG_LEN: usize = 16;
+
+impl UnixDiagMsg {
+    /// Get an attribute of the socket as type `R`
+    pub fn get_attr_payload_as<R>(&self, attr_type: UnixDiagAttr) -> Result<Option<R>, DeError>
+    where
+        R: Nl,
+    {
+        attr_payload(&self.attrs, attr_type)
+    }
+
+    /// Get the path the socket is bound to from the `UnixDiagAttr::Name` attribute. Abstract
+    /// socket names start with a null byte.
+    pub fn name(&self) -> Option<&[u8]> {
+        self.attrs
+            .iter()
+            .find(|attr| attr.nla_type == UnixDiagAttr::Name)
+            .map(|attr| attr.payload.as_slice())
+    }
+
+    /// Get the inode of the peer socket from the `UnixDiagAttr::Peer` attribute
+    pub fn peer(&self) -> Result<Option<u32>, DeError> {
+        self.get_attr_payload_as(UnixDiagAttr::Peer)
+    }
+
+    /// Get the queue lengths from the `UnixDiagAttr::Rqlen` attribute
+    pub fn rqlen(&self) -> Result<Option<UnixDiagRqlen>, DeError> {
+        self.get_attr_payload_as(UnixDiagAttr::Rqlen)
+    }
+}
+
+impl Nl for UnixDiagMsg {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.udiag_family.serialize(mem)?;
+        self.udiag_type.serialize(mem)?;
+        self.udiag_state.serialize(mem)?;
+        Padding::<1>.serialize(mem)?;
+        self.udiag_ino.serialize(mem)?;
+        self.udiag_cookie[0].serialize(mem)?;
+        self.udiag_cookie[1].serialize(mem)?;
+        self.attrs.serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        let size_hint = mem
+            .take_size_hint()
+            .ok_or_else(|| DeError::new("Must provide size hint to deserialize UnixDiagMsg"))?;
+        let attrs_len = sub_len("size_hint", size_hint, UNIX_DIAG_MSG_LEN)?;
+        let udiag_family = RtAddrFamily::deserialize(mem)?;
+        let udiag_type = u8::deserialize(mem)?;
+        let udiag_state = TcpState::deserialize(mem)?;
+        Padding::<1>::deserialize(mem)?;
+        let udiag_ino = u32::deserialize(mem)?;
+        let udiag_cookie = [u32::deserialize(mem)?, u32::deserialize(mem)?];
+        mem.set_size_hint(attrs_len);
+        let attrs = Vec::<Nlattr<UnixDiagAttr, Vec<u8>>>::deserialize(mem)
+            .map_err(|e| e.context("UnixDiagMsg.attrs"))?;
+        Ok(UnixDiagMsg {
+            udiag_family,
+            udiag_type,
+            udiag_state,
+            udiag_ino,
+            udiag_cookie,
+            attrs,
+        })
+    }
+
+    fn size(&self) -> usize {
+        UNIX_DIAG_MSG_LEN + self.attrs.asize()
+    }
+}
+
+impl ToNlValue for UnixDiagMsg {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("udiag_family", self.udiag_family.to_nl_value()),
+            ("udiag_type", self.udiag_type.to_nl_value()),
+            ("udiag_state", self.udiag_state.to_nl_value()),
+            ("udiag_ino", self.udiag_ino.to_nl_value()),
+            ("udiag_cookie", NlValue::list(&self.udiag_cookie)),
+            ("attrs", NlValue::list(&self.attrs)),
+        ])
+    }
+}
+
+/// `struct unix_diag_rqlen`, the payload of `UnixDiagAttr::Rqlen`
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct UnixDiagRqlen {
+    /// Bytes in the receive queue, or pending connections for listening sockets
+    pub udiag_rqueue: u32,
+    /// Bytes in the send queue, or the backlog for listening sockets
+    pub udiag_wqueue: u32,
+}
+
+impl Nl for UnixDiagRqlen {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.udiag_rqueue.serialize(mem)?;
+        self.udiag_wqueue.serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        Ok(UnixDiagRqlen {
+            udiag_rqueue: u32::deserialize(mem)?,
+            udiag_wqueue: u32::deserialize(mem)?,
+        })
+    }
+
+    fn size(&self) -> usize {
+        8
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(8)
+    }
+}
+
+impl ToNlValue for UnixDiagRqlen {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("udiag_rqueue", self.udiag_rqueue.to_nl_value()),
+            ("udiag_wqueue", self.udiag_wqueue.to_nl_value()),
+        ])
+    }
+}
+
+/// `struct netlink_diag_req`, requesting the netlink sockets of one protocol
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct NetlinkDiagReq {
+    /// Address family, always `Netlink`
+    pub sdiag_family: RtAddrFamily,
+    /// Netlink protocol of the sockets, or `NDIAG_PROTO_ALL`
+    pub sdiag_protocol: u8,
+    /// Inode of the socket to look up, unused in dumps
+    pub ndiag_ino: u32,
+    /// Information to include in the replies as attributes
+    pub ndiag_show: Vec<NdiagShow>,
+    /// Cookie of the socket to look up, unused in dumps
+    pub ndiag_cookie: [u32; 2],
+}
+
+impl NetlinkDiagReq {
+    /// Request for the netlink sockets of the given protocol, without attributes
+    pub fn new(protocol: u8) -> Self {
+        NetlinkDiagReq {
+            sdiag_family: RtAddrFamily::Netlink,
+            sdiag_protocol: protocol,
+            ndiag_ino: 0,
+            ndiag_show: Vec::new(),
+            ndiag_cookie: INET_DIAG_NOCOOKIE,
+        }
+    }
+
+    /// Include the given information in the replies
+    pub fn with_show(mut self, show: &[NdiagShow]) -> Self {
+        self.ndiag_show.extend_from_slice(show);
+        self
+    }
+}
+
+impl Nl for NetlinkDiagReq {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.sdiag_family.serialize(mem)?;
+        self.sdiag_protocol.serialize(mem)?;
+        Padding::<2>.serialize(mem)?;
+        self.ndiag_ino.serialize(mem)?;
+        NdiagShowFlags::new(&self.ndiag_show)
+            .bits()
+            .serialize(mem)?;
+        self.ndiag_cookie[0].serialize(mem)?;
+        self.ndiag_cookie[1].serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        let sdiag_family = RtAddrFamily::deserialize(mem)?;
+        let sdiag_protocol = u8::deserialize(mem)?;
+        Padding::<2>::deserialize(mem)?;
+        Ok(NetlinkDiagReq {
+            sdiag_family,
+            sdiag_protocol,
+            ndiag_ino: u32::deserialize(mem)?,
+            ndiag_show: NdiagShowFlags::from_bits(u32::deserialize(mem)?).into_vec(),
+            ndiag_cookie: [u32::deserialize(mem)?, u32::deserialize(mem)?],
+        })
+    }
+
+    fn size(&self) -> usize {
+        20
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(20)
+    }
+}
+
+impl ToNlValue for NetlinkDiagReq {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("sdiag_family", self.sdiag_family.to_nl_value()),
+            ("sdiag_protocol", self.sdiag_protocol.to_nl_value()),
+            ("ndiag_ino", self.ndiag_ino.to_nl_value()),
+            ("ndiag_show", NlValue::list(&self.ndiag_show)),
+            ("ndiag_cookie", NlValue::list(&self.ndiag_cookie)),
+        ])
+    }
+}
+
+/// `struct netlink_diag_msg` describing one netlink socket, followed by its attributes
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct NetlinkDiagMsg {
+    /// Address family, always `Netlink`
+    pub ndiag_family: RtAddrFamily,
+    /// Socket type, `SOCK_RAW` or `SOCK_DGRAM`
+    pub ndiag_type: u8,
+    /// Netlink protocol of the socket
+    pub ndiag_protocol: u8,
+    /// 1 if the socket is connected, 0 otherwise
+    pub ndiag_state: u8,
+    /// Port ID the socket is bound to
+    pub ndiag_portid: u32,
+    /// Port ID the socket is connected to
+    pub ndiag_dst_portid: u32,
+    /// Multicast group the socket is connected to
+    pub ndiag_dst_group: u32,
+    /// Inode of the socket
+    pub ndiag_ino: u32,
+    /// Kernel cookie of the socket
+    pub ndiag_cookie: [u32; 2],
+    /// Attributes requested with `NetlinkDiagReq::ndiag_show`
+    pub attrs: Vec<Nlattr<NetlinkDiagAttr, Vec<u8>>>,
+}
+
+/// Size of `NetlinkDiagMsg` without its attributes
+const NETLINK_DIAG_MSG_LEN: usize = 28;
+
+impl NetlinkDiagMsg {
+    /// Get an attribute of the socket as type `R`
+    pub fn get_attr_payload_as<R>(&self, attr_type: NetlinkDiagAttr) -> Result<Option<R>, DeError>
+    where
+        R: Nl,
+    {
+        attr_payload(&self.attrs, attr_type)
+    }
+
+    /// Get the multicast groups the socket is subscribed to from the `NetlinkDiagAttr::Groups`
+    /// attribute, a bitmap of `unsigned long` words in the byte order of integers
+    pub fn groups(&self) -> Option<Vec<u32>> {
+        const WORD: usize = std::mem::size_of::<libc::c_ulong>();
+        let attr = self
+            .attrs
+            .iter()
+            .find(|attr| attr.nla_type == NetlinkDiagAttr::Groups)?;
+        let mut groups = Vec::new();
+        for (i, chunk) in attr.payload.chunks_exact(WORD).enumerate() {
+            let mut bytes = [0; WORD];
+            bytes.copy_from_slice(chunk);
+            let word = match endian::endian() {
+                Endian::Little => libc::c_ulong::from_le_bytes(bytes),
+                Endian::Big => libc::c_ulong::from_be_bytes(bytes),
+            };
+            for bit in 0..WORD * 8 {
+                if word & (1 << bit) != 0 {
+                    groups.push((i * WORD * 8 + bit + 1) as u32);
+                }
+            }
+        }
+        Some(groups)
+    }
+
+    /// Get the flags of the socket from the `NetlinkDiagAttr::Flags` attribute
+    pub fn flags(&self) -> Result<Option<Vec<NdiagFlag>>, DeError> {
+        Ok(self
+            .get_attr_payload_as::<u32>(NetlinkDiagAttr::Flags)?
+            .map(|bits| NdiagFlags::from_bits(bits).into_vec()))
+    }
+}
+
+impl Nl for NetlinkDiagMsg {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.ndiag_family.serialize(mem)?;
+        self.ndiag_type.serialize(mem)?;
+        self.ndiag_protocol.serialize(mem)?;
+        self.ndiag_state.serialize(mem)?;
+        self.ndiag_portid.serialize(mem)?;
+        self.ndiag_dst_portid.serialize(mem)?;
+        self.ndiag_dst_group.serialize(mem)?;
+        self.ndiag_ino.serialize(mem)?;
+        self.ndiag_cookie[0].serialize(mem)?;
+        self.ndiag_cookie[1].serialize(mem)?;
+        self.attrs.serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        let size_hint = mem
+            .take_size_hint()
+            .ok_or_else(|| DeError::new("Must provide size hint to deserialize NetlinkDiagMsg"))?;
+        let attrs_len = sub_len("size_hint", size_hint, NETLINK_DIAG_MSG_LEN)?;
+        let ndiag_family = RtAddrFamily::deserialize(mem)?;
+        let ndiag_type = u8::deserialize(mem)?;
+        let ndiag_protocol = u8::deserialize(mem)?;
+        let ndiag_state = u8::deserialize(mem)?;
+        let ndiag_portid = u32::deserialize(mem)?;
+        let ndiag_dst_portid = u32::deserialize(mem)?;
+        let ndiag_dst_group = u32::deserialize(mem)?;
+        let ndiag_ino = u32::deserialize(mem)?;
+        let ndiag_cookie = [u32::deserialize(mem)?, u32::deserialize(mem)?];
+        mem.set_size_hint(attrs_len);
+        let attrs = Vec::<Nlattr<NetlinkDiagAttr, Vec<u8>>>::deserialize(mem)
+            .map_err(|e| e.context("NetlinkDiagMsg.attrs"))?;
+        Ok(NetlinkDiagMsg {
+            ndiag_family,
+            ndiag_type,
+            ndiag_protocol,
+            ndiag_state,
+            ndiag_portid,
+            ndiag_dst_portid,
+            ndiag_dst_group,
+            ndiag_ino,
+            ndiag_cookie,
+            attrs,
+        })
+    }
+
+    fn size(&self) -> usize {
+        NETLINK_DIAG_MSG_LEN + self.attrs.asize()
+    }
+}
+
+impl ToNlValue for NetlinkDiagMsg {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("ndiag_family", self.ndiag_family.to_nl_value()),
+            ("ndiag_type", self.ndiag_type.to_nl_value()),
+            ("ndiag_protocol", self.ndiag_protocol.to_nl_value()),
+            ("ndiag_state", self.ndiag_state.to_nl_value()),
+            ("ndiag_portid", self.ndiag_portid.to_nl_value()),
+            ("ndiag_dst_portid", self.ndiag_dst_portid.to_nl_value()),
+            ("ndiag_dst_group", self.ndiag_dst_group.to_nl_value()),
+            ("ndiag_ino", self.ndiag_ino.to_nl_value()),
+            ("ndiag_cookie", NlValue::list(&self.ndiag_cookie)),
+            ("attrs", NlValue::list(&self.attrs)),
+        ])
+    }
+}
+
+/// `struct packet_diag_req`, requesting the `AF_PACKET` sockets
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct PacketDiagReq {
+    /// Address family, always `Packet`
+    pub sdiag_family: RtAddrFamily,
+    /// Protocol, always 0
+    pub sdiag_protocol: u8,
+    /// Inode of the socket to look up, unused in dumps
+    pub pdiag_ino: u32,
+    /// Information to include in the replies as attributes
+    pub pdiag_show: Vec<PacketShow>,
+    /// Cookie of the socket to look up, unused in dumps
+    pub pdiag_cookie: [u32; 2],
+}
+
+impl PacketDiagReq {
+    /// Request for the `AF_PACKET` sockets, without attributes
+    pub fn new() -> Self {
+        PacketDiagReq {
+            sdiag_family: RtAddrFamily::Packet,
+            sdiag_protocol: 0,
+            pdiag_ino: 0,
+            pdiag_show: Vec::new(),
+            pdiag_cookie: INET_DIAG_NOCOOKIE,
+        }
+    }
+
+    /// Include the given information in the replies
+    pub fn with_show(mut self, show: &[PacketShow]) -> Self {
+        self.pdiag_show.extend_from_slice(show);
+        self
+    }
+}
+
+impl Default for PacketDiagReq {
+    fn default() -> Self {
+        PacketDiagReq::new()
+    }
+}
+
+impl Nl for PacketDiagReq {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.sdiag_family.serialize(mem)?;
+        self.sdiag_protocol.serialize(mem)?;
+        Padding::<2>.serialize(mem)?;
+        self.pdiag_ino.serialize(mem)?;
+        PacketShowFlags::new(&self.pdiag_show)
+            .bits()
+            .serialize(mem)?;
+        self.pdiag_cookie[0].serialize(mem)?;
+        self.pdiag_cookie[1].serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        let sdiag_family = RtAddrFamily::deserialize(mem)?;
+        let sdiag_protocol = u8::deserialize(mem)?;
+        Padding::<2>::deserialize(mem)?;
+        Ok(PacketDiagReq {
+            sdiag_family,
+            sdiag_protocol,
+            pdiag_ino: u32::deserialize(mem)?,
+            pdiag_show: PacketShowFlags::from_bits(u32::deserialize(mem)?).into_vec(),
+            pdiag_cookie: [u32::deserialize(mem)?, u32::deserialize(mem)?],
+        })
+    }
+
+    fn size(&self) -> usize {
+        20
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(20)
+    }
+}
+
+impl ToNlValue for PacketDiagReq {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("sdiag_family", self.sdiag_family.to_nl_value()),
+            ("sdiag_protocol", self.sdiag_protocol.to_nl_value()),
+            ("pdiag_ino", self.pdiag_ino.to_nl_value()),
+            ("pdiag_show", NlValue::list(&self.pdiag_show)),
+            ("pdiag_cookie", NlValue::list(&self.pdiag_cookie)),
+        ])
+    }
+}
+
+/// `struct packet_diag_msg` describing one `AF_PACKET` socket, followed by its attributes
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct PacketDiagMsg {
+    /// Address family, always `Packet`
+    pub pdiag_family: RtAddrFamily,
+    /// Socket type, `SOCK_RAW` or `SOCK_DGRAM`
+    pub pdiag_type: u8,
+    /// Ethernet protocol the socket receives, in host byte order
+    pub pdiag_num: u16,
+    /// Inode of the socket
+    pub pdiag_ino: u32,
+    /// Kernel cookie of the socket
+    pub pdiag_cookie: [u32; 2],
+    /// Attributes requested with `PacketDiagReq::pdiag_show`
+    pub attrs: Vec<Nlattr<PacketDiagAttr, Vec<u8>>>,
+}
+
+/// Size of `PacketDiagMsg` without its attributes
+const PACKET_DIAG_MSG_LEN: usize = 16;
+
+impl PacketDiagMsg {
+    /// Get an attribute of the socket as type `R`
+    pub fn get_attr_payload_as<R>(&self, attr_type: PacketDiagAttr) -> Result<Option<R>, DeError>
+    where
+        R: Nl,
+    {
+        attr_payload(&self.attrs, attr_type)
+    }
+
+    /// Get the user ID of the socket owner from the `PacketDiagAttr::Uid` attribute
+    pub fn uid(&self) -> Result<Option<u32>, DeError> {
+        self.get_attr_payload_as(PacketDiagAttr::Uid)
+    }
+}
+
+impl Nl for PacketDiagMsg {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.pdiag_family.serialize(mem)?;
+        self.pdiag_type.serialize(mem)?;
+        self.pdiag_num.serialize(mem)?;
+        self.pdiag_ino.serialize(mem)?;
+        self.pdiag_cookie[0].serialize(mem)?;
+        self.pdiag_cookie[1].serialize(mem)?;
+        self.attrs.serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        let size_hint = mem
+            .take_size_hint()
+            .ok_or_else(|| DeError::new("Must provide size hint to deserialize PacketDiagMsg"))?;
+        let attrs_len = sub_len("size_hint", size_hint, PACKET_DIAG_MSG_LEN)?;
+        let pdiag_family = RtAddrFamily::deserialize(mem)?;
+        let pdiag_type = u8::deserialize(mem)?;
+        let pdiag_num = u16::deserialize(mem)?;
+        let pdiag_ino = u32::deserialize(mem)?;
+        let pdiag_cookie = [u32::deserialize(mem)?, u32::deserialize(mem)?];
+        mem.set_size_hint(attrs_len);
+        let attrs = Vec::<Nlattr<PacketDiagAttr, Vec<u8>>>::deserialize(mem)
+            .map_err(|e| e.context("PacketDiagMsg.attrs"))?;
+        Ok(PacketDiagMsg {
+            pdiag_family,
+            pdiag_type,
+            pdiag_num,
+            pdiag_ino,
+            pdiag_cookie,
+            attrs,
+        })
+    }
+
+    fn size(&self) -> usize {
+        PACKET_DIAG_MSG_LEN + self.attrs.asize()
+    }
+}
+
+impl ToNlValue for PacketDiagMsg {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("pdiag_family", self.pdiag_family.to_nl_value()),
+            ("pdiag_type", self.pdiag_type.to_nl_value()),
+            ("pdiag_num", self.pdiag_num.to_nl_value()),
+            ("pdiag_ino", self.pdiag_ino.to_nl_value()),
+            ("pdiag_cookie", NlValue::list(&self.pdiag_cookie)),
+            ("attrs", NlValue::list(&self.attrs)),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::os::unix::io::AsRawFd;
+    use std::os::unix::net::UnixStream;
+
+    use consts::sockdiag::SockDiagMsg;
+    use consts::{NlFamily, NlmF};
+    use nl::Nlmsghdr;
+    use socket::NlSocket;
+    use test_utils::assert_roundtrip;
+    use {StreamReadBuffer, StreamWriteBuffer};
+
+    fn inet_diag_req() -> InetDiagReqV2 {
+        let mut req = InetDiagReqV2::new(RtAddrFamily::Inet, libc::IPPROTO_TCP as u8)
+            .with_states(&[TcpState::Listen])
+            .with_ext(InetDiagAttr::Info)
+            .unwrap();
+        req.id.set_src(IpAddr::V4(Ipv4Addr::LOCALHOST), 0x1234);
+        req
+    }
+
+    #[test]
+    fn test_inet_diag_req() {
+        let req = inet_diag_req();
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        req.serialize(&mut mem).unwrap();
+        let bytes = mem.as_ref();
+        assert_eq!(bytes.len(), 56);
+        assert_eq!(&bytes[..4], &[libc::AF_INET as u8, 6, 2, 0]);
+        assert_eq!(&bytes[4..8], &(1u32 << 10).to_ne_bytes());
+        assert_eq!(&bytes[8..14], &[0x12, 0x34, 0, 0, 127, 0]);
+        assert_eq!(
+            InetDiagReqV2::deserialize(&mut StreamReadBuffer::new(bytes)).unwrap(),
+            req
+        );
+    }
+
+    #[test]
+    fn test_inet_diag_req_invalid() {
+        // Extensions beyond the 8 bits of idiag_ext cannot be requested
+        assert!(InetDiagReqV2::new(RtAddrFamily::Inet, 6)
+            .with_ext(InetDiagAttr::Mark)
+            .is_err());
+
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        inet_diag_req().serialize(&mut mem).unwrap();
+        let truncated = &mem.as_ref()[..40];
+        assert!(InetDiagReqV2::deserialize(&mut StreamReadBuffer::new(truncated)).is_err());
+    }
+
+    #[test]
+    fn test_inet_diag_msg_invalid() {
+        let bytes = [0u8; INET_DIAG_MSG_LEN];
+        let mut mem = StreamReadBuffer::new(&bytes[..60]);
+        mem.set_size_hint(60);
+        assert!(InetDiagMsg::deserialize(&mut mem).is_err());
+
+        // Addresses of other families cannot be interpreted
+        let id = inet_diag_req().id;
+        assert_eq!(id.src_addr(&RtAddrFamily::Packet), None);
+        assert_eq!(
+            id.src_addr(&RtAddrFamily::Inet),
+            Some(IpAddr::V4(Ipv4Addr::LOCALHOST))
+        );
+    }
+
+    #[test]
+    fn test_inet_diag_dump() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut s = NlSocket::connect(NlFamily::SockOrInetDiag, None, None, true).unwrap();
+        let msg = Nlmsghdr::new(
+            None,
+            SockDiagMsg::ByFamily,
+            vec![NlmF::Request, NlmF::Dump],
+            None,
+            None,
+            InetDiagReqV2::new(RtAddrFamily::Inet, libc::IPPROTO_TCP as u8)
+                .with_states(&[TcpState::Listen]),
+        );
+        let replies = s.request::<_, _, SockDiagMsg, InetDiagMsg>(msg).unwrap();
+        let sock = replies
+            .into_iter()
+            .map(|reply| reply.nl_payload)
+            .find(|sock| sock.id.idiag_sport == port)
+            .expect("listening socket not dumped");
+        assert_eq!(sock.idiag_state, TcpState::Listen);
+        assert_eq!(
+            sock.id.src_addr(&sock.idiag_family),
+            Some(IpAddr::V4(Ipv4Addr::LOCALHOST))
+        );
+    }
+
+    fn unix_diag_req() -> UnixDiagReq {
+        UnixDiagReq::new()
+            .with_states(&[TcpState::Established])
+            .with_show(&[UdiagShow::Peer, UdiagShow::Rqlen])
+    }
+
+    #[test]
+    fn test_unix_diag_req() {
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        unix_diag_req().serialize(&mut mem).unwrap();
+        assert_eq!(mem.as_ref().len(), 24);
+        assert_eq!(&mem.as_ref()[12..16], &0x14u32.to_ne_bytes());
+    }
+
+    #[test]
+    fn test_unix_diag_dump() {
+        let inode = |s: &UnixStream| {
+            let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+            assert_eq!(unsafe { libc::fstat(s.as_raw_fd(), &mut stat) }, 0);
+            stat.st_ino as u32
+        };
+        let (mut a, b) = UnixStream::pair().unwrap();
+        a.write_all(b"hello").unwrap();
+
+        let mut s = NlSocket::connect(NlFamily::SockOrInetDiag, None, None, true).unwrap();
+        let msg = Nlmsghdr::new(
+            None,
+            SockDiagMsg::ByFamily,
+            vec![NlmF::Request, NlmF::Dump],
+            None,
+            None,
+            unix_diag_req(),
+        );
+        let replies = s.request::<_, _, SockDiagMsg, UnixDiagMsg>(msg).unwrap();
+        let sock = replies
+            .into_iter()
+            .map(|reply| reply.nl_payload)
+            .find(|sock| sock.udiag_ino == inode(&b))
+            .expect("socket not dumped");
+        assert_eq!(sock.udiag_type, libc::SOCK_STREAM as u8);
+        assert_eq!(sock.udiag_state, TcpState::Established);
+        assert_eq!(sock.peer().unwrap(), Some(inode(&a)));
+        assert_eq!(sock.rqlen().unwrap().unwrap().udiag_rqueue, 5);
+    }
+
+    #[test]
+    fn test_netlink_diag() {
+        let mut s = NlSocket::connect(NlFamily::SockOrInetDiag, None, None, true).unwrap();
+        let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+        assert_eq!(unsafe { libc::fstat(s.as_raw_fd(), &mut stat) }, 0);
+        let msg = Nlmsghdr::new(
+            None,
+            SockDiagMsg::ByFamily,
+            vec![NlmF::Request, NlmF::Dump],
+            None,
+            None,
+            NetlinkDiagReq::new(libc::NETLINK_SOCK_DIAG as u8)
+                .with_show(&[NdiagShow::Groups, NdiagShow::Flags]),
+        );
+        let replies = s.request::<_, _, SockDiagMsg, NetlinkDiagMsg>(msg).unwrap();
+        let sock = replies
+            .into_iter()
+            .map(|reply| reply.nl_payload)
+            .find(|sock| sock.ndiag_ino == stat.st_ino as u32)
+            .expect("socket not dumped");
+        assert_eq!(sock.ndiag_protocol, libc::NETLINK_SOCK_DIAG as u8);
+        assert_ne!(sock.ndiag_portid, 0);
+        assert!(sock.flags().unwrap().is_some());
+    }
+
+    #[test]
+    fn test_packet_diag_req() {
+        let req = PacketDiagReq::new().with_show(&[PacketShow::Info, PacketShow::Meminfo]);
+        let bytes = assert_roundtrip(&req);
+        assert_eq!(bytes.len(), 20);
+        assert_eq!(bytes[..4], [libc::AF_PACKET as u8, 0, 0, 0]);
+        assert_eq!(&bytes[4..8], &0u32.to_ne_bytes());
+        assert_eq!(&bytes[8..12], &0x11u32.to_ne_bytes());
+        assert_eq!(&bytes[12..], &[0xff; 8]);
+    }
+
+    #[test]
+    fn test_packet_diag_msg() {
+        let msg = PacketDiagMsg {
+            pdiag_family: RtAddrFamily::Packet,
+            pdiag_type: libc::SOCK_RAW as u8,
+            pdiag_num: 0x0003,
+            pdiag_ino: 42,
+            pdiag_cookie: [1, 0],
+            attrs: vec![Nlattr::new(None, PacketDiagAttr::Uid, 1000u32).unwrap()],
+        };
+        let bytes = assert_roundtrip(&msg);
+        assert_eq!(bytes.len(), 24);
+        assert_eq!(msg.uid().unwrap(), Some(1000));
+
+        let mut mem = StreamReadBuffer::new(&bytes[..12]);
+        mem.set_size_hint(12);
+        assert!(PacketDiagMsg::deserialize(&mut mem).is_err());
+    }
+}
@@ -3,17 +3,49 @@
 //! for more information on the serialization and deserialization implementations.
 //!
 //! ## Important methods
+//! * `builder` configures and binds a new socket in one place.
 //! * `send` and `recv` methods are meant to be the most low level calls. They essentially do what
 //! the C system calls `send` and `recv` do with very little abstraction.
 //! * `send_nl` and `recv_nl` methods are meant to provide an interface that is more idiomatic for
 //! the library. The are able to operate on any structure wrapped in an `Nlmsghdr` struct that implements
 //! the `Nl` trait.
+//! * `send_nl_batch` and `recv_nl_batch` send and receive several messages packed into one
+//! datagram, and `recv_nl_buffer` receives a whole datagram as an `NlBuffer`.
+//! * `send_multiple` and `recv_multiple` transfer several datagrams per system call.
+//! * `send_to`, `send_nl_to` and `connect_to` talk to user space netlink sockets instead of the
+//! kernel.
+//! * `enable_passcred` and `recv_with_creds` report the uid, gid and pid of the sender.
+//! * `connect_in_netns` creates a socket in another network namespace.
+//! * `send_nl_in` and `recv_in` serialize and receive into storage from a custom allocator, and
+//! `send_nl_buf` serializes into a buffer reused across calls.
+//! * `recv_into` parses the messages of a datagram received into a reusable caller-provided
+//! buffer, and `recv_borrowed` leaves their payloads in that buffer to be parsed lazily.
+//! * `NlStreamParser` frames messages from a byte stream that arrives in chunks of any size.
 //! * `iter` provides a loop based iteration through messages that are received in a stream over
 //! the socket.
+//! * `set_recv_grow_cap` lets receive calls grow a buffer that is too small for a datagram.
+//! * `set_buffer_pool` recycles receive buffers through a `BufferPool` instead of allocating one
+//! per datagram.
+//! * `recv_dump` collects the messages of a dump with a configurable memory growth policy.
+//! * `subscribe_rt_events` and `recv_rt_event`, defined in `rtnl`, monitor routing netlink
+//! notifications.
+//! * `recv_genl_reply` receives a generic netlink message and verifies its command.
+//! * `request` sends a request and collects its response, ACK or dump.
 //! * `recv_ack` receives an ACK message and verifies it matches the request.
+//! * `enqueue_nl` and `flush_queue` collect messages by priority and send them all at once.
+//! * `shutdown_handle` interrupts blocking receive calls from another thread.
+//! * `stats` reports how much data was received and whether the kernel dropped messages.
+//! * `close` closes the socket and reports errors, and `set_close_on_drop` leaves file
+//! descriptors owned by other code open when the socket is dropped.
+//!
+//! The `NlSocketOps` trait captures the datagram level operations of a socket so that helpers
+//! built on it can also run against `mock::MockSocket`.
 //!
 //! ## Features
 //! The `stream` feature exposed by `cargo` allows the socket to use Rust's tokio for async IO.
+//! With it, `into_stream` turns a socket into a stream of the messages it receives.
+//! The `rayon` feature adds `recv_dump_par` and `parse_dump_par` for parsing large dumps on
+//! several threads.
 //!
 //! ## Additional methods
 //!
@@ -21,23 +53,39 @@
 //! and other convenience functions so see if your use case is supported. If it isn't, please open
 //! a Github issue and submit a feature request.
 
+use std::collections::BTreeSet;
+use std::fs::File;
 use std::io;
 use std::marker::PhantomData;
-use std::mem::{size_of, zeroed};
-use std::os::unix::io::{AsRawFd, IntoRawFd, RawFd};
+use std::mem::{self, size_of, size_of_val, zeroed};
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::path::Path;
+use std::process;
+use std::ptr;
+use std::slice;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use buffering::{StreamReadBuffer, StreamWriteBuffer};
+use byteorder::{ByteOrder, NativeEndian};
 use libc::{self, c_int, c_void};
 
 use consts::{
-    self, AddrFamily, CtrlAttr, CtrlAttrMcastGrp, CtrlCmd, GenlId, NlAttrType, NlFamily, NlType,
-    NlmF,
+    self, AddrFamily, Cmd, CtrlAttr, CtrlAttrMcastGrp, CtrlCmd, GenlId, NlAttrType, NlFamily,
+    NlSockOpt, NlType, NlmF, NlmFFlags,
 };
+use endian;
 use err::{NlError, Nlmsgerr};
-use genl::Genlmsghdr;
-use nl::Nlmsghdr;
+use genl::{GenlOp, Genlmsghdr};
+use limits;
+use nl::{NlBuffer, Nlmsghdr};
 use nlattr::Nlattr;
-use {Nl, MAX_NL_LENGTH};
+use storage::{serialize_in, to_bytes_in, NlAlloc, NlStorage};
+use Nl;
+
+// Space for ancillary data received with `recvmsg`, in `u64`s to keep `cmsghdr` aligned
+const CMSG_BUFFER_LEN: usize = 16;
 
 /// Iterator over messages returned from a `recv_nl` call
 pub struct NlMessageIter<'a, T, P> {
@@ -76,12 +124,631 @@ where
     }
 }
 
+/// Iterator over the messages of a single datagram stored in a borrowed buffer, as returned from
+/// a `recv_into` call
+pub struct NlBufferIter<'a, T, P> {
+    buffer: StreamReadBuffer<&'a [u8]>,
+    data_type: PhantomData<T>,
+    data_payload: PhantomData<P>,
+}
+
+impl<'a, T, P> NlBufferIter<'a, T, P>
+where
+    T: Nl + NlType,
+    P: Nl,
+{
+    /// Construct a new iterator that yields `Nlmsghdr` structs parsed from the provided buffer
+    pub fn new(buf: &'a [u8]) -> Self {
+        NlBufferIter {
+            buffer: StreamReadBuffer::new(buf),
+            data_type: PhantomData,
+            data_payload: PhantomData,
+        }
+    }
+}
+
+impl<'a, T, P> Iterator for NlBufferIter<'a, T, P>
+where
+    T: Nl + NlType,
+    P: Nl,
+{
+    type Item = Result<Nlmsghdr<T, P>, NlError>;
+
+    fn next(&mut self) -> Option<Result<Nlmsghdr<T, P>, NlError>> {
+        if self.buffer.at_end() {
+            return None;
+        }
+        match Nlmsghdr::deserialize(&mut self.buffer) {
+            Ok(msg) => Some(Ok(msg)),
+            Err(e) => {
+                // Stop after the first error as the remaining data cannot be framed
+                self.buffer.set_at_end();
+                Some(Err(e.into()))
+            }
+        }
+    }
+}
+
+/// Iterator over the messages of a single datagram stored in a borrowed buffer, yielding
+/// messages with payloads borrowed from the buffer, as returned from a `recv_borrowed` call
+pub struct NlBorrowedIter<'a, T> {
+    buf: &'a [u8],
+    data_type: PhantomData<T>,
+}
+
+impl<'a, T> NlBorrowedIter<'a, T>
+where
+    T: NlType,
+{
+    /// Construct a new iterator that yields messages parsed from the provided buffer
+    pub fn new(buf: &'a [u8]) -> Self {
+        NlBorrowedIter {
+            buf,
+            data_type: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> Iterator for NlBorrowedIter<'a, T>
+where
+    T: NlType,
+{
+    type Item = Result<Nlmsghdr<T, &'a [u8]>, NlError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.is_empty() {
+            return None;
+        }
+        match Nlmsghdr::parse_borrowed(self.buf) {
+            Ok((msg, rest)) => {
+                self.buf = rest;
+                Some(Ok(msg))
+            }
+            Err(e) => {
+                // Stop after the first error as the remaining data cannot be framed
+                self.buf = &[];
+                Some(Err(e.into()))
+            }
+        }
+    }
+}
+
+/// Parser for messages arriving as a byte stream in chunks of any size, such as from a proxy
+/// or a recording, rather than as whole datagrams
+///
+/// Chunks are added with `push` and complete messages taken with `next_msg`. Incomplete
+/// messages are buffered until the rest of them arrives. Messages are framed as in datagrams,
+/// so each one has to be padded to the netlink alignment.
+pub struct NlStreamParser<T, P> {
+    buf: Vec<u8>,
+    data_type: PhantomData<T>,
+    data_payload: PhantomData<P>,
+}
+
+impl<T, P> NlStreamParser<T, P>
+where
+    T: Nl + NlType,
+    P: Nl,
+{
+    /// Construct a parser with nothing buffered
+    pub fn new() -> Self {
+        NlStreamParser {
+            buf: Vec::new(),
+            data_type: PhantomData,
+            data_payload: PhantomData,
+        }
+    }
+
+    /// Append the next chunk of the stream
+    pub fn push(&mut self, chunk: &[u8]) {
+        self.buf.extend_from_slice(chunk);
+    }
+
+    /// Take the next complete message, or return `None` if more of the stream is needed.
+    ///
+    /// After an error, the buffered data is discarded as the messages following it cannot be
+    /// framed.
+    pub fn next_msg(&mut self) -> Result<Option<Nlmsghdr<T, P>>, NlError> {
+        match self.parse() {
+            Ok(msg) => Ok(msg),
+            Err(e) => {
+                self.buf.clear();
+                Err(e)
+            }
+        }
+    }
+
+    fn parse(&mut self) -> Result<Option<Nlmsghdr<T, P>>, NlError> {
+        if self.buf.len() < 0u32.size() {
+            return Ok(None);
+        }
+        let nl_len = u32::deserialize(&mut StreamReadBuffer::new(&self.buf[..4]))? as usize;
+        limits::check_msg_size(nl_len)?;
+        let len = consts::alignto(nl_len);
+        if self.buf.len() < len {
+            return Ok(None);
+        }
+        let msg = Nlmsghdr::deserialize(&mut StreamReadBuffer::new(&self.buf[..len]))?;
+        self.buf.drain(..len);
+        Ok(Some(msg))
+    }
+
+    /// Number of buffered bytes that do not form a complete message yet
+    pub fn pending(&self) -> usize {
+        self.buf.len()
+    }
+}
+
+impl<T, P> Default for NlStreamParser<T, P>
+where
+    T: Nl + NlType,
+    P: Nl,
+{
+    fn default() -> Self {
+        NlStreamParser::new()
+    }
+}
+
+/// Time at which a datagram was received
+#[derive(Clone, Copy, Debug)]
+pub struct RecvTimestamp {
+    /// Wall-clock time at which the datagram was received. This is the kernel timestamp if
+    /// `enable_timestamps` has been turned on and the protocol reports one, and the time the
+    /// datagram was read from the socket otherwise.
+    pub wall: SystemTime,
+    /// Whether `wall` was reported by the kernel
+    pub from_kernel: bool,
+    /// Monotonic time at which the datagram was read from the socket
+    pub monotonic: Instant,
+}
+
+/// Netlink address of the sender of a datagram
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NlAddr {
+    /// Port ID of the sender, 0 for the kernel
+    pub pid: u32,
+    /// Bitmask of the multicast groups the datagram was sent to, 0 for unicast
+    pub groups: u32,
+}
+
+/// Credentials of the process that sent a datagram, as reported by the kernel with
+/// `SCM_CREDENTIALS`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NlCredentials {
+    /// Process ID of the sender, 0 for the kernel
+    pub pid: i32,
+    /// User ID of the sender
+    pub uid: u32,
+    /// Group ID of the sender
+    pub gid: u32,
+}
+
+/// Outcome for one datagram received with `recv_multiple`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MmsgResult {
+    /// Number of bytes stored in the buffer
+    pub len: usize,
+    /// The datagram was larger than the buffer and has been cut off
+    pub truncated: bool,
+}
+
+/// Counters of received data, as returned by `NlSocket::stats`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct NlSocketStats {
+    /// Number of datagrams received
+    pub datagrams: u64,
+    /// Number of bytes received, counting the full length of truncated datagrams
+    pub bytes: u64,
+    /// Number of messages that could not be parsed by `recv_nl` and similar methods
+    pub parse_failures: u64,
+    /// Number of receive calls that failed with `ENOBUFS` because the socket receive buffer
+    /// overflowed and the kernel dropped messages
+    pub enobufs: u64,
+    /// Number of ACKs received, that is `NLMSG_ERROR` messages with an error code of 0
+    pub acks: u64,
+}
+
+/// Pool of receive buffers that sockets take a buffer from for each datagram and return it to
+/// once all of its messages have been parsed, so that small, frequent datagrams such as events
+/// do not cost an allocation each. A pool can be shared by several sockets.
+#[derive(Clone, Debug)]
+pub struct BufferPool {
+    inner: Arc<Mutex<PoolInner>>,
+}
+
+#[derive(Debug)]
+struct PoolInner {
+    bufs: Vec<Vec<u8>>,
+    max_buffers: usize,
+    capacity: usize,
+}
+
+impl BufferPool {
+    /// Create a pool keeping up to `max_buffers` idle buffers of `capacity` bytes. Datagrams
+    /// larger than `capacity` are received into buffers that are freed instead of returned.
+    pub fn new(max_buffers: usize, capacity: usize) -> Self {
+        BufferPool {
+            inner: Arc::new(Mutex::new(PoolInner {
+                bufs: Vec::with_capacity(max_buffers),
+                max_buffers,
+                capacity,
+            })),
+        }
+    }
+
+    /// Number of idle buffers in the pool
+    pub fn len(&self) -> usize {
+        self.lock().bufs.len()
+    }
+
+    /// Whether the pool has no idle buffers
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, PoolInner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Take a buffer of `len` zeroed bytes, allocating one if the pool is empty
+    fn take(&self, len: usize) -> PooledBuf {
+        let (buf, capacity) = {
+            let mut inner = self.lock();
+            (inner.bufs.pop(), inner.capacity)
+        };
+        let mut buf = buf.unwrap_or_else(|| Vec::with_capacity(capacity.max(len)));
+        buf.resize(len, 0);
+        PooledBuf {
+            buf,
+            pool: Some(self.clone()),
+        }
+    }
+}
+
+/// Receive buffer that returns to its pool when dropped
+struct PooledBuf {
+    buf: Vec<u8>,
+    pool: Option<BufferPool>,
+}
+
+impl PooledBuf {
+    fn unpooled(len: usize) -> Self {
+        PooledBuf {
+            buf: vec![0; len],
+            pool: None,
+        }
+    }
+}
+
+impl AsRef<[u8]> for PooledBuf {
+    fn as_ref(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+impl AsMut<[u8]> for PooledBuf {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.buf
+    }
+}
+
+impl Drop for PooledBuf {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.take() {
+            let mut inner = pool.lock();
+            if inner.bufs.len() < inner.max_buffers && self.buf.capacity() <= inner.capacity {
+                let mut buf = mem::take(&mut self.buf);
+                buf.clear();
+                inner.bufs.push(buf);
+            }
+        }
+    }
+}
+
+/// Network namespace to create a socket in with `NlSocket::connect_in_netns`
+#[derive(Clone, Copy, Debug)]
+pub enum NetnsTarget<'a> {
+    /// Path of a namespace file such as `/var/run/netns/<name>` or `/proc/<pid>/ns/net`
+    Path(&'a Path),
+    /// Open file descriptor referring to a network namespace
+    Fd(RawFd),
+}
+
+impl<'a> From<&'a Path> for NetnsTarget<'a> {
+    fn from(path: &'a Path) -> Self {
+        NetnsTarget::Path(path)
+    }
+}
+
+impl<'a> From<RawFd> for NetnsTarget<'a> {
+    fn from(fd: RawFd) -> Self {
+        NetnsTarget::Fd(fd)
+    }
+}
+
+/// How the container returned by `recv_dump` grows as messages arrive
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DumpGrowth {
+    /// One contiguous vector that doubles its capacity when full. This needs the fewest
+    /// allocations, but all previous messages are copied on every growth and up to half of the
+    /// memory can be unused.
+    Exponential,
+    /// One contiguous vector that grows by the given number of messages when full. This bounds
+    /// unused memory, but all previous messages are copied on every growth.
+    Linear(usize),
+    /// Separate chunks holding the given number of messages each. Previous messages are never
+    /// copied and at most one chunk is partially used.
+    Chunked(usize),
+}
+
+/// Messages collected by `recv_dump`, stored according to a `DumpGrowth` policy
+#[derive(Debug)]
+pub struct DumpChunks<M> {
+    chunks: Vec<Vec<M>>,
+    growth: DumpGrowth,
+    len: usize,
+}
+
+impl<M> DumpChunks<M> {
+    /// Create an empty container growing according to `growth`
+    pub fn new(growth: DumpGrowth) -> Self {
+        DumpChunks {
+            chunks: Vec::new(),
+            growth,
+            len: 0,
+        }
+    }
+
+    /// Append a message
+    pub fn push(&mut self, msg: M) {
+        let needs_chunk = match self.chunks.last() {
+            Some(chunk) => {
+                matches!(self.growth, DumpGrowth::Chunked(_)) && chunk.len() == chunk.capacity()
+            }
+            None => true,
+        };
+        if needs_chunk {
+            let capacity = match self.growth {
+                DumpGrowth::Exponential => 0,
+                DumpGrowth::Linear(step) | DumpGrowth::Chunked(step) => step.max(1),
+            };
+            self.chunks.push(Vec::with_capacity(capacity));
+        }
+        let chunk = self.chunks.last_mut().expect("A chunk was added above");
+        if let DumpGrowth::Linear(step) = self.growth {
+            if chunk.len() == chunk.capacity() {
+                chunk.reserve_exact(step.max(1));
+            }
+        }
+        chunk.push(msg);
+        self.len += 1;
+    }
+
+    /// Number of messages
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return `true` if there are no messages
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Messages grouped by the chunk they are stored in
+    pub fn chunks(&self) -> &[Vec<M>] {
+        &self.chunks
+    }
+
+    /// Iterate over the messages in the order they were received
+    pub fn iter(&self) -> std::iter::Flatten<std::slice::Iter<'_, Vec<M>>> {
+        self.chunks.iter().flatten()
+    }
+
+    /// Move all messages into one vector
+    pub fn into_vec(self) -> Vec<M> {
+        if self.chunks.len() == 1 {
+            return self.chunks.into_iter().next().unwrap_or_default();
+        }
+        let mut msgs = Vec::with_capacity(self.len);
+        for chunk in self.chunks {
+            msgs.extend(chunk);
+        }
+        msgs
+    }
+}
+
+impl<M> IntoIterator for DumpChunks<M> {
+    type Item = M;
+    type IntoIter = std::iter::Flatten<std::vec::IntoIter<Vec<M>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.chunks.into_iter().flatten()
+    }
+}
+
+// Atomics allow counting from methods taking `&self` without making `NlSocket` `!Sync`
+#[derive(Default)]
+struct StatCounters {
+    datagrams: AtomicU64,
+    bytes: AtomicU64,
+    parse_failures: AtomicU64,
+    enobufs: AtomicU64,
+    acks: AtomicU64,
+}
+
+/// Configuration of a new `NlSocket`, as returned by `NlSocket::builder`
+#[derive(Clone, Debug)]
+pub struct NlSocketBuilder {
+    proto: NlFamily,
+    pid: Option<u32>,
+    groups: Vec<u32>,
+    track_seq: bool,
+    recv_buffer: Option<usize>,
+    send_buffer: Option<usize>,
+    ext_ack: bool,
+    strict_checking: bool,
+    nonblocking: bool,
+    recv_timeout: Option<Duration>,
+    send_timeout: Option<Duration>,
+    recv_grow_cap: Option<usize>,
+    pool: Option<BufferPool>,
+}
+
+impl NlSocketBuilder {
+    /// Set the PID to bind to and turn on PID checking as with `NlSocket::bind`. A PID of 0
+    /// lets the kernel choose the PID and takes the PID to check from the first message
+    /// received. PID checking is off by default.
+    pub fn pid(mut self, pid: u32) -> Self {
+        self.pid = Some(pid);
+        self
+    }
+
+    /// Join the multicast groups with the given IDs
+    pub fn groups(mut self, groups: &[u32]) -> Self {
+        self.groups.extend_from_slice(groups);
+        self
+    }
+
+    /// Turn sequence number tracking on or off. It is off by default.
+    pub fn track_seq(mut self, track_seq: bool) -> Self {
+        self.track_seq = track_seq;
+        self
+    }
+
+    /// Set the receive buffer size as with `NlSocket::set_recv_buffer`
+    pub fn recv_buffer(mut self, size: usize) -> Self {
+        self.recv_buffer = Some(size);
+        self
+    }
+
+    /// Set the send buffer size as with `NlSocket::set_send_buffer`
+    pub fn send_buffer(mut self, size: usize) -> Self {
+        self.send_buffer = Some(size);
+        self
+    }
+
+    /// Turn extended ACKs on or off as with `NlSocket::enable_ext_ack`
+    pub fn ext_ack(mut self, enable: bool) -> Self {
+        self.ext_ack = enable;
+        self
+    }
+
+    /// Turn strict checking on or off as with `NlSocket::enable_strict_checking`
+    pub fn strict_checking(mut self, enable: bool) -> Self {
+        self.strict_checking = enable;
+        self
+    }
+
+    /// Put the socket in non-blocking mode
+    pub fn nonblocking(mut self, nonblocking: bool) -> Self {
+        self.nonblocking = nonblocking;
+        self
+    }
+
+    /// Set the receive timeout as with `NlSocket::set_recv_timeout`
+    pub fn recv_timeout(mut self, timeout: Duration) -> Self {
+        self.recv_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the send timeout as with `NlSocket::set_send_timeout`
+    pub fn send_timeout(mut self, timeout: Duration) -> Self {
+        self.send_timeout = Some(timeout);
+        self
+    }
+
+    /// Grow receive buffers up to `cap` bytes as with `NlSocket::set_recv_grow_cap`
+    pub fn recv_grow_cap(mut self, cap: usize) -> Self {
+        self.recv_grow_cap = Some(cap);
+        self
+    }
+
+    /// Receive into buffers taken from `pool` as with `NlSocket::set_buffer_pool`
+    pub fn buffer_pool(mut self, pool: BufferPool) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Create, configure and bind the socket. Buffer sizes are set before binding so that no
+    /// messages are dropped while the socket is set up.
+    pub fn build(self) -> Result<NlSocket, io::Error> {
+        let mut s = NlSocket::new(self.proto, self.track_seq)?;
+        if let Some(size) = self.recv_buffer {
+            s.set_recv_buffer(size)?;
+        }
+        if let Some(size) = self.send_buffer {
+            s.set_send_buffer(size)?;
+        }
+        if self.ext_ack {
+            s.enable_ext_ack(true)?;
+        }
+        if self.strict_checking {
+            s.enable_strict_checking(true)?;
+        }
+        if self.recv_timeout.is_some() {
+            s.set_recv_timeout(self.recv_timeout)?;
+        }
+        if self.send_timeout.is_some() {
+            s.set_send_timeout(self.send_timeout)?;
+        }
+        if self.nonblocking {
+            s.nonblock()?;
+        }
+        s.set_recv_grow_cap(self.recv_grow_cap);
+        s.set_buffer_pool(self.pool);
+        let groups = if self.groups.is_empty() {
+            None
+        } else {
+            Some(self.groups)
+        };
+        s.bind(self.pid, groups)?;
+        Ok(s)
+    }
+}
+
+/// Handle for interrupting blocking receive calls on an `NlSocket` from another thread, as
+/// returned by `NlSocket::shutdown_handle`
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    event: Arc<EventFd>,
+}
+
+impl ShutdownHandle {
+    /// Wake up all threads blocked receiving on the socket. From now on, receive calls on the
+    /// socket fail with `io::ErrorKind::ConnectionAborted`.
+    pub fn shutdown(&self) -> Result<(), io::Error> {
+        let one = 1u64;
+        match unsafe { libc::write(self.event.0, &one as *const _ as *const c_void, 8) } {
+            i if i >= 0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+}
+
+// The event stays readable once signaled so every later receive call fails as well
+struct EventFd(c_int);
+
+impl Drop for EventFd {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.0);
+        }
+    }
+}
+
 /// Handle for the socket file descriptor
 pub struct NlSocket {
     fd: c_int,
-    buffer: Option<StreamReadBuffer<Vec<u8>>>,
+    buffer: Option<StreamReadBuffer<PooledBuf>>,
+    pool: Option<BufferPool>,
     pid: Option<u32>,
     seq: Option<u32>,
+    send_queue: Vec<(i32, Vec<u8>)>,
+    timestamp: Option<RecvTimestamp>,
+    stats: StatCounters,
+    shutdown: Option<Arc<EventFd>>,
+    recv_grow_cap: Option<usize>,
+    close_on_drop: bool,
 }
 
 impl NlSocket {
@@ -96,11 +763,58 @@ impl NlSocket {
         Ok(NlSocket {
             fd,
             buffer: None,
+            pool: None,
             pid: None,
             seq: if track_seq { Some(0) } else { None },
+            send_queue: Vec::new(),
+            timestamp: None,
+            stats: StatCounters::default(),
+            shutdown: None,
+            recv_grow_cap: None,
+            close_on_drop: true,
+        })
+    }
+
+    /// Create a new handle for the same underlying socket with `dup`. PID and sequence number
+    /// checking settings, the receive buffer growth cap, the buffer pool and the shutdown handle
+    /// are copied, buffered received data, queued messages and statistics are not.
+    pub fn try_clone(&self) -> Result<Self, io::Error> {
+        let fd = match unsafe { libc::dup(self.fd) } {
+            i if i >= 0 => i,
+            _ => return Err(io::Error::last_os_error()),
+        };
+        Ok(NlSocket {
+            fd,
+            buffer: None,
+            pool: self.pool.clone(),
+            pid: self.pid,
+            seq: self.seq,
+            send_queue: Vec::new(),
+            timestamp: None,
+            stats: StatCounters::default(),
+            shutdown: self.shutdown.clone(),
+            recv_grow_cap: self.recv_grow_cap,
+            close_on_drop: true,
         })
     }
 
+    /// Choose whether dropping the socket closes its file descriptor. Turn this off for file
+    /// descriptors that are owned by other code, such as ones adopted with `from_raw_fd` from a C
+    /// caller that closes them itself.
+    pub fn set_close_on_drop(&mut self, close_on_drop: bool) {
+        self.close_on_drop = close_on_drop;
+    }
+
+    /// Close the file descriptor, reporting errors that dropping the socket would ignore. The
+    /// file descriptor is closed even if closing on drop was turned off.
+    pub fn close(mut self) -> Result<(), io::Error> {
+        self.close_on_drop = false;
+        match unsafe { libc::close(self.fd) } {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
     /// Manually increment sequence number
     pub fn increment_seq(&mut self) {
         self.seq.map(|seq| seq + 1);
@@ -152,11 +866,29 @@ impl NlSocket {
     /// * `Some(0)` turns checking on, but takes the PID from the first received message.
     /// * `Some(pid)` uses the given PID.
     pub fn bind(&mut self, pid: Option<u32>, groups: Option<Vec<u32>>) -> Result<(), io::Error> {
-        let mut nladdr = unsafe { zeroed::<libc::sockaddr_nl>() };
-        nladdr.nl_family = libc::c_int::from(AddrFamily::Netlink) as u16;
-        nladdr.nl_pid = pid.unwrap_or(0);
         self.pid = pid;
-        nladdr.nl_groups = 0;
+        self.bind_to(pid.unwrap_or(0), &groups.unwrap_or_default())
+    }
+
+    /// Bind to the given netlink ID, or to one chosen by the kernel if `pid` is 0, and join the
+    /// multicast groups with the given IDs. Daemons can use a fixed ID so that other processes
+    /// know where to send messages. Groups 1 to 32 are joined with the bitmask passed to `bind`
+    /// and higher group IDs with `NETLINK_ADD_MEMBERSHIP`. PID checking is turned off if any
+    /// groups are joined and left unchanged otherwise. Group 0 does not exist and is rejected
+    /// before binding.
+    pub fn bind_to(&mut self, pid: u32, groups: &[u32]) -> Result<(), io::Error> {
+        if groups.contains(&0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Multicast group 0 does not exist",
+            ));
+        }
+        let (low, high): (Vec<u32>, Vec<u32>) =
+            groups.iter().partition(|group| (1..=32).contains(*group));
+        let nladdr = sockaddr_nl(NlAddr {
+            pid,
+            groups: low.iter().fold(0, |acc, group| acc | 1 << (group - 1)),
+        });
         match unsafe {
             libc::bind(
                 self.fd,
@@ -167,39 +899,306 @@ impl NlSocket {
             i if i >= 0 => (),
             _ => return Err(io::Error::last_os_error()),
         };
-        if let Some(grps) = groups {
-            self.set_mcast_groups(grps)?;
+        if !groups.is_empty() {
+            self.add_mcast_membership(&high)?;
         }
         Ok(())
     }
 
-    /// Set multicast groups for socket
-    pub fn set_mcast_groups(&mut self, groups: Vec<u32>) -> Result<(), io::Error> {
-        let grps = groups
-            .into_iter()
-            .fold(0, |acc, next| acc | (1 << (next - 1)));
+    /// Netlink address the socket is bound to. The groups bitmask only covers groups 1 to 32;
+    /// use `list_mcast_membership` for all groups.
+    pub fn local_addr(&self) -> Result<NlAddr, io::Error> {
+        let mut nladdr = unsafe { zeroed::<libc::sockaddr_nl>() };
+        let mut len = size_of::<libc::sockaddr_nl>() as libc::socklen_t;
         match unsafe {
-            libc::setsockopt(
+            libc::getsockname(
                 self.fd,
-                libc::SOL_NETLINK,
-                libc::NETLINK_ADD_MEMBERSHIP,
-                &grps as *const _ as *const libc::c_void,
-                size_of::<u32>() as libc::socklen_t,
+                &mut nladdr as *mut _ as *mut libc::sockaddr,
+                &mut len,
             )
         } {
-            i if i == 0 => {
-                self.pid = None;
-                Ok(())
-            }
+            0 => Ok(NlAddr {
+                pid: nladdr.nl_pid,
+                groups: nladdr.nl_groups,
+            }),
             _ => Err(io::Error::last_os_error()),
         }
     }
 
-    /// Send message encoded as byte slice to the netlink ID specified in the netlink header
-    /// (`neli::nl::Nlmsghdr`)
-    pub fn send<B>(&self, buf: B, flags: i32) -> Result<libc::size_t, io::Error>
-    where
-        B: AsRef<[u8]>,
+    /// Set multicast groups for socket
+    pub fn set_mcast_groups(&mut self, groups: Vec<u32>) -> Result<(), io::Error> {
+        self.add_mcast_membership(&groups)
+    }
+
+    /// Join the multicast groups with the given IDs (`NETLINK_ADD_MEMBERSHIP`). Group IDs are not
+    /// limited to the 32 groups that can be expressed in the bitmask passed to `bind`.
+    pub fn add_mcast_membership(&mut self, groups: &[u32]) -> Result<(), io::Error> {
+        for group in groups {
+            self.set_nl_sockopt(NlSockOpt::AddMembership, *group)?;
+        }
+        // Multicast messages are not sent from the PID of the kernel socket
+        self.pid = None;
+        Ok(())
+    }
+
+    /// Leave the multicast groups with the given IDs (`NETLINK_DROP_MEMBERSHIP`)
+    pub fn drop_mcast_membership(&mut self, groups: &[u32]) -> Result<(), io::Error> {
+        for group in groups {
+            self.set_nl_sockopt(NlSockOpt::DropMembership, *group)?;
+        }
+        Ok(())
+    }
+
+    /// List the IDs of the multicast groups the socket is currently a member of
+    /// (`NETLINK_LIST_MEMBERSHIPS`)
+    pub fn list_mcast_membership(&self) -> Result<BTreeSet<u32>, io::Error> {
+        // The kernel reports the membership bitmask as an array of u32 words and always writes
+        // back the length it needs, so grow the buffer until everything fits
+        let mut words = vec![0u32; 1];
+        loop {
+            let mut len = (words.len() * size_of::<u32>()) as libc::socklen_t;
+            match unsafe {
+                libc::getsockopt(
+                    self.fd,
+                    libc::SOL_NETLINK,
+                    NlSockOpt::ListMemberships.into(),
+                    words.as_mut_ptr() as *mut libc::c_void,
+                    &mut len,
+                )
+            } {
+                0 => (),
+                _ => return Err(io::Error::last_os_error()),
+            };
+            let needed = len as usize / size_of::<u32>();
+            if needed <= words.len() {
+                words.truncate(needed);
+                break;
+            }
+            words.resize(needed, 0);
+        }
+        let mut groups = BTreeSet::new();
+        for (i, word) in words.iter().enumerate() {
+            for bit in 0..32 {
+                if word & (1 << bit) != 0 {
+                    groups.insert(i as u32 * 32 + bit + 1);
+                }
+            }
+        }
+        Ok(groups)
+    }
+
+    fn set_nl_sockopt<T>(&self, opt: NlSockOpt, val: T) -> Result<(), io::Error> {
+        self.set_sockopt(libc::SOL_NETLINK, opt.into(), val)
+    }
+
+    fn set_sockopt<T>(&self, level: c_int, name: c_int, val: T) -> Result<(), io::Error> {
+        match unsafe {
+            libc::setsockopt(
+                self.fd,
+                level,
+                name,
+                &val as *const _ as *const libc::c_void,
+                size_of::<T>() as libc::socklen_t,
+            )
+        } {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    /// Enable or disable kernel receive timestamps (`SO_TIMESTAMPNS`), reported by
+    /// `recv_nl_timestamped`
+    pub fn enable_timestamps(&mut self, enable: bool) -> Result<(), io::Error> {
+        self.set_sockopt(libc::SOL_SOCKET, libc::SO_TIMESTAMPNS, enable as c_int)
+    }
+
+    /// Set the priority of packets sent on the socket (`SO_PRIORITY`). Values above 6 require
+    /// `CAP_NET_ADMIN`.
+    pub fn set_priority(&mut self, priority: u32) -> Result<(), io::Error> {
+        self.set_sockopt(libc::SOL_SOCKET, libc::SO_PRIORITY, priority as c_int)
+    }
+
+    /// Set the time in microseconds to busy poll on the device queue when no data is available
+    /// for reading (`SO_BUSY_POLL`). Raising the value requires `CAP_NET_ADMIN`.
+    pub fn set_busy_poll(&mut self, usecs: u32) -> Result<(), io::Error> {
+        self.set_sockopt(libc::SOL_SOCKET, libc::SO_BUSY_POLL, usecs as c_int)
+    }
+
+    /// Set the receive buffer size in bytes, ignoring the `rmem_max` limit (`SO_RCVBUFFORCE`).
+    /// This requires `CAP_NET_ADMIN`.
+    pub fn set_recv_buffer_force(&mut self, size: usize) -> Result<(), io::Error> {
+        let size = size.min(c_int::MAX as usize) as c_int;
+        self.set_sockopt(libc::SOL_SOCKET, libc::SO_RCVBUFFORCE, size)
+    }
+
+    /// Set the receive buffer size in bytes (`SO_RCVBUF`). The kernel doubles the value and caps
+    /// it at `rmem_max`.
+    pub fn set_recv_buffer(&mut self, size: usize) -> Result<(), io::Error> {
+        let size = size.min(c_int::MAX as usize) as c_int;
+        self.set_sockopt(libc::SOL_SOCKET, libc::SO_RCVBUF, size)
+    }
+
+    /// Set the send buffer size in bytes (`SO_SNDBUF`). The kernel doubles the value and caps it
+    /// at `wmem_max`.
+    pub fn set_send_buffer(&mut self, size: usize) -> Result<(), io::Error> {
+        let size = size.min(c_int::MAX as usize) as c_int;
+        self.set_sockopt(libc::SOL_SOCKET, libc::SO_SNDBUF, size)
+    }
+
+    /// Set how long blocking receive calls wait for data before failing with
+    /// `io::ErrorKind::WouldBlock` (`SO_RCVTIMEO`). `None` waits forever.
+    pub fn set_recv_timeout(&mut self, timeout: Option<Duration>) -> Result<(), io::Error> {
+        self.set_sockopt(libc::SOL_SOCKET, libc::SO_RCVTIMEO, timeval(timeout))
+    }
+
+    /// Set how long blocking send calls wait for buffer space before failing with
+    /// `io::ErrorKind::WouldBlock` (`SO_SNDTIMEO`). `None` waits forever.
+    pub fn set_send_timeout(&mut self, timeout: Option<Duration>) -> Result<(), io::Error> {
+        self.set_sockopt(libc::SOL_SOCKET, libc::SO_SNDTIMEO, timeval(timeout))
+    }
+
+    /// Enable or disable extended ACKs (`NETLINK_EXT_ACK`). With extended ACKs, the kernel
+    /// appends attributes such as a textual error message to error messages.
+    pub fn enable_ext_ack(&mut self, enable: bool) -> Result<(), io::Error> {
+        self.set_nl_sockopt(NlSockOpt::ExtAck, enable as libc::c_int)
+    }
+
+    /// Enable or disable strict checking of requests (`NETLINK_GET_STRICT_CHK`). The kernel
+    /// only honors header fields and attributes used as filters in dump requests when strict
+    /// checking is turned on.
+    pub fn enable_strict_checking(&mut self, enable: bool) -> Result<(), io::Error> {
+        self.set_nl_sockopt(NlSockOpt::GetStrictChk, enable as libc::c_int)
+    }
+
+    /// Attach a classic BPF program to the socket (`SO_ATTACH_FILTER`), replacing any filter
+    /// already attached. The kernel runs the program on every datagram before queueing it and
+    /// drops the datagram if the program returns 0.
+    pub fn attach_filter(&mut self, filter: &[libc::sock_filter]) -> Result<(), io::Error> {
+        if filter.len() > u16::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "BPF program has too many instructions",
+            ));
+        }
+        let prog = libc::sock_fprog {
+            len: filter.len() as u16,
+            filter: filter.as_ptr() as *mut libc::sock_filter,
+        };
+        self.set_sockopt(libc::SOL_SOCKET, libc::SO_ATTACH_FILTER, prog)
+    }
+
+    /// Remove the BPF program attached with `attach_filter` (`SO_DETACH_FILTER`)
+    pub fn detach_filter(&mut self) -> Result<(), io::Error> {
+        self.set_sockopt(libc::SOL_SOCKET, libc::SO_DETACH_FILTER, 0 as libc::c_int)
+    }
+
+    /// Let `recv_nl`, `recv_nl_batch` and `recv_nl_from` grow a buffer size given by the caller
+    /// to fit datagrams of up to `cap` bytes instead of returning `NlError::Truncated`. Larger
+    /// datagrams are still discarded. `None`, the default, never grows the buffer and saves the
+    /// extra `recv` call needed to peek at the datagram length.
+    pub fn set_recv_grow_cap(&mut self, cap: Option<usize>) {
+        self.recv_grow_cap = cap;
+    }
+
+    /// Take the buffers that `recv_nl` and the methods built on it receive datagrams into from
+    /// `pool` instead of allocating a new one for each datagram. `None` turns pooling off.
+    pub fn set_buffer_pool(&mut self, pool: Option<BufferPool>) {
+        self.pool = pool;
+    }
+
+    /// Return a handle that interrupts receive calls blocked on this socket, for example to shut
+    /// down a listener thread cleanly. Once a handle exists, receive calls wait for data with
+    /// `poll` on both the socket and the handle.
+    pub fn shutdown_handle(&mut self) -> Result<ShutdownHandle, io::Error> {
+        if self.shutdown.is_none() {
+            let fd = match unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) } {
+                i if i >= 0 => i,
+                _ => return Err(io::Error::last_os_error()),
+            };
+            self.shutdown = Some(Arc::new(EventFd(fd)));
+        }
+        let event = self.shutdown.clone().expect("Event was created above");
+        Ok(ShutdownHandle { event })
+    }
+
+    /// Receive timeout in milliseconds for `poll`, -1 if there is none
+    fn recv_timeout_ms(&self) -> Result<c_int, io::Error> {
+        let mut tv = unsafe { zeroed::<libc::timeval>() };
+        let mut len = size_of::<libc::timeval>() as libc::socklen_t;
+        match unsafe {
+            libc::getsockopt(
+                self.fd,
+                libc::SOL_SOCKET,
+                libc::SO_RCVTIMEO,
+                &mut tv as *mut _ as *mut c_void,
+                &mut len,
+            )
+        } {
+            0 => (),
+            _ => return Err(io::Error::last_os_error()),
+        }
+        let timeout = Duration::new(tv.tv_sec as u64, tv.tv_usec as u32 * 1000);
+        if timeout == Duration::from_secs(0) {
+            return Ok(-1);
+        }
+        // Round up so that poll does not return before the timeout has expired
+        let ms = timeout.as_micros().div_ceil(1000);
+        Ok(ms.min(c_int::MAX as u128) as c_int)
+    }
+
+    /// Wait until the socket is readable if a shutdown handle exists, failing if the handle has
+    /// been triggered
+    fn wait_readable(&self, flags: i32) -> Result<(), io::Error> {
+        let event = match self.shutdown {
+            Some(ref event) => event,
+            None => return Ok(()),
+        };
+        let timeout = if flags & libc::MSG_DONTWAIT != 0 || !self.is_blocking()? {
+            0
+        } else {
+            self.recv_timeout_ms()?
+        };
+        let mut pfds = [
+            libc::pollfd {
+                fd: self.fd,
+                events: libc::POLLIN,
+                revents: 0,
+            },
+            libc::pollfd {
+                fd: event.0,
+                events: libc::POLLIN,
+                revents: 0,
+            },
+        ];
+        let ready = loop {
+            match unsafe { libc::poll(pfds.as_mut_ptr(), 2, timeout) } {
+                i if i >= 0 => break i,
+                _ => {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                }
+            }
+        };
+        if ready == 0 && timeout > 0 {
+            // Report the expired receive timeout like the kernel does
+            return Err(io::Error::from(io::ErrorKind::WouldBlock));
+        }
+        if pfds[1].revents & libc::POLLIN != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "Socket was shut down",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Send message encoded as byte slice to the netlink ID specified in the netlink header
+    /// (`neli::nl::Nlmsghdr`)
+    pub fn send<B>(&self, buf: B, flags: i32) -> Result<libc::size_t, io::Error>
+    where
+        B: AsRef<[u8]>,
     {
         match unsafe {
             libc::send(
@@ -214,12 +1213,54 @@ impl NlSocket {
         }
     }
 
+    /// Send message encoded as byte slice to the given netlink address instead of the kernel or
+    /// the peer set with `connect_to`. With a PID of 0, the message goes to the kernel; other PIDs
+    /// address user space sockets. A nonzero `groups` bitmask multicasts the message to those
+    /// groups, which requires `CAP_NET_ADMIN` for most netlink families.
+    pub fn send_to<B>(&self, buf: B, flags: i32, addr: NlAddr) -> Result<libc::size_t, io::Error>
+    where
+        B: AsRef<[u8]>,
+    {
+        let nladdr = sockaddr_nl(addr);
+        match unsafe {
+            libc::sendto(
+                self.fd,
+                buf.as_ref() as *const _ as *const c_void,
+                buf.as_ref().len(),
+                flags,
+                &nladdr as *const _ as *const libc::sockaddr,
+                size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        } {
+            i if i >= 0 => Ok(i as libc::size_t),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    /// Set the default destination of `send` and similar methods to the given netlink address
+    /// (`connect`). Afterwards, only datagrams from that address are received. Connecting to a
+    /// PID of 0 and no groups restores the default of talking to the kernel.
+    pub fn connect_to(&mut self, addr: NlAddr) -> Result<(), io::Error> {
+        let nladdr = sockaddr_nl(addr);
+        match unsafe {
+            libc::connect(
+                self.fd,
+                &nladdr as *const _ as *const libc::sockaddr,
+                size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        } {
+            i if i >= 0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
     /// Receive message encoded as byte slice from the netlink socket
     pub fn recv<B>(&self, mut buf: B, flags: i32) -> Result<libc::size_t, io::Error>
     where
         B: AsMut<[u8]>,
     {
-        match unsafe {
+        self.wait_readable(flags)?;
+        let result = match unsafe {
             libc::recv(
                 self.fd,
                 buf.as_mut() as *mut _ as *mut c_void,
@@ -229,7 +1270,295 @@ impl NlSocket {
         } {
             i if i >= 0 => Ok(i as libc::size_t),
             _ => Err(io::Error::last_os_error()),
+        };
+        self.count_recv(flags, &result, buf.as_mut());
+        result
+    }
+
+    /// Snapshot of the counters of data received on this handle
+    pub fn stats(&self) -> NlSocketStats {
+        NlSocketStats {
+            datagrams: self.stats.datagrams.load(Ordering::Relaxed),
+            bytes: self.stats.bytes.load(Ordering::Relaxed),
+            parse_failures: self.stats.parse_failures.load(Ordering::Relaxed),
+            enobufs: self.stats.enobufs.load(Ordering::Relaxed),
+            acks: self.stats.acks.load(Ordering::Relaxed),
+        }
+    }
+
+    fn count_parse_failure(&self) {
+        self.stats.parse_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Update the statistics after a receive call that stored data in `buf`
+    fn count_recv(&self, flags: i32, result: &Result<libc::size_t, io::Error>, buf: &[u8]) {
+        match *result {
+            Ok(read) if flags & libc::MSG_PEEK == 0 => {
+                self.stats.datagrams.fetch_add(1, Ordering::Relaxed);
+                self.stats.bytes.fetch_add(read as u64, Ordering::Relaxed);
+                let acks = count_acks(&buf[..read.min(buf.len())]);
+                self.stats.acks.fetch_add(acks, Ordering::Relaxed);
+            }
+            Err(ref e) if e.raw_os_error() == Some(libc::ENOBUFS) => {
+                self.stats.enobufs.fetch_add(1, Ordering::Relaxed);
+            }
+            _ => (),
+        }
+    }
+
+    fn recvmsg<B, F>(
+        &self,
+        mut buf: B,
+        flags: i32,
+        mut cmsg_handler: F,
+    ) -> Result<libc::size_t, io::Error>
+    where
+        B: AsMut<[u8]>,
+        F: FnMut(c_int, c_int, &[u8]),
+    {
+        let mut iov = libc::iovec {
+            iov_base: buf.as_mut() as *mut _ as *mut c_void,
+            iov_len: buf.as_mut().len(),
+        };
+        let mut control = [0u64; CMSG_BUFFER_LEN];
+        let mut hdr = unsafe { zeroed::<libc::msghdr>() };
+        hdr.msg_iov = &mut iov;
+        hdr.msg_iovlen = 1;
+        hdr.msg_control = control.as_mut_ptr() as *mut c_void;
+        hdr.msg_controllen = size_of_val(&control) as _;
+        self.wait_readable(flags)?;
+        let result = match unsafe { libc::recvmsg(self.fd, &mut hdr, flags) } {
+            i if i >= 0 => Ok(i as libc::size_t),
+            _ => Err(io::Error::last_os_error()),
+        };
+        self.count_recv(flags, &result, buf.as_mut());
+        let read = result?;
+        let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&hdr) };
+        while !cmsg.is_null() {
+            let (level, ty, data) = unsafe {
+                let data_len = (*cmsg).cmsg_len as usize - libc::CMSG_LEN(0) as usize;
+                (
+                    (*cmsg).cmsg_level,
+                    (*cmsg).cmsg_type,
+                    slice::from_raw_parts(libc::CMSG_DATA(cmsg), data_len),
+                )
+            };
+            cmsg_handler(level, ty, data);
+            cmsg = unsafe { libc::CMSG_NXTHDR(&hdr, cmsg) };
+        }
+        Ok(read)
+    }
+
+    /// Receive messages from all network namespaces that have an nsid assigned in the
+    /// namespace of the socket (`NETLINK_LISTEN_ALL_NSID`). Use `recv_with_nsid` to find out
+    /// which namespace a message came from.
+    pub fn listen_all_nsid(&mut self, enable: bool) -> Result<(), io::Error> {
+        self.set_nl_sockopt(NlSockOpt::ListenAllNsid, enable as libc::c_int)
+    }
+
+    /// Receive message encoded as byte slice from the netlink socket along with the nsid of the
+    /// network namespace it originated from. The nsid is only reported for messages from other
+    /// namespaces when `listen_all_nsid` has been enabled.
+    pub fn recv_with_nsid<B>(
+        &self,
+        buf: B,
+        flags: i32,
+    ) -> Result<(libc::size_t, Option<i32>), io::Error>
+    where
+        B: AsMut<[u8]>,
+    {
+        let mut nsid = None;
+        let read = self.recvmsg(buf, flags, |level, ty, data| {
+            if level == libc::SOL_NETLINK
+                && ty == c_int::from(NlSockOpt::ListenAllNsid)
+                && data.len() >= size_of::<c_int>()
+            {
+                nsid = Some(NativeEndian::read_i32(data));
+            }
+        })?;
+        Ok((read, nsid))
+    }
+
+    /// Report the credentials of the sender with every received datagram (`SO_PASSCRED`). Use
+    /// `recv_with_creds` to read them.
+    pub fn enable_passcred(&mut self, enable: bool) -> Result<(), io::Error> {
+        self.set_sockopt(libc::SOL_SOCKET, libc::SO_PASSCRED, enable as c_int)
+    }
+
+    /// Receive message encoded as byte slice from the netlink socket along with the credentials
+    /// of the sender. Credentials are only reported when `enable_passcred` has been turned on.
+    pub fn recv_with_creds<B>(
+        &self,
+        buf: B,
+        flags: i32,
+    ) -> Result<(libc::size_t, Option<NlCredentials>), io::Error>
+    where
+        B: AsMut<[u8]>,
+    {
+        let mut creds = None;
+        let read = self.recvmsg(buf, flags, |level, ty, data| {
+            if level == libc::SOL_SOCKET
+                && ty == libc::SCM_CREDENTIALS
+                && data.len() >= size_of::<libc::ucred>()
+            {
+                let ucred = unsafe { ptr::read_unaligned(data.as_ptr() as *const libc::ucred) };
+                creds = Some(NlCredentials {
+                    pid: ucred.pid,
+                    uid: ucred.uid,
+                    gid: ucred.gid,
+                });
+            }
+        })?;
+        Ok((read, creds))
+    }
+
+    /// Receive message encoded as byte slice from the netlink socket along with the address of
+    /// the sender
+    pub fn recv_from<B>(&self, mut buf: B, flags: i32) -> Result<(libc::size_t, NlAddr), io::Error>
+    where
+        B: AsMut<[u8]>,
+    {
+        let mut addr = unsafe { zeroed::<libc::sockaddr_nl>() };
+        let mut addr_len = size_of::<libc::sockaddr_nl>() as libc::socklen_t;
+        self.wait_readable(flags)?;
+        let result = match unsafe {
+            libc::recvfrom(
+                self.fd,
+                buf.as_mut() as *mut _ as *mut c_void,
+                buf.as_mut().len(),
+                flags,
+                &mut addr as *mut _ as *mut libc::sockaddr,
+                &mut addr_len,
+            )
+        } {
+            i if i >= 0 => Ok(i as libc::size_t),
+            _ => Err(io::Error::last_os_error()),
+        };
+        self.count_recv(flags, &result, buf.as_mut());
+        let read = result?;
+        Ok((
+            read,
+            NlAddr {
+                pid: addr.nl_pid,
+                groups: addr.nl_groups,
+            },
+        ))
+    }
+
+    /// Receive the next datagram and return all `Nlmsghdr` structs it contains along with the
+    /// address of the sender. Data left over from an earlier `recv_nl` call is not returned and
+    /// the messages are not checked against the socket PID.
+    pub fn recv_nl_from<T, P>(
+        &self,
+        buf_sz: Option<usize>,
+    ) -> Result<(Vec<Nlmsghdr<T, P>>, NlAddr), NlError>
+    where
+        T: Nl + NlType,
+        P: Nl,
+    {
+        let buf_sz = self.recv_buf_len(buf_sz)?;
+        let mut mem = vec![0; buf_sz];
+        let (read, addr) = self.recv_from(&mut mem, libc::MSG_TRUNC)?;
+        if read == 0 {
+            return Err(NlError::new("No data could be read from the socket"));
+        }
+        if read > buf_sz {
+            return Err(NlError::Truncated);
+        }
+        mem.truncate(read);
+        let msgs = Vec::<Nlmsghdr<T, P>>::deserialize(&mut StreamReadBuffer::new(mem))
+            .inspect_err(|_| self.count_parse_failure())?;
+        Ok((msgs, addr))
+    }
+
+    /// Send each buffer in `bufs` as a separate datagram with a single `sendmmsg` call. Returns
+    /// the number of bytes sent for each datagram that was sent, which may be fewer datagrams
+    /// than given. The kernel handles at most `UIO_MAXIOV` (1024) datagrams per call.
+    pub fn send_multiple<B>(&self, bufs: &[B], flags: i32) -> Result<Vec<libc::size_t>, io::Error>
+    where
+        B: AsRef<[u8]>,
+    {
+        let mut iovs = bufs
+            .iter()
+            .map(|buf| libc::iovec {
+                iov_base: buf.as_ref().as_ptr() as *mut c_void,
+                iov_len: buf.as_ref().len(),
+            })
+            .collect::<Vec<_>>();
+        let mut hdrs = iovs
+            .iter_mut()
+            .map(|iov| {
+                let mut hdr = unsafe { zeroed::<libc::mmsghdr>() };
+                hdr.msg_hdr.msg_iov = iov;
+                hdr.msg_hdr.msg_iovlen = 1;
+                hdr
+            })
+            .collect::<Vec<_>>();
+        let sent = match unsafe {
+            libc::sendmmsg(self.fd, hdrs.as_mut_ptr(), hdrs.len() as _, flags as _)
+        } {
+            i if i >= 0 => i as usize,
+            _ => return Err(io::Error::last_os_error()),
+        };
+        Ok(hdrs[..sent]
+            .iter()
+            .map(|hdr| hdr.msg_len as libc::size_t)
+            .collect())
+    }
+
+    /// Receive up to one datagram into each buffer in `bufs` with a single `recvmmsg` call, so
+    /// the number of buffers sets the batch size. Returns the outcome for each datagram that was
+    /// received. Pass `MSG_WAITFORONE` in `flags` to return as soon as one datagram has arrived
+    /// instead of waiting until all buffers are filled. Use `NlBufferIter` to parse the received
+    /// messages.
+    pub fn recv_multiple<B>(&self, bufs: &mut [B], flags: i32) -> Result<Vec<MmsgResult>, io::Error>
+    where
+        B: AsMut<[u8]>,
+    {
+        let mut iovs = bufs
+            .iter_mut()
+            .map(|buf| libc::iovec {
+                iov_base: buf.as_mut().as_mut_ptr() as *mut c_void,
+                iov_len: buf.as_mut().len(),
+            })
+            .collect::<Vec<_>>();
+        let mut hdrs = iovs
+            .iter_mut()
+            .map(|iov| {
+                let mut hdr = unsafe { zeroed::<libc::mmsghdr>() };
+                hdr.msg_hdr.msg_iov = iov;
+                hdr.msg_hdr.msg_iovlen = 1;
+                hdr
+            })
+            .collect::<Vec<_>>();
+        self.wait_readable(flags)?;
+        let result = match unsafe {
+            libc::recvmmsg(
+                self.fd,
+                hdrs.as_mut_ptr(),
+                hdrs.len() as _,
+                flags as _,
+                ptr::null_mut(),
+            )
+        } {
+            i if i >= 0 => Ok(i as usize),
+            _ => Err(io::Error::last_os_error()),
+        };
+        if result.is_err() {
+            self.count_recv(flags, &result, &[]);
+        }
+        let received = result?;
+        let results = hdrs[..received]
+            .iter()
+            .map(|hdr| MmsgResult {
+                len: hdr.msg_len as usize,
+                truncated: hdr.msg_hdr.msg_flags & libc::MSG_TRUNC != 0,
+            })
+            .collect::<Vec<_>>();
+        for (buf, res) in bufs.iter_mut().zip(results.iter()) {
+            self.count_recv(flags, &Ok(res.len), buf.as_mut());
         }
+        Ok(results)
     }
 
     /// Equivalent of `socket` and `bind` calls.
@@ -244,6 +1573,63 @@ impl NlSocket {
         Ok(s)
     }
 
+    /// Start configuring a socket for the given protocol. The builder covers the options that
+    /// are usually set right after creating a socket, so that the socket is returned fully
+    /// configured.
+    pub fn builder(proto: NlFamily) -> NlSocketBuilder {
+        NlSocketBuilder {
+            proto,
+            pid: None,
+            groups: Vec::new(),
+            track_seq: false,
+            recv_buffer: None,
+            send_buffer: None,
+            ext_ack: false,
+            strict_checking: false,
+            nonblocking: false,
+            recv_timeout: None,
+            send_timeout: None,
+            recv_grow_cap: None,
+            pool: None,
+        }
+    }
+
+    /// Like `connect`, but create the socket in another network namespace. The calling thread
+    /// enters the namespace with `setns` for the duration of the call and then returns to its
+    /// original namespace. The socket keeps operating in the target namespace afterwards. This
+    /// requires `CAP_SYS_ADMIN`.
+    ///
+    /// If the thread fails to return to its original namespace, the process is aborted rather
+    /// than leaving the thread in the target namespace for everything it does afterwards.
+    pub fn connect_in_netns<'a, N>(
+        netns: N,
+        proto: NlFamily,
+        pid: Option<u32>,
+        groups: Option<Vec<u32>>,
+        track_seq: bool,
+    ) -> Result<Self, io::Error>
+    where
+        N: Into<NetnsTarget<'a>>,
+    {
+        let target_file;
+        let target_fd = match netns.into() {
+            NetnsTarget::Path(path) => {
+                target_file = File::open(path)?;
+                target_file.as_raw_fd()
+            }
+            NetnsTarget::Fd(fd) => fd,
+        };
+        // The namespace of the calling thread, which may differ from that of the process
+        let original = File::open("/proc/thread-self/ns/net")?;
+        setns(target_fd)?;
+        let socket = NlSocket::connect(proto, pid, groups, track_seq);
+        if let Err(e) = setns(original.as_raw_fd()) {
+            eprintln!("Failed to return to the original network namespace: {}", e);
+            process::abort();
+        }
+        socket
+    }
+
     fn get_genl_family<T>(
         &mut self,
         family_name: &str,
@@ -263,11 +1649,29 @@ impl NlSocket {
         );
         self.send_nl(nlhdr)?;
 
-        let msg = self.recv_nl(None)?;
+        let msg = self.recv_genl_reply(&[CtrlCmd::Newfamily])?;
         self.recv_ack()?;
         Ok(msg)
     }
 
+    /// Like `recv_nl`, but also verify that the generic netlink command of the message is one
+    /// of `expected` and return `NlError::BadCmd` otherwise. Listing the family's notify command
+    /// next to the reply command accepts notifications received on a shared socket, which the
+    /// caller can then tell apart by `cmd`.
+    pub fn recv_genl_reply<T, C, A>(
+        &mut self,
+        expected: &[C],
+    ) -> Result<Nlmsghdr<T, Genlmsghdr<C, A>>, NlError>
+    where
+        T: Nl + NlType,
+        C: Cmd + Clone,
+        A: NlAttrType,
+    {
+        let msg = self.recv_nl::<T, Genlmsghdr<C, A>>(None)?;
+        msg.nl_payload.check_cmd(expected)?;
+        Ok(msg)
+    }
+
     /// Convenience function for resolving a `&str` containing the multicast group name to a
     /// numeric netlink ID
     pub fn resolve_genl_family(&mut self, family_name: &str) -> Result<u16, NlError> {
@@ -306,6 +1710,20 @@ impl NlSocket {
             .ok_or_else(|| NlError::new("Failed to resolve multicast group ID"))
     }
 
+    /// Convenience function for listing the commands supported by a generic netlink family
+    pub fn resolve_genl_ops(&mut self, family_name: &str) -> Result<Vec<GenlOp>, NlError> {
+        let nlhdr = self.get_genl_family::<CtrlAttr>(family_name)?;
+        nlhdr.nl_payload.ops()
+    }
+
+    /// Convenience function for resolving the ID of a generic netlink multicast group by family
+    /// and group name and joining it. The resolved group ID is returned.
+    pub fn resolve_and_join_mcast(&mut self, family: &str, group: &str) -> Result<u32, NlError> {
+        let id = self.resolve_nl_mcast_group(family, group)?;
+        self.add_mcast_membership(&[id])?;
+        Ok(id)
+    }
+
     /// Convenience function to send an `Nlmsghdr` struct
     pub fn send_nl<T, P>(&mut self, mut msg: Nlmsghdr<T, P>) -> Result<(), NlError>
     where
@@ -317,47 +1735,516 @@ impl NlSocket {
             *seq += 1;
             msg.nl_seq = *seq;
         }
+        msg.check_len()?;
+        msg.serialize(&mut mem)?;
+        self.send(mem, 0)?;
+        Ok(())
+    }
+
+    /// Like `send_nl`, but serialize the message into storage taken from `alloc` instead of a
+    /// heap allocated buffer
+    pub fn send_nl_in<T, P, A>(&mut self, mut msg: Nlmsghdr<T, P>, alloc: &A) -> Result<(), NlError>
+    where
+        T: Nl + NlType,
+        P: Nl,
+        A: NlAlloc,
+    {
+        if let Some(ref mut seq) = self.seq {
+            *seq += 1;
+            msg.nl_seq = *seq;
+        }
+        msg.check_len()?;
+        let storage = serialize_in(&msg, alloc)?;
+        self.send(storage, 0)?;
+        Ok(())
+    }
+
+    /// Like `send_nl`, but serialize the message into `buf` with `storage::to_bytes_in`, so that
+    /// the same buffer can be passed for every message sent instead of allocating one each time
+    pub fn send_nl_buf<T, P>(
+        &mut self,
+        mut msg: Nlmsghdr<T, P>,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), NlError>
+    where
+        T: Nl + NlType,
+        P: Nl,
+    {
+        if let Some(ref mut seq) = self.seq {
+            *seq += 1;
+            msg.nl_seq = *seq;
+        }
+        msg.check_len()?;
+        to_bytes_in(&msg, buf)?;
+        self.send(&buf[..], 0)?;
+        Ok(())
+    }
+
+    /// Like `send_nl`, but send the message to the given netlink address as with `send_to`
+    pub fn send_nl_to<T, P>(&mut self, mut msg: Nlmsghdr<T, P>, addr: NlAddr) -> Result<(), NlError>
+    where
+        T: Nl + NlType,
+        P: Nl,
+    {
+        let mut mem = StreamWriteBuffer::new_growable(Some(msg.asize()));
+        if let Some(ref mut seq) = self.seq {
+            *seq += 1;
+            msg.nl_seq = *seq;
+        }
+        msg.check_len()?;
         msg.serialize(&mut mem)?;
+        self.send_to(mem, 0, addr)?;
+        Ok(())
+    }
+
+    /// Send several `Nlmsghdr` structs packed into a single datagram. If sequence tracking is
+    /// enabled, the messages are numbered consecutively in the order given.
+    pub fn send_nl_batch<T, P>(&mut self, mut msgs: Vec<Nlmsghdr<T, P>>) -> Result<(), NlError>
+    where
+        T: Nl + NlType,
+        P: Nl,
+    {
+        if let Some(ref mut seq) = self.seq {
+            for msg in msgs.iter_mut() {
+                *seq += 1;
+                msg.nl_seq = *seq;
+            }
+        }
+        for msg in msgs.iter() {
+            msg.check_len()?;
+        }
+        let mut mem = StreamWriteBuffer::new_growable(Some(msgs.asize()));
+        msgs.serialize(&mut mem)?;
         self.send(mem, 0)?;
         Ok(())
     }
 
-    /// Convenience function to begin receiving a stream of `Nlmsghdr` structs
+    /// Receive all `Nlmsghdr` structs in the next datagram, or the remaining messages of a
+    /// datagram that has been partially read with `recv_nl`. The messages are checked against the
+    /// socket PID like in `recv_nl`.
+    pub fn recv_nl_batch<T, P>(
+        &mut self,
+        buf_sz: Option<usize>,
+    ) -> Result<Vec<Nlmsghdr<T, P>>, NlError>
+    where
+        T: Nl + NlType,
+        P: Nl,
+    {
+        let mut msgs = vec![self.recv_nl(buf_sz)?];
+        while self.buffer.is_some() {
+            msgs.push(self.recv_nl(None)?);
+        }
+        Ok(msgs)
+    }
+
+    /// Receive all messages of the next datagram, or the remaining messages of a datagram that
+    /// has been partially read with `recv_nl`, as an `NlBuffer`. The messages are checked against
+    /// the socket PID like in `recv_nl`.
+    pub fn recv_nl_buffer<T, P>(&mut self, buf_sz: Option<usize>) -> Result<NlBuffer<T, P>, NlError>
+    where
+        T: Nl + NlType,
+        P: Nl,
+    {
+        self.recv_nl_batch(buf_sz).map(NlBuffer::from)
+    }
+
+    /// Add an `Nlmsghdr` struct to the outbound queue instead of sending it right away. Messages
+    /// with a higher priority are sent first when the queue is flushed and messages with the same
+    /// priority are sent in the order they were queued.
+    pub fn enqueue_nl<T, P>(&mut self, msg: Nlmsghdr<T, P>, priority: i32) -> Result<(), NlError>
+    where
+        T: Nl + NlType,
+        P: Nl,
+    {
+        msg.check_len()?;
+        let mut mem = StreamWriteBuffer::new_growable(Some(msg.asize()));
+        msg.serialize(&mut mem)?;
+        self.send_queue.push((priority, mem.as_ref().to_vec()));
+        Ok(())
+    }
+
+    /// Number of messages waiting in the outbound queue
+    pub fn queued_len(&self) -> usize {
+        self.send_queue.len()
+    }
+
+    /// Drop all messages waiting in the outbound queue without sending them
+    pub fn clear_queue(&mut self) {
+        self.send_queue.clear();
+    }
+
+    /// Send all queued messages in priority order with a single `send` call. If sequence
+    /// tracking is enabled, sequence numbers are assigned in the order the messages are sent.
+    /// Returns the number of messages sent; the queue is left untouched if sending fails.
+    pub fn flush_queue(&mut self) -> Result<usize, NlError> {
+        if self.send_queue.is_empty() {
+            return Ok(0);
+        }
+        // Stable sort keeps messages with equal priority in FIFO order
+        self.send_queue
+            .sort_by_key(|(priority, _)| std::cmp::Reverse(*priority));
+        let mut seq = self.seq;
+        let mut batch = Vec::with_capacity(self.send_queue.iter().map(|(_, m)| m.len()).sum());
+        for (_, msg) in self.send_queue.iter() {
+            let start = batch.len();
+            batch.extend_from_slice(msg);
+            if let Some(ref mut seq) = seq {
+                *seq += 1;
+                // nl_seq follows nl_len, nl_type and nl_flags in the header
+                endian::write_u32(&mut batch[start + 8..start + 12], *seq);
+            }
+        }
+        self.send(&batch, 0)?;
+        self.seq = seq;
+        let sent = self.send_queue.len();
+        self.send_queue.clear();
+        Ok(sent)
+    }
+
+    /// Convenience function to begin receiving a stream of `Nlmsghdr` structs. If `buf_sz` is
+    /// `None`, the receive buffer is sized to fit the next datagram. Otherwise a datagram larger
+    /// than `buf_sz` is discarded and `NlError::Truncated` is returned, unless it fits within
+    /// the cap set with `set_recv_grow_cap`.
     pub fn recv_nl<T, P>(&mut self, buf_sz: Option<usize>) -> Result<Nlmsghdr<T, P>, NlError>
     where
         T: Nl + NlType,
         P: Nl,
     {
         if self.buffer.is_none() {
-            let mut mem = vec![0; buf_sz.unwrap_or(MAX_NL_LENGTH)];
-            let mem_read = self.recv(&mut mem, 0)?;
-            if mem_read == 0 {
-                return Err(NlError::new("No data could be read from the socket"));
-            }
-            mem.truncate(mem_read as usize);
+            let buf_sz = self.recv_buf_len(buf_sz)?;
+            let mut mem = match self.pool {
+                Some(ref pool) => pool.take(buf_sz),
+                None => PooledBuf::unpooled(buf_sz),
+            };
+            let (mem_read, timestamp) = self.recv_checked(mem.as_mut())?;
+            mem.buf.truncate(mem_read);
             self.buffer = Some(StreamReadBuffer::new(mem));
+            self.timestamp = Some(timestamp);
         }
         let msg = match self.buffer {
-            Some(ref mut b) => Nlmsghdr::deserialize(b)?,
+            Some(ref mut b) => Nlmsghdr::deserialize(b),
             None => unreachable!(),
         };
+        let msg = match msg {
+            Ok(msg) => msg,
+            Err(e) => {
+                self.count_parse_failure();
+                return Err(e.into());
+            }
+        };
+        self.check_pid(msg.nl_pid)?;
+        if self.buffer.as_ref().map(|b| b.at_end()).unwrap_or(false) {
+            self.buffer = None;
+        }
+        Ok(msg)
+    }
+
+    fn check_pid(&mut self, nl_pid: u32) -> Result<(), NlError> {
         match self.pid {
             // PID checking turned off.
             None => (),
             // No PID set yet, store the current one.
-            Some(0) => self.pid = Some(msg.nl_pid),
+            Some(0) => self.pid = Some(nl_pid),
             // PID check OK
-            Some(pid) if pid == msg.nl_pid => (),
+            Some(pid) if pid == nl_pid => (),
             // PID doesn't match
             Some(_) => return Err(NlError::BadPid),
         }
-        if let Some(seq) = self.seq.as_mut() {
-            *seq += 1;
+        Ok(())
+    }
+
+    /// Receive the messages of a dump until `NLMSG_DONE`, storing them according to `growth`.
+    /// One receive buffer sized to the largest datagram is reused for the whole dump. An
+    /// `NLMSG_ERROR` message ends the dump with an error. Messages are checked against the
+    /// socket PID like in `recv_nl`.
+    pub fn recv_dump<T, P>(
+        &mut self,
+        growth: DumpGrowth,
+    ) -> Result<DumpChunks<Nlmsghdr<T, P>>, NlError>
+    where
+        T: Nl + NlType,
+        P: Nl,
+    {
+        let mut msgs = DumpChunks::new(growth);
+        let mut buf = Vec::new();
+        loop {
+            let len = self.peek_len()?;
+            if buf.len() < len {
+                buf.resize(len, 0);
+            }
+            let (read, _) = self.recv_checked(&mut buf)?;
+            for frame in DumpFrames::new(&buf[..read]) {
+                let msg_buf = match frame {
+                    Ok(DumpFrame::Msg(msg_buf)) => msg_buf,
+                    Ok(DumpFrame::Done) => return Ok(msgs),
+                    Ok(DumpFrame::Error(error)) => return Err(NlError::from_nlmsgerr(error)),
+                    Err(e) => {
+                        self.count_parse_failure();
+                        return Err(e);
+                    }
+                };
+                let msg = match Nlmsghdr::deserialize(&mut StreamReadBuffer::new(msg_buf)) {
+                    Ok(msg) => msg,
+                    Err(e) => {
+                        self.count_parse_failure();
+                        return Err(e.into());
+                    }
+                };
+                self.check_pid(msg.nl_pid)?;
+                msgs.push(msg);
+            }
         }
-        if self.buffer.as_ref().map(|b| b.at_end()).unwrap_or(false) {
-            self.buffer = None;
+    }
+
+    /// Send a request and collect its responses: the messages of a dump if `NlmF::Dump` is set,
+    /// and otherwise the reply to the request, if any, followed by the ACK. `NlmF::Ack` is added
+    /// to requests that are not dumps. An ACK following `NLMSG_DONE` is consumed as well if it
+    /// has already been received. Every response must carry the sequence number of the request,
+    /// and an `NLMSG_ERROR` message with a non-zero error code is returned as an error.
+    pub fn request<T, P, RT, RP>(
+        &mut self,
+        mut msg: Nlmsghdr<T, P>,
+    ) -> Result<Vec<Nlmsghdr<RT, RP>>, NlError>
+    where
+        T: Nl + NlType,
+        P: Nl,
+        RT: Nl + NlType,
+        RP: Nl,
+    {
+        let flags = u16::from(&NlmFFlags::new(&msg.nl_flags));
+        if flags & libc::NLM_F_DUMP as u16 != libc::NLM_F_DUMP as u16
+            && flags & libc::NLM_F_ACK as u16 == 0
+        {
+            msg.nl_flags.push(NlmF::Ack);
         }
-        Ok(msg)
+        let ack = msg.nl_flags.contains(&NlmF::Ack);
+        let seq = msg.nl_seq;
+        self.send_nl(msg)?;
+        let seq = self.seq.unwrap_or(seq);
+
+        let mut msgs = Vec::new();
+        let mut buf = Vec::new();
+        loop {
+            let len = self.peek_len()?;
+            if buf.len() < len {
+                buf.resize(len, 0);
+            }
+            let (read, _) = self.recv_checked(&mut buf)?;
+            let mut frames = DumpFrames::new(&buf[..read]);
+            let mut done = false;
+            while let Some(frame) = frames.next() {
+                let frame = match frame {
+                    Ok(frame) => frame,
+                    Err(e) => {
+                        self.count_parse_failure();
+                        return Err(e);
+                    }
+                };
+                let (nl_type, nl_seq) = frames.last;
+                if nl_seq != seq {
+                    return Err(NlError::BadSeq);
+                }
+                match frame {
+                    DumpFrame::Msg(msg_buf) => {
+                        let msg = match Nlmsghdr::deserialize(&mut StreamReadBuffer::new(msg_buf)) {
+                            Ok(msg) => msg,
+                            Err(e) => {
+                                self.count_parse_failure();
+                                return Err(e.into());
+                            }
+                        };
+                        self.check_pid(msg.nl_pid)?;
+                        msgs.push(msg);
+                    }
+                    // The ACK comes after NLMSG_DONE
+                    DumpFrame::Done if ack && nl_type == u16::from(consts::Nlmsg::Done) => {
+                        done = true
+                    }
+                    DumpFrame::Done => return Ok(msgs),
+                    DumpFrame::Error(error) => return Err(NlError::from_nlmsgerr(error)),
+                }
+            }
+            if done {
+                self.recv_trailing_ack(seq)?;
+                return Ok(msgs);
+            }
+        }
+    }
+
+    /// Consume an ACK for `seq` if it is the next datagram waiting on the socket, without
+    /// blocking. Linux does not acknowledge dumps, but other peers may.
+    fn recv_trailing_ack(&mut self, seq: u32) -> Result<(), NlError> {
+        // Header and error code of `struct nlmsgerr`
+        let mut hdr = [0u8; 20];
+        match self.recv(&mut hdr[..], libc::MSG_PEEK | libc::MSG_DONTWAIT) {
+            Ok(read) if read >= hdr.len() => (),
+            Ok(_) => return Ok(()),
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+        if endian::read_u16(&hdr[4..]) != u16::from(consts::Nlmsg::Error)
+            || endian::read_u32(&hdr[8..]) != seq
+        {
+            return Ok(());
+        }
+        self.recv(&mut hdr[..], 0)?;
+        match endian::read_i32(&hdr[16..]) {
+            0 => Ok(()),
+            error => Err(NlError::from_nlmsgerr(error)),
+        }
+    }
+
+    /// Receive the datagrams of a dump until `NLMSG_DONE` and parse them on the rayon thread
+    /// pool. Receiving stays sequential, so this pays off when parsing dominates, such as for
+    /// full routing tables with many attributes per route. Messages are returned in the order
+    /// they were received and are checked against the socket PID like in `recv_nl`.
+    #[cfg(feature = "rayon")]
+    pub fn recv_dump_par<T, P>(&mut self) -> Result<Vec<Nlmsghdr<T, P>>, NlError>
+    where
+        T: Nl + NlType + Send,
+        P: Nl + Send,
+    {
+        let mut datagrams = Vec::new();
+        loop {
+            let mut buf = vec![0; self.peek_len()?];
+            let (read, _) = self.recv_checked(&mut buf)?;
+            buf.truncate(read);
+            let mut done = false;
+            for frame in DumpFrames::new(&buf) {
+                match frame {
+                    Ok(DumpFrame::Msg(_)) => (),
+                    Ok(DumpFrame::Done) => done = true,
+                    Ok(DumpFrame::Error(error)) => return Err(NlError::from_nlmsgerr(error)),
+                    Err(e) => {
+                        self.count_parse_failure();
+                        return Err(e);
+                    }
+                }
+            }
+            datagrams.push(buf);
+            if done {
+                break;
+            }
+        }
+        let msgs = match parse_dump_par::<T, P, _>(&datagrams) {
+            Ok(msgs) => msgs,
+            Err(e) => {
+                self.count_parse_failure();
+                return Err(e);
+            }
+        };
+        for msg in msgs.iter() {
+            self.check_pid(msg.nl_pid)?;
+        }
+        Ok(msgs)
+    }
+
+    /// Like `recv_nl`, but also return when the datagram holding the message was received.
+    /// Messages from the same datagram share a timestamp.
+    pub fn recv_nl_timestamped<T, P>(
+        &mut self,
+        buf_sz: Option<usize>,
+    ) -> Result<(Nlmsghdr<T, P>, RecvTimestamp), NlError>
+    where
+        T: Nl + NlType,
+        P: Nl,
+    {
+        let msg = self.recv_nl(buf_sz)?;
+        match self.timestamp {
+            Some(timestamp) => Ok((msg, timestamp)),
+            None => Err(NlError::new("No receive timestamp recorded")),
+        }
+    }
+
+    /// Receive the next datagram into storage of its exact size taken from `alloc`. The messages
+    /// can be parsed with `NlBufferIter`.
+    pub fn recv_in<A>(&self, alloc: &A) -> Result<A::Storage, NlError>
+    where
+        A: NlAlloc,
+    {
+        let mut storage = alloc.alloc(self.peek_len()?)?;
+        let (read, _) = self.recv_checked(storage.as_mut())?;
+        storage.truncate(read);
+        Ok(storage)
+    }
+
+    /// Receive a datagram into a caller-provided buffer and return an iterator over the messages
+    /// it contains. Unlike `recv_nl`, no memory is allocated for the received data, so the same
+    /// buffer can be reused for every call. Messages are not checked against the socket PID.
+    pub fn recv_into<'a, T, P>(&self, buf: &'a mut [u8]) -> Result<NlBufferIter<'a, T, P>, NlError>
+    where
+        T: Nl + NlType,
+        P: Nl,
+    {
+        let (read, _) = self.recv_checked(&mut *buf)?;
+        Ok(NlBufferIter::new(&buf[..read]))
+    }
+
+    /// Like `recv_into`, but leave the payloads of the messages in `buf` instead of parsing them.
+    /// Attributes can then be walked with `Nlmsghdr::attrs` without copying them.
+    pub fn recv_borrowed<'a, T>(&self, buf: &'a mut [u8]) -> Result<NlBorrowedIter<'a, T>, NlError>
+    where
+        T: NlType,
+    {
+        let (read, _) = self.recv_checked(&mut *buf)?;
+        Ok(NlBorrowedIter::new(&buf[..read]))
+    }
+
+    /// Size of the buffer to receive the next datagram into: `buf_sz` if given, grown to fit the
+    /// datagram if it is larger but within `recv_grow_cap`, and the exact datagram length
+    /// otherwise
+    fn recv_buf_len(&self, buf_sz: Option<usize>) -> Result<usize, NlError> {
+        match (buf_sz, self.recv_grow_cap) {
+            (None, _) => self.peek_len(),
+            (Some(sz), Some(cap)) if cap > sz => {
+                let len = self.peek_len()?;
+                Ok(if len > sz && len <= cap { len } else { sz })
+            }
+            (Some(sz), _) => Ok(sz),
+        }
+    }
+
+    /// Length of the next datagram, found without removing it from the socket. A datagram
+    /// larger than the `max_msg_size` parse limit is discarded.
+    fn peek_len(&self) -> Result<usize, NlError> {
+        // With MSG_TRUNC, the full length of the datagram is returned even if it does not fit
+        let len = self.recv(&mut [0u8; 0][..], libc::MSG_PEEK | libc::MSG_TRUNC)?;
+        if len == 0 {
+            return Err(NlError::new("No data could be read from the socket"));
+        }
+        if let Err(e) = limits::check_msg_size(len) {
+            self.recv(&mut [0u8; 0][..], 0)?;
+            self.count_parse_failure();
+            return Err(e.into());
+        }
+        Ok(len)
+    }
+
+    /// Receive a datagram, returning `NlError::Truncated` instead of silently cutting it off if
+    /// it does not fit in `buf`
+    fn recv_checked(&self, buf: &mut [u8]) -> Result<(usize, RecvTimestamp), NlError> {
+        let mut kernel = None;
+        let len = self.recvmsg(&mut *buf, libc::MSG_TRUNC, |level, ty, data| {
+            if level == libc::SOL_SOCKET
+                && ty == libc::SCM_TIMESTAMPNS
+                && data.len() >= size_of::<libc::timespec>()
+            {
+                let ts = unsafe { ptr::read_unaligned(data.as_ptr() as *const libc::timespec) };
+                kernel = Some(UNIX_EPOCH + Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32));
+            }
+        })?;
+        let timestamp = RecvTimestamp {
+            wall: kernel.unwrap_or_else(SystemTime::now),
+            from_kernel: kernel.is_some(),
+            monotonic: Instant::now(),
+        };
+        if len == 0 {
+            return Err(NlError::new("No data could be read from the socket"));
+        }
+        if len > buf.len() {
+            return Err(NlError::Truncated);
+        }
+        Ok((len, timestamp))
     }
 
     /// Consume an ACK and return an error if an ACK is not found
@@ -388,7 +2275,7 @@ impl NlSocket {
     }
 
     /// Return an iterator object
-    pub fn iter<T, P>(&mut self) -> NlMessageIter<T, P>
+    pub fn iter<T, P>(&mut self) -> NlMessageIter<'_, T, P>
     where
         T: NlType,
         P: Nl,
@@ -397,6 +2284,233 @@ impl NlSocket {
     }
 }
 
+/// Parse the messages of dump datagrams, such as those captured from a socket, on the rayon
+/// thread pool. Messages are returned in order and parsing stops at `NLMSG_DONE`. An
+/// `NLMSG_ERROR` message with a non-zero error code is returned as an error.
+#[cfg(feature = "rayon")]
+pub fn parse_dump_par<T, P, B>(datagrams: &[B]) -> Result<Vec<Nlmsghdr<T, P>>, NlError>
+where
+    T: Nl + NlType + Send,
+    P: Nl + Send,
+    B: AsRef<[u8]> + Sync,
+{
+    use rayon::prelude::*;
+
+    // Only parse up to the datagram holding NLMSG_DONE
+    let mut end = datagrams.len();
+    for (i, datagram) in datagrams.iter().enumerate() {
+        if DumpFrames::new(datagram.as_ref()).any(|f| matches!(f, Ok(DumpFrame::Done))) {
+            end = i + 1;
+            break;
+        }
+    }
+    // The workers parse in the byte order selected on this thread
+    let byte_order = endian::endian();
+    let parsed = datagrams[..end]
+        .par_iter()
+        .map(|datagram| {
+            endian::with_endian(byte_order, || {
+                let mut msgs = Vec::new();
+                for frame in DumpFrames::new(datagram.as_ref()) {
+                    match frame? {
+                        DumpFrame::Msg(msg_buf) => {
+                            msgs.push(Nlmsghdr::deserialize(&mut StreamReadBuffer::new(msg_buf))?)
+                        }
+                        DumpFrame::Done => break,
+                        DumpFrame::Error(error) => return Err(NlError::from_nlmsgerr(error)),
+                    }
+                }
+                Ok(msgs)
+            })
+        })
+        .collect::<Result<Vec<Vec<_>>, NlError>>()?;
+    Ok(parsed.into_iter().flatten().collect())
+}
+
+/// Message of a dump datagram, classified by its `nlmsg_type`
+enum DumpFrame<'a> {
+    /// Message carrying dump data
+    Msg(&'a [u8]),
+    /// `NLMSG_DONE` or an ACK terminating the dump
+    Done,
+    /// `NLMSG_ERROR` with a non-zero error code, terminating the dump
+    Error(c_int),
+}
+
+/// Iterator splitting a dump datagram into messages without parsing their payloads
+struct DumpFrames<'a> {
+    datagram: &'a [u8],
+    offset: usize,
+    /// `nlmsg_type` and `nlmsg_seq` of the last message returned
+    last: (u16, u32),
+}
+
+impl<'a> DumpFrames<'a> {
+    fn new(datagram: &'a [u8]) -> Self {
+        DumpFrames {
+            datagram,
+            offset: 0,
+            last: (0, 0),
+        }
+    }
+}
+
+impl<'a> Iterator for DumpFrames<'a> {
+    type Item = Result<DumpFrame<'a>, NlError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Header length and offsets of `nlmsg_type` and `nlmsg_seq` in `struct nlmsghdr`
+        const HDR_LEN: usize = 16;
+        const TYPE_OFFSET: usize = 4;
+        const SEQ_OFFSET: usize = 8;
+
+        if self.datagram.len().saturating_sub(self.offset) < HDR_LEN {
+            return None;
+        }
+        let rest = &self.datagram[self.offset..];
+        let msg_len = endian::read_u32(rest) as usize;
+        if msg_len < HDR_LEN || msg_len > rest.len() {
+            self.offset = self.datagram.len();
+            return Some(Err(NlError::new(
+                "Message length does not match the datagram",
+            )));
+        }
+        let msg_buf = &rest[..msg_len];
+        self.offset = self.offset.saturating_add(consts::alignto(msg_len));
+        let nl_type = endian::read_u16(&msg_buf[TYPE_OFFSET..]);
+        self.last = (nl_type, endian::read_u32(&msg_buf[SEQ_OFFSET..]));
+        if nl_type == u16::from(consts::Nlmsg::Done) {
+            Some(Ok(DumpFrame::Done))
+        } else if nl_type == u16::from(consts::Nlmsg::Error) {
+            if msg_len < HDR_LEN + size_of::<c_int>() {
+                self.offset = self.datagram.len();
+                return Some(Err(NlError::new("Error message is too short")));
+            }
+            match endian::read_i32(&msg_buf[HDR_LEN..]) {
+                // An ACK ends the dump like NLMSG_DONE
+                0 => Some(Ok(DumpFrame::Done)),
+                error => Some(Ok(DumpFrame::Error(error))),
+            }
+        } else {
+            Some(Ok(DumpFrame::Msg(msg_buf)))
+        }
+    }
+}
+
+fn setns(fd: RawFd) -> Result<(), io::Error> {
+    match unsafe { libc::setns(fd, libc::CLONE_NEWNET) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+fn timeval(timeout: Option<Duration>) -> libc::timeval {
+    // A zero timeout means waiting forever, so round tiny timeouts up to one microsecond
+    let timeout = timeout.map(|t| t.max(Duration::from_micros(1)));
+    let timeout = timeout.unwrap_or_default();
+    libc::timeval {
+        tv_sec: timeout.as_secs().min(libc::time_t::MAX as u64) as libc::time_t,
+        tv_usec: libc::suseconds_t::from(timeout.subsec_micros()),
+    }
+}
+
+fn sockaddr_nl(addr: NlAddr) -> libc::sockaddr_nl {
+    let mut nladdr = unsafe { zeroed::<libc::sockaddr_nl>() };
+    nladdr.nl_family = libc::c_int::from(AddrFamily::Netlink) as u16;
+    nladdr.nl_pid = addr.pid;
+    nladdr.nl_groups = addr.groups;
+    nladdr
+}
+
+/// Number of ACK messages in a datagram
+fn count_acks(datagram: &[u8]) -> u64 {
+    // Header length and offsets of the fields of `struct nlmsghdr` and `struct nlmsgerr`
+    const HDR_LEN: usize = 16;
+    let mut acks = 0;
+    let mut offset = 0;
+    while datagram.len().saturating_sub(offset) >= HDR_LEN + size_of::<c_int>() {
+        let len = endian::read_u32(&datagram[offset..]) as usize;
+        if len < HDR_LEN {
+            break;
+        }
+        if endian::read_u16(&datagram[offset + 4..]) == u16::from(consts::Nlmsg::Error)
+            && endian::read_i32(&datagram[offset + HDR_LEN..]) == 0
+        {
+            acks += 1;
+        }
+        offset = offset.saturating_add(consts::alignto(len));
+    }
+    acks
+}
+
+/// Datagram level operations of a netlink socket. Helpers such as `NlTransaction` and
+/// `IfIndexCache::load` are generic over this trait so that code using them can be tested with
+/// `mock::MockSocket` instead of a kernel socket.
+pub trait NlSocketOps {
+    /// Send one datagram
+    fn send_datagram(&mut self, buf: &[u8]) -> Result<(), NlError>;
+
+    /// Receive the next datagram
+    fn recv_datagram(&mut self) -> Result<Vec<u8>, NlError>;
+
+    /// Return the sequence number for the next request, or `None` if sequence numbers are not
+    /// tracked
+    fn next_seq(&mut self) -> Option<u32> {
+        None
+    }
+
+    /// Serialize and send a message, stamping it with the next sequence number if sequence
+    /// numbers are tracked. Returns the sequence number the message was sent with.
+    fn send_msg<T, P>(&mut self, mut msg: Nlmsghdr<T, P>) -> Result<Option<u32>, NlError>
+    where
+        T: Nl + NlType,
+        P: Nl,
+        Self: Sized,
+    {
+        let seq = self.next_seq();
+        if let Some(seq) = seq {
+            msg.nl_seq = seq;
+        }
+        msg.check_len()?;
+        let mut mem = StreamWriteBuffer::new_growable(Some(msg.asize()));
+        msg.serialize(&mut mem)?;
+        self.send_datagram(mem.as_ref())?;
+        Ok(seq)
+    }
+
+    /// Receive the next datagram and parse all of its messages
+    fn recv_msgs<T, P>(&mut self) -> Result<Vec<Nlmsghdr<T, P>>, NlError>
+    where
+        T: Nl + NlType,
+        P: Nl,
+        Self: Sized,
+    {
+        let datagram = self.recv_datagram()?;
+        NlBufferIter::new(&datagram).collect()
+    }
+}
+
+impl NlSocketOps for NlSocket {
+    fn send_datagram(&mut self, buf: &[u8]) -> Result<(), NlError> {
+        self.send(buf, 0)?;
+        Ok(())
+    }
+
+    fn recv_datagram(&mut self) -> Result<Vec<u8>, NlError> {
+        let mut buf = vec![0; self.peek_len()?];
+        let (read, _) = self.recv_checked(&mut buf)?;
+        buf.truncate(read);
+        Ok(buf)
+    }
+
+    fn next_seq(&mut self) -> Option<u32> {
+        self.seq.as_mut().map(|seq| {
+            *seq += 1;
+            *seq
+        })
+    }
+}
+
 impl AsRawFd for NlSocket {
     fn as_raw_fd(&self) -> RawFd {
         self.fd
@@ -404,14 +2518,37 @@ impl AsRawFd for NlSocket {
 }
 
 impl IntoRawFd for NlSocket {
-    fn into_raw_fd(self) -> RawFd {
+    fn into_raw_fd(mut self) -> RawFd {
+        // Ownership of the file descriptor passes to the caller so it must not be closed, but
+        // everything else is dropped as usual
+        self.close_on_drop = false;
         self.fd
     }
 }
 
+impl FromRawFd for NlSocket {
+    /// Wrap an existing netlink socket file descriptor. PID and sequence number checking are
+    /// turned off.
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        NlSocket {
+            fd,
+            buffer: None,
+            pool: None,
+            pid: None,
+            seq: None,
+            send_queue: Vec::new(),
+            timestamp: None,
+            stats: StatCounters::default(),
+            shutdown: None,
+            recv_grow_cap: None,
+            close_on_drop: true,
+        }
+    }
+}
+
 impl io::Read for NlSocket {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.recv(buf, 0).map(|i| i as usize)
+        self.recv(buf, 0)
     }
 }
 
@@ -419,13 +2556,16 @@ impl io::Read for NlSocket {
 pub mod tokio {
     //! Tokio-specific features for neli
     //!
-    //! This module contains a struct that wraps `NlSocket` for async IO.
+    //! This module contains a struct that wraps `NlSocket` for async IO and `NlStream`, a stream of
+    //! the messages received on a socket that handles dump framing.
     use super::*;
 
     use mio::{self, Evented};
     use tokio::prelude::{Async, AsyncRead, Stream};
     use tokio::reactor::PollEvented2;
 
+    use MAX_NL_LENGTH;
+
     /// Tokio-enabled Netlink socket struct
     pub struct NlSocket<T, P> {
         socket: PollEvented2<super::NlSocket>,
@@ -491,6 +2631,111 @@ pub mod tokio {
         }
     }
 
+    impl super::NlSocket {
+        /// Turn the socket into a stream of the messages it receives. Datagrams are split into
+        /// messages and the stream ends after `NLMSG_DONE` or an ACK, so it can be used for a
+        /// dump as well as for multicast notifications. An `NLMSG_ERROR` message with a non-zero
+        /// error code is returned as an error and also ends the stream.
+        pub fn into_stream<T, P>(mut self) -> io::Result<NlStream<T, P>>
+        where
+            T: NlType,
+            P: Nl,
+        {
+            if self.is_blocking()? {
+                self.nonblock()?;
+            }
+            Ok(NlStream {
+                socket: PollEvented2::new(self),
+                datagram: Vec::new(),
+                offset: 0,
+                done: false,
+                type_data: PhantomData,
+                payload_data: PhantomData,
+            })
+        }
+    }
+
+    /// Stream of the messages received on a socket, as returned by `NlSocket::into_stream`
+    pub struct NlStream<T, P> {
+        socket: PollEvented2<super::NlSocket>,
+        datagram: Vec<u8>,
+        offset: usize,
+        done: bool,
+        type_data: PhantomData<T>,
+        payload_data: PhantomData<P>,
+    }
+
+    impl<T, P> NlStream<T, P> {
+        /// Receive the next datagram if one is ready
+        fn poll_datagram(&mut self) -> Result<Async<()>, NlError> {
+            if let Async::NotReady = self.socket.poll_read_ready(mio::Ready::readable())? {
+                return Ok(Async::NotReady);
+            }
+            let socket = self.socket.get_ref();
+            // With MSG_TRUNC, the full length of the datagram is returned even if it does not fit
+            let len = match socket.recv(&mut [0u8; 0][..], libc::MSG_PEEK | libc::MSG_TRUNC) {
+                Ok(len) => len,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    self.socket.clear_read_ready(mio::Ready::readable())?;
+                    return Ok(Async::NotReady);
+                }
+                Err(e) => return Err(e.into()),
+            };
+            self.datagram.resize(len, 0);
+            let (read, _) = socket.recv_checked(&mut self.datagram)?;
+            self.datagram.truncate(read);
+            self.offset = 0;
+            Ok(Async::Ready(()))
+        }
+    }
+
+    impl<T, P> Stream for NlStream<T, P>
+    where
+        T: NlType,
+        P: Nl,
+    {
+        type Item = Nlmsghdr<T, P>;
+        type Error = NlError;
+
+        fn poll(&mut self) -> Result<Async<Option<Self::Item>>, Self::Error> {
+            while !self.done {
+                let mut frames = DumpFrames {
+                    datagram: &self.datagram,
+                    offset: self.offset,
+                    last: (0, 0),
+                };
+                let frame = frames.next();
+                self.offset = frames.offset;
+                match frame {
+                    Some(Ok(DumpFrame::Msg(msg_buf))) => {
+                        return match Nlmsghdr::deserialize(&mut StreamReadBuffer::new(msg_buf)) {
+                            Ok(msg) => Ok(Async::Ready(Some(msg))),
+                            Err(e) => {
+                                self.socket.get_ref().count_parse_failure();
+                                Err(e.into())
+                            }
+                        };
+                    }
+                    Some(Ok(DumpFrame::Done)) => self.done = true,
+                    Some(Ok(DumpFrame::Error(error))) => {
+                        self.done = true;
+                        return Err(NlError::from_nlmsgerr(error));
+                    }
+                    Some(Err(e)) => {
+                        self.socket.get_ref().count_parse_failure();
+                        return Err(e);
+                    }
+                    None => {
+                        if let Async::NotReady = self.poll_datagram()? {
+                            return Ok(Async::NotReady);
+                        }
+                    }
+                }
+            }
+            Ok(Async::Ready(None))
+        }
+    }
+
     impl Evented for super::NlSocket {
         fn register(
             &self,
@@ -529,10 +2774,13 @@ pub mod tokio {
 }
 
 impl Drop for NlSocket {
-    /// Closes underlying file descriptor to avoid file descriptor leaks.
+    /// Closes underlying file descriptor to avoid file descriptor leaks unless this was turned
+    /// off with `set_close_on_drop`.
     fn drop(&mut self) {
-        unsafe {
-            libc::close(self.fd);
+        if self.close_on_drop {
+            unsafe {
+                libc::close(self.fd);
+            }
         }
     }
 }
@@ -543,9 +2791,13 @@ mod test {
 
     use std::io::Read;
 
-    use consts::Nlmsg;
+    use consts::{Nlmsg, RtnlGroup};
+    use genl::getfamily_request;
+    use nl::NlEmpty;
+    use MAX_NL_LENGTH;
 
     #[test]
+    #[allow(clippy::bool_assert_comparison, clippy::unused_io_amount)]
     fn test_socket_nonblock() {
         let mut s = NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
         s.nonblock().unwrap();
@@ -563,6 +2815,778 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_mcast_membership() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
+        // The nlctrl "notify" group shares its ID with the nlctrl family
+        let id = u32::from(u16::from(GenlId::Ctrl));
+        s.add_mcast_membership(&[id]).unwrap();
+        assert!(s.list_mcast_membership().unwrap().contains(&id));
+        s.drop_mcast_membership(&[id]).unwrap();
+        assert!(!s.list_mcast_membership().unwrap().contains(&id));
+    }
+
+    #[test]
+    fn test_flush_queue() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
+        for (name, priority) in &[("nlctrl", 0), ("no_such_family", 1)] {
+            let nlhdr = getfamily_request(name, vec![NlmF::Request]);
+            s.enqueue_nl(nlhdr, *priority).unwrap();
+        }
+        assert_eq!(s.queued_len(), 2);
+        assert_eq!(s.flush_queue().unwrap(), 2);
+        assert_eq!(s.queued_len(), 0);
+
+        // The higher priority request is sent first and fails
+        let err = s.recv_nl::<Nlmsg, Vec<u8>>(None).unwrap();
+        assert_eq!(err.nl_type, Nlmsg::Error);
+        assert_eq!(err.nl_seq, 1);
+        let reply = s.recv_nl::<GenlId, Vec<u8>>(None).unwrap();
+        assert_eq!(reply.nl_type, GenlId::Ctrl);
+        assert_eq!(reply.nl_seq, 2);
+    }
+
+    #[test]
+    fn test_recv_into() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
+        let mut buf = vec![0; MAX_NL_LENGTH];
+        for _ in 0..2 {
+            let nlhdr = getfamily_request("nlctrl", vec![NlmF::Request]);
+            s.send_nl(nlhdr).unwrap();
+            let mut iter = s
+                .recv_into::<GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>>(&mut buf)
+                .unwrap();
+            let msg = iter.next().unwrap().unwrap();
+            assert_eq!(msg.nl_type, GenlId::Ctrl);
+            assert_eq!(msg.nl_payload.cmd, CtrlCmd::Newfamily);
+            assert!(iter.next().is_none());
+        }
+    }
+
+    #[test]
+    fn test_stream_parser() {
+        let msgs = vec![
+            Nlmsghdr::new(
+                None,
+                Nlmsg::Noop,
+                vec![NlmF::Request],
+                Some(1),
+                None,
+                vec![1],
+            ),
+            Nlmsghdr::new(None, Nlmsg::Done, vec![], Some(2), None, vec![]),
+        ];
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        msgs.serialize(&mut mem).unwrap();
+
+        let mut parser = NlStreamParser::<Nlmsg, Vec<u8>>::new();
+        let mut parsed = Vec::new();
+        for chunk in mem.as_ref().chunks(3) {
+            parser.push(chunk);
+            while let Some(msg) = parser.next_msg().unwrap() {
+                parsed.push(msg);
+            }
+        }
+        assert_eq!(parsed, msgs);
+        assert_eq!(parser.pending(), 0);
+
+        // A length shorter than the header cannot be framed
+        parser.push(&[4, 0, 0, 0, 0, 0, 0, 0]);
+        assert!(parser.next_msg().is_err());
+        assert_eq!(parser.pending(), 0);
+    }
+
+    #[test]
+    fn test_recv_borrowed() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
+        let mut buf = vec![0; MAX_NL_LENGTH];
+        let nlhdr = getfamily_request("nlctrl", vec![NlmF::Request]);
+        s.send_nl(nlhdr).unwrap();
+        let buf_range = buf.as_ptr_range();
+        let mut iter = s.recv_borrowed::<GenlId>(&mut buf).unwrap();
+        let msg = iter.next().unwrap().unwrap();
+        assert!(iter.next().is_none());
+        assert_eq!(msg.nl_type, GenlId::Ctrl);
+        assert_eq!(msg.nl_payload[0], u8::from(CtrlCmd::Newfamily));
+        let name = msg
+            .attrs::<CtrlAttr>(4)
+            .map(|attr| attr.unwrap())
+            .find(|attr| attr.nla_type == CtrlAttr::FamilyName)
+            .unwrap();
+        assert!(buf_range.contains(&name.payload.as_ptr()));
+        assert_eq!(name.get_payload_as::<String>().unwrap(), "nlctrl");
+        let groups = msg
+            .attrs::<CtrlAttr>(4)
+            .map(|attr| attr.unwrap())
+            .find(|attr| attr.nla_type == CtrlAttr::McastGroups)
+            .unwrap();
+        assert_eq!(groups.nested::<u16>().count(), 1);
+    }
+
+    #[test]
+    fn test_recv_nl_truncated() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
+        for buf_sz in &[Some(16), None] {
+            let nlhdr = getfamily_request("nlctrl", vec![NlmF::Request]);
+            s.send_nl(nlhdr).unwrap();
+            match s.recv_nl::<GenlId, Vec<u8>>(*buf_sz) {
+                Err(NlError::Truncated) => assert!(buf_sz.is_some()),
+                Ok(msg) => {
+                    assert!(buf_sz.is_none());
+                    assert_eq!(msg.nl_type, GenlId::Ctrl);
+                }
+                Err(e) => panic!("Unexpected error: {}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn test_recv_grow_cap() {
+        let mut s = NlSocket::builder(NlFamily::Generic)
+            .track_seq(true)
+            .recv_grow_cap(MAX_NL_LENGTH)
+            .build()
+            .unwrap();
+        for &(cap, fits) in &[(Some(MAX_NL_LENGTH), true), (Some(32), false)] {
+            s.set_recv_grow_cap(cap);
+            let nlhdr = getfamily_request("nlctrl", vec![NlmF::Request]);
+            s.send_nl(nlhdr).unwrap();
+            match s.recv_nl::<GenlId, Vec<u8>>(Some(16)) {
+                Ok(msg) => {
+                    assert!(fits);
+                    assert_eq!(msg.nl_type, GenlId::Ctrl);
+                }
+                Err(NlError::Truncated) => assert!(!fits),
+                Err(e) => panic!("Unexpected error: {}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn test_buffer_pool() {
+        let pool = BufferPool::new(1, MAX_NL_LENGTH);
+        let mut s = NlSocket::builder(NlFamily::Generic)
+            .track_seq(true)
+            .buffer_pool(pool.clone())
+            .build()
+            .unwrap();
+        for _ in 0..2 {
+            let nlhdr = getfamily_request("nlctrl", vec![NlmF::Request]);
+            s.send_nl(nlhdr).unwrap();
+            let msg = s.recv_nl::<GenlId, Vec<u8>>(None).unwrap();
+            assert_eq!(msg.nl_type, GenlId::Ctrl);
+            // The buffer is returned once the whole datagram has been parsed
+            assert_eq!(pool.len(), 1);
+        }
+
+        // Buffers larger than the configured capacity are not kept
+        let small = BufferPool::new(4, 16);
+        drop(small.take(32));
+        assert!(small.is_empty());
+        drop(small.take(8));
+        assert_eq!(small.len(), 1);
+    }
+
+    #[test]
+    fn test_raw_fd() {
+        let s = NlSocket::connect(NlFamily::Generic, None, None, false).unwrap();
+        let clone = s.try_clone().unwrap();
+        assert_ne!(clone.as_raw_fd(), s.as_raw_fd());
+
+        let fd = s.into_raw_fd();
+        // The file descriptor must still be open after into_raw_fd
+        assert!(unsafe { libc::fcntl(fd, libc::F_GETFD) } >= 0);
+        let s = unsafe { NlSocket::from_raw_fd(fd) };
+        assert!(s.is_blocking().unwrap());
+    }
+
+    #[test]
+    fn test_raw_fd_shutdown() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, false).unwrap();
+        drop(s.shutdown_handle().unwrap());
+        let event = Arc::downgrade(s.shutdown.as_ref().unwrap());
+
+        let fd = s.into_raw_fd();
+        // The eventfd is closed with the rest of the socket state
+        assert!(event.upgrade().is_none());
+        unsafe { NlSocket::from_raw_fd(fd) }.close().unwrap();
+    }
+
+    #[test]
+    fn test_close() {
+        let s = NlSocket::connect(NlFamily::Generic, None, None, false).unwrap();
+        let mut clone = s.try_clone().unwrap();
+        s.close().unwrap();
+
+        let fd = clone.as_raw_fd();
+        clone.set_close_on_drop(false);
+        drop(clone);
+        assert!(unsafe { libc::fcntl(fd, libc::F_GETFD) } >= 0);
+        unsafe { NlSocket::from_raw_fd(fd) }.close().unwrap();
+    }
+
+    #[test]
+    fn test_nl_batch() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
+        let mut batch = Vec::new();
+        for _ in 0..2 {
+            batch.push(getfamily_request("nlctrl", vec![NlmF::Request]));
+        }
+        s.send_nl_batch(batch).unwrap();
+        // The kernel answers each request in the batch separately
+        for seq in 1..3 {
+            let replies = s.recv_nl_batch::<GenlId, Vec<u8>>(None).unwrap();
+            assert_eq!(replies.len(), 1);
+            assert_eq!(replies[0].nl_seq, seq);
+        }
+    }
+
+    #[test]
+    fn test_recv_nl_timestamped() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
+        s.enable_timestamps(true).unwrap();
+        let before = SystemTime::now();
+        let nlhdr = getfamily_request("nlctrl", vec![NlmF::Request]);
+        s.send_nl(nlhdr).unwrap();
+        let (_, timestamp) = s.recv_nl_timestamped::<GenlId, Vec<u8>>(None).unwrap();
+        assert!(timestamp.wall >= before);
+        assert!(timestamp.monotonic <= Instant::now());
+    }
+
+    #[test]
+    fn test_send_recv_multiple() {
+        let s = NlSocket::connect(NlFamily::Generic, None, None, false).unwrap();
+        let mut requests = Vec::new();
+        for seq in 1..3 {
+            let attrs = vec![Nlattr::new(None, CtrlAttr::FamilyName, "nlctrl").unwrap()];
+            let genlhdr = Genlmsghdr::new(CtrlCmd::Getfamily, 2, attrs).unwrap();
+            let nlhdr = Nlmsghdr::new(
+                None,
+                GenlId::Ctrl,
+                vec![NlmF::Request],
+                Some(seq),
+                None,
+                genlhdr,
+            );
+            let mut mem = StreamWriteBuffer::new_growable(None);
+            nlhdr.serialize(&mut mem).unwrap();
+            requests.push(mem.as_ref().to_vec());
+        }
+        assert_eq!(s.send_multiple(&requests, 0).unwrap().len(), 2);
+
+        let mut bufs = vec![vec![0; MAX_NL_LENGTH]; 2];
+        let results = s.recv_multiple(&mut bufs, 0).unwrap();
+        assert_eq!(results.len(), 2);
+        for (seq, (buf, result)) in bufs.iter().zip(results).enumerate() {
+            assert!(!result.truncated);
+            let mut iter = NlBufferIter::<GenlId, Vec<u8>>::new(&buf[..result.len]);
+            assert_eq!(iter.next().unwrap().unwrap().nl_seq, seq as u32 + 1);
+        }
+    }
+
+    #[test]
+    fn test_socket_tuning() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, false).unwrap();
+        s.set_priority(6).unwrap();
+        s.set_busy_poll(0).unwrap();
+    }
+
+    #[test]
+    fn test_socket_stats() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
+        let nlhdr = getfamily_request("nlctrl", vec![NlmF::Request, NlmF::Ack]);
+        s.send_nl(nlhdr).unwrap();
+        let reply = s.recv_nl::<GenlId, Vec<u8>>(None).unwrap();
+        s.recv_ack().unwrap();
+        let stats = s.stats();
+        assert_eq!(stats.datagrams, 2);
+        assert!(stats.bytes > u64::from(reply.nl_len));
+        assert_eq!(stats.acks, 1);
+        assert_eq!(stats.parse_failures, 0);
+        assert_eq!(stats.enobufs, 0);
+    }
+
+    #[test]
+    fn test_recv_dump() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
+        let mut dump = |growth| {
+            let genlhdr =
+                Genlmsghdr::<CtrlCmd, CtrlAttr>::new(CtrlCmd::Getfamily, 2, vec![]).unwrap();
+            let nlhdr = Nlmsghdr::new(
+                None,
+                GenlId::Ctrl,
+                vec![NlmF::Request, NlmF::Dump],
+                None,
+                None,
+                genlhdr,
+            );
+            s.send_nl(nlhdr).unwrap();
+            s.recv_dump::<GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>>(growth)
+                .unwrap()
+        };
+        let all = dump(DumpGrowth::Exponential);
+        assert!(all.len() > 1);
+        assert_eq!(all.chunks().len(), 1);
+
+        let chunked = dump(DumpGrowth::Chunked(1));
+        assert_eq!(chunked.len(), all.len());
+        assert_eq!(chunked.chunks().len(), all.len());
+        assert!(chunked
+            .iter()
+            .all(|msg| msg.nl_payload.cmd == CtrlCmd::Newfamily));
+
+        let linear = dump(DumpGrowth::Linear(2));
+        assert_eq!(linear.chunks()[0].capacity(), all.len().div_ceil(2) * 2);
+        assert_eq!(linear.into_vec().len(), all.len());
+    }
+
+    #[test]
+    fn test_request() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
+        let getfamily = |name: Option<&str>, flags| {
+            let attrs = name
+                .map(|name| vec![Nlattr::new(None, CtrlAttr::FamilyName, name).unwrap()])
+                .unwrap_or_default();
+            let genlhdr =
+                Genlmsghdr::<CtrlCmd, CtrlAttr>::new(CtrlCmd::Getfamily, 2, attrs).unwrap();
+            Nlmsghdr::new(None, GenlId::Ctrl, flags, None, None, genlhdr)
+        };
+        let reply = s
+            .request::<_, _, GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>>(getfamily(
+                Some("nlctrl"),
+                vec![NlmF::Request],
+            ))
+            .unwrap();
+        assert_eq!(reply.len(), 1);
+        assert_eq!(reply[0].nl_payload.cmd, CtrlCmd::Newfamily);
+
+        let dump = s
+            .request::<_, _, GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>>(getfamily(
+                None,
+                vec![NlmF::Request, NlmF::Dump],
+            ))
+            .unwrap();
+        assert!(dump.len() > 1);
+
+        // A dump spelled as its two bits is still a dump
+        let dump = s
+            .request::<_, _, GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>>(getfamily(
+                None,
+                vec![NlmF::Request, NlmF::Root, NlmF::Match],
+            ))
+            .unwrap();
+        assert!(dump.len() > 1);
+
+        match s.request::<_, _, GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>>(getfamily(
+            Some("no_such_family"),
+            vec![NlmF::Request],
+        )) {
+            Err(NlError::Errno(libc::ENOENT)) => (),
+            res => panic!("Unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_recv_nl_keeps_seq() {
+        // Receiving the reply must not advance the sequence number, or the ACK following it
+        // would be rejected with BadSeq
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
+        for seq in 1..3 {
+            s.send_nl(getfamily_request("nlctrl", vec![NlmF::Request, NlmF::Ack]))
+                .unwrap();
+            let reply = s
+                .recv_nl::<GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>>(None)
+                .unwrap();
+            assert_eq!(reply.nl_seq, seq);
+            s.recv_ack().unwrap();
+        }
+    }
+
+    /// Socket tracking sequence numbers that sends its requests to a user space peer, which
+    /// answers with `replies` ahead of time. The peer is returned as well to keep it open.
+    fn request_peer(replies: &[&[(u16, u32, &[u8])]]) -> (NlSocket, NlSocket) {
+        let mut s = NlSocket::connect(NlFamily::Usersock, None, None, true).unwrap();
+        let peer = NlSocket::connect(NlFamily::Usersock, None, None, false).unwrap();
+        s.connect_to(peer.local_addr().unwrap()).unwrap();
+        for datagram in replies {
+            let mut buf = Vec::new();
+            for &(ty, seq, payload) in datagram.iter() {
+                buf.extend_from_slice(&(16 + payload.len() as u32).to_ne_bytes());
+                buf.extend_from_slice(&ty.to_ne_bytes());
+                buf.extend_from_slice(&(libc::NLM_F_MULTI as u16).to_ne_bytes());
+                buf.extend_from_slice(&seq.to_ne_bytes());
+                buf.extend_from_slice(&0u32.to_ne_bytes());
+                buf.extend_from_slice(payload);
+            }
+            peer.send_to(buf, 0, s.local_addr().unwrap()).unwrap();
+        }
+        (s, peer)
+    }
+
+    #[test]
+    fn test_request_bad_seq() {
+        let done = u16::from(Nlmsg::Done);
+        let (mut s, _peer) =
+            request_peer(&[&[(16, 1, &[0; 4]), (16, 2, &[0; 4]), (done, 1, &[0; 4])]]);
+        let request = Nlmsghdr::<u16, NlEmpty>::new(
+            None,
+            16,
+            vec![NlmF::Request, NlmF::Dump],
+            None,
+            None,
+            NlEmpty,
+        );
+        match s.request::<_, _, u16, Vec<u8>>(request) {
+            Err(NlError::BadSeq) => (),
+            res => panic!("Unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_request_dump_ack() {
+        let (done, error) = (u16::from(Nlmsg::Done), u16::from(Nlmsg::Error));
+        let ack = [0; 20];
+        let (mut s, _peer) = request_peer(&[
+            &[(16, 1, &[1, 2, 3, 4]), (done, 1, &[0; 4])],
+            &[(error, 1, &ack)],
+            &[(17, 2, &[0; 4])],
+        ]);
+        let request = Nlmsghdr::<u16, NlEmpty>::new(
+            None,
+            16,
+            vec![NlmF::Request, NlmF::Dump, NlmF::Ack],
+            None,
+            None,
+            NlEmpty,
+        );
+        let msgs = s.request::<_, _, u16, Vec<u8>>(request).unwrap();
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].nl_payload, vec![1, 2, 3, 4]);
+        assert_eq!(s.stats().acks, 1);
+
+        // The ACK following NLMSG_DONE has been consumed
+        let mut buf = [0u8; 20];
+        s.recv(&mut buf[..], 0).unwrap();
+        assert_eq!(u16::from_ne_bytes([buf[4], buf[5]]), 17);
+    }
+
+    #[cfg(feature = "stream")]
+    #[test]
+    fn test_into_stream() {
+        use tokio::prelude::Stream;
+
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
+        let genlhdr = Genlmsghdr::<CtrlCmd, CtrlAttr>::new(CtrlCmd::Getfamily, 2, vec![]).unwrap();
+        let nlhdr = Nlmsghdr::new(
+            None,
+            GenlId::Ctrl,
+            vec![NlmF::Request, NlmF::Dump],
+            None,
+            None,
+            genlhdr,
+        );
+        s.send_nl(nlhdr).unwrap();
+        let stream = s
+            .into_stream::<GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>>()
+            .unwrap();
+        let msgs = ::tokio::runtime::current_thread::block_on_all(stream.collect()).unwrap();
+        assert!(msgs.len() > 1);
+        assert!(msgs
+            .iter()
+            .all(|msg| msg.nl_payload.cmd == CtrlCmd::Newfamily));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_recv_dump_par() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
+        let send_dump = |s: &mut NlSocket| {
+            let genlhdr =
+                Genlmsghdr::<CtrlCmd, CtrlAttr>::new(CtrlCmd::Getfamily, 2, vec![]).unwrap();
+            let nlhdr = Nlmsghdr::new(
+                None,
+                GenlId::Ctrl,
+                vec![NlmF::Request, NlmF::Dump],
+                None,
+                None,
+                genlhdr,
+            );
+            s.send_nl(nlhdr).unwrap();
+        };
+        send_dump(&mut s);
+        let seq = s
+            .recv_dump::<GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>>(DumpGrowth::Exponential)
+            .unwrap();
+        send_dump(&mut s);
+        let par = s
+            .recv_dump_par::<GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>>()
+            .unwrap();
+        assert_eq!(par.len(), seq.len());
+        for (a, b) in par.iter().zip(seq.iter()) {
+            assert_eq!(a.nl_len, b.nl_len);
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_parse_dump_par_endian() {
+        use consts::Rtm;
+        use endian::Endian;
+        use storage::HeapAlloc;
+
+        let other = match Endian::NATIVE {
+            Endian::Little => Endian::Big,
+            Endian::Big => Endian::Little,
+        };
+        let msg = Nlmsghdr::new(
+            None,
+            Rtm::Newlink,
+            vec![NlmF::Multi],
+            Some(1),
+            None,
+            vec![1, 2, 3, 4],
+        );
+        let done = Nlmsghdr::new(None, Nlmsg::Done, vec![NlmF::Multi], Some(1), None, 0i32);
+        let datagrams = endian::with_endian(other, || {
+            vec![
+                serialize_in(&msg, &HeapAlloc).unwrap(),
+                serialize_in(&done, &HeapAlloc).unwrap(),
+            ]
+        });
+        let parsed = endian::with_endian(other, || {
+            parse_dump_par::<Rtm, Vec<u8>, _>(&datagrams).unwrap()
+        });
+        assert_eq!(parsed, vec![msg]);
+    }
+
+    #[test]
+    fn test_connect_in_netns() {
+        let netns = File::open("/proc/self/ns/net").unwrap();
+        let mut s =
+            NlSocket::connect_in_netns(netns.as_raw_fd(), NlFamily::Generic, None, None, true)
+                .unwrap();
+        assert_eq!(
+            s.resolve_genl_family("nlctrl").unwrap(),
+            u16::from(GenlId::Ctrl)
+        );
+        assert!(NlSocket::connect_in_netns(
+            Path::new("/nonexistent"),
+            NlFamily::Generic,
+            None,
+            None,
+            true
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_socket_builder() {
+        let mut s = NlSocket::builder(NlFamily::Route)
+            .groups(&[libc::RTNLGRP_LINK, libc::RTNLGRP_BRVLAN])
+            .recv_buffer(1 << 16)
+            .ext_ack(true)
+            .strict_checking(true)
+            .recv_timeout(Duration::from_millis(10))
+            .build()
+            .unwrap();
+        let groups = s.list_mcast_membership().unwrap();
+        assert!(groups.contains(&libc::RTNLGRP_LINK));
+        assert!(groups.contains(&libc::RTNLGRP_BRVLAN));
+        assert!(s.is_blocking().unwrap());
+
+        // The receive timeout also applies while waiting for the shutdown handle
+        s.shutdown_handle().unwrap();
+        let err = s.recv(&mut [0; 64][..], 0).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+
+        let s = NlSocket::builder(NlFamily::Generic)
+            .nonblocking(true)
+            .build()
+            .unwrap();
+        assert!(!s.is_blocking().unwrap());
+    }
+
+    #[test]
+    fn test_send_recv_in() {
+        use storage::HeapAlloc;
+
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
+        let nlhdr = getfamily_request("nlctrl", vec![NlmF::Request]);
+        s.send_nl_in(nlhdr, &HeapAlloc).unwrap();
+        let storage = s.recv_in(&HeapAlloc).unwrap();
+        let msgs = NlBufferIter::<GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>>::new(&storage)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].nl_len as usize, storage.len());
+    }
+
+    #[test]
+    fn test_shutdown_handle() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, false).unwrap();
+        let handle = s.shutdown_handle().unwrap();
+        let listener = std::thread::spawn(move || s.recv(&mut [0; 64][..], 0));
+        std::thread::sleep(Duration::from_millis(50));
+        handle.shutdown().unwrap();
+        let err = listener.join().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionAborted);
+    }
+
+    #[test]
+    fn test_attach_filter() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, false).unwrap();
+        s.nonblock().unwrap();
+        // ret #0: drop every datagram
+        let drop_all = [libc::sock_filter {
+            code: (libc::BPF_RET | libc::BPF_K) as u16,
+            jt: 0,
+            jf: 0,
+            k: 0,
+        }];
+        s.attach_filter(&drop_all).unwrap();
+        s.send_nl(getfamily_request("nlctrl", vec![NlmF::Request]))
+            .unwrap();
+        let mut buf = [0; 4096];
+        match s.recv(&mut buf, 0) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => (),
+            res => panic!("Datagram was not filtered: {:?}", res),
+        }
+
+        s.detach_filter().unwrap();
+        s.send_nl(getfamily_request("nlctrl", vec![NlmF::Request]))
+            .unwrap();
+        assert!(s.recv(&mut buf, 0).unwrap() > 0);
+    }
+
+    #[test]
+    fn test_recv_nl_from() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, false).unwrap();
+        let nlhdr = getfamily_request("nlctrl", vec![NlmF::Request]);
+        s.send_nl(nlhdr).unwrap();
+        let (msgs, addr) = s.recv_nl_from::<GenlId, Vec<u8>>(None).unwrap();
+        assert_eq!(msgs.len(), 1);
+        // Unicast reply from the kernel
+        assert_eq!(addr, NlAddr { pid: 0, groups: 0 });
+    }
+
+    #[test]
+    fn test_send_to_peer() {
+        // PIDs that are not used by the kernel when assigning PIDs on its own
+        let pid = std::process::id() | 0x4000_0000;
+        let mut a = NlSocket::connect(NlFamily::Usersock, Some(pid), None, false).unwrap();
+        let mut b = NlSocket::connect(NlFamily::Usersock, Some(pid + 1), None, false).unwrap();
+        let msg = Nlmsghdr::new(
+            None,
+            consts::Nlmsg::Noop,
+            vec![NlmF::Request],
+            Some(1),
+            Some(pid),
+            vec![1u8, 2, 3, 4],
+        );
+        a.send_nl_to(
+            msg,
+            NlAddr {
+                pid: pid + 1,
+                groups: 0,
+            },
+        )
+        .unwrap();
+        let (msgs, addr) = b.recv_nl_from::<consts::Nlmsg, Vec<u8>>(None).unwrap();
+        assert_eq!(addr, NlAddr { pid, groups: 0 });
+        assert_eq!(msgs[0].nl_payload, vec![1, 2, 3, 4]);
+
+        b.connect_to(addr).unwrap();
+        b.send(&[0u8; 16][..], 0).unwrap();
+        let (_, addr) = a.recv_from(&mut [0u8; 16][..], 0).unwrap();
+        assert_eq!(
+            addr,
+            NlAddr {
+                pid: pid + 1,
+                groups: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_recv_with_creds() {
+        let pid = std::process::id() | 0x6000_0000;
+        let a = NlSocket::connect(NlFamily::Usersock, Some(pid), None, false).unwrap();
+        let mut b = NlSocket::connect(NlFamily::Usersock, Some(pid + 1), None, false).unwrap();
+        b.enable_passcred(true).unwrap();
+        let addr = NlAddr {
+            pid: pid + 1,
+            groups: 0,
+        };
+        a.send_to(&[0u8; 16][..], 0, addr).unwrap();
+        let (read, creds) = b.recv_with_creds(&mut [0u8; 16][..], 0).unwrap();
+        assert_eq!(read, 16);
+        assert_eq!(
+            creds,
+            Some(NlCredentials {
+                pid: std::process::id() as i32,
+                uid: unsafe { libc::getuid() },
+                gid: unsafe { libc::getgid() },
+            })
+        );
+    }
+
+    #[test]
+    fn test_bind_to() {
+        let mut s = NlSocket::new(NlFamily::Route, false).unwrap();
+        s.bind_to(0, &[]).unwrap();
+        assert_ne!(s.local_addr().unwrap().pid, 0);
+
+        let pid = std::process::id() | 0x5000_0000;
+        let mut s = NlSocket::new(NlFamily::Route, false).unwrap();
+        s.bind_to(pid, &[libc::RTNLGRP_LINK, libc::RTNLGRP_BRVLAN])
+            .unwrap();
+        assert_eq!(
+            s.local_addr().unwrap(),
+            NlAddr {
+                pid,
+                groups: 1 << (libc::RTNLGRP_LINK - 1),
+            }
+        );
+        let groups = s.list_mcast_membership().unwrap();
+        assert!(groups.contains(&libc::RTNLGRP_LINK));
+        assert!(groups.contains(&libc::RTNLGRP_BRVLAN));
+    }
+
+    #[test]
+    fn test_bind_to_rtnl_group() {
+        let mut s = NlSocket::new(NlFamily::Route, false).unwrap();
+        // Group 0 is rejected without binding the socket
+        let err = s.bind_to(0, &[RtnlGroup::Link.group(), 0]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(s.local_addr().unwrap().pid, 0);
+        s.bind_to(0, &[RtnlGroup::Link.group(), RtnlGroup::Brvlan.group()])
+            .unwrap();
+        // Groups beyond the legacy bitmask are only visible in the membership list
+        assert_eq!(
+            s.local_addr().unwrap().groups,
+            RtnlGroup::Link.bitmask().unwrap()
+        );
+        assert_eq!(
+            RtnlGroup::from_bitmask(s.local_addr().unwrap().groups),
+            vec![RtnlGroup::Link]
+        );
+        let groups = s.list_mcast_membership().unwrap();
+        assert!(groups.contains(&RtnlGroup::Brvlan.group()));
+    }
+
+    #[test]
+    fn test_recv_with_nsid() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
+        s.listen_all_nsid(true).unwrap();
+        let nlhdr = getfamily_request("nlctrl", vec![NlmF::Request]);
+        s.send_nl(nlhdr).unwrap();
+        let mut buf = vec![0; MAX_NL_LENGTH];
+        let (read, nsid) = s.recv_with_nsid(&mut buf, 0).unwrap();
+        assert!(read > 0);
+        // Replies from the kernel in our own namespace carry no nsid
+        assert_eq!(nsid, None);
+    }
+
     #[test]
     fn multi_msg_iter() {
         let mut vec = vec![];
@@ -606,9 +3630,19 @@ mod test {
 
         let mut s = NlSocket {
             fd: -1,
-            buffer: Some(StreamReadBuffer::new(vec)),
+            buffer: Some(StreamReadBuffer::new(PooledBuf {
+                buf: vec,
+                pool: None,
+            })),
+            pool: None,
             seq: None,
             pid: None,
+            send_queue: Vec::new(),
+            timestamp: None,
+            stats: StatCounters::default(),
+            shutdown: None,
+            recv_grow_cap: None,
+            close_on_drop: true,
         };
         let mut iter = s.iter();
         if let Some(Ok(nl_next)) = iter.next() {
@@ -0,0 +1,474 @@
+//! This module abstracts the memory that serialized messages are stored in so that programs can
+//! use their own allocation strategy, such as arena allocators or shared memory segments,
+//! instead of the heap.
+//!
+//! An `NlAlloc` hands out `NlStorage` buffers of a requested length. `serialize_in` serializes
+//! any `Nl` type into such a buffer and `NlSocket::send_nl_in` and `NlSocket::recv_in` send and
+//! receive through them. Received storage can be parsed with `NlBufferIter` or any `Nl`
+//! implementation through a `SliceBuffer`.
+//!
+//! `Nl` reads from any `Buffer` and writes to any `BufferMut`. Both are implemented for the
+//! stream buffers of the `buffering` crate and for `SliceBuffer` and `SliceBufferMut`, which
+//! wrap plain byte slices. `serialize_into` and `deserialize_from` use the latter to work on
+//! arrays on the stack without any buffer type in the way.
+//! `to_bytes_in` reuses the capacity of a vector across messages, which `NlSocket::send_nl_buf`
+//! does for sending, so that programs sending many requests do not allocate for each of them.
+//!
+//! # Design decisions
+//!
+//! Serialization into storage goes through a `SliceBufferMut`, so writing past the end of the
+//! storage fails instead of growing it. Allocating exactly `Nl::asize` bytes up front means the
+//! storage never needs to grow while a message is written.
+//!
+//! `Buffer` and `BufferMut` carry the size hints and rewinding that `Nl` implementations rely on,
+//! which is why they are not implemented for `&[u8]` and `&mut [u8]` directly.
+
+use std::io::{self, Read, Write};
+use std::mem;
+
+use buffering::{StreamReadBuffer, StreamWriteBuffer};
+
+use err::{DeError, SerError};
+use Nl;
+
+/// Source that `Nl::deserialize` reads from
+pub trait Buffer: Read + AsRef<[u8]> {
+    /// Set the number of bytes the next value to be read takes up
+    fn set_size_hint(&mut self, size_hint: usize);
+
+    /// Remove the size hint and return it
+    fn take_size_hint(&mut self) -> Option<usize>;
+
+    /// Return the size hint without removing it
+    fn peek_size_hint(&self) -> Option<usize>;
+
+    /// Return `true` if a size hint is present
+    fn has_size_hint(&self) -> bool {
+        self.peek_size_hint().is_some()
+    }
+
+    /// Return `true` if every byte of the buffer has been read
+    fn at_end(&self) -> bool;
+
+    /// Move back to the position before the last read
+    fn rewind(&mut self);
+
+    /// Skip the rest of the buffer
+    fn set_at_end(&mut self);
+}
+
+/// Destination that `Nl::serialize` writes to. `AsRef` returns the bytes written so far.
+pub trait BufferMut: Write + AsRef<[u8]> {
+    /// Set the number of bytes the next value to be written takes up
+    fn set_size_hint(&mut self, size_hint: usize);
+
+    /// Remove the size hint and return it
+    fn take_size_hint(&mut self) -> Option<usize>;
+
+    /// Return the size hint without removing it
+    fn peek_size_hint(&self) -> Option<usize>;
+
+    /// Return `true` if a size hint is present
+    fn has_size_hint(&self) -> bool {
+        self.peek_size_hint().is_some()
+    }
+
+    /// Move to `position` bytes from the start of the buffer
+    fn set_position(&mut self, position: u64);
+}
+
+impl<T> Buffer for StreamReadBuffer<T>
+where
+    T: AsRef<[u8]>,
+{
+    fn set_size_hint(&mut self, size_hint: usize) {
+        StreamReadBuffer::set_size_hint(self, size_hint)
+    }
+
+    fn take_size_hint(&mut self) -> Option<usize> {
+        StreamReadBuffer::take_size_hint(self)
+    }
+
+    fn peek_size_hint(&self) -> Option<usize> {
+        StreamReadBuffer::peek_size_hint(self)
+    }
+
+    fn at_end(&self) -> bool {
+        StreamReadBuffer::at_end(self)
+    }
+
+    fn rewind(&mut self) {
+        StreamReadBuffer::rewind(self)
+    }
+
+    fn set_at_end(&mut self) {
+        StreamReadBuffer::set_at_end(self)
+    }
+}
+
+impl<'a> BufferMut for StreamWriteBuffer<'a> {
+    fn set_size_hint(&mut self, size_hint: usize) {
+        StreamWriteBuffer::set_size_hint(self, size_hint)
+    }
+
+    fn take_size_hint(&mut self) -> Option<usize> {
+        StreamWriteBuffer::take_size_hint(self)
+    }
+
+    fn peek_size_hint(&self) -> Option<usize> {
+        StreamWriteBuffer::peek_size_hint(self)
+    }
+
+    fn set_position(&mut self, position: u64) {
+        StreamWriteBuffer::set_position(self, position)
+    }
+}
+
+impl<B> Buffer for &mut B
+where
+    B: Buffer + ?Sized,
+{
+    fn set_size_hint(&mut self, size_hint: usize) {
+        (**self).set_size_hint(size_hint)
+    }
+
+    fn take_size_hint(&mut self) -> Option<usize> {
+        (**self).take_size_hint()
+    }
+
+    fn peek_size_hint(&self) -> Option<usize> {
+        (**self).peek_size_hint()
+    }
+
+    fn at_end(&self) -> bool {
+        (**self).at_end()
+    }
+
+    fn rewind(&mut self) {
+        (**self).rewind()
+    }
+
+    fn set_at_end(&mut self) {
+        (**self).set_at_end()
+    }
+}
+
+impl<B> BufferMut for &mut B
+where
+    B: BufferMut + ?Sized,
+{
+    fn set_size_hint(&mut self, size_hint: usize) {
+        (**self).set_size_hint(size_hint)
+    }
+
+    fn take_size_hint(&mut self) -> Option<usize> {
+        (**self).take_size_hint()
+    }
+
+    fn peek_size_hint(&self) -> Option<usize> {
+        (**self).peek_size_hint()
+    }
+
+    fn set_position(&mut self, position: u64) {
+        (**self).set_position(position)
+    }
+}
+
+/// `Buffer` reading from a byte slice, such as an array on the stack
+#[derive(Debug)]
+pub struct SliceBuffer<'a> {
+    buf: &'a [u8],
+    position: usize,
+    rewind_position: usize,
+    size_hint: Option<usize>,
+}
+
+impl<'a> SliceBuffer<'a> {
+    /// Read from the start of `buf`
+    pub fn new(buf: &'a [u8]) -> Self {
+        SliceBuffer {
+            buf,
+            position: 0,
+            rewind_position: 0,
+            size_hint: None,
+        }
+    }
+
+    /// Number of bytes read so far
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl<'a> Read for SliceBuffer<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.buf.len() - self.position);
+        buf[..len].copy_from_slice(&self.buf[self.position..self.position + len]);
+        self.rewind_position = self.position;
+        self.position += len;
+        Ok(len)
+    }
+}
+
+impl<'a> AsRef<[u8]> for SliceBuffer<'a> {
+    fn as_ref(&self) -> &[u8] {
+        self.buf
+    }
+}
+
+impl<'a> Buffer for SliceBuffer<'a> {
+    fn set_size_hint(&mut self, size_hint: usize) {
+        self.size_hint = Some(size_hint);
+    }
+
+    fn take_size_hint(&mut self) -> Option<usize> {
+        self.size_hint.take()
+    }
+
+    fn peek_size_hint(&self) -> Option<usize> {
+        self.size_hint
+    }
+
+    fn at_end(&self) -> bool {
+        self.position == self.buf.len()
+    }
+
+    fn rewind(&mut self) {
+        self.position = self.rewind_position;
+    }
+
+    fn set_at_end(&mut self) {
+        self.position = self.buf.len();
+    }
+}
+
+/// `BufferMut` writing into a byte slice, such as an array on the stack. Writing past the end
+/// of the slice fails instead of allocating.
+#[derive(Debug)]
+pub struct SliceBufferMut<'a> {
+    buf: &'a mut [u8],
+    position: usize,
+    size_hint: Option<usize>,
+}
+
+impl<'a> SliceBufferMut<'a> {
+    /// Write from the start of `buf`
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        SliceBufferMut {
+            buf,
+            position: 0,
+            size_hint: None,
+        }
+    }
+
+    /// Number of bytes written so far
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl<'a> Write for SliceBufferMut<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.buf.len() - self.position);
+        self.buf[self.position..self.position + len].copy_from_slice(&buf[..len]);
+        self.position += len;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> AsRef<[u8]> for SliceBufferMut<'a> {
+    fn as_ref(&self) -> &[u8] {
+        &self.buf[..self.position]
+    }
+}
+
+impl<'a> BufferMut for SliceBufferMut<'a> {
+    fn set_size_hint(&mut self, size_hint: usize) {
+        self.size_hint = Some(size_hint);
+    }
+
+    fn take_size_hint(&mut self) -> Option<usize> {
+        self.size_hint.take()
+    }
+
+    fn peek_size_hint(&self) -> Option<usize> {
+        self.size_hint
+    }
+
+    fn set_position(&mut self, position: u64) {
+        self.position = (position as usize).min(self.buf.len());
+    }
+}
+
+/// Memory holding a serialized message or a received datagram
+pub trait NlStorage: AsRef<[u8]> + AsMut<[u8]> {
+    /// Shorten the storage to `len` bytes. Has no effect if `len` is not smaller than the
+    /// current length.
+    fn truncate(&mut self, len: usize);
+}
+
+/// Source of `NlStorage` buffers
+pub trait NlAlloc {
+    /// Type of the buffers handed out
+    type Storage: NlStorage;
+
+    /// Return storage of exactly `len` bytes, or an error if the memory is exhausted
+    fn alloc(&self, len: usize) -> Result<Self::Storage, io::Error>;
+}
+
+impl NlStorage for Vec<u8> {
+    fn truncate(&mut self, len: usize) {
+        Vec::truncate(self, len)
+    }
+}
+
+impl NlStorage for &mut [u8] {
+    fn truncate(&mut self, len: usize) {
+        if len < self.len() {
+            let storage = mem::take(self);
+            *self = &mut storage[..len];
+        }
+    }
+}
+
+/// Allocator handing out heap allocated vectors
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HeapAlloc;
+
+impl NlAlloc for HeapAlloc {
+    type Storage = Vec<u8>;
+
+    fn alloc(&self, len: usize) -> Result<Vec<u8>, io::Error> {
+        Ok(vec![0; len])
+    }
+}
+
+/// Serialize `msg` into storage taken from `alloc`
+pub fn serialize_in<T, A>(msg: &T, alloc: &A) -> Result<A::Storage, SerError>
+where
+    T: Nl,
+    A: NlAlloc,
+{
+    let mut storage = alloc.alloc(msg.asize())?;
+    let len = {
+        let mut mem = SliceBufferMut::new(storage.as_mut());
+        msg.serialize(&mut mem)?;
+        mem.position()
+    };
+    storage.truncate(len);
+    Ok(storage)
+}
+
+/// Serialize `msg` into the start of `buf` and return the number of bytes written
+pub fn serialize_into<T>(msg: &T, buf: &mut [u8]) -> Result<usize, SerError>
+where
+    T: Nl,
+{
+    if buf.len() < msg.asize() {
+        return Err(SerError::new(format!(
+            "Buffer of {} bytes is too small for a message of {} bytes",
+            buf.len(),
+            msg.asize()
+        )));
+    }
+    let mut mem = SliceBufferMut::new(&mut buf[..msg.asize()]);
+    msg.serialize(&mut mem)?;
+    Ok(mem.position())
+}
+
+/// Serialize `msg` into `buf`, replacing its contents but keeping its capacity, so that one
+/// buffer can be reused for many messages without allocating for each of them. `buf` is left
+/// empty if serialization fails.
+pub fn to_bytes_in<T>(msg: &T, buf: &mut Vec<u8>) -> Result<(), SerError>
+where
+    T: Nl,
+{
+    buf.clear();
+    buf.resize(msg.asize(), 0);
+    match serialize_into(msg, buf) {
+        Ok(len) => {
+            buf.truncate(len);
+            Ok(())
+        }
+        Err(e) => {
+            buf.clear();
+            Err(e)
+        }
+    }
+}
+
+/// Deserialize a `T` from the start of `buf`
+pub fn deserialize_from<T>(buf: &[u8]) -> Result<T, DeError>
+where
+    T: Nl,
+{
+    T::deserialize(&mut SliceBuffer::new(buf))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use consts::{NlmF, Rtm};
+    use nl::Nlmsghdr;
+
+    #[test]
+    fn test_slice_storage() {
+        let msg = Nlmsghdr::new(None, Rtm::Getlink, vec![NlmF::Request], None, None, vec![1]);
+        let mut buf = [0; 64];
+        let len = serialize_into(&msg, &mut buf).unwrap();
+        assert_eq!(len, 20);
+        assert_eq!(
+            deserialize_from::<Nlmsghdr<Rtm, Vec<u8>>>(&buf[..len]).unwrap(),
+            msg
+        );
+        assert!(serialize_into(&msg, &mut buf[..16]).is_err());
+
+        let mut storage = &mut buf[..];
+        storage.truncate(len);
+        assert_eq!(storage.len(), len);
+    }
+
+    #[test]
+    fn test_slice_buffers() {
+        let mut buf = [0; 6];
+        let mut mem = SliceBufferMut::new(&mut buf);
+        1u32.serialize(&mut mem).unwrap();
+        assert_eq!(mem.as_ref(), &1u32.to_ne_bytes());
+        assert!(2u32.serialize(&mut mem).is_err());
+
+        let mut mem = SliceBuffer::new(&buf);
+        assert_eq!(u32::deserialize(&mut mem).unwrap(), 1);
+        mem.rewind();
+        assert_eq!(
+            u16::deserialize(&mut mem).unwrap(),
+            u16::from_ne_bytes([buf[0], buf[1]])
+        );
+        mem.set_at_end();
+        assert!(mem.at_end());
+        assert!(u8::deserialize(&mut mem).is_err());
+    }
+
+    #[test]
+    fn test_to_bytes_in() {
+        let mut buf = Vec::new();
+        let msg = Nlmsghdr::new(None, Rtm::Getlink, vec![], None, None, vec![1u8; 32]);
+        to_bytes_in(&msg, &mut buf).unwrap();
+        assert_eq!(buf.len(), 48);
+        let ptr = buf.as_ptr();
+
+        let msg = Nlmsghdr::new(None, Rtm::Getlink, vec![NlmF::Request], None, None, vec![1]);
+        to_bytes_in(&msg, &mut buf).unwrap();
+        assert_eq!(buf.len(), 20);
+        // The allocation of the first message is reused
+        assert_eq!(buf.as_ptr(), ptr);
+        assert_eq!(
+            deserialize_from::<Nlmsghdr<Rtm, Vec<u8>>>(&buf).unwrap(),
+            msg
+        );
+    }
+}
@@ -0,0 +1,470 @@
+//! Per-task and per-cgroup statistics of the taskstats generic netlink family
+//!
+//! `NlSocket::taskstats_pid` and `NlSocket::taskstats_tgid` request the delay accounting, CPU,
+//! memory and I/O statistics of a thread or of all threads of a process as a `Taskstats`, and
+//! `NlSocket::cgroupstats` counts the tasks of a cgroup by state as a `Cgroupstats`.
+//!
+//! ```no_run
+//! use neli::consts::NlFamily;
+//! use neli::genl::GenlFamilyCache;
+//! use neli::socket::NlSocket;
+//!
+//! let mut socket = NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
+//! let mut cache = GenlFamilyCache::new();
+//! let stats = socket.taskstats_tgid(&mut cache, std::process::id()).unwrap();
+//! println!("{} waited {} ns for a CPU", stats.comm(), stats.cpu_delay_total);
+//! ```
+//!
+//! # Design decisions
+//!
+//! `struct taskstats` only ever grows at its end, with `version` telling which fields the kernel
+//! filled in. Deserializing accepts a struct of any version: the fields missing from an older
+//! kernel are zero and the fields added by a newer kernel than `neli` knows of are skipped.
+//! Serializing always writes the layout of `TASKSTATS_VERSION`.
+
+use std::os::unix::io::AsRawFd;
+
+use consts::taskstats::{
+    CgroupstatsCmdAttr, CgroupstatsType, TaskstatsCmd, TaskstatsCmdAttr, TaskstatsFamily,
+    TaskstatsType, TS_COMM_LEN,
+};
+use consts::NlmF;
+use err::{DeError, NlError, SerError};
+use genl::{GenlFamily, GenlFamilyCache, Genlmsghdr};
+use nl::Nlmsghdr;
+use nlattr::Nlattr;
+use socket::NlSocket;
+use value::{NlValue, ToNlValue};
+use {Buffer, BufferMut, Nl, Padding, StreamReadBuffer};
+
+/// Length of `struct taskstats` of `TASKSTATS_VERSION`
+const TASKSTATS_LEN: usize = 416;
+
+macro_rules! impl_taskstats {
+    ( $( $(#[$doc:meta])* $field:ident: $ty:ty, $pad:expr );* ) => {
+        /// `struct taskstats`, the statistics of a task or of a thread group
+        ///
+        /// Times are given in nanoseconds for delays and in microseconds otherwise, and memory
+        /// usage integrals in MB-microseconds.
+        #[derive(Clone, Debug, Default, PartialEq)]
+        #[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+        pub struct Taskstats {
+            $(
+                $(#[$doc])*
+                pub $field: $ty,
+            )*
+        }
+
+        impl Nl for Taskstats {
+            fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+            where
+                B: BufferMut,
+            {
+                $(
+                    self.$field.serialize(mem)?;
+                    Padding::<{ $pad }>.serialize(mem)?;
+                )*
+                Ok(())
+            }
+
+            fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+            where
+                B: Buffer,
+            {
+                let size_hint = mem.take_size_hint().ok_or_else(|| {
+                    DeError::new("Must provide size hint to deserialize Taskstats")
+                })?;
+                let mut buf = vec![0; size_hint.max(TASKSTATS_LEN)];
+                mem.read_exact(&mut buf[..size_hint])?;
+                let mut mem = StreamReadBuffer::new(&buf);
+                Ok(Taskstats {
+                    $(
+                        $field: {
+                            let field = <$ty>::deserialize(&mut mem)?;
+                            Padding::<{ $pad }>::deserialize(&mut mem)?;
+                            field
+                        },
+                    )*
+                })
+            }
+
+            fn size(&self) -> usize {
+                TASKSTATS_LEN
+            }
+
+            fn type_size() -> Option<usize> {
+                Some(TASKSTATS_LEN)
+            }
+        }
+
+        impl ToNlValue for Taskstats {
+            fn to_nl_value(&self) -> NlValue {
+                NlValue::map(vec![
+                    $( (stringify!($field), self.$field.to_nl_value()), )*
+                ])
+            }
+        }
+    };
+}
+
+impl_taskstats!(
+    /// Version of the struct filled in by the kernel
+    version: u16, 2;
+    /// Exit status
+    ac_exitcode: u32, 0;
+    /// Accounting flags, as defined in `linux/acct.h`
+    ac_flag: u8, 0;
+    /// Nice value
+    ac_nice: u8, 6;
+    /// Number of delays waiting for a CPU while runnable
+    cpu_count: u64, 0;
+    /// Total delay waiting for a CPU while runnable
+    cpu_delay_total: u64, 0;
+    /// Number of delays waiting for synchronous block I/O
+    blkio_count: u64, 0;
+    /// Total delay waiting for synchronous block I/O
+    blkio_delay_total: u64, 0;
+    /// Number of delays waiting for pages to be swapped in
+    swapin_count: u64, 0;
+    /// Total delay waiting for pages to be swapped in
+    swapin_delay_total: u64, 0;
+    /// Wall clock time running on a CPU
+    cpu_run_real_total: u64, 0;
+    /// Virtual time running on a CPU
+    cpu_run_virtual_total: u64, 0;
+    /// Command name, padded with NUL bytes
+    ac_comm: [u8; TS_COMM_LEN], 0;
+    /// Scheduling discipline
+    ac_sched: u8, 7;
+    /// User ID
+    ac_uid: u32, 0;
+    /// Group ID
+    ac_gid: u32, 0;
+    /// Process ID
+    ac_pid: u32, 0;
+    /// Parent process ID
+    ac_ppid: u32, 0;
+    /// Start time in seconds since the epoch, truncated to 32 bits
+    ac_btime: u32, 4;
+    /// Elapsed time
+    ac_etime: u64, 0;
+    /// User CPU time
+    ac_utime: u64, 0;
+    /// System CPU time
+    ac_stime: u64, 0;
+    /// Minor page faults
+    ac_minflt: u64, 0;
+    /// Major page faults
+    ac_majflt: u64, 0;
+    /// Integral of the RSS usage
+    coremem: u64, 0;
+    /// Integral of the virtual memory usage
+    virtmem: u64, 0;
+    /// High watermark of the RSS usage in KB
+    hiwater_rss: u64, 0;
+    /// High watermark of the virtual memory usage in KB
+    hiwater_vm: u64, 0;
+    /// Bytes read
+    read_char: u64, 0;
+    /// Bytes written
+    write_char: u64, 0;
+    /// Read system calls
+    read_syscalls: u64, 0;
+    /// Write system calls
+    write_syscalls: u64, 0;
+    /// Bytes read from storage
+    read_bytes: u64, 0;
+    /// Bytes written to storage
+    write_bytes: u64, 0;
+    /// Bytes whose writing to storage was cancelled
+    cancelled_write_bytes: u64, 0;
+    /// Voluntary context switches
+    nvcsw: u64, 0;
+    /// Involuntary context switches
+    nivcsw: u64, 0;
+    /// User CPU time scaled by the CPU frequency
+    ac_utimescaled: u64, 0;
+    /// System CPU time scaled by the CPU frequency
+    ac_stimescaled: u64, 0;
+    /// `cpu_run_real_total` scaled by the CPU frequency
+    cpu_scaled_run_real_total: u64, 0;
+    /// Number of delays reclaiming memory
+    freepages_count: u64, 0;
+    /// Total delay reclaiming memory
+    freepages_delay_total: u64, 0;
+    /// Number of delays waiting for thrashing pages
+    thrashing_count: u64, 0;
+    /// Total delay waiting for thrashing pages
+    thrashing_delay_total: u64, 0;
+    /// Start time in seconds since the epoch
+    ac_btime64: u64, 0;
+    /// Number of delays compacting memory
+    compact_count: u64, 0;
+    /// Total delay compacting memory
+    compact_delay_total: u64, 0;
+    /// Thread group ID
+    ac_tgid: u32, 4;
+    /// Elapsed time of the thread group
+    ac_tgetime: u64, 0;
+    /// Device ID of the program binary
+    ac_exe_dev: u64, 0;
+    /// Inode number of the program binary
+    ac_exe_inode: u64, 0;
+    /// Number of delays copying write-protected pages
+    wpcopy_count: u64, 0;
+    /// Total delay copying write-protected pages
+    wpcopy_delay_total: u64, 0
+);
+
+impl Taskstats {
+    /// Command name without its NUL padding
+    pub fn comm(&self) -> String {
+        let len = self
+            .ac_comm
+            .iter()
+            .position(|b| *b == 0)
+            .unwrap_or(TS_COMM_LEN);
+        String::from_utf8_lossy(&self.ac_comm[..len]).into_owned()
+    }
+
+    /// Statistics in the `TaskstatsType::AggrPid` or `TaskstatsType::AggrTgid` attribute of a
+    /// `TaskstatsCmd::New` message, whichever comes first
+    pub fn from_genl(genl: &Genlmsghdr<TaskstatsCmd, TaskstatsType>) -> Result<Self, NlError> {
+        let aggr = genl
+            .get_attr_handle()
+            .iter()
+            .find(|attr| {
+                attr.nla_type == TaskstatsType::AggrPid || attr.nla_type == TaskstatsType::AggrTgid
+            })
+            .ok_or_else(|| NlError::new("No statistics found in taskstats message"))?
+            .get_nested_attributes::<TaskstatsType>()?
+            .get_attr_payload_as::<Taskstats>(TaskstatsType::Stats)?;
+        Ok(aggr)
+    }
+}
+
+/// `struct cgroupstats`, the number of tasks of a cgroup in each state
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Cgroupstats {
+    /// Sleeping tasks
+    pub nr_sleeping: u64,
+    /// Running tasks
+    pub nr_running: u64,
+    /// Stopped tasks
+    pub nr_stopped: u64,
+    /// Tasks in uninterruptible sleep
+    pub nr_uninterruptible: u64,
+    /// Tasks waiting for I/O
+    pub nr_io_wait: u64,
+}
+
+impl Nl for Cgroupstats {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.nr_sleeping.serialize(mem)?;
+        self.nr_running.serialize(mem)?;
+        self.nr_stopped.serialize(mem)?;
+        self.nr_uninterruptible.serialize(mem)?;
+        self.nr_io_wait.serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        Ok(Cgroupstats {
+            nr_sleeping: u64::deserialize(mem)?,
+            nr_running: u64::deserialize(mem)?,
+            nr_stopped: u64::deserialize(mem)?,
+            nr_uninterruptible: u64::deserialize(mem)?,
+            nr_io_wait: u64::deserialize(mem)?,
+        })
+    }
+
+    fn size(&self) -> usize {
+        40
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(40)
+    }
+}
+
+impl ToNlValue for Cgroupstats {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("nr_sleeping", self.nr_sleeping.to_nl_value()),
+            ("nr_running", self.nr_running.to_nl_value()),
+            ("nr_stopped", self.nr_stopped.to_nl_value()),
+            ("nr_uninterruptible", self.nr_uninterruptible.to_nl_value()),
+            ("nr_io_wait", self.nr_io_wait.to_nl_value()),
+        ])
+    }
+}
+
+impl NlSocket {
+    /// Statistics of the thread with ID `pid`, resolving the ID of the family through `cache`
+    pub fn taskstats_pid(
+        &mut self,
+        cache: &mut GenlFamilyCache,
+        pid: u32,
+    ) -> Result<Taskstats, NlError> {
+        self.request_taskstats(cache, TaskstatsCmdAttr::Pid, pid)
+    }
+
+    /// Statistics summed over the threads of the process with ID `tgid`, resolving the ID of the
+    /// family through `cache`
+    pub fn taskstats_tgid(
+        &mut self,
+        cache: &mut GenlFamilyCache,
+        tgid: u32,
+    ) -> Result<Taskstats, NlError> {
+        self.request_taskstats(cache, TaskstatsCmdAttr::Tgid, tgid)
+    }
+
+    fn request_taskstats(
+        &mut self,
+        cache: &mut GenlFamilyCache,
+        attr: TaskstatsCmdAttr,
+        id: u32,
+    ) -> Result<Taskstats, NlError> {
+        let attrs = vec![Nlattr::new(None, attr, id)?];
+        let msg = cache.request::<TaskstatsFamily>(
+            self,
+            TaskstatsCmd::Get,
+            vec![NlmF::Request],
+            attrs,
+        )?;
+        self.send_nl(msg)?;
+        let reply =
+            self.recv_genl_reply::<u16, TaskstatsCmd, TaskstatsType>(&[TaskstatsCmd::New])?;
+        Taskstats::from_genl(&reply.nl_payload)
+    }
+
+    /// Number of tasks in each state of the cgroup whose directory is open as `cgroup`,
+    /// resolving the ID of the family through `cache`. Only cgroups of version 1 hierarchies are
+    /// supported by the kernel.
+    pub fn cgroupstats<F>(
+        &mut self,
+        cache: &mut GenlFamilyCache,
+        cgroup: &F,
+    ) -> Result<Cgroupstats, NlError>
+    where
+        F: AsRawFd,
+    {
+        let id = cache.id::<TaskstatsFamily>(self)?;
+        let attrs = vec![Nlattr::new(
+            None,
+            CgroupstatsCmdAttr::Fd,
+            cgroup.as_raw_fd() as u32,
+        )?];
+        let genlhdr = Genlmsghdr::new(
+            TaskstatsCmd::CgroupstatsGet,
+            TaskstatsFamily::VERSION,
+            attrs,
+        )?;
+        self.send_nl(Nlmsghdr::new(
+            None,
+            id,
+            vec![NlmF::Request],
+            None,
+            None,
+            genlhdr,
+        ))?;
+        let reply = self.recv_genl_reply::<u16, TaskstatsCmd, CgroupstatsType>(&[
+            TaskstatsCmd::CgroupstatsNew,
+        ])?;
+        Ok(reply
+            .nl_payload
+            .get_attr_handle()
+            .get_attr_payload_as::<Cgroupstats>(CgroupstatsType::CgroupStats)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use consts::taskstats::TASKSTATS_VERSION;
+    use consts::NlFamily;
+    use test_utils::assert_roundtrip;
+
+    fn stats() -> Taskstats {
+        let mut stats = Taskstats {
+            version: TASKSTATS_VERSION,
+            ac_pid: 42,
+            ac_tgid: 42,
+            wpcopy_delay_total: 7,
+            ..Default::default()
+        };
+        stats.ac_comm[..4].copy_from_slice(b"neli");
+        stats
+    }
+
+    #[test]
+    fn test_taskstats_layout() {
+        let stats = stats();
+        let bytes = assert_roundtrip(&stats);
+        assert_eq!(bytes.len(), TASKSTATS_LEN);
+        assert_eq!(&bytes[80..84], b"neli");
+        assert_eq!(&bytes[128..132], &42u32.to_ne_bytes());
+        assert_eq!(&bytes[368..372], &42u32.to_ne_bytes());
+        assert_eq!(stats.comm(), "neli");
+    }
+
+    #[test]
+    fn test_taskstats_versions() {
+        let stats = stats();
+        let bytes = assert_roundtrip(&stats);
+        // Fields of an older version are zero and those of a newer version are skipped
+        let mut mem = StreamReadBuffer::new(&bytes[..328]);
+        mem.set_size_hint(328);
+        let old = Taskstats::deserialize(&mut mem).unwrap();
+        assert_eq!((old.ac_pid, old.ac_tgid), (42, 0));
+        let mut longer = bytes.clone();
+        longer.extend_from_slice(&[0xff; 16]);
+        let mut mem = StreamReadBuffer::new(&longer);
+        mem.set_size_hint(longer.len());
+        assert_eq!(Taskstats::deserialize(&mut mem).unwrap(), stats);
+    }
+
+    #[test]
+    fn test_taskstats_truncated() {
+        let bytes = assert_roundtrip(&stats());
+        // The size hint claims more bytes than the buffer holds
+        let mut mem = StreamReadBuffer::new(&bytes[..100]);
+        mem.set_size_hint(TASKSTATS_LEN);
+        assert!(Taskstats::deserialize(&mut mem).is_err());
+        // Without a size hint, the version of the struct is unknown
+        assert!(Taskstats::deserialize(&mut StreamReadBuffer::new(&bytes)).is_err());
+    }
+
+    #[test]
+    fn test_cgroupstats() {
+        let stats = Cgroupstats {
+            nr_sleeping: 1,
+            nr_running: 2,
+            nr_stopped: 3,
+            nr_uninterruptible: 4,
+            nr_io_wait: 5,
+        };
+        let bytes = assert_roundtrip(&stats);
+        assert_eq!(&bytes[32..], &5u64.to_ne_bytes());
+        assert!(Cgroupstats::deserialize(&mut StreamReadBuffer::new(&bytes[..32])).is_err());
+    }
+
+    #[test]
+    fn test_taskstats_request() {
+        let mut s = NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
+        let mut cache = GenlFamilyCache::new();
+        let pid = std::process::id();
+        let stats = s.taskstats_pid(&mut cache, pid).unwrap();
+        assert_eq!(stats.ac_pid, pid);
+        assert!(stats.version >= 1);
+        assert!(s.taskstats_tgid(&mut cache, pid).unwrap().version >= 1);
+    }
+}
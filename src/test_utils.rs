@@ -0,0 +1,159 @@
+//! This module provides the assertions that `neli` checks its own wire format with, so that
+//! crates implementing protocols or families on top of `neli` can test theirs the same way.
+//!
+//! * `assert_roundtrip` serializes a value, parses the bytes back and checks that the same value
+//!   and the same bytes come out again.
+//! * `assert_serializes_to` compares the serialized form of a value against expected bytes.
+//! * `assert_vector` checks a golden byte vector loaded with `vectors::load`.
+//!
+//! ```
+//! use neli::consts::{Nlmsg, NlmF};
+//! use neli::nl::Nlmsghdr;
+//! use neli::test_utils::assert_roundtrip;
+//!
+//! let msg = Nlmsghdr::new(None, Nlmsg::Noop, vec![NlmF::Request], Some(1), None, vec![1u8, 2]);
+//! let bytes = assert_roundtrip(&msg);
+//! assert_eq!(bytes.len(), 20);
+//! ```
+//!
+//! # Design decisions
+//!
+//! The module is only built with the `test-util` feature, which downstream crates enable in
+//! their `dev-dependencies`, and for the tests of `neli` itself. The assertions panic with the
+//! offset of the first differing byte and hexdumps of both sides instead of returning errors, as
+//! they are meant to be called directly from tests.
+
+use std::fmt::{Debug, Write};
+
+use buffering::{StreamReadBuffer, StreamWriteBuffer};
+
+use vectors::TestVector;
+use Nl;
+
+/// Serialize `value`, panicking on errors
+fn serialize<T>(value: &T) -> Vec<u8>
+where
+    T: Nl,
+{
+    let mut mem = StreamWriteBuffer::new_growable(Some(value.asize()));
+    if let Err(e) = value.serialize(&mut mem) {
+        panic!("Serializing failed: {}", e);
+    }
+    mem.as_ref().to_vec()
+}
+
+/// Format `bytes` as rows of 16 hexadecimal pairs
+fn hexdump(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for (i, row) in bytes.chunks(16).enumerate() {
+        let _ = write!(out, "\n  {:04x} ", i * 16);
+        for byte in row {
+            let _ = write!(out, " {:02x}", byte);
+        }
+    }
+    out
+}
+
+/// Panic with a description of where `got` first differs from `expected`, unless they are equal
+fn compare(what: &str, got: &[u8], expected: &[u8]) {
+    if got == expected {
+        return;
+    }
+    let offset = got
+        .iter()
+        .zip(expected.iter())
+        .position(|(a, b)| a != b)
+        .unwrap_or_else(|| got.len().min(expected.len()));
+    panic!(
+        "{} differ at offset {} ({} bytes, expected {})\ngot:{}\nexpected:{}",
+        what,
+        offset,
+        got.len(),
+        expected.len(),
+        hexdump(got),
+        hexdump(expected)
+    );
+}
+
+/// Serialize `value`, parse the bytes back and check that the parsed value equals `value` and
+/// serializes to the same bytes. Returns the serialized bytes.
+pub fn assert_roundtrip<T>(value: &T) -> Vec<u8>
+where
+    T: Nl + Debug + PartialEq,
+{
+    let bytes = serialize(value);
+    let mut mem = StreamReadBuffer::new(&bytes);
+    mem.set_size_hint(bytes.len());
+    let parsed = match T::deserialize(&mut mem) {
+        Ok(parsed) => parsed,
+        Err(e) => panic!("Parsing {:?} back failed: {}{}", value, e, hexdump(&bytes)),
+    };
+    if !mem.at_end() {
+        panic!(
+            "Parsing {:?} back did not consume all of its bytes{}",
+            value,
+            hexdump(&bytes)
+        );
+    }
+    assert_eq!(
+        &parsed, value,
+        "Parsed value differs from the serialized one"
+    );
+    compare(
+        "Serialized bytes of the parsed value",
+        &serialize(&parsed),
+        &bytes,
+    );
+    bytes
+}
+
+/// Check that `value` serializes to `expected`
+pub fn assert_serializes_to<T>(value: &T, expected: &[u8])
+where
+    T: Nl,
+{
+    compare("Serialized bytes", &serialize(value), expected);
+}
+
+/// Parse `vector` as a `T` and check that it serializes back to the same bytes, as with
+/// `TestVector::roundtrip`. Returns the parsed value.
+pub fn assert_vector<T>(vector: &TestVector) -> T
+where
+    T: Nl,
+{
+    match vector.roundtrip::<T>() {
+        Ok(parsed) => parsed,
+        Err(e) => panic!("Vector {} failed: {}{}", vector, e, hexdump(&vector.bytes)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::panic;
+
+    use consts::{NlmF, Nlmsg};
+    use nl::Nlmsghdr;
+
+    #[test]
+    fn test_assertions() {
+        let msg = Nlmsghdr::new(
+            None,
+            Nlmsg::Noop,
+            vec![NlmF::Request],
+            Some(1),
+            None,
+            vec![7u8],
+        );
+        let bytes = assert_roundtrip(&msg);
+        assert_serializes_to(&msg, &bytes);
+
+        let mut wrong = bytes.clone();
+        wrong[16] = 8;
+        let res = panic::catch_unwind(|| assert_serializes_to(&msg, &wrong));
+        let err = res.unwrap_err();
+        let err = err.downcast_ref::<String>().unwrap();
+        assert!(err.starts_with("Serialized bytes differ at offset 16"));
+    }
+}
@@ -0,0 +1,220 @@
+//! This module implements `Nl` for the time structures that the kernel embeds in messages and
+//! attributes, and converts them to `Duration` and `SystemTime`.
+//!
+//! * `Timeval` and `Timespec` have the layout of `struct timeval` and `struct timespec` on the
+//!   target, whose fields are 32 bits wide on most 32-bit platforms and 64 bits wide otherwise.
+//! * `KernelTimespec` has the layout of `struct __kernel_timespec`, with 64-bit fields on all
+//!   platforms.
+//! * `BeTimeval` holds seconds and microseconds as 64-bit integers in network byte order, as in
+//!   `NfLogAttr::Timestamp`.
+//!
+//! # Design decisions
+//!
+//! The fields use the C types of the target so that the structures can be read from and
+//! written to memory shared with C code as is. Conversions fail instead of truncating when a
+//! value does not fit, such as a `Duration` longer than a 32-bit `time_t` can hold or a
+//! timestamp with a negative or out of range fraction of a second.
+
+use std::convert::TryFrom;
+use std::mem;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use libc;
+
+use err::{DeError, SerError};
+use value::{NlValue, ToNlValue};
+use {BeU64, Buffer, BufferMut, Nl};
+
+/// Split a time given as seconds and a fraction of `units` per second into seconds and
+/// nanoseconds, checking that the fraction is in range
+fn split(sec: i128, frac: i128, units: i128) -> Result<(i128, u32), DeError> {
+    if frac < 0 || frac >= units {
+        return Err(DeError::Msg(format!(
+            "Fraction of a second {} is not in the range from 0 to {}",
+            frac,
+            units - 1
+        )));
+    }
+    Ok((sec, (frac * (1_000_000_000 / units)) as u32))
+}
+
+/// Convert seconds and nanoseconds since the epoch into a `SystemTime`
+fn system_time(sec: i128, nanos: u32) -> Result<SystemTime, DeError> {
+    let time = if sec >= 0 {
+        u64::try_from(sec)
+            .ok()
+            .and_then(|sec| UNIX_EPOCH.checked_add(Duration::new(sec, nanos)))
+    } else {
+        u64::try_from(-sec)
+            .ok()
+            .and_then(|sec| UNIX_EPOCH.checked_sub(Duration::from_secs(sec)))
+            .and_then(|time| time.checked_add(Duration::from_nanos(u64::from(nanos))))
+    };
+    time.ok_or_else(|| DeError::Msg(format!("Time of {} seconds is out of range", sec)))
+}
+
+macro_rules! impl_time {
+    ( $(
+        $(#[$doc:meta])*
+        $name:ident, $sec_ty:ty as $sec_wire:ty, $frac:ident: $frac_ty:ty as $frac_wire:ty,
+        $units:expr
+    );* ) => {
+        $(
+            $(#[$doc])*
+            #[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+            pub struct $name {
+                /// Seconds
+                pub tv_sec: $sec_ty,
+                /// Fraction of a second
+                pub $frac: $frac_ty,
+            }
+
+            impl $name {
+                /// Time since the epoch as a `SystemTime`, which can also be before the epoch
+                pub fn to_system_time(&self) -> Result<SystemTime, DeError> {
+                    let (sec, nanos) =
+                        split(i128::from(self.tv_sec), i128::from(self.$frac), $units)?;
+                    system_time(sec, nanos)
+                }
+            }
+
+            impl Nl for $name {
+                fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+                where
+                    B: BufferMut,
+                {
+                    <$sec_wire>::from(self.tv_sec).serialize(mem)?;
+                    <$frac_wire>::from(self.$frac).serialize(mem)?;
+                    Ok(())
+                }
+
+                fn deserialize<T>(mem: &mut T) -> Result<Self, DeError>
+                where
+                    T: Buffer,
+                {
+                    Ok($name {
+                        tv_sec: <$sec_wire>::deserialize(mem)?.into(),
+                        $frac: <$frac_wire>::deserialize(mem)?.into(),
+                    })
+                }
+
+                fn size(&self) -> usize {
+                    mem::size_of::<$sec_ty>() + mem::size_of::<$frac_ty>()
+                }
+
+                fn type_size() -> Option<usize> {
+                    Some(mem::size_of::<$sec_ty>() + mem::size_of::<$frac_ty>())
+                }
+            }
+
+            impl TryFrom<$name> for Duration {
+                type Error = DeError;
+
+                fn try_from(v: $name) -> Result<Self, Self::Error> {
+                    let (sec, nanos) = split(i128::from(v.tv_sec), i128::from(v.$frac), $units)?;
+                    let sec = u64::try_from(sec).map_err(|_| {
+                        DeError::Msg(format!("Negative time of {} seconds", sec))
+                    })?;
+                    Ok(Duration::new(sec, nanos))
+                }
+            }
+
+            impl TryFrom<Duration> for $name {
+                type Error = SerError;
+
+                fn try_from(v: Duration) -> Result<Self, Self::Error> {
+                    let frac = i128::from(v.subsec_nanos()) / (1_000_000_000 / $units);
+                    Ok($name {
+                        tv_sec: <$sec_ty>::try_from(v.as_secs()).map_err(|_| {
+                            SerError::new(format!("{:?} does not fit in {}", v, stringify!($name)))
+                        })?,
+                        $frac: <$frac_ty>::try_from(frac).map_err(SerError::new)?,
+                    })
+                }
+            }
+
+            impl ToNlValue for $name {
+                fn to_nl_value(&self) -> NlValue {
+                    NlValue::map(vec![
+                        ("tv_sec", self.tv_sec.to_nl_value()),
+                        (stringify!($frac), self.$frac.to_nl_value()),
+                    ])
+                }
+            }
+        )*
+    };
+}
+
+impl_time!(
+    /// `struct timeval` of the target
+    Timeval, libc::time_t as libc::time_t, tv_usec: libc::suseconds_t as libc::suseconds_t,
+    1_000_000;
+    /// `struct timespec` of the target
+    Timespec, libc::time_t as libc::time_t, tv_nsec: libc::c_long as libc::c_long, 1_000_000_000;
+    /// `struct __kernel_timespec`, with 64-bit fields on all platforms
+    KernelTimespec, i64 as i64, tv_nsec: i64 as i64, 1_000_000_000;
+    /// Seconds and microseconds as 64-bit integers in network byte order, as used for
+    /// `NfLogAttr::Timestamp`. The values are held in host byte order.
+    BeTimeval, u64 as BeU64, tv_usec: u64 as BeU64, 1_000_000
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use {StreamReadBuffer, StreamWriteBuffer};
+
+    #[test]
+    fn test_time() {
+        let ts = KernelTimespec {
+            tv_sec: 5,
+            tv_nsec: 250_000_000,
+        };
+        let mut buf = vec![0; 16];
+        ts.serialize(&mut StreamWriteBuffer::new_sized(&mut buf))
+            .unwrap();
+        assert_eq!(
+            KernelTimespec::deserialize(&mut StreamReadBuffer::new(&buf)).unwrap(),
+            ts
+        );
+        assert_eq!(Duration::try_from(ts).unwrap(), Duration::from_millis(5250));
+        assert_eq!(
+            ts.to_system_time().unwrap(),
+            UNIX_EPOCH + Duration::from_millis(5250)
+        );
+
+        let before_epoch = KernelTimespec {
+            tv_sec: -2,
+            tv_nsec: 500_000_000,
+        };
+        assert_eq!(
+            before_epoch.to_system_time().unwrap(),
+            UNIX_EPOCH - Duration::from_millis(1500)
+        );
+        assert!(Duration::try_from(before_epoch).is_err());
+        let bad_nsec = KernelTimespec {
+            tv_sec: 0,
+            tv_nsec: 1_000_000_000,
+        };
+        assert!(Duration::try_from(bad_nsec).is_err());
+
+        let tv = BeTimeval::try_from(Duration::new(1, 2_345_678)).unwrap();
+        assert_eq!(
+            tv,
+            BeTimeval {
+                tv_sec: 1,
+                tv_usec: 2345
+            }
+        );
+        let mut buf = vec![0; 16];
+        tv.serialize(&mut StreamWriteBuffer::new_sized(&mut buf))
+            .unwrap();
+        assert_eq!(buf, [0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0x09, 0x29]);
+
+        assert_eq!(Timeval::type_size(), Some(mem::size_of::<libc::timeval>()));
+        assert_eq!(
+            Timespec::type_size(),
+            Some(mem::size_of::<libc::timespec>())
+        );
+    }
+}
@@ -0,0 +1,7 @@
+//! This module contains library versions of debugging tools, so that a tool can be a thin binary
+//! on top of it and downstream programs can embed the same functionality.
+//!
+//! * `monitor` - This subscribes to multicast groups of several protocols at once and yields the
+//!   notifications it receives as annotated `NlValue` trees.
+
+pub mod monitor;
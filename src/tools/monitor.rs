@@ -0,0 +1,368 @@
+//! This module provides the engine of a netlink monitor in the style of `ip monitor`, extended
+//! to any mix of protocols.
+//!
+//! A `Monitor` holds one socket per protocol, each joined to the multicast groups it was asked
+//! to subscribe to. `next_event` waits on all of them and returns every received message as a
+//! `MonitorEvent`, annotated with the protocol, multicast group and receive time and decoded
+//! into an `NlValue` tree.
+//!
+//! # Design decisions
+//!
+//! Messages are decoded with the built-in payload types of the library: routing netlink messages
+//! by their `Rtm` type, generic netlink messages with numeric commands and attribute types and
+//! NFLOG packets as `LogPacket`. Anything else keeps its raw payload. `set_decoder` replaces the
+//! decoder of a protocol for payload types defined outside of this crate.
+
+use std::collections::VecDeque;
+use std::fmt::{self, Display};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::time::{Duration, SystemTime};
+
+use buffering::StreamReadBuffer;
+use libc;
+
+use consts::netfilter::NetfilterMsg;
+use consts::{NlFamily, NlType, Rtm, RtnlGroup};
+use err::{DeError, NlError};
+use genl::Genlmsghdr;
+use netfilter::LogPacket;
+use nl::Nlmsghdr;
+use rtnl::{Ifaddrmsg, Ifinfomsg, Ndmsg, Rtmsg, Tcmsg};
+use socket::{NlAddr, NlSocket};
+use value::{NlValue, ToNlValue};
+use Nl;
+
+/// Function decoding a received message into an `NlValue` tree
+pub type MonitorDecoder = fn(&Nlmsghdr<u16, Vec<u8>>) -> Result<NlValue, DeError>;
+
+/// Notification received by a `Monitor`
+#[derive(Clone, Debug, PartialEq)]
+pub struct MonitorEvent {
+    /// Protocol of the socket that received the message
+    pub family: NlFamily,
+    /// Lowest multicast group the message was sent to, or `None` for unicast messages and
+    /// groups above 32, which the kernel does not report
+    pub group: Option<u32>,
+    /// Address of the sender
+    pub sender: NlAddr,
+    /// Time at which the message was received
+    pub received: SystemTime,
+    /// Decoded message
+    pub msg: NlValue,
+}
+
+impl Display for MonitorEvent {
+    /// One line summary with the protocol, group and message type
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{:?}", self.family)?;
+        if let Some(group) = self.group {
+            write!(f, " group {}", group)?;
+        }
+        write!(f, "] ")?;
+        match self.msg.get("nlmsg_type") {
+            Some(NlValue::Const {
+                name: Some(ref name),
+                ..
+            }) => write!(f, "{}", name),
+            Some(NlValue::Const { value, .. }) => write!(f, "type {}", value),
+            Some(NlValue::Uint(value)) => write!(f, "type {}", value),
+            _ => write!(f, "unknown type"),
+        }
+    }
+}
+
+/// Multi-protocol netlink monitor
+pub struct Monitor {
+    sockets: Vec<(NlFamily, NlSocket, MonitorDecoder)>,
+    events: VecDeque<Result<MonitorEvent, NlError>>,
+    timeout: Option<Duration>,
+}
+
+impl Monitor {
+    /// Create a monitor that is not subscribed to any group yet
+    pub fn new() -> Self {
+        Monitor {
+            sockets: Vec::new(),
+            events: VecDeque::new(),
+            timeout: None,
+        }
+    }
+
+    /// Join the multicast groups with the given IDs of the given protocol
+    pub fn subscribe(&mut self, family: NlFamily, groups: &[u32]) -> Result<&mut Self, NlError> {
+        self.socket(family)?.add_mcast_membership(groups)?;
+        Ok(self)
+    }
+
+    /// Join the given routing netlink multicast groups
+    pub fn subscribe_rt(&mut self, groups: &[RtnlGroup]) -> Result<&mut Self, NlError> {
+        self.socket(NlFamily::Route)?.subscribe_rt_events(groups)?;
+        Ok(self)
+    }
+
+    /// Join a generic netlink multicast group given by family and group name
+    pub fn subscribe_genl(&mut self, family: &str, group: &str) -> Result<&mut Self, NlError> {
+        self.socket(NlFamily::Generic)?
+            .resolve_and_join_mcast(family, group)?;
+        Ok(self)
+    }
+
+    /// Decode messages of the given protocol with `decoder` instead of the built-in decoder
+    pub fn set_decoder(
+        &mut self,
+        family: NlFamily,
+        decoder: MonitorDecoder,
+    ) -> Result<&mut Self, NlError> {
+        self.socket(family.clone())?;
+        for (f, _, d) in self.sockets.iter_mut() {
+            if *f == family {
+                *d = decoder;
+            }
+        }
+        Ok(self)
+    }
+
+    /// Make `next_event` return `NlError::Io` with an `io::ErrorKind::WouldBlock` error if no
+    /// message arrives within `timeout`. `None`, the default, waits indefinitely.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Protocols the monitor has sockets for
+    pub fn families(&self) -> Vec<NlFamily> {
+        self.sockets.iter().map(|(f, _, _)| f.clone()).collect()
+    }
+
+    /// Return the next notification, waiting for one if none has been received yet. Messages
+    /// that fail to decode and datagrams that fail to be received are returned as errors in
+    /// their place without ending the monitor or dropping the other messages.
+    pub fn next_event(&mut self) -> Result<MonitorEvent, NlError> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return event;
+            }
+            if self.sockets.is_empty() {
+                return Err(NlError::new("Monitor is not subscribed to any group"));
+            }
+            let mut fds = self
+                .sockets
+                .iter()
+                .map(|(_, s, _)| libc::pollfd {
+                    fd: s.as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                })
+                .collect::<Vec<_>>();
+            let timeout = self
+                .timeout
+                .map(|t| t.as_millis().min(libc::c_int::MAX as u128) as libc::c_int)
+                .unwrap_or(-1);
+            match unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) } {
+                0 => {
+                    return Err(NlError::Io(io::Error::new(
+                        io::ErrorKind::WouldBlock,
+                        "No message received before the timeout",
+                    )))
+                }
+                i if i < 0 => {
+                    let e = io::Error::last_os_error();
+                    if e.kind() == io::ErrorKind::Interrupted {
+                        continue;
+                    }
+                    return Err(NlError::Io(e));
+                }
+                _ => (),
+            }
+            for (pollfd, (family, socket, decoder)) in fds.iter().zip(self.sockets.iter()) {
+                if pollfd.revents == 0 {
+                    continue;
+                }
+                let (msgs, sender) = match socket.recv_nl_from::<u16, Vec<u8>>(None) {
+                    Ok(received) => received,
+                    Err(e) => {
+                        self.events.push_back(Err(e));
+                        continue;
+                    }
+                };
+                let received = SystemTime::now();
+                let group = match sender.groups {
+                    0 => None,
+                    groups => Some(groups.trailing_zeros() + 1),
+                };
+                for msg in msgs.iter() {
+                    let event = decoder(msg).map_err(NlError::from).map(|msg| MonitorEvent {
+                        family: family.clone(),
+                        group,
+                        sender,
+                        received,
+                        msg,
+                    });
+                    self.events.push_back(event);
+                }
+            }
+        }
+    }
+
+    fn socket(&mut self, family: NlFamily) -> Result<&mut NlSocket, NlError> {
+        let pos = match self.sockets.iter().position(|(f, _, _)| *f == family) {
+            Some(pos) => pos,
+            None => {
+                let socket = NlSocket::connect(family.clone(), None, None, false)?;
+                let decoder = default_decoder(&family);
+                self.sockets.push((family, socket, decoder));
+                self.sockets.len() - 1
+            }
+        };
+        Ok(&mut self.sockets[pos].1)
+    }
+}
+
+impl Default for Monitor {
+    fn default() -> Self {
+        Monitor::new()
+    }
+}
+
+impl Iterator for Monitor {
+    type Item = Result<MonitorEvent, NlError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_event())
+    }
+}
+
+/// Built-in decoder for messages of the given protocol
+pub fn default_decoder(family: &NlFamily) -> MonitorDecoder {
+    match *family {
+        NlFamily::Route => decode_route,
+        NlFamily::Generic => decode_genl,
+        NlFamily::Netfilter => decode_netfilter,
+        _ => decode_raw,
+    }
+}
+
+/// Decode a message keeping its raw payload
+pub fn decode_raw(msg: &Nlmsghdr<u16, Vec<u8>>) -> Result<NlValue, DeError> {
+    Ok(msg.to_nl_value())
+}
+
+/// Decode a routing netlink message according to its `Rtm` type
+pub fn decode_route(msg: &Nlmsghdr<u16, Vec<u8>>) -> Result<NlValue, DeError> {
+    let nl_type = Rtm::from(msg.nl_type);
+    match nl_type {
+        Rtm::Newlink | Rtm::Dellink => typed::<_, Ifinfomsg>(msg, nl_type),
+        Rtm::Newaddr | Rtm::Deladdr => typed::<_, Ifaddrmsg>(msg, nl_type),
+        Rtm::Newroute | Rtm::Delroute => typed::<_, Rtmsg>(msg, nl_type),
+        Rtm::Newneigh | Rtm::Delneigh => typed::<_, Ndmsg>(msg, nl_type),
+        Rtm::Newqdisc
+        | Rtm::Delqdisc
+        | Rtm::Newtclass
+        | Rtm::Deltclass
+        | Rtm::Newtfilter
+        | Rtm::Deltfilter => typed::<_, Tcmsg>(msg, nl_type),
+        _ => typed::<_, Vec<u8>>(msg, nl_type),
+    }
+}
+
+/// Decode a generic netlink message with numeric command and attribute types
+pub fn decode_genl(msg: &Nlmsghdr<u16, Vec<u8>>) -> Result<NlValue, DeError> {
+    typed::<_, Genlmsghdr<u8, u16>>(msg, msg.nl_type)
+}
+
+/// Decode a netfilter message, parsing NFLOG packets as `LogPacket`
+pub fn decode_netfilter(msg: &Nlmsghdr<u16, Vec<u8>>) -> Result<NlValue, DeError> {
+    match NetfilterMsg::from(msg.nl_type) {
+        NetfilterMsg::LogPacket => typed::<_, LogPacket>(msg, NetfilterMsg::LogPacket),
+        nl_type => typed::<_, Vec<u8>>(msg, nl_type),
+    }
+}
+
+/// Parse the payload of `msg` as `P` and convert the message with header type `T`
+fn typed<T, P>(msg: &Nlmsghdr<u16, Vec<u8>>, nl_type: T) -> Result<NlValue, DeError>
+where
+    T: NlType + ToNlValue,
+    P: Nl + ToNlValue,
+{
+    let mut mem = StreamReadBuffer::new(&msg.nl_payload);
+    mem.set_size_hint(msg.nl_payload.len());
+    let nl_payload = P::deserialize(&mut mem)?;
+    let msg = Nlmsghdr {
+        nl_len: msg.nl_len,
+        nl_type,
+        nl_flags: msg.nl_flags.clone(),
+        nl_seq: msg.nl_seq,
+        nl_pid: msg.nl_pid,
+        nl_payload,
+    };
+    Ok(msg.to_nl_value())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use buffering::StreamWriteBuffer;
+
+    use consts::{Arphrd, Iff, NlmF, RtAddrFamily};
+    use rtnl::{IfIndex, Rtattrs};
+
+    #[test]
+    fn test_monitor() {
+        let link = Nlmsghdr::new(
+            None,
+            Rtm::Newlink,
+            vec![],
+            None,
+            None,
+            Ifinfomsg::new(
+                RtAddrFamily::Unspec,
+                Arphrd::None,
+                IfIndex(1),
+                vec![Iff::Up],
+                Rtattrs::empty(),
+            ),
+        );
+        let mut mem = StreamWriteBuffer::new_growable(None);
+        link.serialize(&mut mem).unwrap();
+        let raw = Nlmsghdr::<u16, Vec<u8>>::deserialize(&mut StreamReadBuffer::new(mem.as_ref()))
+            .unwrap();
+        let msg = decode_route(&raw).unwrap();
+        assert_eq!(
+            msg.get("payload").and_then(|p| p.get("ifi_index")),
+            Some(&NlValue::Uint(1))
+        );
+        let event = MonitorEvent {
+            family: NlFamily::Route,
+            group: Some(libc::RTNLGRP_LINK),
+            sender: NlAddr { pid: 0, groups: 1 },
+            received: SystemTime::now(),
+            msg,
+        };
+        assert_eq!(event.to_string(), "[Route group 1] Newlink");
+
+        let mut monitor = Monitor::new();
+        monitor
+            .subscribe_rt(&[RtnlGroup::Link])
+            .unwrap()
+            .subscribe_genl("nlctrl", "notify")
+            .unwrap()
+            .set_decoder(NlFamily::Generic, decode_raw)
+            .unwrap();
+        assert_eq!(monitor.families(), vec![NlFamily::Route, NlFamily::Generic]);
+        monitor.set_timeout(Some(Duration::from_millis(10)));
+        match monitor.next_event() {
+            Err(NlError::Io(ref e)) if e.kind() == io::ErrorKind::WouldBlock => (),
+            res => panic!("Unexpected result {:?}", res),
+        }
+        assert!(decode_genl(&Nlmsghdr::new(
+            None,
+            0x10,
+            vec![NlmF::Request],
+            None,
+            None,
+            vec![1, 2]
+        ))
+        .is_err());
+    }
+}
@@ -0,0 +1,225 @@
+//! This module provides transactions for requests that create or delete kernel objects such as
+//! links, addresses and routes.
+//!
+//! An `NlTransaction` sends each request, waits for the kernel to acknowledge it and records the
+//! request that undoes it. If a later request fails, `rollback` sends the recorded requests in
+//! reverse order to restore the previous state. `commit` discards them.
+//!
+//! # Design decisions
+//!
+//! Inverse requests are stored serialized so that a single transaction can mix message types
+//! from different protocols. For rtnetlink, `apply_rtnl` derives the inverse from the request
+//! itself by swapping the `RTM_NEW*` type for the matching `RTM_DEL*` type and vice versa. Deleting
+//! an object with the same attributes used to create it works for all rtnetlink objects, but
+//! recreating an object from a delete request only restores the attributes present in that
+//! request.
+
+use buffering::StreamWriteBuffer;
+
+use consts::{NlType, NlmF, Nlmsg, Rtm};
+use endian;
+use err::{NlError, Nlmsgerr};
+use nl::Nlmsghdr;
+use socket::{NlSocket, NlSocketOps};
+use Nl;
+
+/// Group of requests that can be undone as a whole
+pub struct NlTransaction<'a, S = NlSocket> {
+    socket: &'a mut S,
+    inverses: Vec<Vec<u8>>,
+}
+
+impl<'a, S> NlTransaction<'a, S>
+where
+    S: NlSocketOps,
+{
+    /// Start a transaction on the given socket
+    pub fn new(socket: &'a mut S) -> Self {
+        NlTransaction {
+            socket,
+            inverses: Vec::new(),
+        }
+    }
+
+    /// Send a request and record `inverse` to undo it once the kernel has acknowledged the
+    /// request. `NlmF::Ack` is added to both messages if it is missing.
+    pub fn apply<T, P, U, Q>(
+        &mut self,
+        mut msg: Nlmsghdr<T, P>,
+        mut inverse: Nlmsghdr<U, Q>,
+    ) -> Result<(), NlError>
+    where
+        T: Nl + NlType,
+        P: Nl,
+        U: Nl + NlType,
+        Q: Nl,
+    {
+        if !msg.nl_flags.contains(&NlmF::Ack) {
+            msg.nl_flags.push(NlmF::Ack);
+        }
+        if !inverse.nl_flags.contains(&NlmF::Ack) {
+            inverse.nl_flags.push(NlmF::Ack);
+        }
+        let mut msg = serialize(&msg)?;
+        let inverse = serialize(&inverse)?;
+        self.send_and_ack(&mut msg)?;
+        self.inverses.push(inverse);
+        Ok(())
+    }
+
+    /// Send an rtnetlink request that creates or deletes an object and record the request that
+    /// undoes it. Only `RTM_NEW*` and `RTM_DEL*` requests with a counterpart are supported.
+    pub fn apply_rtnl<P>(&mut self, mut msg: Nlmsghdr<Rtm, P>) -> Result<(), NlError>
+    where
+        P: Nl,
+    {
+        let (inverse_type, inverse_flags) = rtm_inverse(&msg.nl_type)
+            .ok_or_else(|| NlError::new(&format!("No inverse operation for {:?}", msg.nl_type)))?;
+        if !msg.nl_flags.contains(&NlmF::Ack) {
+            msg.nl_flags.push(NlmF::Ack);
+        }
+        let mut msg = serialize(&msg)?;
+        let mut inverse = msg.clone();
+        endian::write_u16(&mut inverse[4..6], inverse_type.into());
+        endian::write_u16(
+            &mut inverse[6..8],
+            inverse_flags
+                .into_iter()
+                .fold(0, |acc, flag| acc | u16::from(flag)),
+        );
+        self.send_and_ack(&mut msg)?;
+        self.inverses.push(inverse);
+        Ok(())
+    }
+
+    /// Number of applied requests that would be undone by `rollback`
+    pub fn len(&self) -> usize {
+        self.inverses.len()
+    }
+
+    /// Returns true if no requests have been applied
+    pub fn is_empty(&self) -> bool {
+        self.inverses.is_empty()
+    }
+
+    /// Keep all applied changes
+    pub fn commit(self) {}
+
+    /// Undo all applied changes, most recent first. Rolling back stops at the first inverse
+    /// request that the kernel rejects and returns its error.
+    pub fn rollback(mut self) -> Result<(), NlError> {
+        while let Some(mut inverse) = self.inverses.pop() {
+            self.send_and_ack(&mut inverse)?;
+        }
+        Ok(())
+    }
+
+    fn send_and_ack(&mut self, msg: &mut [u8]) -> Result<(), NlError> {
+        let seq = self.socket.next_seq();
+        if let Some(seq) = seq {
+            // nl_seq follows nl_len, nl_type and nl_flags in the header
+            endian::write_u32(&mut msg[8..12], seq);
+        }
+        self.socket.send_datagram(msg)?;
+        let ack = self
+            .socket
+            .recv_msgs::<Nlmsg, Nlmsgerr<Nlmsg>>()
+            .ok()
+            .and_then(|msgs| msgs.into_iter().next())
+            .ok_or(NlError::NoAck)?;
+        if ack.nl_type != Nlmsg::Error {
+            return Err(NlError::NoAck);
+        }
+        if let Some(seq) = seq {
+            if seq != ack.nl_seq {
+                return Err(NlError::BadSeq);
+            }
+        }
+        match ack.nl_payload.error {
+            0 => Ok(()),
+            e => Err(NlError::from_nlmsgerr(e)),
+        }
+    }
+}
+
+fn serialize<T, P>(msg: &Nlmsghdr<T, P>) -> Result<Vec<u8>, NlError>
+where
+    T: Nl + NlType,
+    P: Nl,
+{
+    let mut mem = StreamWriteBuffer::new_growable(Some(msg.asize()));
+    msg.serialize(&mut mem)?;
+    Ok(mem.as_ref().to_vec())
+}
+
+/// Message type and flags of the request undoing a request of the given type
+fn rtm_inverse(rtm: &Rtm) -> Option<(Rtm, Vec<NlmF>)> {
+    let delete = |ty| Some((ty, vec![NlmF::Request, NlmF::Ack]));
+    let create = |ty| Some((ty, vec![NlmF::Request, NlmF::Ack, NlmF::Create, NlmF::Excl]));
+    match *rtm {
+        Rtm::Newlink => delete(Rtm::Dellink),
+        Rtm::Newaddr => delete(Rtm::Deladdr),
+        Rtm::Newroute => delete(Rtm::Delroute),
+        Rtm::Newneigh => delete(Rtm::Delneigh),
+        Rtm::Newrule => delete(Rtm::Delrule),
+        Rtm::Newqdisc => delete(Rtm::Delqdisc),
+        Rtm::Newtclass => delete(Rtm::Deltclass),
+        Rtm::Newtfilter => delete(Rtm::Deltfilter),
+        Rtm::Newaction => delete(Rtm::Delaction),
+        Rtm::Newaddrlabel => delete(Rtm::Deladdrlabel),
+        Rtm::Newmdb => delete(Rtm::Delmdb),
+        Rtm::Newnsid => delete(Rtm::Delnsid),
+        Rtm::Dellink => create(Rtm::Newlink),
+        Rtm::Deladdr => create(Rtm::Newaddr),
+        Rtm::Delroute => create(Rtm::Newroute),
+        Rtm::Delneigh => create(Rtm::Newneigh),
+        Rtm::Delrule => create(Rtm::Newrule),
+        Rtm::Delqdisc => create(Rtm::Newqdisc),
+        Rtm::Deltclass => create(Rtm::Newtclass),
+        Rtm::Deltfilter => create(Rtm::Newtfilter),
+        Rtm::Delaction => create(Rtm::Newaction),
+        Rtm::Deladdrlabel => create(Rtm::Newaddrlabel),
+        Rtm::Delmdb => create(Rtm::Newmdb),
+        Rtm::Delnsid => create(Rtm::Newnsid),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use consts::NlFamily;
+
+    #[test]
+    fn test_rtm_inverse() {
+        assert_eq!(
+            rtm_inverse(&Rtm::Newaddr),
+            Some((Rtm::Deladdr, vec![NlmF::Request, NlmF::Ack]))
+        );
+        assert_eq!(
+            rtm_inverse(&Rtm::Delroute),
+            Some((
+                Rtm::Newroute,
+                vec![NlmF::Request, NlmF::Ack, NlmF::Create, NlmF::Excl]
+            ))
+        );
+        assert_eq!(rtm_inverse(&Rtm::Getlink), None);
+    }
+
+    #[test]
+    fn test_apply_rtnl_unsupported() {
+        let mut s = NlSocket::connect(NlFamily::Route, None, None, true).unwrap();
+        let mut transaction = NlTransaction::new(&mut s);
+        let msg = Nlmsghdr::new(
+            None,
+            Rtm::Setlink,
+            vec![NlmF::Request],
+            None,
+            None,
+            Vec::<u8>::new(),
+        );
+        assert!(transaction.apply_rtnl(msg).is_err());
+        assert!(transaction.is_empty());
+    }
+}
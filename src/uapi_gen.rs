@@ -0,0 +1,330 @@
+//! This module generates `impl_var!` definitions from kernel UAPI headers so that new constants
+//! can be picked up without writing out every enum variant by hand.
+//!
+//! It is meant to be called from a build script with `neli` added as a build dependency with the
+//! `uapi-gen` feature enabled:
+//!
+//! ```ignore
+//! let defs = neli::uapi_gen::generate_from_file(
+//!     "/usr/include/linux/if_link.h",
+//!     "Ifla",
+//!     "u16",
+//!     Some("NlAttrType"),
+//!     "IFLA_",
+//! )?;
+//! std::fs::write(out_dir.join("ifla.rs"), defs)?;
+//! ```
+//!
+//! The output can then be pulled in with `include!` in a module where the `neli` macros are in
+//! scope.
+//!
+//! # Design decisions
+//!
+//! * Only the subset of C needed for UAPI constants is understood: `#define`s with integer
+//!   values and `enum` bodies with implicit or explicit values. Values may be integer literals,
+//!   previously seen constants, or a single `+`, `-`, `|` or `<<` of those. Anything else is
+//!   skipped rather than guessed at.
+//! * Values are emitted as integer literals instead of `libc` paths because `libc` does not
+//!   define every constant found in the headers.
+//! * Names starting with `__` and names ending with `_MAX` are left out as they mark the bounds
+//!   of an enum rather than valid values, matching the hand-written enums in `consts`.
+//! * Enum values are only taken from the first `enum` body containing the prefix. Nested
+//!   attributes often share the prefix of their parent, such as `CTRL_ATTR_OP_*` and
+//!   `CTRL_ATTR_`, and restart their values at zero.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Read a UAPI header from `path` and generate definitions as with `generate`
+pub fn generate_from_file<P>(
+    path: P,
+    name: &str,
+    ty: &str,
+    marker: Option<&str>,
+    prefix: &str,
+) -> Result<String, io::Error>
+where
+    P: AsRef<Path>,
+{
+    generate(&fs::read_to_string(path)?, name, ty, marker, prefix)
+}
+
+/// Generate an `impl_var!` definition named `name` with values of type `ty` from the constants
+/// in `header` starting with `prefix`: the `#define`s and the values of the first `enum` body
+/// containing the prefix, leaving out nested enums that share it, such as `CTRL_ATTR_OP_*` for
+/// `CTRL_ATTR_`. If `marker` is given, `impl_var_trait!` is used instead so the enum implements
+/// that marker trait.
+pub fn generate(
+    header: &str,
+    name: &str,
+    ty: &str,
+    marker: Option<&str>,
+    prefix: &str,
+) -> Result<String, io::Error> {
+    let constants = parse_constants(&strip_comments(header))
+        .into_iter()
+        .filter(|(c_name, _, _)| {
+            c_name.starts_with(prefix) && c_name.len() > prefix.len() && !c_name.ends_with("_MAX")
+        })
+        .collect::<Vec<_>>();
+    let first_enum = constants.iter().find_map(|(_, _, block)| *block);
+    let variants = constants
+        .into_iter()
+        .filter(|(_, _, block)| block.is_none() || *block == first_enum)
+        .map(|(c_name, val, _)| (variant_name(&c_name[prefix.len()..]), val))
+        .collect::<Vec<_>>();
+    if variants.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("No constants starting with {} found", prefix),
+        ));
+    }
+
+    let mut out = String::new();
+    out.push_str(match marker {
+        Some(_) => "impl_var_trait!(\n",
+        None => "impl_var!(\n",
+    });
+    out.push_str(&format!("    /// Generated from `{}*` constants\n", prefix));
+    match marker {
+        Some(m) => out.push_str(&format!("    {}, {}, {},\n", name, ty, m)),
+        None => out.push_str(&format!("    {}, {},\n", name, ty)),
+    }
+    let lines = variants
+        .iter()
+        .map(|(var, val)| format!("    {} => {}", var, val))
+        .collect::<Vec<_>>();
+    out.push_str(&lines.join(",\n"));
+    out.push_str("\n);\n");
+    Ok(out)
+}
+
+/// Convert the part of a C constant name after the prefix to the variant naming used in
+/// `consts`, for example `MCAST_GROUPS` to `McastGroups`
+fn variant_name(c_name: &str) -> String {
+    c_name
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => {
+                    first.to_ascii_uppercase().to_string() + &chars.as_str().to_ascii_lowercase()
+                }
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+fn strip_comments(header: &str) -> String {
+    let mut out = String::with_capacity(header.len());
+    let mut rest = header;
+    while let Some(start) = rest.find("/*") {
+        out.push_str(&rest[..start]);
+        rest = match rest[start..].find("*/") {
+            Some(end) => &rest[start + end + 2..],
+            None => "",
+        };
+    }
+    out.push_str(rest);
+    out.lines()
+        .map(|line| match line.find("//") {
+            Some(i) => &line[..i],
+            None => line,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Collect `#define` and `enum` constants in the order they appear, along with the index of the
+/// `enum` body of enum values
+fn parse_constants(header: &str) -> Vec<(String, i64, Option<usize>)> {
+    let mut known = HashMap::new();
+    let mut constants = Vec::new();
+    let mut add = |name: &str, val: i64, block, known: &mut HashMap<String, i64>| {
+        // Headers often follow an enum value with `#define NAME NAME`
+        if known.insert(name.to_string(), val).is_none() && !name.starts_with("__") {
+            constants.push((name.to_string(), val, block));
+        }
+    };
+    let mut blocks = 0;
+
+    let mut rest = header;
+    loop {
+        let define = rest.find("#define");
+        let enumeration = find_enum(rest);
+        match (define, enumeration) {
+            (Some(d), e) if e.map(|e| d < e).unwrap_or(true) => {
+                let line_end = rest[d..].find('\n').map(|i| d + i).unwrap_or(rest.len());
+                let mut words = rest[d + "#define".len()..line_end]
+                    .trim()
+                    .splitn(2, char::is_whitespace);
+                if let (Some(name), Some(expr)) = (words.next(), words.next()) {
+                    if let Some(val) = eval(expr, &known) {
+                        add(name, val, None, &mut known);
+                    }
+                }
+                rest = &rest[line_end..];
+            }
+            (_, Some(e)) => {
+                let (open, close) = match rest[e..].find('{').and_then(|open| {
+                    rest[e + open..]
+                        .find('}')
+                        .map(|close| (e + open, e + open + close))
+                }) {
+                    Some(span) => span,
+                    None => break,
+                };
+                let mut next = 0;
+                let body = rest[open + 1..close]
+                    .lines()
+                    .filter(|line| !line.trim_start().starts_with('#'))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                for entry in body.split(',') {
+                    let mut parts = entry.splitn(2, '=');
+                    let name = parts.next().unwrap_or("").trim();
+                    if name.is_empty() {
+                        continue;
+                    }
+                    let val = match parts.next() {
+                        Some(expr) => match eval(expr, &known) {
+                            Some(val) => val,
+                            // Later implicit values depend on this one, so stop guessing
+                            None => break,
+                        },
+                        None => next,
+                    };
+                    add(name, val, Some(blocks), &mut known);
+                    next = val + 1;
+                }
+                blocks += 1;
+                rest = &rest[close + 1..];
+            }
+            _ => break,
+        }
+    }
+    constants
+}
+
+fn find_enum(s: &str) -> Option<usize> {
+    let mut offset = 0;
+    while let Some(i) = s[offset..].find("enum") {
+        let start = offset + i;
+        let end = start + "enum".len();
+        let before_ok = s[..start]
+            .chars()
+            .last()
+            .map(|c| !c.is_alphanumeric() && c != '_')
+            .unwrap_or(true);
+        // Only enum definitions: an optional tag followed by the opening brace
+        let after_ok = s[end..]
+            .find(|c: char| !c.is_alphanumeric() && c != '_' && !c.is_whitespace())
+            .map(|i| s[end..].starts_with(char::is_whitespace) && s[end + i..].starts_with('{'))
+            .unwrap_or(false)
+            || s[end..].starts_with('{');
+        if before_ok && after_ok {
+            return Some(start);
+        }
+        offset = end;
+    }
+    None
+}
+
+fn eval(expr: &str, known: &HashMap<String, i64>) -> Option<i64> {
+    let expr = expr.trim();
+    if expr.starts_with('(') && expr.ends_with(')') {
+        return eval(&expr[1..expr.len() - 1], known);
+    }
+    // Operators from loosest to tightest binding
+    for op in &["|", "<<", "+", "-"] {
+        if let Some(i) = expr.rfind(op) {
+            if i > 0 {
+                let lhs = eval(&expr[..i], known)?;
+                let rhs = eval(&expr[i + op.len()..], known)?;
+                return match *op {
+                    "<<" => Some(lhs << rhs),
+                    "|" => Some(lhs | rhs),
+                    "+" => Some(lhs + rhs),
+                    _ => Some(lhs - rhs),
+                };
+            }
+        }
+    }
+    if let Some(val) = known.get(expr) {
+        return Some(*val);
+    }
+    let literal = expr.trim_end_matches(&['u', 'U', 'l', 'L'][..]);
+    if literal.starts_with("0x") || literal.starts_with("0X") {
+        i64::from_str_radix(&literal[2..], 16).ok()
+    } else {
+        literal.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const HEADER: &str = r#"
+#define GENL_ID_CTRL		NLMSG_MIN_TYPE
+#define CTRL_FLAG_A 0x1 /* first flag */
+#define CTRL_FLAG_B (1U << 3)
+
+enum {
+	CTRL_ATTR_UNSPEC,
+	CTRL_ATTR_FAMILY_ID,
+	CTRL_ATTR_FAMILY_NAME, // name
+#define CTRL_ATTR_FAMILY_NAME CTRL_ATTR_FAMILY_NAME
+	CTRL_ATTR_MCAST_GROUPS = 7,
+	CTRL_ATTR_OP,
+	__CTRL_ATTR_MAX,
+};
+
+#define CTRL_ATTR_MAX (__CTRL_ATTR_MAX - 1)
+
+enum {
+	CTRL_ATTR_OP_UNSPEC,
+	CTRL_ATTR_OP_ID,
+	__CTRL_ATTR_OP_MAX,
+};
+
+struct ctrl_op {
+	enum ctrl_op_kind kind;
+};
+
+enum ctrl_flags { CTRL_FLAG_C = CTRL_FLAG_A | CTRL_FLAG_B };
+"#;
+
+    #[test]
+    fn test_generate() {
+        assert_eq!(
+            generate(HEADER, "CtrlAttr", "u16", Some("NlAttrType"), "CTRL_ATTR_").unwrap(),
+            "impl_var_trait!(
+    /// Generated from `CTRL_ATTR_*` constants
+    CtrlAttr, u16, NlAttrType,
+    Unspec => 0,
+    FamilyId => 1,
+    FamilyName => 2,
+    McastGroups => 7,
+    Op => 8
+);
+"
+        );
+        assert_eq!(
+            generate(HEADER, "CtrlFlag", "u32", None, "CTRL_FLAG_").unwrap(),
+            "impl_var!(
+    /// Generated from `CTRL_FLAG_*` constants
+    CtrlFlag, u32,
+    A => 1,
+    B => 8,
+    C => 9
+);
+"
+        );
+        assert!(generate(HEADER, "Genl", "u16", None, "GENL_ID_").is_err());
+    }
+}
@@ -0,0 +1,217 @@
+//! This module provides a structured representation of parsed messages for scripting layers and
+//! debugging tools that want to inspect netlink traffic without knowing the Rust types involved.
+//!
+//! Any parsed message can be converted into an `NlValue` tree with `ToNlValue::to_nl_value`.
+//! Structs become maps keyed by the C field names, constants keep both their name and numeric
+//! value and attribute payloads stay raw bytes, as their format depends on the attribute type.
+//!
+//! With the `serde` feature, `NlValue` implements `serde::Serialize` so the tree can be passed to
+//! `serde_json` or any other serde format.
+//!
+//! # Design decisions
+//!
+//! `ToNlValue` is implemented for every enum generated by `impl_var!` so user-defined constants
+//! work out of the box. It is a separate trait rather than a requirement of `Nl` so that existing
+//! `Nl` implementations outside of this crate keep compiling.
+
+use std::ffi::CString;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Structured value of a parsed message or one of its fields
+#[derive(Clone, Debug, PartialEq)]
+pub enum NlValue {
+    /// Absent optional field
+    Null,
+    /// Signed integer
+    Int(i64),
+    /// Unsigned integer
+    Uint(u64),
+    /// Text
+    Str(String),
+    /// Raw bytes
+    Bytes(Vec<u8>),
+    /// Constant with its name, if the value is known, and its numeric value
+    Const {
+        /// Name of the constant
+        name: Option<String>,
+        /// Numeric value of the constant, wide enough for both signed and unsigned 64-bit constants
+        value: i128,
+    },
+    /// Sequence of values such as flags or attributes
+    List(Vec<NlValue>),
+    /// Named fields in declaration order
+    Map(Vec<(String, NlValue)>),
+}
+
+impl NlValue {
+    /// Build a `List` from a slice of convertible values
+    pub fn list<T>(items: &[T]) -> Self
+    where
+        T: ToNlValue,
+    {
+        NlValue::List(items.iter().map(ToNlValue::to_nl_value).collect())
+    }
+
+    /// Build a `Map` from field names and values
+    pub fn map(fields: Vec<(&str, NlValue)>) -> Self {
+        NlValue::Map(
+            fields
+                .into_iter()
+                .map(|(name, val)| (name.to_string(), val))
+                .collect(),
+        )
+    }
+
+    /// Look up a field of a `Map` by name
+    pub fn get(&self, field: &str) -> Option<&NlValue> {
+        match *self {
+            NlValue::Map(ref fields) => fields
+                .iter()
+                .find(|(name, _)| name == field)
+                .map(|(_, val)| val),
+            _ => None,
+        }
+    }
+}
+
+/// Conversion of parsed messages into an `NlValue` tree
+pub trait ToNlValue {
+    /// Convert `self` into a structured value
+    fn to_nl_value(&self) -> NlValue;
+}
+
+macro_rules! impl_to_nl_value {
+    ( $variant:ident, $conv:ty, $( $ty:ty ),* ) => {
+        $(
+            impl ToNlValue for $ty {
+                fn to_nl_value(&self) -> NlValue {
+                    NlValue::$variant(<$conv>::from(*self))
+                }
+            }
+        )*
+    };
+}
+
+impl_to_nl_value!(Uint, u64, u8, u16, u32, u64);
+impl_to_nl_value!(Int, i64, i8, i16, i32, i64);
+
+impl ToNlValue for String {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::Str(self.clone())
+    }
+}
+
+impl ToNlValue for CString {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::Str(self.to_string_lossy().into_owned())
+    }
+}
+
+impl ToNlValue for Vec<u8> {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::Bytes(self.clone())
+    }
+}
+
+impl<const N: usize> ToNlValue for [u8; N] {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::Bytes(self.to_vec())
+    }
+}
+
+macro_rules! impl_addr_to_nl_value {
+    ( $( $ty:ty ),* ) => {
+        $(
+            impl ToNlValue for $ty {
+                fn to_nl_value(&self) -> NlValue {
+                    NlValue::Str(self.to_string())
+                }
+            }
+        )*
+    };
+}
+
+impl_addr_to_nl_value!(IpAddr, Ipv4Addr, Ipv6Addr);
+
+impl ToNlValue for SystemTime {
+    fn to_nl_value(&self) -> NlValue {
+        let since_epoch = self.duration_since(UNIX_EPOCH).unwrap_or_default();
+        NlValue::map(vec![
+            ("sec", since_epoch.as_secs().to_nl_value()),
+            ("usec", since_epoch.subsec_micros().to_nl_value()),
+        ])
+    }
+}
+
+impl<T> ToNlValue for Option<T>
+where
+    T: ToNlValue,
+{
+    fn to_nl_value(&self) -> NlValue {
+        match *self {
+            Some(ref val) => val.to_nl_value(),
+            None => NlValue::Null,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for NlValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ::serde::Serializer,
+    {
+        use serde::ser::{SerializeMap, SerializeSeq};
+
+        match *self {
+            NlValue::Null => serializer.serialize_none(),
+            NlValue::Int(i) => serializer.serialize_i64(i),
+            NlValue::Uint(u) => serializer.serialize_u64(u),
+            NlValue::Str(ref s) => serializer.serialize_str(s),
+            NlValue::Bytes(ref b) => serializer.serialize_bytes(b),
+            NlValue::Const {
+                ref name,
+                ref value,
+            } => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("name", name)?;
+                map.serialize_entry("value", value)?;
+                map.end()
+            }
+            NlValue::List(ref items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(item)?;
+                }
+                seq.end()
+            }
+            NlValue::Map(ref fields) => {
+                let mut map = serializer.serialize_map(Some(fields.len()))?;
+                for (name, val) in fields {
+                    map.serialize_entry(name, val)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+    use super::*;
+
+    use consts::NlmF;
+
+    #[test]
+    fn test_serialize_json() {
+        let val = NlValue::map(vec![
+            ("flags", NlValue::list(&[NlmF::Request])),
+            ("missing", None::<u32>.to_nl_value()),
+        ]);
+        assert_eq!(
+            serde_json::to_string(&val).unwrap(),
+            r#"{"flags":[{"name":"Request","value":1}],"missing":null}"#
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! This module loads golden byte vectors of netlink messages from text files, so that the wire
+//! format can be checked against messages captured from real kernels.
+//!
+//! Vectors are stored below a root directory as `<protocol>/<kernel>/<name>.hex`, such as
+//! `rtnl/linux-6.18/newlink.hex`. Each file holds the bytes of one message as whitespace
+//! separated hexadecimal pairs. Lines starting with `#` are comments, usually describing the
+//! message and the type it is parsed as.
+//!
+//! The vectors checked by the tests of `neli` itself are shipped with the crate in the directory
+//! given by `VECTORS_DIR`. Crates implementing their own protocols or families on top of `neli`
+//! can keep vectors in the same layout and run them through `load` and `TestVector::roundtrip`.
+//!
+//! ```no_run
+//! use neli::consts::Rtm;
+//! use neli::nl::Nlmsghdr;
+//! use neli::rtnl::Ifinfomsg;
+//! use neli::vectors;
+//!
+//! for vector in vectors::load(vectors::VECTORS_DIR).unwrap() {
+//!     if vector.protocol == "rtnl" && vector.name == "newlink" {
+//!         vector.roundtrip::<Nlmsghdr<Rtm, Ifinfomsg>>().unwrap();
+//!     }
+//! }
+//! ```
+//!
+//! # Design decisions
+//!
+//! Vectors are text rather than binary files so that changes to them can be reviewed in diffs.
+//! They are captured on little endian machines and always parsed in little endian byte order
+//! with `endian::with_endian`, so that they can be checked on any host.
+
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::Path;
+
+use buffering::{StreamReadBuffer, StreamWriteBuffer};
+
+use endian::{self, Endian};
+use err::NlError;
+use Nl;
+
+/// Directory of the vectors shipped with `neli`
+pub const VECTORS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/vectors");
+
+/// Message bytes loaded from a vector file
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestVector {
+    /// Protocol the message belongs to, from the first directory level
+    pub protocol: String,
+    /// Kernel the message was captured from, from the second directory level
+    pub kernel: String,
+    /// Name of the vector, from the file name without the `.hex` extension
+    pub name: String,
+    /// Bytes of the message
+    pub bytes: Vec<u8>,
+}
+
+impl TestVector {
+    /// Deserialize the vector as a `T`, which has to consume all of the bytes
+    pub fn parse<T>(&self) -> Result<T, NlError>
+    where
+        T: Nl,
+    {
+        endian::with_endian(Endian::Little, || {
+            let mut mem = StreamReadBuffer::new(&self.bytes);
+            let parsed = T::deserialize(&mut mem)?;
+            if !mem.at_end() {
+                return Err(NlError::Msg(format!(
+                    "{} did not consume the whole vector",
+                    self
+                )));
+            }
+            Ok(parsed)
+        })
+    }
+
+    /// Deserialize the vector as a `T` and check that serializing it again gives the same bytes
+    pub fn roundtrip<T>(&self) -> Result<T, NlError>
+    where
+        T: Nl,
+    {
+        let parsed = self.parse::<T>()?;
+        let out = endian::with_endian(Endian::Little, || {
+            let mut mem = StreamWriteBuffer::new_growable(Some(self.bytes.len()));
+            parsed.serialize(&mut mem).map(|_| mem)
+        })?;
+        if out.as_ref() != &self.bytes[..] {
+            return Err(NlError::Msg(format!(
+                "Serializing {} gives {:?} instead",
+                self,
+                out.as_ref()
+            )));
+        }
+        Ok(parsed)
+    }
+}
+
+impl Display for TestVector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}/{}", self.protocol, self.kernel, self.name)
+    }
+}
+
+/// Parse the contents of a vector file
+pub fn parse_hex(text: &str) -> Result<Vec<u8>, NlError> {
+    text.lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .flat_map(|line| line.split_whitespace())
+        .map(|byte| {
+            if byte.len() != 2 {
+                return Err(NlError::Msg(format!("Invalid byte {:?}", byte)));
+            }
+            u8::from_str_radix(byte, 16)
+                .map_err(|_| NlError::Msg(format!("Invalid byte {:?}", byte)))
+        })
+        .collect()
+}
+
+/// Load all vectors below `root`, sorted by protocol, kernel and name
+pub fn load<P>(root: P) -> Result<Vec<TestVector>, NlError>
+where
+    P: AsRef<Path>,
+{
+    let mut vectors = Vec::new();
+    for protocol in fs::read_dir(root)? {
+        let protocol = protocol?;
+        if !protocol.file_type()?.is_dir() {
+            continue;
+        }
+        for kernel in fs::read_dir(protocol.path())? {
+            let kernel = kernel?;
+            if !kernel.file_type()?.is_dir() {
+                continue;
+            }
+            for file in fs::read_dir(kernel.path())? {
+                let path = file?.path();
+                if path.extension().and_then(|ext| ext.to_str()) != Some("hex") {
+                    continue;
+                }
+                let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+                    Some(name) => name.to_string(),
+                    None => continue,
+                };
+                let bytes = parse_hex(&fs::read_to_string(&path)?)
+                    .map_err(|e| NlError::Msg(format!("{}: {}", path.display(), e)))?;
+                vectors.push(TestVector {
+                    protocol: protocol.file_name().to_string_lossy().into_owned(),
+                    kernel: kernel.file_name().to_string_lossy().into_owned(),
+                    name,
+                    bytes,
+                });
+            }
+        }
+    }
+    vectors
+        .sort_by(|a, b| (&a.protocol, &a.kernel, &a.name).cmp(&(&b.protocol, &b.kernel, &b.name)));
+    Ok(vectors)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use consts::netfilter::{NetfilterMsg, NfLogCfg};
+    use consts::{CtrlAttr, CtrlCmd, GenlId, Nlmsg, Rtm};
+    use err::Nlmsgerr;
+    use genl::Genlmsghdr;
+    use netfilter::{LogConfigMode, LogPacket};
+    use nl::Nlmsghdr;
+    use nlattr::Nlattr;
+    use rtnl::{Ifaddrmsg, Ifinfomsg};
+    use test_utils::assert_vector;
+
+    #[test]
+    fn test_vectors() {
+        let vectors = load(VECTORS_DIR).unwrap();
+        assert!(!vectors.is_empty());
+        for vector in vectors {
+            match (vector.protocol.as_str(), vector.name.as_str()) {
+                ("nl", "ack") => {
+                    assert_vector::<Nlmsghdr<Nlmsg, Nlmsgerr<Nlmsg>>>(&vector);
+                }
+                ("nl", "done") => {
+                    assert_vector::<Nlmsghdr<Nlmsg, i32>>(&vector);
+                }
+                ("rtnl", "newlink") => {
+                    assert_vector::<Nlmsghdr<Rtm, Ifinfomsg>>(&vector);
+                }
+                ("rtnl", "newaddr") => {
+                    assert_vector::<Nlmsghdr<Rtm, Ifaddrmsg>>(&vector);
+                }
+                ("genl", "newfamily") => {
+                    assert_vector::<Nlmsghdr<GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>>>(&vector);
+                }
+                ("netfilter", "log_mode") => {
+                    assert_vector::<Nlattr<NfLogCfg, LogConfigMode>>(&vector);
+                }
+                // Log packets are never sent to the kernel so they can only be deserialized
+                ("netfilter", "log_packet") => {
+                    vector.parse::<Nlmsghdr<NetfilterMsg, LogPacket>>().unwrap();
+                }
+                _ => panic!("No type known for vector {}", vector),
+            }
+        }
+
+        assert_eq!(
+            parse_hex("# comment\n01 ff\n 0a").unwrap(),
+            vec![1, 0xff, 0xa]
+        );
+        assert!(parse_hex("1ff").is_err());
+    }
+}
@@ -0,0 +1,625 @@
+//! Typed attributes of the WireGuard generic netlink family
+//!
+//! `WgCmd::GetDevice` dumps the configuration of a device selected by `WgdeviceAttr::Ifindex` or
+//! `WgdeviceAttr::Ifname`, and `WgCmd::SetDevice` changes it. Keys are held as `WgKey`, which
+//! parses and prints the base64 encoding used by `wg(8)`, peers as `WgPeer` with their endpoints
+//! and allowed IPs, and the configuration of the device itself as `WgDevice`.
+//!
+//! ```no_run
+//! use neli::consts::wireguard::{WgCmd, WgdeviceAttr, Wireguard};
+//! use neli::consts::{NlFamily, NlmF};
+//! use neli::genl::{GenlFamilyCache, Genlmsghdr};
+//! use neli::nlattr::Nlattr;
+//! use neli::socket::NlSocket;
+//! use neli::wireguard::{WgDevice, WgPeer};
+//!
+//! let mut socket = NlSocket::connect(NlFamily::Generic, None, None, true).unwrap();
+//! let mut cache = GenlFamilyCache::new();
+//! let attrs = vec![Nlattr::new(None, WgdeviceAttr::Ifname, "wg0").unwrap()];
+//! let flags = vec![NlmF::Request, NlmF::Dump];
+//! let msg = cache
+//!     .request::<Wireguard>(&mut socket, WgCmd::GetDevice, flags, attrs)
+//!     .unwrap();
+//! let replies = socket
+//!     .request::<_, _, u16, Genlmsghdr<WgCmd, WgdeviceAttr>>(msg)
+//!     .unwrap();
+//! let mut device = WgDevice::from_genl(&replies[0].nl_payload).unwrap();
+//! // Further messages carry more peers, starting with the last one if its allowed IPs did not fit
+//! for reply in &replies[1..] {
+//!     for peer in WgPeer::from_device(&reply.nl_payload).unwrap() {
+//!         match device.peers.last_mut() {
+//!             Some(last) if last.public_key == peer.public_key => {
+//!                 last.allowed_ips.extend(peer.allowed_ips)
+//!             }
+//!             _ => device.peers.push(peer),
+//!         }
+//!     }
+//! }
+//! println!("{:?} listening on {:?}", device.ifname, device.listen_port);
+//! for peer in device.peers {
+//!     println!("{} {:?}", peer.public_key, peer.endpoint);
+//! }
+//! ```
+//!
+//! # Design decisions
+//!
+//! Peers and allowed IPs are sent as lists of nested attributes whose own types are ignored by
+//! the kernel, so `WgPeer` and `WgAllowedIp` convert from and to single list entries instead of
+//! implementing `Nl`. The kernel sets `NLA_F_NESTED` on the nested attributes it sends, which is
+//! masked out when looking them up. The configuration of a device with many peers is split over
+//! several messages of a dump, each carrying a part of the peers.
+
+use std::convert::TryFrom;
+use std::fmt::{self, Display};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::str::FromStr;
+
+use consts::wireguard::{
+    WgCmd, WgallowedipAttr, WgdeviceAttr, WgdeviceF, WgdeviceFlags, WgpeerAttr, WgpeerF,
+    WgpeerFlags, Wireguard, WG_KEY_LEN,
+};
+use consts::NlAttrType;
+use err::{DeError, SerError};
+use genl::{GenlFamily, Genlmsghdr};
+use nlattr::Nlattr;
+use time::KernelTimespec;
+use value::{NlValue, ToNlValue};
+use {BeU16, BeU32, Buffer, BufferMut, Nl, StreamReadBuffer};
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Size of `struct sockaddr_in`
+const SOCKADDR_IN_LEN: usize = 16;
+/// Size of `struct sockaddr_in6`
+const SOCKADDR_IN6_LEN: usize = 28;
+
+/// Type of an attribute without the `NLA_F_NESTED` and `NLA_F_NET_BYTEORDER` flags
+fn attr_type<T>(attr: &Nlattr<T, Vec<u8>>) -> u16
+where
+    T: NlAttrType + Clone,
+{
+    let ty: u16 = attr.nla_type.clone().into();
+    ty & libc::NLA_TYPE_MASK as u16
+}
+
+/// Payload of the first attribute of type `ty` in `attrs`, if any
+fn find_attr<T, R>(attrs: &[Nlattr<T, Vec<u8>>], ty: T) -> Result<Option<R>, DeError>
+where
+    T: NlAttrType + Clone,
+    R: Nl,
+{
+    let ty: u16 = ty.into();
+    attrs
+        .iter()
+        .find(|attr| attr_type(attr) == ty)
+        .map(|attr| attr.get_payload_as())
+        .transpose()
+}
+
+/// Entries of the list of nested attributes of type `ty` in `attrs`
+fn find_list<T>(attrs: &[Nlattr<T, Vec<u8>>], ty: T) -> Result<Vec<Nlattr<u16, Vec<u8>>>, DeError>
+where
+    T: NlAttrType + Clone,
+{
+    let ty: u16 = ty.into();
+    match attrs.iter().find(|attr| attr_type(attr) == ty) {
+        Some(attr) => Vec::deserialize(&mut StreamReadBuffer::new(&attr.payload)),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Public, private or preshared key of a WireGuard device or peer
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct WgKey(pub [u8; WG_KEY_LEN]);
+
+impl Display for WgKey {
+    /// Base64 encoding of the key, as printed by `wg(8)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for chunk in self.0.chunks(3) {
+            let bits = chunk
+                .iter()
+                .enumerate()
+                .fold(0u32, |bits, (i, b)| bits | (u32::from(*b) << (16 - 8 * i)));
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    let index = (bits >> (18 - 6 * i)) & 0x3f;
+                    write!(f, "{}", BASE64[index as usize] as char)?;
+                } else {
+                    write!(f, "=")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for WgKey {
+    type Err = DeError;
+
+    /// Parse the base64 encoding of a key, as accepted by `wg(8)`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || DeError::Msg(format!("{:?} is not a base64 encoded key", s));
+        let s = s.trim_end_matches('=');
+        let mut bytes = Vec::with_capacity(WG_KEY_LEN + 2);
+        for chunk in s.as_bytes().chunks(4) {
+            let mut bits = 0u32;
+            for (i, c) in chunk.iter().enumerate() {
+                let index = BASE64.iter().position(|b| b == c).ok_or_else(invalid)?;
+                bits |= (index as u32) << (18 - 6 * i);
+            }
+            let len = chunk.len() * 6 / 8;
+            bytes.extend((0..len).map(|i| (bits >> (16 - 8 * i)) as u8));
+        }
+        let key = <[u8; WG_KEY_LEN]>::try_from(bytes.as_slice()).map_err(|_| invalid())?;
+        Ok(WgKey(key))
+    }
+}
+
+impl Nl for WgKey {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.0.serialize(mem)
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        Ok(WgKey(<[u8; WG_KEY_LEN]>::deserialize(mem)?))
+    }
+
+    fn size(&self) -> usize {
+        WG_KEY_LEN
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(WG_KEY_LEN)
+    }
+}
+
+impl ToNlValue for WgKey {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::Bytes(self.0.to_vec())
+    }
+}
+
+/// Endpoint of a peer in `WgpeerAttr::Endpoint`, a `struct sockaddr_in` or `struct sockaddr_in6`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct WgEndpoint(pub SocketAddr);
+
+impl Nl for WgEndpoint {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        match self.0 {
+            SocketAddr::V4(addr) => {
+                (libc::AF_INET as u16).serialize(mem)?;
+                BeU16(addr.port()).serialize(mem)?;
+                addr.ip().serialize(mem)?;
+                mem.write_all(&[0; 8])?;
+            }
+            SocketAddr::V6(addr) => {
+                (libc::AF_INET6 as u16).serialize(mem)?;
+                BeU16(addr.port()).serialize(mem)?;
+                BeU32(addr.flowinfo()).serialize(mem)?;
+                addr.ip().serialize(mem)?;
+                addr.scope_id().serialize(mem)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        let size_hint = mem.take_size_hint();
+        let family = u16::deserialize(mem)?;
+        let (addr, len) = match i32::from(family) {
+            libc::AF_INET => {
+                let port = BeU16::deserialize(mem)?.0;
+                let ip = Ipv4Addr::deserialize(mem)?;
+                mem.read_exact(&mut [0; 8])?;
+                (SocketAddr::V4(SocketAddrV4::new(ip, port)), SOCKADDR_IN_LEN)
+            }
+            libc::AF_INET6 => {
+                let port = BeU16::deserialize(mem)?.0;
+                let flowinfo = BeU32::deserialize(mem)?.0;
+                let ip = Ipv6Addr::deserialize(mem)?;
+                let scope_id = u32::deserialize(mem)?;
+                let addr = SocketAddrV6::new(ip, port, flowinfo, scope_id);
+                (SocketAddr::V6(addr), SOCKADDR_IN6_LEN)
+            }
+            _ => {
+                return Err(DeError::Msg(format!(
+                    "Invalid endpoint address family {}",
+                    family
+                )))
+            }
+        };
+        match size_hint {
+            Some(size_hint) if size_hint != len => Err(DeError::Msg(format!(
+                "Endpoint of {} bytes does not match its address family",
+                size_hint
+            ))),
+            _ => Ok(WgEndpoint(addr)),
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self.0 {
+            SocketAddr::V4(_) => SOCKADDR_IN_LEN,
+            SocketAddr::V6(_) => SOCKADDR_IN6_LEN,
+        }
+    }
+}
+
+impl ToNlValue for WgEndpoint {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::Str(self.0.to_string())
+    }
+}
+
+/// Range of addresses routed to a peer, an entry of `WgpeerAttr::Allowedips`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct WgAllowedIp {
+    /// Address of the range
+    pub addr: IpAddr,
+    /// Prefix length of the range
+    pub cidr: u8,
+}
+
+impl WgAllowedIp {
+    /// Create an allowed IP range
+    pub fn new(addr: IpAddr, cidr: u8) -> Self {
+        WgAllowedIp { addr, cidr }
+    }
+
+    /// Build the nested attribute of a list entry
+    pub fn to_attr(&self) -> Result<Nlattr<u16, Vec<u8>>, SerError> {
+        let family = match self.addr {
+            IpAddr::V4(_) => libc::AF_INET as u16,
+            IpAddr::V6(_) => libc::AF_INET6 as u16,
+        };
+        let mut attr = Nlattr::new(None, 0u16, Vec::<u8>::new())?;
+        attr.add_nested_attribute(&Nlattr::new(None, WgallowedipAttr::Family, family)?)?;
+        attr.add_nested_attribute(&Nlattr::new(None, WgallowedipAttr::Ipaddr, self.addr)?)?;
+        attr.add_nested_attribute(&Nlattr::new(None, WgallowedipAttr::CidrMask, self.cidr)?)?;
+        Ok(attr)
+    }
+
+    /// Parse the nested attribute of a list entry
+    pub fn from_attr(attr: &Nlattr<u16, Vec<u8>>) -> Result<Self, DeError> {
+        let handle = attr.get_nested_attributes::<WgallowedipAttr>()?;
+        let attrs = handle.get_slice();
+        let addr = find_attr::<_, IpAddr>(attrs, WgallowedipAttr::Ipaddr)?
+            .ok_or_else(|| DeError::new("Allowed IP without an address"))?;
+        let cidr = find_attr(attrs, WgallowedipAttr::CidrMask)?
+            .ok_or_else(|| DeError::new("Allowed IP without a prefix length"))?;
+        Ok(WgAllowedIp { addr, cidr })
+    }
+}
+
+/// Configuration and statistics of a peer, an entry of `WgdeviceAttr::Peers`
+///
+/// Fields set to `None` are left out of requests so that the kernel keeps their current values.
+/// The statistics are only reported by the kernel and are never sent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WgPeer {
+    /// Public key identifying the peer
+    pub public_key: WgKey,
+    /// Preshared key, all zeros to remove it
+    pub preshared_key: Option<WgKey>,
+    /// Flags of the request, such as `WgpeerF::ReplaceAllowedips`
+    pub flags: Vec<WgpeerF>,
+    /// Address of the peer
+    pub endpoint: Option<WgEndpoint>,
+    /// Interval of keepalive packets in seconds, 0 to disable them
+    pub persistent_keepalive_interval: Option<u16>,
+    /// Address ranges routed to the peer, added to the current ones unless
+    /// `WgpeerF::ReplaceAllowedips` is set
+    pub allowed_ips: Vec<WgAllowedIp>,
+    /// Time of the last handshake, all zeros if there was none
+    pub last_handshake_time: Option<KernelTimespec>,
+    /// Bytes received from the peer
+    pub rx_bytes: Option<u64>,
+    /// Bytes sent to the peer
+    pub tx_bytes: Option<u64>,
+    /// Version of the WireGuard protocol used with the peer
+    pub protocol_version: Option<u32>,
+}
+
+impl WgPeer {
+    /// Create a peer with only its public key set
+    pub fn new(public_key: WgKey) -> Self {
+        WgPeer {
+            public_key,
+            preshared_key: None,
+            flags: Vec::new(),
+            endpoint: None,
+            persistent_keepalive_interval: None,
+            allowed_ips: Vec::new(),
+            last_handshake_time: None,
+            rx_bytes: None,
+            tx_bytes: None,
+            protocol_version: None,
+        }
+    }
+
+    /// Build the nested attribute of a list entry
+    pub fn to_attr(&self) -> Result<Nlattr<u16, Vec<u8>>, SerError> {
+        let mut attr = Nlattr::new(None, 0u16, Vec::<u8>::new())?;
+        attr.add_nested_attribute(&Nlattr::new(None, WgpeerAttr::PublicKey, self.public_key)?)?;
+        if let Some(key) = self.preshared_key {
+            attr.add_nested_attribute(&Nlattr::new(None, WgpeerAttr::PresharedKey, key)?)?;
+        }
+        if !self.flags.is_empty() {
+            let flags = WgpeerFlags::new(&self.flags).bits();
+            attr.add_nested_attribute(&Nlattr::new(None, WgpeerAttr::Flags, flags)?)?;
+        }
+        if let Some(endpoint) = self.endpoint {
+            attr.add_nested_attribute(&Nlattr::new(None, WgpeerAttr::Endpoint, endpoint)?)?;
+        }
+        if let Some(interval) = self.persistent_keepalive_interval {
+            attr.add_nested_attribute(&Nlattr::new(
+                None,
+                WgpeerAttr::PersistentKeepaliveInterval,
+                interval,
+            )?)?;
+        }
+        if !self.allowed_ips.is_empty() {
+            let mut list = Nlattr::new(None, WgpeerAttr::Allowedips, Vec::<u8>::new())?;
+            for allowed_ip in self.allowed_ips.iter() {
+                list.add_nested_attribute(&allowed_ip.to_attr()?)?;
+            }
+            attr.add_nested_attribute(&list)?;
+        }
+        Ok(attr)
+    }
+
+    /// Parse the nested attribute of a list entry
+    pub fn from_attr(attr: &Nlattr<u16, Vec<u8>>) -> Result<Self, DeError> {
+        let handle = attr.get_nested_attributes::<WgpeerAttr>()?;
+        let attrs = handle.get_slice();
+        let public_key = find_attr(attrs, WgpeerAttr::PublicKey)?
+            .ok_or_else(|| DeError::new("Peer without a public key"))?;
+        let flags = find_attr::<_, u32>(attrs, WgpeerAttr::Flags)?
+            .map(|bits| WgpeerFlags::from_bits(bits).into_vec())
+            .unwrap_or_default();
+        let allowed_ips = find_list(attrs, WgpeerAttr::Allowedips)?
+            .iter()
+            .map(WgAllowedIp::from_attr)
+            .collect::<Result<_, _>>()?;
+        Ok(WgPeer {
+            public_key,
+            preshared_key: find_attr(attrs, WgpeerAttr::PresharedKey)?,
+            flags,
+            endpoint: find_attr(attrs, WgpeerAttr::Endpoint)?,
+            persistent_keepalive_interval: find_attr(
+                attrs,
+                WgpeerAttr::PersistentKeepaliveInterval,
+            )?,
+            allowed_ips,
+            last_handshake_time: find_attr(attrs, WgpeerAttr::LastHandshakeTime)?,
+            rx_bytes: find_attr(attrs, WgpeerAttr::RxBytes)?,
+            tx_bytes: find_attr(attrs, WgpeerAttr::TxBytes)?,
+            protocol_version: find_attr(attrs, WgpeerAttr::ProtocolVersion)?,
+        })
+    }
+
+    /// Build the `WgdeviceAttr::Peers` attribute of a `WgCmd::SetDevice` request
+    pub fn list_attr(peers: &[WgPeer]) -> Result<Nlattr<WgdeviceAttr, Vec<u8>>, SerError> {
+        let mut list = Nlattr::new(None, WgdeviceAttr::Peers, Vec::<u8>::new())?;
+        for peer in peers {
+            list.add_nested_attribute(&peer.to_attr()?)?;
+        }
+        Ok(list)
+    }
+
+    /// Peers in the `WgdeviceAttr::Peers` attribute of a `WgCmd::GetDevice` reply
+    pub fn from_device(genl: &Genlmsghdr<WgCmd, WgdeviceAttr>) -> Result<Vec<Self>, DeError> {
+        find_list(genl.get_attr_handle().get_slice(), WgdeviceAttr::Peers)?
+            .iter()
+            .map(WgPeer::from_attr)
+            .collect()
+    }
+}
+
+/// Configuration of a WireGuard device, the attributes of `WgCmd::GetDevice` replies and
+/// `WgCmd::SetDevice` requests
+///
+/// Fields set to `None` are left out of requests so that the kernel keeps their current values.
+/// The public key is derived from the private key by the kernel and is never sent.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WgDevice {
+    /// Index of the device
+    pub ifindex: Option<u32>,
+    /// Name of the device
+    pub ifname: Option<String>,
+    /// Private key, all zeros to remove it
+    pub private_key: Option<WgKey>,
+    /// Public key derived from the private key
+    pub public_key: Option<WgKey>,
+    /// Flags of the request, such as `WgdeviceF::ReplacePeers`
+    pub flags: Vec<WgdeviceF>,
+    /// UDP port to listen on, 0 to pick a random one
+    pub listen_port: Option<u16>,
+    /// Firewall mark of outgoing packets, 0 to disable it
+    pub fwmark: Option<u32>,
+    /// Peers of the device, added to or updating the current ones unless
+    /// `WgdeviceF::ReplacePeers` is set
+    pub peers: Vec<WgPeer>,
+}
+
+impl WgDevice {
+    /// Create a configuration selecting the device named `ifname` and changing nothing
+    pub fn new(ifname: &str) -> Self {
+        WgDevice {
+            ifname: Some(ifname.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Build a `WgCmd::SetDevice` request
+    pub fn to_genl(&self) -> Result<Genlmsghdr<WgCmd, WgdeviceAttr>, SerError> {
+        let mut attrs = Vec::new();
+        if let Some(ifindex) = self.ifindex {
+            attrs.push(Nlattr::new(None, WgdeviceAttr::Ifindex, ifindex)?);
+        }
+        if let Some(ref ifname) = self.ifname {
+            attrs.push(Nlattr::new(None, WgdeviceAttr::Ifname, ifname.clone())?);
+        }
+        if let Some(key) = self.private_key {
+            attrs.push(Nlattr::new(None, WgdeviceAttr::PrivateKey, key)?);
+        }
+        if !self.flags.is_empty() {
+            let flags = WgdeviceFlags::new(&self.flags).bits();
+            attrs.push(Nlattr::new(None, WgdeviceAttr::Flags, flags)?);
+        }
+        if let Some(port) = self.listen_port {
+            attrs.push(Nlattr::new(None, WgdeviceAttr::ListenPort, port)?);
+        }
+        if let Some(fwmark) = self.fwmark {
+            attrs.push(Nlattr::new(None, WgdeviceAttr::Fwmark, fwmark)?);
+        }
+        if !self.peers.is_empty() {
+            attrs.push(WgPeer::list_attr(&self.peers)?);
+        }
+        Genlmsghdr::new(WgCmd::SetDevice, Wireguard::VERSION, attrs)
+    }
+
+    /// Parse a message of a `WgCmd::GetDevice` reply. The peers of a device with many peers are
+    /// split over several messages of the dump, whose `peers` have to be appended to those of
+    /// the first one.
+    pub fn from_genl(genl: &Genlmsghdr<WgCmd, WgdeviceAttr>) -> Result<Self, DeError> {
+        let handle = genl.get_attr_handle();
+        let attrs = handle.get_slice();
+        let flags = find_attr::<_, u32>(attrs, WgdeviceAttr::Flags)?
+            .map(|bits| WgdeviceFlags::from_bits(bits).into_vec())
+            .unwrap_or_default();
+        Ok(WgDevice {
+            ifindex: find_attr(attrs, WgdeviceAttr::Ifindex)?,
+            ifname: find_attr(attrs, WgdeviceAttr::Ifname)?,
+            private_key: find_attr(attrs, WgdeviceAttr::PrivateKey)?,
+            public_key: find_attr(attrs, WgdeviceAttr::PublicKey)?,
+            flags,
+            listen_port: find_attr(attrs, WgdeviceAttr::ListenPort)?,
+            fwmark: find_attr(attrs, WgdeviceAttr::Fwmark)?,
+            peers: WgPeer::from_device(genl)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use test_utils::assert_roundtrip;
+
+    const KEY: &str = "yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=";
+
+    fn peer() -> WgPeer {
+        let mut peer = WgPeer::new(WgKey::from_str(KEY).unwrap());
+        peer.flags = vec![WgpeerF::ReplaceAllowedips];
+        peer.endpoint = Some(WgEndpoint("192.0.2.1:51820".parse().unwrap()));
+        peer.persistent_keepalive_interval = Some(25);
+        peer.allowed_ips = vec![
+            WgAllowedIp::new("10.0.0.0".parse().unwrap(), 24),
+            WgAllowedIp::new("2001:db8::".parse().unwrap(), 64),
+        ];
+        peer
+    }
+
+    /// `SetDevice` message whose peer list holds `entry`
+    fn device_with_peer_entry(entry: Nlattr<u16, Vec<u8>>) -> Genlmsghdr<WgCmd, WgdeviceAttr> {
+        let mut list = Nlattr::new(None, WgdeviceAttr::Peers, Vec::<u8>::new()).unwrap();
+        list.add_nested_attribute(&entry).unwrap();
+        Genlmsghdr::new(WgCmd::SetDevice, 1, vec![list]).unwrap()
+    }
+
+    #[test]
+    fn test_key() {
+        let key = WgKey::from_str(KEY).unwrap();
+        assert_eq!(key.0[..3], [0xc8, 0x09, 0xf3]);
+        assert_eq!(key.to_string(), KEY);
+        assert_eq!(assert_roundtrip(&key).len(), WG_KEY_LEN);
+    }
+
+    #[test]
+    fn test_key_invalid() {
+        assert!(WgKey::from_str("yAnz5TF+").is_err());
+        assert!(WgKey::from_str(&KEY.replace('+', "!")).is_err());
+        assert!(WgKey::from_str(&format!("{}AAAA", KEY.trim_end_matches('='))).is_err());
+    }
+
+    #[test]
+    fn test_endpoint() {
+        let endpoint = WgEndpoint("[2001:db8::1]:51820".parse().unwrap());
+        let bytes = assert_roundtrip(&endpoint);
+        assert_eq!(bytes.len(), SOCKADDR_IN6_LEN);
+        assert_eq!(&bytes[2..4], &[0xca, 0x6c]);
+        let endpoint = WgEndpoint("192.0.2.1:51820".parse().unwrap());
+        let bytes = assert_roundtrip(&endpoint);
+        assert_eq!(bytes.len(), SOCKADDR_IN_LEN);
+        assert_eq!(&bytes[4..8], &[192, 0, 2, 1]);
+    }
+
+    #[test]
+    fn test_endpoint_bad_family() {
+        let mut bytes = vec![0; SOCKADDR_IN_LEN];
+        bytes[..2].copy_from_slice(&(libc::AF_UNIX as u16).to_ne_bytes());
+        assert!(WgEndpoint::deserialize(&mut StreamReadBuffer::new(&bytes)).is_err());
+
+        // A peer with such an endpoint is rejected as a whole
+        let mut entry = Nlattr::new(None, 0u16, Vec::<u8>::new()).unwrap();
+        let key = WgKey::from_str(KEY).unwrap();
+        entry
+            .add_nested_attribute(&Nlattr::new(None, WgpeerAttr::PublicKey, key).unwrap())
+            .unwrap();
+        entry
+            .add_nested_attribute(&Nlattr::new(None, WgpeerAttr::Endpoint, bytes).unwrap())
+            .unwrap();
+        assert!(WgPeer::from_device(&device_with_peer_entry(entry)).is_err());
+    }
+
+    #[test]
+    fn test_endpoint_truncated() {
+        let endpoint = WgEndpoint("[2001:db8::1]:51820".parse().unwrap());
+        let bytes = assert_roundtrip(&endpoint);
+        let truncated = &bytes[..SOCKADDR_IN_LEN];
+        assert!(WgEndpoint::deserialize(&mut StreamReadBuffer::new(truncated)).is_err());
+    }
+
+    #[test]
+    fn test_peer() {
+        let peer = peer();
+        let list = WgPeer::list_attr(std::slice::from_ref(&peer)).unwrap();
+        let genl = Genlmsghdr::new(WgCmd::SetDevice, 1, vec![list]).unwrap();
+        assert_eq!(WgPeer::from_device(&genl).unwrap(), vec![peer]);
+    }
+
+    #[test]
+    fn test_peer_without_public_key() {
+        let entry = Nlattr::new(None, 0u16, Vec::<u8>::new()).unwrap();
+        assert!(WgPeer::from_device(&device_with_peer_entry(entry)).is_err());
+    }
+
+    #[test]
+    fn test_device() {
+        let mut device = WgDevice::new("wg0");
+        device.private_key = Some(WgKey::from_str(KEY).unwrap());
+        device.flags = vec![WgdeviceF::ReplacePeers];
+        device.listen_port = Some(51820);
+        device.fwmark = Some(0x42);
+        device.peers = vec![peer()];
+        let genl = device.to_genl().unwrap();
+        assert_eq!(genl.cmd, WgCmd::SetDevice);
+        assert_eq!(WgDevice::from_genl(&genl).unwrap(), device);
+    }
+}
@@ -0,0 +1,1533 @@
+//! IPsec security associations and policies over `NETLINK_XFRM`
+//!
+//! Security associations are added with `XfrmMsg::NewSa` or `XfrmMsg::UpdSa` messages carrying
+//! an `XfrmUsersaInfo` followed by the algorithm attributes, and policies with
+//! `XfrmMsg::NewPolicy` or `XfrmMsg::UpdPolicy` messages carrying an `XfrmUserpolicyInfo`
+//! followed by `XfrmAttr::Tmpl` attributes. Dumping `XfrmMsg::GetSa` or `XfrmMsg::GetPolicy`
+//! returns the same structures, so `ip xfrm state` and `ip xfrm policy` can be reproduced.
+//!
+//! ```no_run
+//! use std::net::{IpAddr, Ipv4Addr};
+//!
+//! use neli::consts::xfrm::{XfrmAttr, XfrmMode, XfrmMsg};
+//! use neli::consts::{NlFamily, NlmF, RtAddrFamily};
+//! use neli::nl::Nlmsghdr;
+//! use neli::nlattr::Nlattr;
+//! use neli::socket::NlSocket;
+//! use neli::xfrm::{XfrmAlgo, XfrmId, XfrmUsersaInfo};
+//!
+//! let src = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+//! let dst = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+//! let id = XfrmId::new(dst, 0x1000, libc::IPPROTO_ESP as u8);
+//! let mut sa = XfrmUsersaInfo::new(RtAddrFamily::Inet, id, src.into(), XfrmMode::Tunnel);
+//! let crypt = XfrmAlgo::new("cbc(aes)", vec![0; 16]);
+//! sa.attrs.push(Nlattr::new(None, XfrmAttr::AlgCrypt, crypt).unwrap());
+//!
+//! let mut socket = NlSocket::connect(NlFamily::Xfrm, None, None, true).unwrap();
+//! let msg = Nlmsghdr::new(None, XfrmMsg::NewSa, vec![NlmF::Request, NlmF::Ack], None, None, sa);
+//! socket.send_nl(msg).unwrap();
+//! ```
+//!
+//! # Design decisions
+//!
+//! Addresses are held as the 16 bytes of `xfrm_address_t`, as their meaning depends on the family
+//! stored next to them; `XfrmAddress` converts from and to `IpAddr`. SPIs and ports are held in
+//! host byte order and converted from and to the network byte order of the wire format. The
+//! kernel aligns `xfrm_usersa_info` and `xfrm_userpolicy_info` to their 64-bit lifetime counters,
+//! so their trailing padding depends on the alignment of `u64` on the target.
+
+use std::convert::TryFrom;
+use std::mem;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use consts::xfrm::{
+    XfrmAttr, XfrmMode, XfrmPolicyAction, XfrmPolicyDir, XfrmPolicyFlag, XfrmPolicyFlags,
+    XfrmShare, XfrmStateFlag, XfrmStateFlags,
+};
+use consts::RtAddrFamily;
+use err::{sub_len, DeError, SerError};
+use nlattr::Nlattr;
+use value::{NlValue, ToNlValue};
+use {BeU16, BeU32, Buffer, BufferMut, Nl, Padding, StreamReadBuffer, StreamWriteBuffer};
+
+/// Lifetime limit meaning no limit
+pub const XFRM_INF: u64 = !0;
+
+/// Length of the algorithm names of `XfrmAlgo`, `XfrmAlgoAuth` and `XfrmAlgoAead`
+const ALG_NAME_LEN: usize = 64;
+
+type XfrmAttrs = Vec<Nlattr<XfrmAttr, Vec<u8>>>;
+
+/// Round `len` up to the alignment of `u64`
+fn align_u64(len: usize) -> usize {
+    let align = mem::align_of::<u64>();
+    (len + align - 1) & !(align - 1)
+}
+
+/// Serialize an address family stored in 16 bits
+fn serialize_family<B>(family: &RtAddrFamily, mem: &mut B) -> Result<(), SerError>
+where
+    B: BufferMut,
+{
+    u16::from(u8::from(family)).serialize(mem)
+}
+
+/// Deserialize an address family stored in 16 bits
+fn deserialize_family<B>(mem: &mut B) -> Result<RtAddrFamily, DeError>
+where
+    B: Buffer,
+{
+    let family = u16::deserialize(mem)?;
+    u8::try_from(family)
+        .map(RtAddrFamily::from)
+        .map_err(|_| DeError::Msg(format!("Invalid address family {}", family)))
+}
+
+/// Serialize an algorithm name as a null padded array
+fn serialize_name<B>(name: &str, mem: &mut B) -> Result<(), SerError>
+where
+    B: BufferMut,
+{
+    if name.len() >= ALG_NAME_LEN {
+        return Err(SerError::new(format!(
+            "Algorithm name {:?} is longer than {} bytes",
+            name,
+            ALG_NAME_LEN - 1
+        )));
+    }
+    let mut bytes = [0; ALG_NAME_LEN];
+    bytes[..name.len()].copy_from_slice(name.as_bytes());
+    bytes.serialize(mem)
+}
+
+/// Deserialize an algorithm name from a null padded array
+fn deserialize_name<B>(mem: &mut B) -> Result<String, DeError>
+where
+    B: Buffer,
+{
+    let bytes = <[u8; ALG_NAME_LEN]>::deserialize(mem)?;
+    let len = bytes.iter().position(|b| *b == 0).unwrap_or(ALG_NAME_LEN);
+    String::from_utf8(bytes[..len].to_vec()).map_err(|e| DeError::Msg(e.to_string()))
+}
+
+/// Deserialize a key whose length is given in bits, following a header of `hdr_len` bytes in a
+/// structure of `size_hint` bytes
+fn deserialize_key<B>(
+    mem: &mut B,
+    bits: u32,
+    size_hint: Option<usize>,
+    hdr_len: usize,
+) -> Result<Vec<u8>, DeError>
+where
+    B: Buffer,
+{
+    let len = (bits as usize).div_ceil(8);
+    if let Some(size_hint) = size_hint {
+        let available = sub_len("size_hint", size_hint, hdr_len)?;
+        if len > available {
+            return Err(DeError::InvalidLength {
+                field: "alg_key_len",
+                expected: available,
+                got: len,
+            });
+        }
+    }
+    mem.set_size_hint(len);
+    let key = Vec::<u8>::deserialize(mem)?;
+    if key.len() != len {
+        return Err(DeError::InvalidLength {
+            field: "alg_key_len",
+            expected: key.len(),
+            got: len,
+        });
+    }
+    Ok(key)
+}
+
+/// `xfrm_address_t`, an IPv4 address in its first 4 bytes or an IPv6 address
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct XfrmAddress(pub [u8; 16]);
+
+impl XfrmAddress {
+    /// Interpret the address according to `family`
+    pub fn to_ip(&self, family: &RtAddrFamily) -> Option<IpAddr> {
+        match *family {
+            RtAddrFamily::Inet => Some(IpAddr::V4(Ipv4Addr::new(
+                self.0[0], self.0[1], self.0[2], self.0[3],
+            ))),
+            RtAddrFamily::Inet6 => Some(IpAddr::V6(Ipv6Addr::from(self.0))),
+            _ => None,
+        }
+    }
+}
+
+impl From<IpAddr> for XfrmAddress {
+    fn from(addr: IpAddr) -> Self {
+        let mut bytes = [0; 16];
+        match addr {
+            IpAddr::V4(v4) => bytes[..4].copy_from_slice(&v4.octets()),
+            IpAddr::V6(v6) => bytes = v6.octets(),
+        }
+        XfrmAddress(bytes)
+    }
+}
+
+impl Nl for XfrmAddress {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.0.serialize(mem)
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        Ok(XfrmAddress(<[u8; 16]>::deserialize(mem)?))
+    }
+
+    fn size(&self) -> usize {
+        16
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(16)
+    }
+}
+
+impl ToNlValue for XfrmAddress {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::Bytes(self.0.to_vec())
+    }
+}
+
+/// `struct xfrm_selector`, matching the traffic a security association or policy applies to
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct XfrmSelector {
+    /// Destination address
+    pub daddr: XfrmAddress,
+    /// Source address
+    pub saddr: XfrmAddress,
+    /// Destination port, in host byte order
+    pub dport: u16,
+    /// Mask of the destination port, in host byte order
+    pub dport_mask: u16,
+    /// Source port, in host byte order
+    pub sport: u16,
+    /// Mask of the source port, in host byte order
+    pub sport_mask: u16,
+    /// Address family of the addresses
+    pub family: RtAddrFamily,
+    /// Prefix length of the destination address
+    pub prefixlen_d: u8,
+    /// Prefix length of the source address
+    pub prefixlen_s: u8,
+    /// IP protocol number, or 0 for any
+    pub proto: u8,
+    /// Interface index, or 0 for any
+    pub ifindex: i32,
+    /// User ID of the socket owner
+    pub user: u32,
+}
+
+impl XfrmSelector {
+    /// Selector matching all traffic of the given family
+    pub fn any(family: RtAddrFamily) -> Self {
+        XfrmSelector {
+            daddr: XfrmAddress::default(),
+            saddr: XfrmAddress::default(),
+            dport: 0,
+            dport_mask: 0,
+            sport: 0,
+            sport_mask: 0,
+            family,
+            prefixlen_d: 0,
+            prefixlen_s: 0,
+            proto: 0,
+            ifindex: 0,
+            user: 0,
+        }
+    }
+}
+
+impl Nl for XfrmSelector {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.daddr.serialize(mem)?;
+        self.saddr.serialize(mem)?;
+        BeU16(self.dport).serialize(mem)?;
+        BeU16(self.dport_mask).serialize(mem)?;
+        BeU16(self.sport).serialize(mem)?;
+        BeU16(self.sport_mask).serialize(mem)?;
+        serialize_family(&self.family, mem)?;
+        self.prefixlen_d.serialize(mem)?;
+        self.prefixlen_s.serialize(mem)?;
+        self.proto.serialize(mem)?;
+        Padding::<3>.serialize(mem)?;
+        self.ifindex.serialize(mem)?;
+        self.user.serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        let daddr = XfrmAddress::deserialize(mem)?;
+        let saddr = XfrmAddress::deserialize(mem)?;
+        let dport = BeU16::deserialize(mem)?.0;
+        let dport_mask = BeU16::deserialize(mem)?.0;
+        let sport = BeU16::deserialize(mem)?.0;
+        let sport_mask = BeU16::deserialize(mem)?.0;
+        let family = deserialize_family(mem)?;
+        let prefixlen_d = u8::deserialize(mem)?;
+        let prefixlen_s = u8::deserialize(mem)?;
+        let proto = u8::deserialize(mem)?;
+        Padding::<3>::deserialize(mem)?;
+        Ok(XfrmSelector {
+            daddr,
+            saddr,
+            dport,
+            dport_mask,
+            sport,
+            sport_mask,
+            family,
+            prefixlen_d,
+            prefixlen_s,
+            proto,
+            ifindex: i32::deserialize(mem)?,
+            user: u32::deserialize(mem)?,
+        })
+    }
+
+    fn size(&self) -> usize {
+        56
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(56)
+    }
+}
+
+impl ToNlValue for XfrmSelector {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("daddr", self.daddr.to_nl_value()),
+            ("saddr", self.saddr.to_nl_value()),
+            ("dport", self.dport.to_nl_value()),
+            ("dport_mask", self.dport_mask.to_nl_value()),
+            ("sport", self.sport.to_nl_value()),
+            ("sport_mask", self.sport_mask.to_nl_value()),
+            ("family", self.family.to_nl_value()),
+            ("prefixlen_d", self.prefixlen_d.to_nl_value()),
+            ("prefixlen_s", self.prefixlen_s.to_nl_value()),
+            ("proto", self.proto.to_nl_value()),
+            ("ifindex", self.ifindex.to_nl_value()),
+            ("user", self.user.to_nl_value()),
+        ])
+    }
+}
+
+/// `struct xfrm_id`, identifying a security association by destination, SPI and protocol
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct XfrmId {
+    /// Destination address
+    pub daddr: XfrmAddress,
+    /// Security parameter index, in host byte order
+    pub spi: u32,
+    /// IPsec protocol, `IPPROTO_ESP`, `IPPROTO_AH` or `IPPROTO_COMP`
+    pub proto: u8,
+}
+
+impl XfrmId {
+    /// Create an ID from its destination, SPI and protocol
+    pub fn new(daddr: IpAddr, spi: u32, proto: u8) -> Self {
+        XfrmId {
+            daddr: daddr.into(),
+            spi,
+            proto,
+        }
+    }
+}
+
+impl Nl for XfrmId {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.daddr.serialize(mem)?;
+        BeU32(self.spi).serialize(mem)?;
+        self.proto.serialize(mem)?;
+        Padding::<3>.serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        let id = XfrmId {
+            daddr: XfrmAddress::deserialize(mem)?,
+            spi: BeU32::deserialize(mem)?.0,
+            proto: u8::deserialize(mem)?,
+        };
+        Padding::<3>::deserialize(mem)?;
+        Ok(id)
+    }
+
+    fn size(&self) -> usize {
+        24
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(24)
+    }
+}
+
+impl ToNlValue for XfrmId {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("daddr", self.daddr.to_nl_value()),
+            ("spi", self.spi.to_nl_value()),
+            ("proto", self.proto.to_nl_value()),
+        ])
+    }
+}
+
+/// `struct xfrm_lifetime_cfg`, the soft and hard limits of a security association or policy
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct XfrmLifetimeCfg {
+    /// Bytes after which the soft limit expires
+    pub soft_byte_limit: u64,
+    /// Bytes after which the hard limit expires
+    pub hard_byte_limit: u64,
+    /// Packets after which the soft limit expires
+    pub soft_packet_limit: u64,
+    /// Packets after which the hard limit expires
+    pub hard_packet_limit: u64,
+    /// Seconds after creation at which the soft limit expires
+    pub soft_add_expires_seconds: u64,
+    /// Seconds after creation at which the hard limit expires
+    pub hard_add_expires_seconds: u64,
+    /// Seconds after first use at which the soft limit expires
+    pub soft_use_expires_seconds: u64,
+    /// Seconds after first use at which the hard limit expires
+    pub hard_use_expires_seconds: u64,
+}
+
+impl Default for XfrmLifetimeCfg {
+    /// No byte or packet limits and no expiry
+    fn default() -> Self {
+        XfrmLifetimeCfg {
+            soft_byte_limit: XFRM_INF,
+            hard_byte_limit: XFRM_INF,
+            soft_packet_limit: XFRM_INF,
+            hard_packet_limit: XFRM_INF,
+            soft_add_expires_seconds: 0,
+            hard_add_expires_seconds: 0,
+            soft_use_expires_seconds: 0,
+            hard_use_expires_seconds: 0,
+        }
+    }
+}
+
+impl Nl for XfrmLifetimeCfg {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.soft_byte_limit.serialize(mem)?;
+        self.hard_byte_limit.serialize(mem)?;
+        self.soft_packet_limit.serialize(mem)?;
+        self.hard_packet_limit.serialize(mem)?;
+        self.soft_add_expires_seconds.serialize(mem)?;
+        self.hard_add_expires_seconds.serialize(mem)?;
+        self.soft_use_expires_seconds.serialize(mem)?;
+        self.hard_use_expires_seconds.serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        Ok(XfrmLifetimeCfg {
+            soft_byte_limit: u64::deserialize(mem)?,
+            hard_byte_limit: u64::deserialize(mem)?,
+            soft_packet_limit: u64::deserialize(mem)?,
+            hard_packet_limit: u64::deserialize(mem)?,
+            soft_add_expires_seconds: u64::deserialize(mem)?,
+            hard_add_expires_seconds: u64::deserialize(mem)?,
+            soft_use_expires_seconds: u64::deserialize(mem)?,
+            hard_use_expires_seconds: u64::deserialize(mem)?,
+        })
+    }
+
+    fn size(&self) -> usize {
+        64
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(64)
+    }
+}
+
+impl ToNlValue for XfrmLifetimeCfg {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("soft_byte_limit", self.soft_byte_limit.to_nl_value()),
+            ("hard_byte_limit", self.hard_byte_limit.to_nl_value()),
+            ("soft_packet_limit", self.soft_packet_limit.to_nl_value()),
+            ("hard_packet_limit", self.hard_packet_limit.to_nl_value()),
+            (
+                "soft_add_expires_seconds",
+                self.soft_add_expires_seconds.to_nl_value(),
+            ),
+            (
+                "hard_add_expires_seconds",
+                self.hard_add_expires_seconds.to_nl_value(),
+            ),
+            (
+                "soft_use_expires_seconds",
+                self.soft_use_expires_seconds.to_nl_value(),
+            ),
+            (
+                "hard_use_expires_seconds",
+                self.hard_use_expires_seconds.to_nl_value(),
+            ),
+        ])
+    }
+}
+
+/// `struct xfrm_lifetime_cur`, the current usage of a security association or policy
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct XfrmLifetimeCur {
+    /// Bytes processed
+    pub bytes: u64,
+    /// Packets processed
+    pub packets: u64,
+    /// Creation time, in seconds since the epoch
+    pub add_time: u64,
+    /// Time of first use, in seconds since the epoch
+    pub use_time: u64,
+}
+
+impl Nl for XfrmLifetimeCur {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.bytes.serialize(mem)?;
+        self.packets.serialize(mem)?;
+        self.add_time.serialize(mem)?;
+        self.use_time.serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        Ok(XfrmLifetimeCur {
+            bytes: u64::deserialize(mem)?,
+            packets: u64::deserialize(mem)?,
+            add_time: u64::deserialize(mem)?,
+            use_time: u64::deserialize(mem)?,
+        })
+    }
+
+    fn size(&self) -> usize {
+        32
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(32)
+    }
+}
+
+impl ToNlValue for XfrmLifetimeCur {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("bytes", self.bytes.to_nl_value()),
+            ("packets", self.packets.to_nl_value()),
+            ("add_time", self.add_time.to_nl_value()),
+            ("use_time", self.use_time.to_nl_value()),
+        ])
+    }
+}
+
+/// `struct xfrm_stats`, the error counters of a security association
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct XfrmStats {
+    /// Packets dropped because they were outside the replay window
+    pub replay_window: u32,
+    /// Packets dropped as replays
+    pub replay: u32,
+    /// Packets dropped because their integrity check failed
+    pub integrity_failed: u32,
+}
+
+impl Nl for XfrmStats {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.replay_window.serialize(mem)?;
+        self.replay.serialize(mem)?;
+        self.integrity_failed.serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        Ok(XfrmStats {
+            replay_window: u32::deserialize(mem)?,
+            replay: u32::deserialize(mem)?,
+            integrity_failed: u32::deserialize(mem)?,
+        })
+    }
+
+    fn size(&self) -> usize {
+        12
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(12)
+    }
+}
+
+impl ToNlValue for XfrmStats {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("replay_window", self.replay_window.to_nl_value()),
+            ("replay", self.replay.to_nl_value()),
+            ("integrity_failed", self.integrity_failed.to_nl_value()),
+        ])
+    }
+}
+
+/// `struct xfrm_usersa_info` describing a security association, followed by its attributes
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct XfrmUsersaInfo {
+    /// Traffic the security association applies to
+    pub sel: XfrmSelector,
+    /// Destination, SPI and protocol of the security association
+    pub id: XfrmId,
+    /// Source address
+    pub saddr: XfrmAddress,
+    /// Configured lifetime limits
+    pub lft: XfrmLifetimeCfg,
+    /// Current usage, only set by the kernel
+    pub curlft: XfrmLifetimeCur,
+    /// Error counters, only set by the kernel
+    pub stats: XfrmStats,
+    /// Sequence number of the `XfrmMsg::Acquire` this security association answers
+    pub seq: u32,
+    /// Request ID matching the security association to the templates of policies
+    pub reqid: u32,
+    /// Address family of `id` and `saddr`
+    pub family: RtAddrFamily,
+    /// Mode of the security association
+    pub mode: XfrmMode,
+    /// Size of the replay window, in packets
+    pub replay_window: u8,
+    /// Flags of the security association
+    pub flags: Vec<XfrmStateFlag>,
+    /// Attributes such as the algorithms of the security association
+    pub attrs: XfrmAttrs,
+}
+
+impl XfrmUsersaInfo {
+    /// Security association of the given ID and source address, matching all traffic and without
+    /// lifetime limits or attributes
+    pub fn new(family: RtAddrFamily, id: XfrmId, saddr: XfrmAddress, mode: XfrmMode) -> Self {
+        XfrmUsersaInfo {
+            sel: XfrmSelector::any(family.clone()),
+            id,
+            saddr,
+            lft: XfrmLifetimeCfg::default(),
+            curlft: XfrmLifetimeCur::default(),
+            stats: XfrmStats::default(),
+            seq: 0,
+            reqid: 0,
+            family,
+            mode,
+            replay_window: 0,
+            flags: Vec::new(),
+            attrs: Vec::new(),
+        }
+    }
+
+    /// Get an attribute of the security association as type `R`
+    pub fn get_attr_payload_as<R>(&self, attr_type: XfrmAttr) -> Result<Option<R>, DeError>
+    where
+        R: Nl,
+    {
+        match self.attrs.iter().find(|attr| attr.nla_type == attr_type) {
+            Some(attr) => attr.get_payload_as().map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Size of the structure without its attributes
+    fn header_len() -> usize {
+        align_u64(217)
+    }
+}
+
+impl Nl for XfrmUsersaInfo {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.sel.serialize(mem)?;
+        self.id.serialize(mem)?;
+        self.saddr.serialize(mem)?;
+        self.lft.serialize(mem)?;
+        self.curlft.serialize(mem)?;
+        self.stats.serialize(mem)?;
+        self.seq.serialize(mem)?;
+        self.reqid.serialize(mem)?;
+        serialize_family(&self.family, mem)?;
+        self.mode.serialize(mem)?;
+        self.replay_window.serialize(mem)?;
+        XfrmStateFlags::new(&self.flags).bits().serialize(mem)?;
+        mem.write_all(&vec![0; Self::header_len() - 217])?;
+        self.attrs.serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        let size_hint = mem
+            .take_size_hint()
+            .ok_or_else(|| DeError::new("Must provide size hint to deserialize XfrmUsersaInfo"))?;
+        let attrs_len = sub_len("size_hint", size_hint, Self::header_len())?;
+        let sel = XfrmSelector::deserialize(mem)?;
+        let id = XfrmId::deserialize(mem)?;
+        let saddr = XfrmAddress::deserialize(mem)?;
+        let lft = XfrmLifetimeCfg::deserialize(mem)?;
+        let curlft = XfrmLifetimeCur::deserialize(mem)?;
+        let stats = XfrmStats::deserialize(mem)?;
+        let seq = u32::deserialize(mem)?;
+        let reqid = u32::deserialize(mem)?;
+        let family = deserialize_family(mem)?;
+        let mode = XfrmMode::deserialize(mem)?;
+        let replay_window = u8::deserialize(mem)?;
+        let flags = XfrmStateFlags::from_bits(u8::deserialize(mem)?).into_vec();
+        mem.read_exact(&mut vec![0; Self::header_len() - 217])?;
+        mem.set_size_hint(attrs_len);
+        let attrs = XfrmAttrs::deserialize(mem).map_err(|e| e.context("XfrmUsersaInfo.attrs"))?;
+        Ok(XfrmUsersaInfo {
+            sel,
+            id,
+            saddr,
+            lft,
+            curlft,
+            stats,
+            seq,
+            reqid,
+            family,
+            mode,
+            replay_window,
+            flags,
+            attrs,
+        })
+    }
+
+    fn size(&self) -> usize {
+        Self::header_len() + self.attrs.asize()
+    }
+}
+
+impl ToNlValue for XfrmUsersaInfo {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("sel", self.sel.to_nl_value()),
+            ("id", self.id.to_nl_value()),
+            ("saddr", self.saddr.to_nl_value()),
+            ("lft", self.lft.to_nl_value()),
+            ("curlft", self.curlft.to_nl_value()),
+            ("stats", self.stats.to_nl_value()),
+            ("seq", self.seq.to_nl_value()),
+            ("reqid", self.reqid.to_nl_value()),
+            ("family", self.family.to_nl_value()),
+            ("mode", self.mode.to_nl_value()),
+            ("replay_window", self.replay_window.to_nl_value()),
+            ("flags", NlValue::list(&self.flags)),
+            ("attrs", NlValue::list(&self.attrs)),
+        ])
+    }
+}
+
+/// `struct xfrm_usersa_id`, identifying a security association to get or delete, followed by
+/// attributes such as `XfrmAttr::Srcaddr`
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct XfrmUsersaId {
+    /// Destination address
+    pub daddr: XfrmAddress,
+    /// Security parameter index, in host byte order
+    pub spi: u32,
+    /// Address family of `daddr`
+    pub family: RtAddrFamily,
+    /// IPsec protocol, `IPPROTO_ESP`, `IPPROTO_AH` or `IPPROTO_COMP`
+    pub proto: u8,
+    /// Attributes of the request
+    pub attrs: XfrmAttrs,
+}
+
+/// Size of `XfrmUsersaId` without its attributes
+const XFRM_USERSA_ID_LEN: usize = 24;
+
+impl Nl for XfrmUsersaId {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.daddr.serialize(mem)?;
+        BeU32(self.spi).serialize(mem)?;
+        serialize_family(&self.family, mem)?;
+        self.proto.serialize(mem)?;
+        Padding::<1>.serialize(mem)?;
+        self.attrs.serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        let size_hint = mem
+            .take_size_hint()
+            .ok_or_else(|| DeError::new("Must provide size hint to deserialize XfrmUsersaId"))?;
+        let attrs_len = sub_len("size_hint", size_hint, XFRM_USERSA_ID_LEN)?;
+        let daddr = XfrmAddress::deserialize(mem)?;
+        let spi = BeU32::deserialize(mem)?.0;
+        let family = deserialize_family(mem)?;
+        let proto = u8::deserialize(mem)?;
+        Padding::<1>::deserialize(mem)?;
+        mem.set_size_hint(attrs_len);
+        let attrs = XfrmAttrs::deserialize(mem).map_err(|e| e.context("XfrmUsersaId.attrs"))?;
+        Ok(XfrmUsersaId {
+            daddr,
+            spi,
+            family,
+            proto,
+            attrs,
+        })
+    }
+
+    fn size(&self) -> usize {
+        XFRM_USERSA_ID_LEN + self.attrs.asize()
+    }
+}
+
+impl ToNlValue for XfrmUsersaId {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("daddr", self.daddr.to_nl_value()),
+            ("spi", self.spi.to_nl_value()),
+            ("family", self.family.to_nl_value()),
+            ("proto", self.proto.to_nl_value()),
+            ("attrs", NlValue::list(&self.attrs)),
+        ])
+    }
+}
+
+/// `struct xfrm_userpolicy_info` describing a policy, followed by its attributes
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct XfrmUserpolicyInfo {
+    /// Traffic the policy applies to
+    pub sel: XfrmSelector,
+    /// Configured lifetime limits
+    pub lft: XfrmLifetimeCfg,
+    /// Current usage, only set by the kernel
+    pub curlft: XfrmLifetimeCur,
+    /// Priority of the policy, lower values taking precedence
+    pub priority: u32,
+    /// Index of the policy, chosen by the kernel if 0
+    pub index: u32,
+    /// Direction of the traffic the policy applies to
+    pub dir: XfrmPolicyDir,
+    /// Action of the policy
+    pub action: XfrmPolicyAction,
+    /// Flags of the policy
+    pub flags: Vec<XfrmPolicyFlag>,
+    /// Sharing mode of the policy
+    pub share: XfrmShare,
+    /// Attributes such as the `XfrmAttr::Tmpl` templates of the policy
+    pub attrs: XfrmAttrs,
+}
+
+impl XfrmUserpolicyInfo {
+    /// Policy allowing the traffic matching `sel` in the given direction, without lifetime
+    /// limits or attributes
+    pub fn new(sel: XfrmSelector, dir: XfrmPolicyDir) -> Self {
+        XfrmUserpolicyInfo {
+            sel,
+            lft: XfrmLifetimeCfg::default(),
+            curlft: XfrmLifetimeCur::default(),
+            priority: 0,
+            index: 0,
+            dir,
+            action: XfrmPolicyAction::Allow,
+            flags: Vec::new(),
+            share: XfrmShare::Any,
+            attrs: Vec::new(),
+        }
+    }
+
+    /// Get the templates of the policy from its `XfrmAttr::Tmpl` attribute
+    pub fn templates(&self) -> Result<Vec<XfrmUserTmpl>, DeError> {
+        let attr = match self.attrs.iter().find(|a| a.nla_type == XfrmAttr::Tmpl) {
+            Some(attr) => attr,
+            None => return Ok(Vec::new()),
+        };
+        let mut mem = StreamReadBuffer::new(&attr.payload);
+        let mut templates = Vec::new();
+        while !mem.at_end() {
+            templates.push(XfrmUserTmpl::deserialize(&mut mem)?);
+        }
+        Ok(templates)
+    }
+
+    /// Set the templates of the policy in its `XfrmAttr::Tmpl` attribute
+    pub fn set_templates(&mut self, templates: &[XfrmUserTmpl]) -> Result<(), SerError> {
+        let mut mem = StreamWriteBuffer::new_growable(Some(templates.len() * 64));
+        for template in templates {
+            template.serialize(&mut mem)?;
+        }
+        let attr = Nlattr::new(None, XfrmAttr::Tmpl, mem.as_ref().to_vec())?;
+        self.attrs.retain(|a| a.nla_type != XfrmAttr::Tmpl);
+        self.attrs.push(attr);
+        Ok(())
+    }
+
+    /// Size of the structure without its attributes
+    fn header_len() -> usize {
+        align_u64(164)
+    }
+}
+
+impl Nl for XfrmUserpolicyInfo {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.sel.serialize(mem)?;
+        self.lft.serialize(mem)?;
+        self.curlft.serialize(mem)?;
+        self.priority.serialize(mem)?;
+        self.index.serialize(mem)?;
+        self.dir.serialize(mem)?;
+        self.action.serialize(mem)?;
+        XfrmPolicyFlags::new(&self.flags).bits().serialize(mem)?;
+        self.share.serialize(mem)?;
+        mem.write_all(&vec![0; Self::header_len() - 164])?;
+        self.attrs.serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        let size_hint = mem.take_size_hint().ok_or_else(|| {
+            DeError::new("Must provide size hint to deserialize XfrmUserpolicyInfo")
+        })?;
+        let attrs_len = sub_len("size_hint", size_hint, Self::header_len())?;
+        let sel = XfrmSelector::deserialize(mem)?;
+        let lft = XfrmLifetimeCfg::deserialize(mem)?;
+        let curlft = XfrmLifetimeCur::deserialize(mem)?;
+        let priority = u32::deserialize(mem)?;
+        let index = u32::deserialize(mem)?;
+        let dir = XfrmPolicyDir::deserialize(mem)?;
+        let action = XfrmPolicyAction::deserialize(mem)?;
+        let flags = XfrmPolicyFlags::from_bits(u8::deserialize(mem)?).into_vec();
+        let share = XfrmShare::deserialize(mem)?;
+        mem.read_exact(&mut vec![0; Self::header_len() - 164])?;
+        mem.set_size_hint(attrs_len);
+        let attrs =
+            XfrmAttrs::deserialize(mem).map_err(|e| e.context("XfrmUserpolicyInfo.attrs"))?;
+        Ok(XfrmUserpolicyInfo {
+            sel,
+            lft,
+            curlft,
+            priority,
+            index,
+            dir,
+            action,
+            flags,
+            share,
+            attrs,
+        })
+    }
+
+    fn size(&self) -> usize {
+        Self::header_len() + self.attrs.asize()
+    }
+}
+
+impl ToNlValue for XfrmUserpolicyInfo {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("sel", self.sel.to_nl_value()),
+            ("lft", self.lft.to_nl_value()),
+            ("curlft", self.curlft.to_nl_value()),
+            ("priority", self.priority.to_nl_value()),
+            ("index", self.index.to_nl_value()),
+            ("dir", self.dir.to_nl_value()),
+            ("action", self.action.to_nl_value()),
+            ("flags", NlValue::list(&self.flags)),
+            ("share", self.share.to_nl_value()),
+            ("attrs", NlValue::list(&self.attrs)),
+        ])
+    }
+}
+
+/// `struct xfrm_userpolicy_id`, identifying a policy to get or delete by selector or index
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct XfrmUserpolicyId {
+    /// Selector of the policy, used if `index` is 0
+    pub sel: XfrmSelector,
+    /// Index of the policy
+    pub index: u32,
+    /// Direction of the policy
+    pub dir: XfrmPolicyDir,
+    /// Attributes of the request
+    pub attrs: XfrmAttrs,
+}
+
+/// Size of `XfrmUserpolicyId` without its attributes
+const XFRM_USERPOLICY_ID_LEN: usize = 64;
+
+impl Nl for XfrmUserpolicyId {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.sel.serialize(mem)?;
+        self.index.serialize(mem)?;
+        self.dir.serialize(mem)?;
+        Padding::<3>.serialize(mem)?;
+        self.attrs.serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        let size_hint = mem.take_size_hint().ok_or_else(|| {
+            DeError::new("Must provide size hint to deserialize XfrmUserpolicyId")
+        })?;
+        let attrs_len = sub_len("size_hint", size_hint, XFRM_USERPOLICY_ID_LEN)?;
+        let sel = XfrmSelector::deserialize(mem)?;
+        let index = u32::deserialize(mem)?;
+        let dir = XfrmPolicyDir::deserialize(mem)?;
+        Padding::<3>::deserialize(mem)?;
+        mem.set_size_hint(attrs_len);
+        let attrs = XfrmAttrs::deserialize(mem).map_err(|e| e.context("XfrmUserpolicyId.attrs"))?;
+        Ok(XfrmUserpolicyId {
+            sel,
+            index,
+            dir,
+            attrs,
+        })
+    }
+
+    fn size(&self) -> usize {
+        XFRM_USERPOLICY_ID_LEN + self.attrs.asize()
+    }
+}
+
+impl ToNlValue for XfrmUserpolicyId {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("sel", self.sel.to_nl_value()),
+            ("index", self.index.to_nl_value()),
+            ("dir", self.dir.to_nl_value()),
+            ("attrs", NlValue::list(&self.attrs)),
+        ])
+    }
+}
+
+/// `struct xfrm_user_tmpl`, a template of the security associations a policy requires
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct XfrmUserTmpl {
+    /// Destination, SPI and protocol of the security association
+    pub id: XfrmId,
+    /// Address family of `id` and `saddr`
+    pub family: RtAddrFamily,
+    /// Source address
+    pub saddr: XfrmAddress,
+    /// Request ID of the security association, or 0 for any
+    pub reqid: u32,
+    /// Mode of the security association
+    pub mode: XfrmMode,
+    /// Sharing mode of the security association
+    pub share: XfrmShare,
+    /// 1 if the security association may be missing
+    pub optional: u8,
+    /// Bits of the allowed authentication algorithms
+    pub aalgos: u32,
+    /// Bits of the allowed encryption algorithms
+    pub ealgos: u32,
+    /// Bits of the allowed compression algorithms
+    pub calgos: u32,
+}
+
+impl XfrmUserTmpl {
+    /// Template for security associations of the given ID, allowing all algorithms
+    pub fn new(family: RtAddrFamily, id: XfrmId, saddr: XfrmAddress, mode: XfrmMode) -> Self {
+        XfrmUserTmpl {
+            id,
+            family,
+            saddr,
+            reqid: 0,
+            mode,
+            share: XfrmShare::Any,
+            optional: 0,
+            aalgos: !0,
+            ealgos: !0,
+            calgos: !0,
+        }
+    }
+}
+
+impl Nl for XfrmUserTmpl {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.id.serialize(mem)?;
+        serialize_family(&self.family, mem)?;
+        Padding::<2>.serialize(mem)?;
+        self.saddr.serialize(mem)?;
+        self.reqid.serialize(mem)?;
+        self.mode.serialize(mem)?;
+        self.share.serialize(mem)?;
+        self.optional.serialize(mem)?;
+        Padding::<1>.serialize(mem)?;
+        self.aalgos.serialize(mem)?;
+        self.ealgos.serialize(mem)?;
+        self.calgos.serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        let id = XfrmId::deserialize(mem)?;
+        let family = deserialize_family(mem)?;
+        Padding::<2>::deserialize(mem)?;
+        let saddr = XfrmAddress::deserialize(mem)?;
+        let reqid = u32::deserialize(mem)?;
+        let mode = XfrmMode::deserialize(mem)?;
+        let share = XfrmShare::deserialize(mem)?;
+        let optional = u8::deserialize(mem)?;
+        Padding::<1>::deserialize(mem)?;
+        Ok(XfrmUserTmpl {
+            id,
+            family,
+            saddr,
+            reqid,
+            mode,
+            share,
+            optional,
+            aalgos: u32::deserialize(mem)?,
+            ealgos: u32::deserialize(mem)?,
+            calgos: u32::deserialize(mem)?,
+        })
+    }
+
+    fn size(&self) -> usize {
+        64
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(64)
+    }
+}
+
+impl ToNlValue for XfrmUserTmpl {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("id", self.id.to_nl_value()),
+            ("family", self.family.to_nl_value()),
+            ("saddr", self.saddr.to_nl_value()),
+            ("reqid", self.reqid.to_nl_value()),
+            ("mode", self.mode.to_nl_value()),
+            ("share", self.share.to_nl_value()),
+            ("optional", self.optional.to_nl_value()),
+            ("aalgos", self.aalgos.to_nl_value()),
+            ("ealgos", self.ealgos.to_nl_value()),
+            ("calgos", self.calgos.to_nl_value()),
+        ])
+    }
+}
+
+/// `struct xfrm_algo`, the payload of `XfrmAttr::AlgCrypt`, `XfrmAttr::AlgAuth` and
+/// `XfrmAttr::AlgComp`
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct XfrmAlgo {
+    /// Kernel crypto API name of the algorithm, such as `cbc(aes)`
+    pub alg_name: String,
+    /// Key of the algorithm, its length in bits being sent along with it
+    pub alg_key: Vec<u8>,
+}
+
+impl XfrmAlgo {
+    /// Algorithm of the given name and key
+    pub fn new<S>(alg_name: S, alg_key: Vec<u8>) -> Self
+    where
+        S: Into<String>,
+    {
+        XfrmAlgo {
+            alg_name: alg_name.into(),
+            alg_key,
+        }
+    }
+}
+
+impl Nl for XfrmAlgo {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        serialize_name(&self.alg_name, mem)?;
+        ((self.alg_key.len() * 8) as u32).serialize(mem)?;
+        self.alg_key.serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        let size_hint = mem.take_size_hint();
+        let alg_name = deserialize_name(mem)?;
+        let bits = u32::deserialize(mem)?;
+        Ok(XfrmAlgo {
+            alg_name,
+            alg_key: deserialize_key(mem, bits, size_hint, ALG_NAME_LEN + 4)?,
+        })
+    }
+
+    fn size(&self) -> usize {
+        ALG_NAME_LEN + 4 + self.alg_key.len()
+    }
+}
+
+impl ToNlValue for XfrmAlgo {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("alg_name", self.alg_name.to_nl_value()),
+            ("alg_key", NlValue::Bytes(self.alg_key.clone())),
+        ])
+    }
+}
+
+/// `struct xfrm_algo_auth`, the payload of `XfrmAttr::AlgAuthTrunc`
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct XfrmAlgoAuth {
+    /// Kernel crypto API name of the algorithm, such as `hmac(sha256)`
+    pub alg_name: String,
+    /// Length of the truncated integrity check value, in bits
+    pub alg_trunc_len: u32,
+    /// Key of the algorithm, its length in bits being sent along with it
+    pub alg_key: Vec<u8>,
+}
+
+impl Nl for XfrmAlgoAuth {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        serialize_name(&self.alg_name, mem)?;
+        ((self.alg_key.len() * 8) as u32).serialize(mem)?;
+        self.alg_trunc_len.serialize(mem)?;
+        self.alg_key.serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        let size_hint = mem.take_size_hint();
+        let alg_name = deserialize_name(mem)?;
+        let bits = u32::deserialize(mem)?;
+        Ok(XfrmAlgoAuth {
+            alg_name,
+            alg_trunc_len: u32::deserialize(mem)?,
+            alg_key: deserialize_key(mem, bits, size_hint, ALG_NAME_LEN + 8)?,
+        })
+    }
+
+    fn size(&self) -> usize {
+        ALG_NAME_LEN + 8 + self.alg_key.len()
+    }
+}
+
+impl ToNlValue for XfrmAlgoAuth {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("alg_name", self.alg_name.to_nl_value()),
+            ("alg_trunc_len", self.alg_trunc_len.to_nl_value()),
+            ("alg_key", NlValue::Bytes(self.alg_key.clone())),
+        ])
+    }
+}
+
+/// `struct xfrm_algo_aead`, the payload of `XfrmAttr::AlgAead`
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct XfrmAlgoAead {
+    /// Kernel crypto API name of the algorithm, such as `rfc4106(gcm(aes))`
+    pub alg_name: String,
+    /// Length of the integrity check value, in bits
+    pub alg_icv_len: u32,
+    /// Key of the algorithm including its salt, its length in bits being sent along with it
+    pub alg_key: Vec<u8>,
+}
+
+impl Nl for XfrmAlgoAead {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        serialize_name(&self.alg_name, mem)?;
+        ((self.alg_key.len() * 8) as u32).serialize(mem)?;
+        self.alg_icv_len.serialize(mem)?;
+        self.alg_key.serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        let size_hint = mem.take_size_hint();
+        let alg_name = deserialize_name(mem)?;
+        let bits = u32::deserialize(mem)?;
+        Ok(XfrmAlgoAead {
+            alg_name,
+            alg_icv_len: u32::deserialize(mem)?,
+            alg_key: deserialize_key(mem, bits, size_hint, ALG_NAME_LEN + 8)?,
+        })
+    }
+
+    fn size(&self) -> usize {
+        ALG_NAME_LEN + 8 + self.alg_key.len()
+    }
+}
+
+impl ToNlValue for XfrmAlgoAead {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("alg_name", self.alg_name.to_nl_value()),
+            ("alg_icv_len", self.alg_icv_len.to_nl_value()),
+            ("alg_key", NlValue::Bytes(self.alg_key.clone())),
+        ])
+    }
+}
+
+/// `struct xfrm_mark`, the payload of `XfrmAttr::Mark` matching packets by their mark
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct XfrmMark {
+    /// Value of the mark
+    pub v: u32,
+    /// Mask applied to the mark of packets before comparing it to `v`
+    pub m: u32,
+}
+
+impl Nl for XfrmMark {
+    fn serialize<B>(&self, mem: &mut B) -> Result<(), SerError>
+    where
+        B: BufferMut,
+    {
+        self.v.serialize(mem)?;
+        self.m.serialize(mem)?;
+        Ok(())
+    }
+
+    fn deserialize<B>(mem: &mut B) -> Result<Self, DeError>
+    where
+        B: Buffer,
+    {
+        Ok(XfrmMark {
+            v: u32::deserialize(mem)?,
+            m: u32::deserialize(mem)?,
+        })
+    }
+
+    fn size(&self) -> usize {
+        8
+    }
+
+    fn type_size() -> Option<usize> {
+        Some(8)
+    }
+}
+
+impl ToNlValue for XfrmMark {
+    fn to_nl_value(&self) -> NlValue {
+        NlValue::map(vec![
+            ("v", self.v.to_nl_value()),
+            ("m", self.m.to_nl_value()),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use test_utils::assert_roundtrip;
+
+    fn addrs() -> (IpAddr, IpAddr) {
+        (
+            IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)),
+            IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2)),
+        )
+    }
+
+    fn sa() -> XfrmUsersaInfo {
+        let (src, dst) = addrs();
+        let id = XfrmId::new(dst, 0x0102_0304, libc::IPPROTO_ESP as u8);
+        let mut sa = XfrmUsersaInfo::new(RtAddrFamily::Inet, id, src.into(), XfrmMode::Tunnel);
+        sa.reqid = 7;
+        sa.flags = vec![XfrmStateFlag::NoEcn, XfrmStateFlag::Esn];
+        sa
+    }
+
+    fn policy() -> (XfrmUserpolicyInfo, XfrmUserTmpl) {
+        let (src, dst) = addrs();
+        let sel = XfrmSelector::any(RtAddrFamily::Inet);
+        let mut policy = XfrmUserpolicyInfo::new(sel, XfrmPolicyDir::Out);
+        let tmpl = XfrmUserTmpl::new(
+            RtAddrFamily::Inet,
+            XfrmId::new(dst, 0, libc::IPPROTO_ESP as u8),
+            src.into(),
+            XfrmMode::Tunnel,
+        );
+        policy.set_templates(std::slice::from_ref(&tmpl)).unwrap();
+        (policy, tmpl)
+    }
+
+    #[test]
+    fn test_usersa_info() {
+        let bytes = assert_roundtrip(&sa());
+        assert_eq!(bytes.len(), XfrmUsersaInfo::header_len());
+        // id.daddr, id.spi in network byte order and id.proto
+        assert_eq!(&bytes[56..60], &[192, 0, 2, 2]);
+        assert_eq!(&bytes[72..77], &[1, 2, 3, 4, libc::IPPROTO_ESP as u8]);
+        // reqid, family, mode, replay_window and flags
+        assert_eq!(&bytes[208..212], &7u32.to_ne_bytes());
+        assert_eq!(&bytes[212..214], &(libc::AF_INET as u16).to_ne_bytes());
+        assert_eq!(&bytes[214..217], &[1, 0, 129]);
+    }
+
+    #[test]
+    fn test_usersa_info_algo() {
+        let mut sa = sa();
+        let crypt = XfrmAlgo::new("cbc(aes)", vec![0xaa; 16]);
+        sa.attrs
+            .push(Nlattr::new(None, XfrmAttr::AlgCrypt, crypt.clone()).unwrap());
+        let bytes = assert_roundtrip(&sa);
+        let header_len = XfrmUsersaInfo::header_len();
+        assert_eq!(bytes.len(), header_len + 4 + 84);
+        assert_eq!(
+            sa.get_attr_payload_as::<XfrmAlgo>(XfrmAttr::AlgCrypt)
+                .unwrap(),
+            Some(crypt)
+        );
+        assert_eq!(
+            &bytes[header_len + 68..header_len + 72],
+            &128u32.to_ne_bytes()
+        );
+    }
+
+    #[test]
+    fn test_usersa_info_truncated() {
+        let bytes = assert_roundtrip(&sa());
+        let mut mem = StreamReadBuffer::new(&bytes[..200]);
+        mem.set_size_hint(200);
+        assert!(XfrmUsersaInfo::deserialize(&mut mem).is_err());
+    }
+
+    #[test]
+    fn test_userpolicy_info() {
+        let (policy, tmpl) = policy();
+        let bytes = assert_roundtrip(&policy);
+        assert_eq!(bytes.len(), XfrmUserpolicyInfo::header_len() + 4 + 64);
+        assert_eq!(policy.templates().unwrap(), vec![tmpl]);
+    }
+
+    #[test]
+    fn test_userpolicy_info_truncated_template() {
+        let (mut policy, _) = policy();
+        for attr in policy.attrs.iter_mut() {
+            attr.payload.pop();
+        }
+        assert!(policy.templates().is_err());
+    }
+
+    #[test]
+    fn test_xfrm_algo_key_len() {
+        let crypt = XfrmAlgo::new("cbc(aes)", vec![0xaa; 16]);
+        let mut bytes = vec![0; crypt.size()];
+        crypt
+            .serialize(&mut StreamWriteBuffer::new_sized(&mut bytes))
+            .unwrap();
+        let parse = |bytes: &[u8]| {
+            let mut mem = StreamReadBuffer::new(bytes);
+            mem.set_size_hint(bytes.len());
+            XfrmAlgo::deserialize(&mut mem)
+        };
+        assert_eq!(parse(&bytes).unwrap(), crypt);
+
+        // A key length far beyond the attribute
+        let mut huge = bytes.clone();
+        huge[ALG_NAME_LEN..ALG_NAME_LEN + 4].copy_from_slice(&u32::MAX.to_ne_bytes());
+        assert!(matches!(
+            parse(&huge),
+            Err(DeError::InvalidLength {
+                field: "alg_key_len",
+                ..
+            })
+        ));
+
+        // A truncated key
+        assert!(matches!(
+            parse(&bytes[..bytes.len() - 1]),
+            Err(DeError::InvalidLength {
+                field: "alg_key_len",
+                ..
+            })
+        ));
+    }
+}